@@ -1,6 +1,66 @@
 package build
 
+import (
+	"fmt"
+	"runtime/debug"
+	"strings"
+)
+
 var (
 	Name    = "treefmt"    //nolint:gochecknoglobals
 	Version = "v0.0.1+dev" //nolint:gochecknoglobals
+
+	// Commit is the git commit treefmt was built from, e.g. set with -ldflags "-X ...build.Commit=$GIT_SHA".
+	// When empty, the revision recorded by the go toolchain is used instead, if there is one.
+	Commit = "" //nolint:gochecknoglobals
+	// Profile describes how treefmt was built, e.g. release for published binaries.
+	Profile = "" //nolint:gochecknoglobals
 )
+
+// Info returns a single line identifying this build, such as `treefmt v2.1.0 (commit abc123, build release)`.
+// The details in brackets are only included when known.
+func Info() string {
+	var details []string
+
+	if commit := commit(); commit != "" {
+		details = append(details, "commit "+commit)
+	}
+
+	if Profile != "" {
+		details = append(details, "build "+Profile)
+	}
+
+	if len(details) == 0 {
+		return fmt.Sprintf("%s %s", Name, Version)
+	}
+
+	return fmt.Sprintf("%s %s (%s)", Name, Version, strings.Join(details, ", "))
+}
+
+func commit() string {
+	if Commit != "" {
+		return Commit
+	}
+
+	info, ok := debug.ReadBuildInfo()
+	if !ok {
+		return ""
+	}
+
+	var revision, modified string
+
+	for _, setting := range info.Settings {
+		switch setting.Key {
+		case "vcs.revision":
+			revision = setting.Value
+		case "vcs.modified":
+			modified = setting.Value
+		}
+	}
+
+	if revision != "" && modified == "true" {
+		revision += "-dirty"
+	}
+
+	return revision
+}
@@ -0,0 +1,27 @@
+package build_test
+
+import (
+	"testing"
+
+	"github.com/numtide/treefmt/v2/build"
+	"github.com/stretchr/testify/require"
+)
+
+func TestInfo(t *testing.T) {
+	as := require.New(t)
+
+	version, commit, profile := build.Version, build.Commit, build.Profile
+
+	t.Cleanup(func() {
+		build.Version, build.Commit, build.Profile = version, commit, profile
+	})
+
+	build.Version = "v2.1.0"
+
+	build.Commit = "abc123"
+	build.Profile = "release"
+	as.Equal("treefmt v2.1.0 (commit abc123, build release)", build.Info())
+
+	build.Profile = ""
+	as.Equal("treefmt v2.1.0 (commit abc123)", build.Info())
+}
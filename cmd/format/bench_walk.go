@@ -0,0 +1,113 @@
+package format
+
+import (
+	"context"
+	"errors"
+	"fmt"
+	"io"
+	"slices"
+	"time"
+
+	"github.com/numtide/treefmt/v2/config"
+	"github.com/numtide/treefmt/v2/format"
+	"github.com/numtide/treefmt/v2/stats"
+	"github.com/numtide/treefmt/v2/walk"
+)
+
+// benchWalk traverses the given paths cfg.BenchWalk times, matching every file against the formatters, and prints
+// timing statistics for the runs to stdout. Nothing is formatted and the cache is never used.
+func benchWalk(
+	ctx context.Context,
+	cfg *config.Config,
+	statz *stats.Stats,
+	walkType walk.Type,
+	paths []string,
+) error {
+	formatter, err := format.NewCompositeFormatter(cfg, statz, cfg.BatchSize)
+	if err != nil {
+		return fmt.Errorf("failed to create composite formatter: %w", err)
+	}
+
+	var traversed, matched int
+
+	durations := make([]time.Duration, cfg.BenchWalk)
+
+	for i := range durations {
+		start := time.Now()
+
+		if traversed, matched, err = walkOnce(ctx, cfg, formatter, walkType, paths); err != nil {
+			return err
+		}
+
+		durations[i] = time.Since(start)
+	}
+
+	slices.Sort(durations)
+
+	median := durations[len(durations)/2]
+	if len(durations)%2 == 0 {
+		median = (durations[len(durations)/2-1] + median) / 2
+	}
+
+	fmt.Printf(
+		"walked %d times, traversing %d files of which %d matched a formatter\n",
+		len(durations), traversed, matched,
+	)
+	fmt.Printf("  min:       %v\n", durations[0])
+	fmt.Printf("  median:    %v\n", median)
+	fmt.Printf("  max:       %v\n", durations[len(durations)-1])
+	fmt.Printf("  files/sec: %.0f\n", float64(traversed)/median.Seconds())
+
+	return nil
+}
+
+// walkOnce traverses the given paths, returning how many files were traversed and how many of them matched at least
+// one formatter.
+func walkOnce(
+	ctx context.Context,
+	cfg *config.Config,
+	formatter *format.CompositeFormatter,
+	walkType walk.Type,
+	paths []string,
+) (traversed, matched int, err error) {
+	// each run has its own stats, so the runs do not add up in the summary
+	runStats := stats.New()
+
+	walker, err := walk.NewCompositeReader(walkType, cfg.TreeRoot, paths, cfg.MaxDepth, cfg.CacheDir, "", nil, &runStats)
+	if err != nil {
+		return 0, 0, fmt.Errorf("failed to create walker: %w", err)
+	}
+
+	files := make([]*walk.File, BatchSize)
+
+	for {
+		readCtx, cancelRead := context.WithTimeout(ctx, 1*time.Second)
+
+		n, readErr := walker.Read(readCtx, files)
+
+		cancelRead()
+
+		traversed += n
+
+		for _, file := range files[:n] {
+			if len(formatter.FormattersFor(file)) > 0 {
+				matched++
+			}
+		}
+
+		//nolint:gocritic
+		if errors.Is(readErr, io.EOF) {
+			break
+		} else if errors.Is(readErr, context.DeadlineExceeded) {
+			return 0, 0, errors.New("timeout reading files")
+		} else if readErr != nil {
+			return 0, 0, fmt.Errorf("failed to read files: %w", readErr)
+		}
+	}
+
+	if err = walker.Close(); err != nil {
+		return 0, 0, fmt.Errorf("failed to close walker: %w", err)
+	}
+
+	return traversed, matched, nil
+}
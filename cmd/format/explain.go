@@ -0,0 +1,195 @@
+package format
+
+import (
+	"errors"
+	"fmt"
+	"os"
+	"os/exec"
+	"path/filepath"
+	"strings"
+
+	"github.com/numtide/treefmt/v2/config"
+	"github.com/numtide/treefmt/v2/format"
+	"github.com/numtide/treefmt/v2/stats"
+	"github.com/numtide/treefmt/v2/walk"
+)
+
+// explain prints a step-by-step report to stdout describing whether path would be formatted when treefmt is run
+// against the tree root, and why.
+func explain(cfg *config.Config, statz *stats.Stats, path string) error {
+	formatter, err := format.NewCompositeFormatter(cfg, statz, cfg.BatchSize)
+	if err != nil {
+		return fmt.Errorf("failed to create composite formatter: %w", err)
+	}
+
+	walkType, err := walk.TypeString(cfg.Walk)
+	if err != nil {
+		return fmt.Errorf("invalid walk type: %w", err)
+	}
+
+	absPath, err := filepath.Abs(path)
+	if err != nil {
+		return fmt.Errorf("error computing absolute path of %s: %w", path, err)
+	}
+
+	relPath, err := filepath.Rel(cfg.TreeRoot, absPath)
+	if err != nil {
+		return fmt.Errorf("error computing relative path from %s to %s: %w", cfg.TreeRoot, absPath, err)
+	}
+
+	// the first reason for skipping the path determines the verdict, but we report on every step regardless
+	var skipped string
+
+	skip := func(reason string, args ...any) {
+		if skipped == "" {
+			skipped = fmt.Sprintf(reason, args...)
+		}
+	}
+
+	report := func(step string, detail string, args ...any) {
+		fmt.Printf("  %s: %s\n", step, fmt.Sprintf(detail, args...))
+	}
+
+	fmt.Println(path)
+
+	if strings.HasPrefix(relPath, "..") {
+		report("tree root", "outside %s", cfg.TreeRoot)
+		fmt.Println("verdict: skipped because it is outside the tree root")
+
+		return nil
+	}
+
+	report("tree root", "inside %s as %s", cfg.TreeRoot, relPath)
+
+	info, err := os.Stat(absPath)
+	if err != nil {
+		report("file", "not found")
+		skip("it does not exist")
+	} else if info.IsDir() {
+		report("file", "is a directory")
+		skip("it is a directory")
+	}
+
+	// determine which walker would be used, as they differ in which files they traverse
+	if walkType == walk.Auto {
+		walkType = walk.Filesystem
+		if isGitWorkTree(cfg.TreeRoot) {
+			walkType = walk.Git
+		}
+	}
+
+	switch {
+	case walk.IsInternal(relPath, walk.RelCacheDir(cfg.TreeRoot, cfg.CacheDir)):
+		report("walk", "a temporary file or the cache, so it is never traversed")
+		skip("it belongs to treefmt itself")
+	case walkType == walk.Git:
+		tracked, err := isTrackedByGit(cfg.TreeRoot, relPath)
+		if err != nil {
+			return err
+		}
+
+		if tracked {
+			report("walk", "tracked by git")
+		} else {
+			report("walk", "not tracked by git, so it is not traversed")
+			skip("it is not tracked by git")
+		}
+	default:
+		report("walk", "traversed by the %s walker", walkType)
+	}
+
+	if cfg.MaxDepth > 0 {
+		depth := strings.Count(relPath, string(filepath.Separator)) + 1
+		if depth > cfg.MaxDepth {
+			report("max depth", "nested %d levels deep, beyond the max depth of %d", depth, cfg.MaxDepth)
+			skip("it is nested beyond the max depth")
+		} else {
+			report("max depth", "nested %d levels deep, within the max depth of %d", depth, cfg.MaxDepth)
+		}
+	}
+
+	file := &walk.File{Path: absPath, RelPath: relPath, Info: info}
+
+	globalExclude, matches := formatter.Explain(file)
+	switch {
+	case globalExclude != "":
+		report("global excludes", "excluded by %q", globalExclude)
+		skip("it matches the global exclude %q", globalExclude)
+	case cfg.NoGlobalExcludes:
+		report("global excludes", "ignored because of --no-global-excludes")
+	default:
+		report("global excludes", "no match")
+	}
+
+	if limit := formatter.MaxFileSize(); limit > 0 && info != nil && info.Mode().IsRegular() {
+		if size := info.Size(); size > limit {
+			report("max file size", "%d bytes, larger than the max-file-size of %d bytes", size, limit)
+			skip("it is larger than the max-file-size of %d bytes", limit)
+		} else {
+			report("max file size", "%d bytes, within the max-file-size of %d bytes", size, limit)
+		}
+	}
+
+	for _, match := range matches {
+		step := "formatter " + match.Formatter.Name()
+
+		switch {
+		case match.Include == "":
+			report(step, "no include matches")
+		case match.Exclude != "":
+			report(step, "included by %q but excluded by %q", match.Include, match.Exclude)
+		case match.MaxFileSize > 0:
+			report(step, "included by %q but larger than its max-file-size of %d bytes", match.Include, match.MaxFileSize)
+		default:
+			report(step, "included by %q", match.Include)
+		}
+	}
+
+	// formatters are reported by name above, but applied in order of priority
+	applied := formatter.FormattersFor(file)
+	if len(applied) == 0 {
+		skip("no formatter matches it")
+	}
+
+	if skipped != "" {
+		fmt.Printf("verdict: skipped because %s\n", skipped)
+
+		return nil
+	}
+
+	names := make([]string, len(applied))
+	for i, f := range applied {
+		names[i] = f.Name()
+	}
+
+	fmt.Printf("verdict: formatted by %s\n", strings.Join(names, ", then "))
+
+	return nil
+}
+
+// isGitWorkTree returns true if root is within a git work tree.
+func isGitWorkTree(root string) bool {
+	cmd := exec.Command("git", "rev-parse", "--is-inside-work-tree")
+	cmd.Dir = root
+
+	out, err := cmd.Output()
+
+	return err == nil && strings.TrimSpace(string(out)) == "true"
+}
+
+// isTrackedByGit returns true if relPath is tracked within the git work tree at root.
+func isTrackedByGit(root string, relPath string) (bool, error) {
+	cmd := exec.Command("git", "ls-files", "--error-unmatch", "--", relPath)
+	cmd.Dir = root
+
+	err := cmd.Run()
+
+	var exitErr *exec.ExitError
+	if errors.As(err, &exitErr) {
+		return false, nil
+	} else if err != nil {
+		return false, fmt.Errorf("failed to check if %s is tracked by git: %w", relPath, err)
+	}
+
+	return true, nil
+}
@@ -38,6 +38,56 @@ func Run(v *viper.Viper, statz *stats.Stats, cmd *cobra.Command, paths []string)
 		return fmt.Errorf("failed to load config: %w", err)
 	}
 
+	// the stats were created before the config file was read, so this includes reading it
+	statz.AddPhase("load config", statz.Elapsed())
+
+	if len(cfg.FormatterConfigs) == 0 {
+		return fmt.Errorf("no formatters defined in %s; add a [formatter.<name>] section", v.ConfigFileUsed())
+	}
+
+	if cfg.Validate {
+		// creating the composite formatter resolves every command and compiles every glob, without executing anything,
+		// so the min-version of each formatter is not checked
+		if _, err = format.NewCompositeFormatter(cfg, statz, cfg.BatchSize); err != nil {
+			return fmt.Errorf("invalid config: %w", err)
+		}
+
+		fmt.Printf("%s is valid\n", v.ConfigFileUsed())
+
+		return nil
+	}
+
+	if cfg.PrintCachePath {
+		dir := cfg.CacheDir
+		if dir == "" {
+			dir = cache.Dir()
+		}
+
+		fmt.Println(dir)
+		fmt.Println(cache.File(dir, cfg.TreeRoot))
+
+		return nil
+	}
+
+	// the tree root may lie anywhere relative to the config file, but it must be a directory, otherwise walking it
+	// fails in ways which do not point back at the tree root
+	treeRootInfo, err := os.Stat(cfg.TreeRoot)
+	if err != nil {
+		return fmt.Errorf("invalid tree root: %w", err)
+	}
+
+	if !treeRootInfo.IsDir() {
+		return fmt.Errorf("invalid tree root: %s is not a directory", cfg.TreeRoot)
+	}
+
+	if cfg.Explain != "" {
+		if len(paths) > 0 {
+			return errors.New("--explain cannot be used with path arguments")
+		}
+
+		return explain(cfg, statz, cfg.Explain)
+	}
+
 	if cfg.CI {
 		log.Info("ci mode enabled")
 
@@ -56,7 +106,9 @@ func Run(v *viper.Viper, statz *stats.Stats, cmd *cobra.Command, paths []string)
 		// This can fail in CI between checkout and running treefmt if everything happens too quickly.
 		// For humans, the second level precision should not be a problem as they are unlikely to run treefmt in
 		// sub-second succession.
+		endPhase := statz.Phase("wait for next second")
 		time.Sleep(time.Until(startAfter))
+		endPhase()
 	}
 
 	// cpu profiling
@@ -81,17 +133,38 @@ func Run(v *viper.Viper, statz *stats.Stats, cmd *cobra.Command, paths []string)
 
 	// open the db unless --no-cache was specified
 	if !cfg.NoCache {
-		db, err = cache.Open(cfg.TreeRoot)
+		endPhase := statz.Phase("open cache")
+
+		configFile, err := filepath.Abs(v.ConfigFileUsed())
+		if err != nil {
+			return fmt.Errorf("failed to get absolute path for config file: %w", err)
+		}
+
+		if cfg.CacheMode == config.CacheModeReadOnly {
+			// db will be nil if there is no cache to read from
+			db, err = cache.OpenReadOnly(cfg.CacheDir, cfg.TreeRoot)
+		} else {
+			db, err = cache.Open(cfg.CacheDir, cfg.TreeRoot, configFile)
+		}
+
 		if err != nil {
 			return fmt.Errorf("failed to open cache: %w", err)
 		}
 
 		// ensure db is closed after we're finished
-		defer func() {
-			if err := db.Close(); err != nil {
-				log.Errorf("failed to close cache: %v", err)
-			}
-		}()
+		if db != nil {
+			defer func() {
+				if err := db.Close(); err != nil {
+					log.Errorf("failed to close cache: %v", err)
+				}
+			}()
+		}
+
+		endPhase()
+	}
+
+	if cfg.ClearCache && cfg.CacheMode == config.CacheModeReadOnly {
+		return errors.New("--clear-cache cannot be used with a read-only cache")
 	}
 
 	if db != nil {
@@ -121,6 +194,62 @@ func Run(v *viper.Viper, statz *stats.Stats, cmd *cobra.Command, paths []string)
 		return fmt.Errorf("invalid walk type: %w", err)
 	}
 
+	// the path and content are both read from a json request, in place of the path argument and stdin
+	var exchange *walk.StdinExchange
+
+	if cfg.StdinJSON {
+		switch {
+		case walkType != walk.Stdin:
+			return errors.New("--stdin-json can only be used with --stdin")
+		case len(paths) > 0:
+			return errors.New("--stdin-json reads the path from the request, it cannot be given as an argument")
+		case cfg.Output != "":
+			return errors.New("--output cannot be used with --stdin-json")
+		case cfg.StdinReport:
+			return errors.New("--stdin-report cannot be used with --stdin-json")
+		}
+
+		request, requestErr := readStdinRequest(os.Stdin)
+		if requestErr != nil {
+			return writeStdinError(os.Stdout, requestErr)
+		}
+
+		if request.Filename != "" {
+			paths = []string{request.Filename}
+		}
+
+		exchange = &walk.StdinExchange{Content: []byte(request.Content)}
+		ctx = walk.SetStdinExchange(ctx, exchange)
+	}
+
+	// the path given with --stdin names the file the content belongs to, which cannot be a directory
+	if walkType == walk.Stdin && len(paths) > 0 && isDirectory(paths[0]) {
+		return fmt.Errorf("--stdin expects a file path, got a directory: %s", paths[0])
+	}
+
+	if cfg.Language != "" {
+		if walkType != walk.Stdin {
+			return errors.New("--language can only be used with --stdin")
+		}
+
+		// the content has no meaningful path, but we still need a name for the temporary file it is written to
+		if len(paths) == 0 {
+			paths = []string{filepath.Join(cfg.TreeRoot, "stdin")}
+		}
+	}
+
+	if cfg.StdinDetect {
+		if walkType != walk.Stdin {
+			return errors.New("--stdin-detect can only be used with --stdin")
+		}
+
+		// detection is a last resort, used only when neither a path nor a language has been given
+		if len(paths) == 0 {
+			ctx = walk.SetStdinDetect(ctx, true)
+			paths = []string{filepath.Join(cfg.TreeRoot, "stdin")}
+		}
+	}
+
 	if walkType == walk.Stdin && len(paths) != 1 {
 		// check we have only received one path arg which we use for the file extension / matching to formatters
 		return errors.New("exactly one path should be specified when using the --stdin flag")
@@ -129,42 +258,365 @@ func Run(v *viper.Viper, statz *stats.Stats, cmd *cobra.Command, paths []string)
 	// checks all paths are contained within the tree root and exist
 	// also "normalize" paths so they're relative to cfg.TreeRoot
 	for i, path := range paths {
-		absolutePath, err := filepath.Abs(path)
-		if err != nil {
-			return fmt.Errorf("error computing absolute path of %s: %w", path, err)
+		if paths[i], err = relativeToTreeRoot(cfg, path, walkType != walk.Stdin); err != nil {
+			return err
 		}
 
-		relativePath, err := filepath.Rel(cfg.TreeRoot, absolutePath)
-		if err != nil {
-			return fmt.Errorf("error computing relative path from %s to %s: %w", cfg.TreeRoot, absolutePath, err)
+		// symlinks named explicitly are formatted via their target, whereas those found while walking are skipped
+		if walkType != walk.Stdin {
+			if paths[i], err = resolveSymlink(cfg, paths[i]); err != nil {
+				return err
+			}
+		}
+	}
+
+	// forced paths are normalized in the same way
+	for i, path := range cfg.Force {
+		if cfg.Force[i], err = relativeToTreeRoot(cfg, path, true); err != nil {
+			return err
 		}
+	}
+
+	if walkType != walk.Stdin && cfg.Output != "" {
+		return errors.New("--output can only be used with --stdin")
+	}
+
+	if walkType == walk.Stdin && cfg.Watch {
+		return errors.New("--watch cannot be used with --stdin")
+	}
+
+	if walkType == walk.Stdin && cfg.ChangedFiles {
+		return errors.New("--changed-files cannot be used with --stdin")
+	}
 
-		if strings.HasPrefix(relativePath, "..") {
-			return fmt.Errorf("path %s not inside the tree root %s", path, cfg.TreeRoot)
+	// the output is only the NUL terminated paths, so it is always safe to pass to xargs -0
+	if cfg.Print0 {
+		switch {
+		case walkType == walk.Stdin:
+			return errors.New("--print0 cannot be used with --stdin")
+		case cfg.ChangedFiles:
+			return errors.New("--print0 cannot be used with --changed-files")
+		case cfg.Porcelain:
+			return errors.New("--print0 cannot be used with --porcelain")
+		case cfg.Diff:
+			return errors.New("--print0 cannot be used with --diff")
 		}
+	}
+
+	// porcelain output is written to stdout in place of any other output
+	if cfg.Porcelain {
+		switch {
+		case walkType == walk.Stdin:
+			return errors.New("--porcelain cannot be used with --stdin")
+		case cfg.ChangedFiles:
+			return errors.New("--porcelain cannot be used with --changed-files")
+		case cfg.Interactive:
+			return errors.New("--porcelain cannot be used with --interactive")
+		}
+	}
 
-		paths[i] = relativePath
+	// diffs are written to stdout, so they cannot be mixed with formatted content or other machine readable output
+	if cfg.Diff {
+		switch {
+		case walkType == walk.Stdin:
+			return errors.New("--diff cannot be used with --stdin")
+		case cfg.Porcelain:
+			return errors.New("--diff cannot be used with --porcelain")
+		case cfg.Interactive:
+			return errors.New("--diff cannot be used with --interactive")
+		}
+	}
 
+	// the changes are confirmed by answering a prompt, so we need stdin to ourselves and someone to answer it
+	if cfg.Interactive {
+		switch {
+		case walkType == walk.Stdin:
+			return errors.New("--interactive cannot be used with --stdin")
+		case cfg.Watch:
+			return errors.New("--interactive cannot be used with --watch")
+		case !isTerminal(os.Stdin):
+			return errors.New("--interactive requires stdin to be a terminal")
+		}
+	}
+
+	// staged content is extracted into temporary files, which are only cleaned up once they have been formatted
+	if walkType == walk.Staged {
+		switch {
+		case cfg.Watch:
+			return errors.New("--watch cannot be used with --staged")
+		case cfg.ChangedFiles:
+			return errors.New("--changed-files cannot be used with --staged")
+		case cfg.Porcelain:
+			return errors.New("--porcelain cannot be used with --staged")
+		case cfg.ListFiles:
+			return errors.New("--list-files cannot be used with --staged")
+		case cfg.DumpMatches:
+			return errors.New("--dump-matches cannot be used with --staged")
+		case cfg.BenchWalk != 0:
+			return errors.New("--bench-walk cannot be used with --staged")
+		}
+	}
+
+	// only the files which changed since the given ref are read, which requires git
+	if cfg.Since != "" {
+		if walkType != walk.Auto && walkType != walk.Git {
+			return fmt.Errorf("--since cannot be used with the %s walk", walkType)
+		}
+
+		if cfg.OnlyChanged {
+			return errors.New("--only-changed cannot be used with --since")
+		}
+
+		if err = walk.VerifyRef(cfg.TreeRoot, cfg.Since); err != nil {
+			return fmt.Errorf("invalid --since: %w", err)
+		}
+
+		walkType = walk.Git
+		ctx = walk.SetSince(ctx, cfg.Since)
+	}
+
+	// likewise for files with uncommitted changes
+	if cfg.OnlyChanged {
+		if walkType != walk.Auto && walkType != walk.Git {
+			return fmt.Errorf("--only-changed cannot be used with the %s walk", walkType)
+		}
+
+		walkType = walk.Git
+		ctx = walk.SetOnlyChanged(ctx, true)
+	}
+
+	if cfg.StdinReport {
 		if walkType != walk.Stdin {
-			if _, err = os.Stat(absolutePath); err != nil {
-				return fmt.Errorf("path %s not found", path)
-			}
+			return errors.New("--stdin-report can only be used with --stdin")
 		}
+
+		ctx = walk.SetStdinReport(ctx, true)
+	}
+
+	if cfg.ListFiles {
+		if walkType == walk.Stdin {
+			return errors.New("--list-files cannot be used with --stdin")
+		}
+
+		return listFiles(ctx, cfg, statz, walkType, paths)
 	}
 
+	if cfg.DumpMatches {
+		if walkType == walk.Stdin {
+			return errors.New("--dump-matches cannot be used with --stdin")
+		}
+
+		return dumpMatches(ctx, cfg, statz, walkType, paths)
+	}
+
+	if cfg.SelfTest {
+		if walkType == walk.Stdin || walkType == walk.Staged {
+			return fmt.Errorf("--self-test cannot be used with --%s", walkType)
+		}
+
+		return selfTest(ctx, cfg, statz, walkType, paths)
+	}
+
+	if cfg.BenchWalk != 0 {
+		if walkType == walk.Stdin {
+			return errors.New("--bench-walk cannot be used with --stdin")
+		}
+
+		if cfg.BenchWalk < 0 {
+			return errors.New("--bench-walk must be a positive number of runs")
+		}
+
+		return benchWalk(ctx, cfg, statz, walkType, paths)
+	}
+
+	if len(cfg.Force) > 0 {
+		if db == nil {
+			log.Warn("--force has no effect when the cache is disabled")
+		}
+
+		ctx = walk.SetForce(ctx, cfg.Force)
+	}
+
+	changed, changedBy, err := process(ctx, cfg, statz, walkType, paths, db)
+
+	// print stats to stderr, unless they are replaced by the stdin report
+	if !cfg.Quiet && !cfg.NoSummary && !cfg.StdinReport {
+		statz.PrintToStderr(db != nil)
+	}
+
+	if cfg.Profile {
+		statz.PrintPhasesToStderr()
+	}
+
+	if cfg.ProfileFile != "" {
+		if profileErr := statz.WritePhases(cfg.ProfileFile); profileErr != nil {
+			log.Errorf("failed to write profile: %v", profileErr)
+		}
+	}
+
+	if exchange != nil {
+		if err != nil {
+			return writeStdinError(os.Stdout, err)
+		}
+
+		return writeStdinResponse(os.Stdout, exchange, changed)
+	}
+
+	if err != nil {
+		return err
+	}
+
+	if cfg.ChangedFiles {
+		for _, change := range changed {
+			fmt.Println(change.Path)
+		}
+	}
+
+	if cfg.Print0 {
+		for _, change := range changed {
+			fmt.Printf("%s\x00", change.Path)
+		}
+	}
+
+	if cfg.Diff {
+		for _, change := range changed {
+			fmt.Print(change.Diff)
+		}
+	}
+
+	if cfg.Porcelain {
+		// this format must remain stable, as scripts depend on it
+		for _, change := range changed {
+			fmt.Printf("M\t%s\t%s\n", strings.Join(change.Formatters, ","), change.Path)
+		}
+	}
+
+	if len(cfg.FailOnChangeFormatters) > 0 {
+		// only changes made by the given formatters count
+		if len(changedBy) > 0 {
+			return fmt.Errorf("%w: files were changed by %s", ErrFailOnChange, strings.Join(changedBy, ", "))
+		}
+	} else if cfg.FailOnChange && statz.Value(stats.Changed) != 0 {
+		// if fail on change has been enabled, check that no files were actually changed, throwing an error if so
+		return ErrFailOnChange
+	}
+
+	if cfg.Watch {
+		reload := func() (*config.Config, error) {
+			return reloadViper(v, cmd.Flags())
+		}
+
+		return watch(ctx, cfg, v.ConfigFileUsed(), reload, walkType, paths, db)
+	}
+
+	return nil
+}
+
+// relativeToTreeRoot returns path relative to the tree root, returning an error if it lies outside the tree root or,
+// when mustExist is true, it does not exist.
+func relativeToTreeRoot(cfg *config.Config, path string, mustExist bool) (string, error) {
+	absolutePath, err := filepath.Abs(path)
+	if err != nil {
+		return "", fmt.Errorf("error computing absolute path of %s: %w", path, err)
+	}
+
+	relativePath, err := filepath.Rel(cfg.TreeRoot, absolutePath)
+	if err != nil {
+		return "", fmt.Errorf("error computing relative path from %s to %s: %w", cfg.TreeRoot, absolutePath, err)
+	}
+
+	if strings.HasPrefix(relativePath, "..") {
+		return "", fmt.Errorf("path %s not inside the tree root %s", path, cfg.TreeRoot)
+	}
+
+	if mustExist {
+		if _, err = os.Stat(absolutePath); err != nil {
+			return "", fmt.Errorf("path %s not found", path)
+		}
+	}
+
+	return relativePath, nil
+}
+
+// isDirectory returns true if path is an existing directory, or ends with a path separator and so could only ever
+// be one.
+func isDirectory(path string) bool {
+	if strings.HasSuffix(path, string(filepath.Separator)) {
+		return true
+	}
+
+	info, err := os.Stat(path)
+
+	return err == nil && info.IsDir()
+}
+
+// resolveSymlink returns the path, relative to the tree root, of the target of the symlink at relPath, returning an
+// error if the target lies outside the tree root.
+// If relPath is not a symlink, it is returned unchanged.
+func resolveSymlink(cfg *config.Config, relPath string) (string, error) {
+	path := filepath.Join(cfg.TreeRoot, relPath)
+
+	info, err := os.Lstat(path)
+	if err != nil {
+		return "", fmt.Errorf("failed to stat %s: %w", path, err)
+	} else if info.Mode()&os.ModeSymlink == 0 {
+		return relPath, nil
+	}
+
+	target, err := filepath.EvalSymlinks(path)
+	if err != nil {
+		return "", fmt.Errorf("failed to resolve symlink %s: %w", relPath, err)
+	}
+
+	// the tree root may itself be reached via a symlink, e.g. /tmp on macOS, so we compare against its resolved path
+	treeRoot, err := filepath.EvalSymlinks(cfg.TreeRoot)
+	if err != nil {
+		return "", fmt.Errorf("failed to resolve tree root %s: %w", cfg.TreeRoot, err)
+	}
+
+	targetRelPath, err := filepath.Rel(treeRoot, target)
+	if err != nil || strings.HasPrefix(targetRelPath, "..") {
+		return "", fmt.Errorf(
+			"symlink %s resolves to %s, which is not inside the tree root %s", relPath, target, cfg.TreeRoot,
+		)
+	}
+
+	log.Debugf("resolved symlink %s to %s", relPath, targetRelPath)
+
+	return targetRelPath, nil
+}
+
+// process traverses the given paths, applying the configured formatters to each file.
+// It returns a sorted list of the paths, relative to the tree root, of files which were changed, and a sorted list of
+// the formatters named in fail-on-change-formatters which changed them.
+func process(
+	ctx context.Context,
+	cfg *config.Config,
+	statz *stats.Stats,
+	walkType walk.Type,
+	paths []string,
+	db *bolt.DB,
+) ([]format.Change, []string, error) {
 	// create a composite formatter which will handle applying the correct formatters to each file we traverse
-	formatter, err := format.NewCompositeFormatter(cfg, statz, BatchSize)
+	formatter, err := format.NewCompositeFormatter(cfg, statz, cfg.BatchSize)
 	if err != nil {
-		return fmt.Errorf("failed to create composite formatter: %w", err)
+		return nil, nil, fmt.Errorf("failed to create composite formatter: %w", err)
+	}
+
+	if cfg.Interactive {
+		formatter.ConfirmWith(confirmChanges(os.Stdin, os.Stderr))
 	}
 
 	// create a new walker for traversing the paths
-	walker, err := walk.NewCompositeReader(walkType, cfg.TreeRoot, paths, db, statz)
+	walker, err := walk.NewCompositeReader(
+		walkType, cfg.TreeRoot, paths, cfg.MaxDepth, cfg.CacheDir, cfg.Output, db, statz,
+	)
 	if err != nil {
-		return fmt.Errorf("failed to create walker: %w", err)
+		return nil, nil, fmt.Errorf("failed to create walker: %w", err)
 	}
 
 	// start traversing
+	endWalkPhase := statz.Phase("walk and format")
 	files := make([]*walk.File, BatchSize)
 
 	var (
@@ -193,16 +645,24 @@ func Run(v *viper.Viper, statz *stats.Stats, cmd *cobra.Command, paths []string)
 		}
 	}
 
+	endWalkPhase()
+
 	// finalize formatting (there could be formatting tasks in-flight)
+	endFormatPhase := statz.Phase("finalise formatting")
 	formatCloseErr := formatter.Close(ctx)
 
+	endFormatPhase()
+
+	// when traversing the whole tree, point out formatters which ended up with nothing to do
+	if len(paths) == 0 && formatErr == nil && errors.Is(readErr, io.EOF) {
+		formatter.ReportUnmatched()
+	}
+
 	// close the walker, ensuring any pending file release hooks finish
+	endClosePhase := statz.Phase("close walker")
 	walkerCloseErr := walker.Close()
 
-	// print stats to stderr
-	if !cfg.Quiet {
-		statz.PrintToStderr()
-	}
+	endClosePhase()
 
 	// process errors
 
@@ -212,28 +672,30 @@ func Run(v *viper.Viper, statz *stats.Stats, cmd *cobra.Command, paths []string)
 		log.Debugf("no more files to read")
 	} else if errors.Is(readErr, context.DeadlineExceeded) {
 		// the read timed-out
-		return errors.New("timeout reading files")
+		return nil, nil, errors.New("timeout reading files")
 	} else if readErr != nil {
 		// something unexpected happened
-		return fmt.Errorf("failed to read files: %w", readErr)
+		return nil, nil, fmt.Errorf("failed to read files: %w", readErr)
 	}
 
 	if formatErr != nil {
-		return fmt.Errorf("failed to format files: %w", formatErr)
+		return nil, nil, fmt.Errorf("failed to format files: %w", formatErr)
 	}
 
 	if formatCloseErr != nil {
-		return fmt.Errorf("failed to finalise formatting: %w", formatCloseErr)
+		return nil, nil, fmt.Errorf("failed to finalise formatting: %w", formatCloseErr)
 	}
 
 	if walkerCloseErr != nil {
-		return fmt.Errorf("failed to close walker: %w", walkerCloseErr)
+		return nil, nil, fmt.Errorf("failed to close walker: %w", walkerCloseErr)
 	}
 
-	if cfg.FailOnChange && statz.Value(stats.Changed) != 0 {
-		// if fail on change has been enabled, check that no files were actually changed, throwing an error if so
-		return ErrFailOnChange
+	// the files which were formatted in time have been cached, so running again continues where we left off
+	if remaining := formatter.Remaining(); remaining > 0 {
+		return nil, nil, fmt.Errorf(
+			"%w: %d file(s) were left unformatted, run treefmt again to continue", format.ErrDeadlineReached, remaining,
+		)
 	}
 
-	return nil
+	return formatter.Changed(), formatter.ChangedBy(), nil
 }
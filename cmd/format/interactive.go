@@ -0,0 +1,54 @@
+package format
+
+import (
+	"bufio"
+	"errors"
+	"fmt"
+	"io"
+	"os"
+	"slices"
+	"strings"
+
+	"github.com/numtide/treefmt/v2/format"
+	"golang.org/x/term"
+)
+
+// confirmChanges returns a format.ConfirmFunc which writes a summary of the changes made by each formatter to w, then
+// asks whether to apply them, reading the answer from r. Anything other than yes declines.
+func confirmChanges(r io.Reader, w io.Writer) format.ConfirmFunc {
+	return func(changedBy map[string]int, total int) (bool, error) {
+		names := make([]string, 0, len(changedBy))
+		for name := range changedBy {
+			names = append(names, name)
+		}
+
+		slices.Sort(names)
+
+		_, _ = fmt.Fprintln(w, "\nformatting would change:")
+
+		for _, name := range names {
+			_, _ = fmt.Fprintf(w, "  %s: %d file(s)\n", name, changedBy[name])
+		}
+
+		_, _ = fmt.Fprintf(w, "Apply these %d changes? [y/N] ", total)
+
+		answer, err := bufio.NewReader(r).ReadString('\n')
+		if err != nil && !errors.Is(err, io.EOF) {
+			return false, fmt.Errorf("failed to read answer: %w", err)
+		}
+
+		switch strings.ToLower(strings.TrimSpace(answer)) {
+		case "y", "yes":
+			return true, nil
+		default:
+			_, _ = fmt.Fprintln(w, "no changes were applied")
+
+			return false, nil
+		}
+	}
+}
+
+// isTerminal returns true if f is a terminal, rather than e.g. a pipe, a regular file or /dev/null.
+func isTerminal(f *os.File) bool {
+	return term.IsTerminal(int(f.Fd()))
+}
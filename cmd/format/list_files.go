@@ -0,0 +1,136 @@
+package format
+
+import (
+	"context"
+	"encoding/json"
+	"errors"
+	"fmt"
+	"io"
+	"slices"
+	"time"
+
+	"github.com/charmbracelet/log"
+	"github.com/numtide/treefmt/v2/config"
+	"github.com/numtide/treefmt/v2/format"
+	"github.com/numtide/treefmt/v2/stats"
+	"github.com/numtide/treefmt/v2/walk"
+)
+
+// listFiles traverses the given paths and prints to stdout, grouped by formatter, every file which would be passed to
+// each formatter. Nothing is formatted.
+func listFiles(
+	ctx context.Context,
+	cfg *config.Config,
+	statz *stats.Stats,
+	walkType walk.Type,
+	paths []string,
+) error {
+	filesByFormatter, err := matchFiles(ctx, cfg, statz, walkType, paths)
+	if err != nil {
+		return err
+	}
+
+	names := make([]string, 0, len(filesByFormatter))
+	for name := range filesByFormatter {
+		names = append(names, name)
+	}
+
+	slices.Sort(names)
+
+	for _, name := range names {
+		if description := cfg.FormatterConfigs[name].Description; description != "" {
+			fmt.Printf("%s: # %s\n", name, description)
+		} else {
+			fmt.Printf("%s:\n", name)
+		}
+
+		for _, relPath := range filesByFormatter[name] {
+			fmt.Printf("  %s\n", relPath)
+		}
+	}
+
+	return nil
+}
+
+// dumpMatches traverses the given paths and prints to stdout a JSON object mapping the name of each formatter to the
+// sorted paths, relative to the tree root, of every file which would be passed to it.
+// Formatters which match no files are omitted. Nothing is formatted.
+func dumpMatches(
+	ctx context.Context,
+	cfg *config.Config,
+	statz *stats.Stats,
+	walkType walk.Type,
+	paths []string,
+) error {
+	filesByFormatter, err := matchFiles(ctx, cfg, statz, walkType, paths)
+	if err != nil {
+		return err
+	}
+
+	// the keys of a map are always encoded in sorted order, so the output is stable between runs
+	out, err := json.MarshalIndent(filesByFormatter, "", "  ")
+	if err != nil {
+		return fmt.Errorf("failed to marshal matches: %w", err)
+	}
+
+	fmt.Println(string(out))
+
+	return nil
+}
+
+// matchFiles traverses the given paths and returns the sorted relative paths of the files matched by each formatter.
+func matchFiles(
+	ctx context.Context,
+	cfg *config.Config,
+	statz *stats.Stats,
+	walkType walk.Type,
+	paths []string,
+) (map[string][]string, error) {
+	formatter, err := format.NewCompositeFormatter(cfg, statz, cfg.BatchSize)
+	if err != nil {
+		return nil, fmt.Errorf("failed to create composite formatter: %w", err)
+	}
+
+	// we never use the cache, as we want to list every file regardless of whether it has changed
+	walker, err := walk.NewCompositeReader(walkType, cfg.TreeRoot, paths, cfg.MaxDepth, cfg.CacheDir, "", nil, statz)
+	if err != nil {
+		return nil, fmt.Errorf("failed to create walker: %w", err)
+	}
+
+	filesByFormatter := make(map[string][]string)
+	files := make([]*walk.File, BatchSize)
+
+	for {
+		readCtx, cancelRead := context.WithTimeout(ctx, 1*time.Second)
+
+		n, readErr := walker.Read(readCtx, files)
+		log.Debugf("read %d files", n)
+
+		cancelRead()
+
+		for _, file := range files[:n] {
+			for _, f := range formatter.FormattersFor(file) {
+				filesByFormatter[f.Name()] = append(filesByFormatter[f.Name()], file.RelPath)
+			}
+		}
+
+		//nolint:gocritic
+		if errors.Is(readErr, io.EOF) {
+			break
+		} else if errors.Is(readErr, context.DeadlineExceeded) {
+			return nil, errors.New("timeout reading files")
+		} else if readErr != nil {
+			return nil, fmt.Errorf("failed to read files: %w", readErr)
+		}
+	}
+
+	if err = walker.Close(); err != nil {
+		return nil, fmt.Errorf("failed to close walker: %w", err)
+	}
+
+	for _, relPaths := range filesByFormatter {
+		slices.Sort(relPaths)
+	}
+
+	return filesByFormatter, nil
+}
@@ -0,0 +1,127 @@
+package format
+
+import (
+	"context"
+	"errors"
+	"fmt"
+	"io"
+	"os"
+	"text/tabwriter"
+	"time"
+
+	"github.com/charmbracelet/log"
+	"github.com/numtide/treefmt/v2/config"
+	"github.com/numtide/treefmt/v2/format"
+	"github.com/numtide/treefmt/v2/stats"
+	"github.com/numtide/treefmt/v2/walk"
+)
+
+// selfTest checks each configured formatter against the smallest file it matches in the given paths, printing a
+// table of the results to stdout. The files in the tree are never modified, as the formatters are applied to copies.
+func selfTest(
+	ctx context.Context,
+	cfg *config.Config,
+	statz *stats.Stats,
+	walkType walk.Type,
+	paths []string,
+) error {
+	// formatters which cannot be found are reported in the results, rather than failing straight away
+	cfg.AllowMissingFormatter = true
+
+	formatter, err := format.NewCompositeFormatter(cfg, statz, cfg.BatchSize)
+	if err != nil {
+		return fmt.Errorf("failed to create composite formatter: %w", err)
+	}
+
+	samples, err := findSamples(ctx, cfg, formatter, statz, walkType, paths)
+	if err != nil {
+		return err
+	}
+
+	results := formatter.SelfTest(ctx, samples)
+
+	w := tabwriter.NewWriter(os.Stdout, 0, 0, 2, ' ', 0)
+	_, _ = fmt.Fprintln(w, "FORMATTER\tRESULT\tSAMPLE\tDESCRIPTION")
+
+	failed := 0
+
+	for _, result := range results {
+		outcome := "pass"
+
+		switch {
+		case result.Err != nil:
+			outcome = "fail: " + result.Err.Error()
+			failed++
+		case result.Warning != nil:
+			outcome = "warn: " + result.Warning.Error()
+		}
+
+		_, _ = fmt.Fprintf(
+			w, "%s\t%s\t%s\t%s\n",
+			result.Name, outcome, result.Sample, cfg.FormatterConfigs[result.Name].Description,
+		)
+	}
+
+	if err = w.Flush(); err != nil {
+		return fmt.Errorf("failed to write results: %w", err)
+	}
+
+	if failed > 0 {
+		return fmt.Errorf("%d of %d formatter(s) failed the self-test", failed, len(results))
+	}
+
+	return nil
+}
+
+// findSamples traverses the given paths, returning the smallest file matched by each formatter, with ties broken by
+// path so the same samples are found each time.
+func findSamples(
+	ctx context.Context,
+	cfg *config.Config,
+	formatter *format.CompositeFormatter,
+	statz *stats.Stats,
+	walkType walk.Type,
+	paths []string,
+) (map[string]*walk.File, error) {
+	walker, err := walk.NewCompositeReader(walkType, cfg.TreeRoot, paths, cfg.MaxDepth, cfg.CacheDir, "", nil, statz)
+	if err != nil {
+		return nil, fmt.Errorf("failed to create walker: %w", err)
+	}
+
+	samples := make(map[string]*walk.File)
+	files := make([]*walk.File, BatchSize)
+
+	for {
+		readCtx, cancelRead := context.WithTimeout(ctx, 1*time.Second)
+
+		n, readErr := walker.Read(readCtx, files)
+		log.Debugf("read %d files", n)
+
+		cancelRead()
+
+		for _, file := range files[:n] {
+			for _, f := range formatter.FormattersFor(file) {
+				sample, ok := samples[f.Name()]
+				if !ok || file.Info.Size() < sample.Info.Size() ||
+					(file.Info.Size() == sample.Info.Size() && file.RelPath < sample.RelPath) {
+					samples[f.Name()] = file
+				}
+			}
+		}
+
+		//nolint:gocritic
+		if errors.Is(readErr, io.EOF) {
+			break
+		} else if errors.Is(readErr, context.DeadlineExceeded) {
+			return nil, errors.New("timeout reading files")
+		} else if readErr != nil {
+			return nil, fmt.Errorf("failed to read files: %w", readErr)
+		}
+	}
+
+	if err = walker.Close(); err != nil {
+		return nil, fmt.Errorf("failed to close walker: %w", err)
+	}
+
+	return samples, nil
+}
@@ -0,0 +1,78 @@
+package format
+
+import (
+	"bytes"
+	"encoding/json"
+	"errors"
+	"fmt"
+	"io"
+	"strings"
+
+	"github.com/numtide/treefmt/v2/format"
+	"github.com/numtide/treefmt/v2/walk"
+)
+
+// stdinRequest is read from stdin when using --stdin-json.
+// Filename is used to select formatters in the same way as the path given with --stdin, and may be omitted when
+// --language or --stdin-detect is used instead.
+type stdinRequest struct {
+	Filename string `json:"filename"`
+	Content  string `json:"content"`
+}
+
+// stdinResponse is written to stdout when using --stdin-json, once the content of a stdinRequest has been formatted.
+type stdinResponse struct {
+	Formatted string `json:"formatted"`
+	Changed   bool   `json:"changed"`
+	// Formatter is a comma separated list of the formatters which changed the content, in the order they were applied
+	Formatter string `json:"formatter,omitempty"`
+}
+
+// stdinError is written to stdout in place of a stdinResponse when the request could not be read or formatted.
+type stdinError struct {
+	Error string `json:"error"`
+}
+
+// readStdinRequest decodes a single stdinRequest from r.
+func readStdinRequest(r io.Reader) (*stdinRequest, error) {
+	var request stdinRequest
+
+	decoder := json.NewDecoder(r)
+	decoder.DisallowUnknownFields()
+
+	if err := decoder.Decode(&request); err != nil {
+		return nil, fmt.Errorf("failed to decode request: %w", err)
+	}
+
+	if request.Filename == "" && request.Content == "" {
+		return nil, errors.New("request has neither a filename nor content")
+	}
+
+	return &request, nil
+}
+
+// writeStdinResponse writes the result of formatting the content in exchange to w, attributing it to the formatters
+// recorded against the single change, if any.
+func writeStdinResponse(w io.Writer, exchange *walk.StdinExchange, changes []format.Change) error {
+	response := stdinResponse{
+		Formatted: string(exchange.Formatted),
+		Changed:   !bytes.Equal(exchange.Content, exchange.Formatted),
+	}
+
+	if response.Changed && len(changes) > 0 {
+		response.Formatter = strings.Join(changes[0].Formatters, ",")
+	}
+
+	if err := json.NewEncoder(w).Encode(response); err != nil {
+		return fmt.Errorf("failed to write response: %w", err)
+	}
+
+	return nil
+}
+
+// writeStdinError writes err to w as a stdinError, returning err so it is still reported by the exit code.
+func writeStdinError(w io.Writer, err error) error {
+	_ = json.NewEncoder(w).Encode(stdinError{Error: err.Error()})
+
+	return err
+}
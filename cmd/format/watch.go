@@ -0,0 +1,528 @@
+package format
+
+import (
+	"context"
+	"errors"
+	"fmt"
+	"io/fs"
+	"os"
+	"os/exec"
+	"path/filepath"
+	"slices"
+	"strings"
+	"time"
+
+	"github.com/charmbracelet/log"
+	"github.com/fsnotify/fsnotify"
+	"github.com/numtide/treefmt/v2/config"
+	"github.com/numtide/treefmt/v2/stats"
+	"github.com/numtide/treefmt/v2/walk"
+	"github.com/spf13/pflag"
+	"github.com/spf13/viper"
+	bolt "go.etcd.io/bbolt"
+)
+
+// watchDebounce is how long we wait after the last change event before formatting, so that a burst of events (e.g. an
+// editor saving several files) results in a single run.
+const watchDebounce = 100 * time.Millisecond
+
+// reloadFunc reads the config file again, returning the config it now describes.
+type reloadFunc func() (*config.Config, error)
+
+// watch keeps treefmt resident, formatting files within the tree root as they change until ctx is cancelled.
+// When the config file, or any other file the config is read from, changes, the config is reloaded with reload and
+// the given paths are formatted again, see configInputs.
+func watch(
+	ctx context.Context,
+	cfg *config.Config,
+	configFile string,
+	reload reloadFunc,
+	walkType walk.Type,
+	paths []string,
+	db *bolt.DB,
+) error {
+	watcher, err := fsnotify.NewWatcher()
+	if err != nil {
+		return fmt.Errorf("failed to create watcher: %w", err)
+	}
+	defer watcher.Close()
+
+	// only the files and directories a normal run would read are watched and formatted
+	filter, err := newWatchFilter(cfg.TreeRoot, walkType, cfg.CacheDir)
+	if err != nil {
+		return err
+	}
+
+	// fsnotify does not watch recursively, so we register every directory within the tree root
+	if err = watchDirs(watcher, cfg.TreeRoot, filter); err != nil {
+		return err
+	}
+
+	if configFile, err = filepath.Abs(configFile); err != nil {
+		return fmt.Errorf("failed to get absolute path for config file: %w", err)
+	}
+
+	inputs := configInputs(cfg, configFile)
+	watchInputs(watcher, inputs)
+
+	log.Infof("watching %s for changes", cfg.TreeRoot)
+
+	var (
+		flush <-chan time.Time
+
+		// changed paths which are waiting to be formatted
+		pending = make(map[string]struct{})
+		// reloadPending indicates one of the inputs of the config has changed since the last run
+		reloadPending bool
+
+		// paths from the last run, used to ignore the events generated by the formatters writing to them
+		formatted   = make(map[string]struct{})
+		formattedAt time.Time
+	)
+
+	for {
+		select {
+		case <-ctx.Done():
+			return nil
+
+		case err, ok := <-watcher.Errors:
+			if !ok {
+				return nil
+			}
+
+			return fmt.Errorf("failed to watch %s: %w", cfg.TreeRoot, err)
+
+		case event, ok := <-watcher.Events:
+			if !ok {
+				return nil
+			}
+
+			_, formattedByUs := formatted[event.Name]
+			formattedByUs = formattedByUs && time.Since(formattedAt) < watchDebounce
+
+			// editors often save by replacing the file, so every kind of event counts as a change to the config
+			if inputs.contains(event.Name) && !formattedByUs {
+				log.Debugf("config input changed: %s", event.Name)
+
+				reloadPending = true
+				flush = time.After(watchDebounce)
+			}
+
+			if event.Has(fsnotify.Create) {
+				// start watching any new directories, unless they are ignored
+				if info, err := os.Stat(event.Name); err == nil && info.IsDir() {
+					if err = filter.refresh(); err != nil {
+						return err
+					}
+
+					if err = watchDirs(watcher, event.Name, filter); err != nil {
+						return err
+					}
+
+					continue
+				}
+			}
+
+			if !event.Has(fsnotify.Create) && !event.Has(fsnotify.Write) {
+				continue
+			}
+
+			// ignore temporary files being formatted, and the cache
+			if relPath, err := filepath.Rel(cfg.TreeRoot, event.Name); err == nil && walk.IsInternal(relPath, filter.cacheDir) {
+				continue
+			}
+
+			if formattedByUs {
+				continue
+			}
+
+			pending[event.Name] = struct{}{}
+			flush = time.After(watchDebounce)
+
+		case <-flush:
+			flush = nil
+
+			reloaded := false
+
+			if reloadPending {
+				reloadPending = false
+
+				if next := reloadConfig(cfg, reload); next != nil {
+					cfg = next
+					inputs = configInputs(cfg, configFile)
+					watchInputs(watcher, inputs)
+
+					reloaded = true
+				}
+			}
+
+			// the changes are filtered with the reloaded config, if any, and are never dropped by a reload
+			changedPaths, err := watchedChanges(cfg, filter, pending)
+			if err != nil {
+				log.Errorf("failed to filter changes: %v", err)
+			}
+
+			formatted, pending = pending, make(map[string]struct{})
+
+			switch {
+			case reloaded:
+				// every file is formatted again, although those whose formatters are unchanged are cache hits
+				formattedAt = watchRun(ctx, cfg, walkType, withChanges(paths, changedPaths), db)
+			case len(changedPaths) > 0:
+				formattedAt = watchRun(ctx, cfg, walkType, changedPaths, db)
+			}
+		}
+	}
+}
+
+// watchedChanges returns the paths which changed, relative to the tree root, which a normal run would format.
+// Files which have been removed again, or which are nested beyond the max depth of cfg, are left out.
+func watchedChanges(cfg *config.Config, filter *watchFilter, changed map[string]struct{}) ([]string, error) {
+	relPaths := make([]string, 0, len(changed))
+
+	for path := range changed {
+		// the file may have been removed again before we got to it
+		if _, err := os.Stat(path); err != nil {
+			continue
+		}
+
+		relPath, err := filepath.Rel(cfg.TreeRoot, path)
+		if err != nil {
+			return nil, fmt.Errorf("error computing relative path from %s to %s: %w", cfg.TreeRoot, path, err)
+		}
+
+		// as when walking the tree root, the max depth is counted from the tree root
+		if !walk.WithinMaxDepth(cfg.TreeRoot, path, cfg.MaxDepth) {
+			log.Debugf("ignoring change to %s, which is nested beyond the max depth of %d", relPath, cfg.MaxDepth)
+
+			continue
+		}
+
+		relPaths = append(relPaths, relPath)
+	}
+
+	return filter.files(relPaths)
+}
+
+// withChanges returns paths along with those of changedPaths which do not lie within any of them, so that a run of
+// paths also formats every change. With no paths, the whole tree root is formatted, which covers every change already.
+func withChanges(paths []string, changedPaths []string) []string {
+	if len(paths) == 0 {
+		return paths
+	}
+
+	result := slices.Clone(paths)
+
+	for _, changed := range changedPaths {
+		covered := slices.ContainsFunc(paths, func(path string) bool {
+			return path == "." || changed == path || strings.HasPrefix(changed, path+string(filepath.Separator))
+		})
+
+		if !covered {
+			result = append(result, changed)
+		}
+	}
+
+	return result
+}
+
+// watchRun formats the given paths, printing a summary unless asked not to, and returns the time at which it finished.
+func watchRun(ctx context.Context, cfg *config.Config, walkType walk.Type, paths []string, db *bolt.DB) time.Time {
+	statz := stats.New()
+
+	if _, _, err := process(ctx, cfg, &statz, walkType, paths, db); err != nil {
+		// a failed run should not stop us from watching
+		log.Errorf("failed to format changes: %v", err)
+	}
+
+	formattedAt := time.Now()
+
+	if !cfg.Quiet && !cfg.NoSummary {
+		_, _ = fmt.Fprintf(
+			os.Stderr,
+			"%s formatted %d files (%d changed) in %v\n",
+			formattedAt.Format(time.TimeOnly),
+			statz.Value(stats.Formatted),
+			statz.Value(stats.Changed),
+			statz.Elapsed().Round(time.Millisecond),
+		)
+	}
+
+	return formattedAt
+}
+
+// reloadViper reads the config file used by v into a new viper bound to flags, rather than into v, so that nothing
+// set whilst reading the previous config lingers, and returns the config it describes.
+func reloadViper(v *viper.Viper, flags *pflag.FlagSet) (*config.Config, error) {
+	fresh, err := config.NewViper()
+	if err != nil {
+		return nil, fmt.Errorf("failed to create viper: %w", err)
+	}
+
+	if err = fresh.BindPFlags(flags); err != nil {
+		return nil, fmt.Errorf("failed to bind flags: %w", err)
+	}
+
+	// as with the root command, PRJ_ROOT can be used in place of --tree-root
+	if err = fresh.BindPFlag("prj_root", flags.Lookup("tree-root")); err != nil {
+		return nil, fmt.Errorf("failed to bind prj_root: %w", err)
+	}
+
+	if err = config.Read(fresh, v.ConfigFileUsed()); err != nil {
+		return nil, err //nolint:wrapcheck
+	}
+
+	if err = config.ReadFormattersDir(fresh); err != nil {
+		return nil, fmt.Errorf("failed to read formatters-dir: %w", err)
+	}
+
+	cfg, err := config.FromViper(fresh)
+	if err != nil {
+		return nil, fmt.Errorf("failed to load config: %w", err)
+	}
+
+	return cfg, nil
+}
+
+// reloadConfig returns the config read again with reload, or nil if it is invalid, in which case the previous config
+// remains in use until the config is fixed.
+func reloadConfig(cfg *config.Config, reload reloadFunc) *config.Config {
+	reloaded, err := reload()
+
+	switch {
+	case err != nil:
+		log.Errorf("failed to reload config, continuing with the previous config: %v", err)
+
+		return nil
+	case reloaded.TreeRoot != cfg.TreeRoot:
+		log.Errorf(
+			"the tree root changed from %s to %s, restart treefmt to watch it, continuing with the previous config",
+			cfg.TreeRoot, reloaded.TreeRoot,
+		)
+
+		return nil
+	}
+
+	log.Info("reloaded config")
+
+	return reloaded
+}
+
+// watchedInputs are the files the config is read from, along with the directories whose *.toml files are read.
+type watchedInputs struct {
+	files map[string]struct{}
+	dirs  map[string]struct{}
+}
+
+// contains returns true if changing the file at path affects the config.
+func (w watchedInputs) contains(path string) bool {
+	if _, ok := w.files[path]; ok {
+		return true
+	}
+
+	_, ok := w.dirs[filepath.Dir(path)]
+
+	return ok && filepath.Ext(path) == ".toml"
+}
+
+// configInputs returns the absolute paths of the files cfg was read from, which are the config file itself, the
+// *.toml files in formatters-dir, the global excludes-from file, the includes-from and excludes-from files of each
+// formatter, and the config-files each formatter reads, which are part of its signature.
+func configInputs(cfg *config.Config, configFile string) watchedInputs {
+	inputs := watchedInputs{
+		files: map[string]struct{}{configFile: {}},
+		dirs:  make(map[string]struct{}),
+	}
+
+	// resolves path against dir, unless it is already absolute
+	resolve := func(dir string, path string) string {
+		if filepath.IsAbs(path) {
+			return path
+		}
+
+		return filepath.Join(dir, path)
+	}
+
+	configDir := filepath.Dir(configFile)
+
+	if cfg.FormattersDir != "" {
+		inputs.dirs[resolve(configDir, cfg.FormattersDir)] = struct{}{}
+	}
+
+	if cfg.ExcludesFrom != "" {
+		inputs.files[resolve(cfg.WorkingDirectory, cfg.ExcludesFrom)] = struct{}{}
+	}
+
+	for _, formatterCfg := range cfg.FormatterConfigs {
+		for _, path := range []string{formatterCfg.IncludesFrom, formatterCfg.ExcludesFrom} {
+			if path != "" {
+				inputs.files[resolve(configDir, path)] = struct{}{}
+			}
+		}
+
+		for _, path := range formatterCfg.ConfigFiles {
+			inputs.files[resolve(cfg.TreeRoot, path)] = struct{}{}
+		}
+	}
+
+	return inputs
+}
+
+// watchInputs adds the directories containing inputs to watcher, as the config file and formatters-dir may lie
+// outside the tree root, and files are often replaced rather than written to when they are saved.
+// Directories which do not exist are skipped, as are those already being watched.
+func watchInputs(watcher *fsnotify.Watcher, inputs watchedInputs) {
+	dirs := make(map[string]struct{}, len(inputs.dirs))
+	for dir := range inputs.dirs {
+		dirs[dir] = struct{}{}
+	}
+
+	for path := range inputs.files {
+		dirs[filepath.Dir(path)] = struct{}{}
+	}
+
+	for dir := range dirs {
+		if err := watcher.Add(dir); err != nil {
+			log.Debugf("not watching %s for config changes: %v", dir, err)
+		}
+	}
+}
+
+// watchFilter decides which directories are watched and which changed files are formatted, so that --watch formats
+// the same files as a normal run would. With git, only tracked files are formatted, and ignored directories, such as
+// node_modules, are not watched at all. Otherwise, everything except treefmt's own artifacts is.
+type watchFilter struct {
+	root string
+	git  bool
+
+	// cacheDir is the configured cache directory relative to root, if it lies within it
+	cacheDir string
+
+	// ignoredDirs are the directories, relative to root, which git ignores
+	ignoredDirs map[string]struct{}
+}
+
+func newWatchFilter(root string, walkType walk.Type, cacheDir string) (*watchFilter, error) {
+	filter := &watchFilter{
+		root:     root,
+		git:      walkType == walk.Git || (walkType == walk.Auto && isGitWorkTree(root)),
+		cacheDir: walk.RelCacheDir(root, cacheDir),
+	}
+
+	if err := filter.refresh(); err != nil {
+		return nil, err
+	}
+
+	return filter, nil
+}
+
+// refresh lists the directories git ignores again, e.g. after a directory has been created.
+func (w *watchFilter) refresh() error {
+	w.ignoredDirs = make(map[string]struct{})
+
+	if !w.git {
+		return nil
+	}
+
+	// with --directory, an ignored directory is listed once with a trailing slash, rather than every file within it
+	out, err := w.gitOutput("ls-files", "-z", "--others", "--ignored", "--exclude-standard", "--directory")
+	if err != nil {
+		return fmt.Errorf("failed to list ignored directories: %w", err)
+	}
+
+	for _, entry := range strings.Split(string(out), "\x00") {
+		if dir, ok := strings.CutSuffix(entry, "/"); ok {
+			w.ignoredDirs[filepath.FromSlash(dir)] = struct{}{}
+		}
+	}
+
+	return nil
+}
+
+// skipDir returns true if the directory at path, which is within root, should not be watched.
+func (w *watchFilter) skipDir(path string) bool {
+	if filepath.Base(path) == ".git" {
+		return true
+	}
+
+	relPath, err := filepath.Rel(w.root, path)
+	if err != nil {
+		return false
+	}
+
+	if walk.IsInternal(relPath, w.cacheDir) {
+		return true
+	}
+
+	_, ignored := w.ignoredDirs[relPath]
+
+	return ignored
+}
+
+// files returns those of the given paths, relative to root, which a normal run would read.
+func (w *watchFilter) files(relPaths []string) ([]string, error) {
+	if !w.git || len(relPaths) == 0 {
+		return relPaths, nil
+	}
+
+	// the paths are file names rather than patterns
+	args := append([]string{"--literal-pathspecs", "ls-files", "-z", "--"}, relPaths...)
+
+	out, err := w.gitOutput(args...)
+	if err != nil {
+		return nil, fmt.Errorf("failed to list tracked files: %w", err)
+	}
+
+	tracked := make(map[string]struct{}, len(relPaths))
+	for _, entry := range strings.Split(string(out), "\x00") {
+		tracked[filepath.FromSlash(entry)] = struct{}{}
+	}
+
+	var result []string
+
+	for _, relPath := range relPaths {
+		if _, ok := tracked[relPath]; ok {
+			result = append(result, relPath)
+		} else {
+			log.Debugf("ignoring change to %s, which is not tracked by git", relPath)
+		}
+	}
+
+	return result, nil
+}
+
+func (w *watchFilter) gitOutput(args ...string) ([]byte, error) {
+	cmd := exec.Command("git", args...)
+	cmd.Dir = w.root
+
+	out, err := cmd.Output()
+	if err != nil {
+		return nil, fmt.Errorf("failed to run git %s: %w", strings.Join(args, " "), err)
+	}
+
+	return out, nil
+}
+
+// watchDirs adds root and every directory beneath it to watcher, skipping those the filter excludes.
+func watchDirs(watcher *fsnotify.Watcher, root string, filter *watchFilter) error {
+	err := filepath.WalkDir(root, func(path string, entry fs.DirEntry, err error) error {
+		if err != nil {
+			return err
+		}
+
+		if !entry.IsDir() {
+			return nil
+		}
+
+		if filter.skipDir(path) {
+			return filepath.SkipDir
+		}
+
+		return watcher.Add(path)
+	})
+	if err != nil && !errors.Is(err, fs.ErrNotExist) {
+		return fmt.Errorf("failed to watch %s: %w", root, err)
+	}
+
+	return nil
+}
@@ -0,0 +1,230 @@
+package format //nolint:testpackage
+
+import (
+	"context"
+	"os"
+	"os/exec"
+	"path/filepath"
+	"strings"
+	"testing"
+	"time"
+
+	"github.com/numtide/treefmt/v2/config"
+	"github.com/numtide/treefmt/v2/walk"
+	"github.com/stretchr/testify/require"
+)
+
+func TestConfigInputs(t *testing.T) {
+	as := require.New(t)
+
+	configDir := t.TempDir()
+	treeRoot := t.TempDir()
+	workingDir := t.TempDir()
+	configFile := filepath.Join(configDir, "treefmt.toml")
+
+	cfg := &config.Config{
+		TreeRoot:         treeRoot,
+		WorkingDirectory: workingDir,
+		ExcludesFrom:     "vendored.txt",
+		FormattersDir:    "formatters",
+		FormatterConfigs: map[string]*config.Formatter{
+			"prettier": {
+				Command:     "prettier",
+				Includes:    []string{"*.js"},
+				ConfigFiles: []string{".prettierrc", "/etc/prettierrc"},
+			},
+			"ruff": {
+				Command:      "ruff",
+				IncludesFrom: "includes.txt",
+				ExcludesFrom: filepath.Join(treeRoot, "excludes.txt"),
+			},
+		},
+	}
+
+	inputs := configInputs(cfg, configFile)
+
+	// the config file itself, with includes-from and excludes-from relative to it
+	as.True(inputs.contains(configFile))
+	as.True(inputs.contains(filepath.Join(configDir, "includes.txt")))
+	as.True(inputs.contains(filepath.Join(treeRoot, "excludes.txt")))
+
+	// the global excludes-from is relative to the working directory
+	as.True(inputs.contains(filepath.Join(workingDir, "vendored.txt")))
+
+	// config-files are relative to the tree root
+	as.True(inputs.contains(filepath.Join(treeRoot, ".prettierrc")))
+	as.True(inputs.contains("/etc/prettierrc"))
+	as.False(inputs.contains(filepath.Join(configDir, ".prettierrc")))
+
+	// any *.toml file in formatters-dir, including those which do not exist yet
+	as.True(inputs.contains(filepath.Join(configDir, "formatters", "new.toml")))
+	as.False(inputs.contains(filepath.Join(configDir, "formatters", "README.md")))
+	as.False(inputs.contains(filepath.Join(configDir, "formatters", "nested", "other.toml")))
+
+	// but not the files being formatted
+	as.False(inputs.contains(filepath.Join(treeRoot, "main.js")))
+	as.False(inputs.contains(filepath.Join(configDir, "other.toml")))
+}
+
+func TestWatch(t *testing.T) {
+	as := require.New(t)
+
+	treeRoot := t.TempDir()
+
+	// a formatter which marks every file it is given, kept outside the tree root
+	script := "#!/bin/sh\nfor f in \"$@\"; do echo formatted >> \"$f\"; done\n"
+	command := filepath.Join(t.TempDir(), "mark-fmt")
+	as.NoError(os.WriteFile(command, []byte(script), 0o755)) //nolint:gosec
+
+	git := func(args ...string) {
+		cmd := exec.Command("git", args...)
+		cmd.Dir = treeRoot
+		as.NoError(cmd.Run(), "failed to run git %v", args)
+	}
+
+	// these are also called from within Eventually, which runs the condition on another goroutine, so they do not
+	// fail the test themselves
+	write := func(relPath string) error {
+		return os.WriteFile(filepath.Join(treeRoot, relPath), []byte("content\n"), 0o600)
+	}
+
+	read := func(relPath string) string {
+		content, _ := os.ReadFile(filepath.Join(treeRoot, relPath))
+
+		return string(content)
+	}
+
+	git("init")
+
+	as.NoError(os.WriteFile(filepath.Join(treeRoot, ".gitignore"), []byte("node_modules/\n"), 0o600))
+	as.NoError(os.Mkdir(filepath.Join(treeRoot, "node_modules"), 0o755))
+	as.NoError(os.Mkdir(filepath.Join(treeRoot, "nested"), 0o755))
+	as.NoError(write("tracked.txt"))
+	as.NoError(write("nested/deep.txt"))
+	as.NoError(write("node_modules/ignored.txt"))
+
+	git("add", ".gitignore", "tracked.txt", "nested/deep.txt")
+
+	cfg := &config.Config{
+		OnUnmatched: "debug",
+		BatchSize:   1024,
+		MaxDepth:    1,
+		NoSummary:   true,
+		TreeRoot:    treeRoot,
+		FormatterConfigs: map[string]*config.Formatter{
+			"mark": {
+				Command:  command,
+				Includes: []string{"*.txt"},
+			},
+		},
+	}
+
+	reload := func() (*config.Config, error) {
+		return cfg, nil
+	}
+
+	ctx, cancel := context.WithCancel(context.Background())
+	defer cancel()
+
+	done := make(chan error)
+
+	go func() {
+		done <- watch(ctx, cfg, filepath.Join(treeRoot, "treefmt.toml"), reload, walk.Auto, nil, nil)
+	}()
+
+	// we cannot tell when the watcher is ready, so we keep changing the files until the tracked one is formatted
+	as.Eventually(func() bool {
+		if strings.Contains(read("tracked.txt"), "formatted") {
+			return true
+		}
+
+		_ = write("tracked.txt")
+		_ = write("untracked.txt")
+		_ = write("nested/deep.txt")
+		_ = write("node_modules/ignored.txt")
+
+		return false
+	}, 10*time.Second, 5*watchDebounce)
+
+	// give any stray events a chance to be formatted
+	time.Sleep(5 * watchDebounce)
+
+	cancel()
+	as.NoError(<-done)
+
+	// files a normal run would not read are left alone
+	as.Equal("content\n", read("untracked.txt"))
+	as.Equal("content\n", read("node_modules/ignored.txt"))
+
+	// as are those nested beyond the max depth
+	as.Equal("content\n", read("nested/deep.txt"))
+}
+
+func TestWatchReload(t *testing.T) {
+	as := require.New(t)
+
+	treeRoot := t.TempDir()
+	configFile := filepath.Join(treeRoot, "treefmt.toml")
+
+	// a formatter which marks every file it is given, kept outside the tree root
+	script := "#!/bin/sh\nfor f in \"$@\"; do echo formatted >> \"$f\"; done\n"
+	command := filepath.Join(t.TempDir(), "mark-fmt")
+	as.NoError(os.WriteFile(command, []byte(script), 0o755)) //nolint:gosec
+
+	write := func(relPath string) error {
+		return os.WriteFile(filepath.Join(treeRoot, relPath), []byte("content\n"), 0o600)
+	}
+
+	read := func(relPath string) string {
+		content, _ := os.ReadFile(filepath.Join(treeRoot, relPath))
+
+		return string(content)
+	}
+
+	as.NoError(os.Mkdir(filepath.Join(treeRoot, "src"), 0o755))
+	as.NoError(write("src/main.txt"))
+	as.NoError(write("other.txt"))
+	as.NoError(os.WriteFile(configFile, nil, 0o600))
+
+	cfg := &config.Config{
+		OnUnmatched: "debug",
+		BatchSize:   1024,
+		NoSummary:   true,
+		TreeRoot:    treeRoot,
+		FormatterConfigs: map[string]*config.Formatter{
+			"mark": {
+				Command:  command,
+				Includes: []string{"*.txt"},
+			},
+		},
+	}
+
+	reload := func() (*config.Config, error) {
+		return cfg, nil
+	}
+
+	ctx, cancel := context.WithCancel(context.Background())
+	defer cancel()
+
+	done := make(chan error)
+
+	// only src is formatted again when the config is reloaded
+	go func() {
+		done <- watch(ctx, cfg, configFile, reload, walk.Filesystem, []string{"src"}, nil)
+	}()
+
+	// the config changes alongside a file outside src, so both are pending when the config is reloaded
+	as.Eventually(func() bool {
+		if strings.Contains(read("other.txt"), "formatted") {
+			return true
+		}
+
+		_ = write("other.txt")
+		_ = os.WriteFile(configFile, nil, 0o600)
+
+		return false
+	}, 10*time.Second, 5*watchDebounce)
+
+	cancel()
+	as.NoError(<-done)
+}
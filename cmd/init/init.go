@@ -2,7 +2,9 @@ package init
 
 import (
 	_ "embed"
+	"errors"
 	"fmt"
+	"io/fs"
 	"os"
 )
 
@@ -11,11 +13,41 @@ import (
 //go:embed init.toml
 var initBytes []byte
 
-func Run() error {
-	if err := os.WriteFile("treefmt.toml", initBytes, 0o600); err != nil {
+// ErrConfigExists is returned when a config file already exists in the current directory and overwrite is not set.
+var ErrConfigExists = errors.New("config file already exists")
+
+// Run writes a sample treefmt.toml to the current directory.
+// An existing treefmt.toml, or a .treefmt.toml which it would take precedence over, is only replaced if overwrite is
+// set, so that running it in a configured repository never destroys the config.
+func Run(overwrite bool) error {
+	flags := os.O_WRONLY | os.O_CREATE | os.O_TRUNC
+
+	if !overwrite {
+		if _, err := os.Stat(".treefmt.toml"); err == nil {
+			return fmt.Errorf("%w: .treefmt.toml, use --overwrite to write treefmt.toml anyway", ErrConfigExists)
+		}
+
+		// fail rather than truncate, should the file be created in the meantime
+		flags = os.O_WRONLY | os.O_CREATE | os.O_EXCL
+	}
+
+	file, err := os.OpenFile("treefmt.toml", flags, 0o600)
+	if errors.Is(err, fs.ErrExist) {
+		return fmt.Errorf("%w: treefmt.toml, use --overwrite to replace it", ErrConfigExists)
+	} else if err != nil {
+		return fmt.Errorf("failed to create treefmt.toml: %w", err)
+	}
+
+	if _, err = file.Write(initBytes); err != nil {
+		_ = file.Close()
+
 		return fmt.Errorf("failed to write treefmt.toml: %w", err)
 	}
 
+	if err = file.Close(); err != nil {
+		return fmt.Errorf("failed to close treefmt.toml: %w", err)
+	}
+
 	fmt.Printf("Generated treefmt.toml. Now it's your turn to edit it.\n")
 
 	return nil
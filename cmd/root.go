@@ -1,16 +1,20 @@
 package cmd
 
 import (
+	"encoding/json"
+	"errors"
 	"fmt"
 	"os"
 	"path/filepath"
 
 	"github.com/charmbracelet/log"
+	"github.com/muesli/termenv"
 	"github.com/numtide/treefmt/v2/build"
 	"github.com/numtide/treefmt/v2/cmd/format"
 	_init "github.com/numtide/treefmt/v2/cmd/init"
 	"github.com/numtide/treefmt/v2/config"
 	"github.com/numtide/treefmt/v2/stats"
+	"github.com/numtide/treefmt/v2/walk/cache"
 	"github.com/spf13/cobra"
 	"github.com/spf13/viper"
 )
@@ -18,6 +22,9 @@ import (
 func NewRoot() (*cobra.Command, *stats.Stats) {
 	var (
 		treefmtInit bool
+		overwrite   bool
+		cacheGC     bool
+		dumpSchema  bool
 		configFile  string
 	)
 
@@ -41,27 +48,57 @@ func NewRoot() (*cobra.Command, *stats.Stats) {
 	}
 
 	// update version template
-	cmd.SetVersionTemplate("treefmt {{.Version}}")
+	cmd.SetVersionTemplate(build.Info())
 
 	fs := cmd.Flags()
 
 	// add our config flags to the command's flag set
 	config.SetFlags(fs)
 
+	// benchmarking the walk is intended for maintainers and performance testing, so it is left out of the help
+	cobra.CheckErr(fs.MarkHidden("bench-walk"))
+
 	// xor tree-root and tree-root-file flags
 	cmd.MarkFlagsMutuallyExclusive("tree-root", "tree-root-file")
 
+	// both print the files each formatter would be passed, in different formats
+	cmd.MarkFlagsMutuallyExclusive("list-files", "dump-matches")
+
+	// xor quiet and verbose flags
+	cmd.MarkFlagsMutuallyExclusive("quiet", "verbose")
+
+	// xor stdin and staged flags
+	cmd.MarkFlagsMutuallyExclusive("stdin", "staged")
+
+	// no-vcs determines the tree root itself, and staged content only exists in git
+	cmd.MarkFlagsMutuallyExclusive("no-vcs", "tree-root")
+	cmd.MarkFlagsMutuallyExclusive("no-vcs", "tree-root-file")
+	cmd.MarkFlagsMutuallyExclusive("no-vcs", "staged")
+
 	cmd.HelpTemplate()
 
 	// add a couple of special flags which don't have a corresponding entry in treefmt.toml
 	fs.StringVar(
 		&configFile, "config-file", "",
-		"Load the config file from the given path (defaults to searching upwards for treefmt.toml or "+
-			".treefmt.toml).",
+		"Load the config file from the given path (defaults to searching upwards for treefmt.toml, "+
+			".treefmt.toml, or a pyproject.toml or package.json embedding the config).",
 	)
 	fs.BoolVarP(
 		&treefmtInit, "init", "i", false,
-		"Create a treefmt.toml file in the current directory.",
+		"Create a treefmt.toml file in the current directory, unless a config file already exists there.",
+	)
+	fs.BoolVar(
+		&overwrite, "overwrite", false,
+		"With --init, replace an existing treefmt.toml file.",
+	)
+	fs.BoolVar(
+		&cacheGC, "gc", false,
+		"Remove cache files belonging to tree roots which no longer exist, from the user's cache directory or an "+
+			"absolute --cache-dir.",
+	)
+	fs.BoolVar(
+		&dumpSchema, "dump-schema", false,
+		"Print a JSON Schema describing the config file, for editors to validate it and offer completion.",
 	)
 
 	// bind our command's flags to viper
@@ -90,8 +127,14 @@ func runE(v *viper.Viper, statz *stats.Stats, cmd *cobra.Command, args []string)
 	// check if we are running the init command
 	if init, err := flags.GetBool("init"); err != nil {
 		return fmt.Errorf("failed to read init flag: %w", err)
+	} else if overwrite, err := flags.GetBool("overwrite"); err != nil {
+		return fmt.Errorf("failed to read overwrite flag: %w", err)
+	} else if overwrite && !init {
+		return errors.New("--overwrite can only be used with --init")
 	} else if init {
-		err := _init.Run()
+		cmd.SilenceUsage = true
+
+		err := _init.Run(overwrite)
 		if err != nil {
 			return fmt.Errorf("failed to run init command: %w", err)
 		}
@@ -99,6 +142,52 @@ func runE(v *viper.Viper, statz *stats.Stats, cmd *cobra.Command, args []string)
 		return nil
 	}
 
+	// check if we are running the cache gc command
+	if gc, err := flags.GetBool("gc"); err != nil {
+		return fmt.Errorf("failed to read gc flag: %w", err)
+	} else if gc {
+		cacheDir, err := flags.GetString("cache-dir")
+		if err != nil {
+			return fmt.Errorf("failed to read cache-dir flag: %w", err)
+		}
+
+		// fallback to env
+		if !flags.Changed("cache-dir") && os.Getenv("TREEFMT_CACHE_DIR") != "" {
+			cacheDir = os.Getenv("TREEFMT_CACHE_DIR")
+		}
+
+		// caches stored within a tree are removed along with it, and we have no tree root to resolve them against
+		switch {
+		case cacheDir == config.CacheDirXDG:
+			cacheDir = ""
+		case cacheDir != "" && !filepath.IsAbs(cacheDir):
+			return fmt.Errorf("--gc requires an absolute cache-dir or %q, got %q", config.CacheDirXDG, cacheDir)
+		}
+
+		removed, reclaimed, err := cache.GC(cacheDir)
+		if err != nil {
+			return fmt.Errorf("failed to gc cache: %w", err)
+		}
+
+		fmt.Printf("removed %d stale cache file(s), reclaimed %d bytes\n", removed, reclaimed)
+
+		return nil
+	}
+
+	// check if we are dumping the config schema
+	if dumpSchema, err := flags.GetBool("dump-schema"); err != nil {
+		return fmt.Errorf("failed to read dump-schema flag: %w", err)
+	} else if dumpSchema {
+		schema, err := json.MarshalIndent(config.Schema(flags), "", "  ")
+		if err != nil {
+			return fmt.Errorf("failed to marshal config schema: %w", err)
+		}
+
+		fmt.Println(string(schema))
+
+		return nil
+	}
+
 	// otherwise attempt to load the config file
 
 	// use the path specified by the flag
@@ -112,16 +201,24 @@ func runE(v *viper.Viper, statz *stats.Stats, cmd *cobra.Command, args []string)
 		configFile = os.Getenv("TREEFMT_CONFIG")
 	}
 
-	filenames := []string{"treefmt.toml", ".treefmt.toml"}
+	// an explicit config file is relative to the working directory, which we have already changed into
+	if configFile != "" {
+		if configFile, err = filepath.Abs(configFile); err != nil {
+			return fmt.Errorf("failed to get absolute path for config file: %w", err)
+		}
+	}
+
+	// a project file embedding the config which was ignored in favour of a dedicated config file
+	var ignoredConfig string
 
 	// look in PRJ_ROOT if set
 	if prjRoot := os.Getenv("PRJ_ROOT"); configFile == "" && prjRoot != "" {
-		configFile, _ = config.Find(prjRoot, filenames...)
+		configFile, ignoredConfig, _ = config.Locate(prjRoot)
 	}
 
 	// search up from the working directory
 	if configFile == "" {
-		configFile, _, err = config.FindUp(workingDir, filenames...)
+		configFile, ignoredConfig, err = config.LocateUp(workingDir)
 	}
 
 	// error out if we couldn't find the config file
@@ -134,16 +231,29 @@ func runE(v *viper.Viper, statz *stats.Stats, cmd *cobra.Command, args []string)
 	log.Debugf("using config file: %s", configFile)
 
 	// read in the config
-	v.SetConfigFile(configFile)
+	if err := config.Read(v, configFile); err != nil {
+		cmd.SilenceUsage = true
+
+		return err
+	}
 
-	if err := v.ReadInConfig(); err != nil {
-		cobra.CheckErr(fmt.Errorf("failed to read config file '%s': %w", configFile, err))
+	// merge in any formatters defined in a separate directory
+	if err := config.ReadFormattersDir(v); err != nil {
+		cmd.SilenceUsage = true
+
+		return fmt.Errorf("failed to read formatters-dir: %w", err)
 	}
 
 	// configure logging
 	log.SetOutput(os.Stderr)
 	log.SetReportTimestamp(false)
 
+	if err := setColorProfile(v.GetString("color")); err != nil {
+		cmd.SilenceUsage = true
+
+		return err
+	}
+
 	if v.GetBool("quiet") {
 		// if quiet, we only log errors
 		log.SetLevel(log.ErrorLevel)
@@ -159,6 +269,27 @@ func runE(v *viper.Viper, statz *stats.Stats, cmd *cobra.Command, args []string)
 		}
 	}
 
+	if ignoredConfig != "" {
+		log.Warnf("ignoring the config embedded in %s, as %s takes precedence", ignoredConfig, configFile)
+	}
+
 	// format
 	return format.Run(v, statz, cmd, args) //nolint:wrapcheck
 }
+
+// setColorProfile configures whether log output is colored, according to one of the config.Color* modes.
+func setColorProfile(mode string) error {
+	switch mode {
+	case config.ColorAuto:
+		// unlike the default detection, this also honours NO_COLOR and CLICOLOR_FORCE
+		log.SetColorProfile(termenv.NewOutput(os.Stderr).EnvColorProfile())
+	case config.ColorAlways:
+		log.SetColorProfile(termenv.ANSI256)
+	case config.ColorNever:
+		log.SetColorProfile(termenv.Ascii)
+	default:
+		return fmt.Errorf("invalid color mode %q, must be one of auto, always or never", mode)
+	}
+
+	return nil
+}
@@ -3,13 +3,17 @@ package cmd_test
 import (
 	"bufio"
 	"bytes"
+	"encoding/json"
+	"errors"
 	"fmt"
 	"io"
+	"io/fs"
 	"os"
 	"os/exec"
 	"path"
 	"path/filepath"
 	"regexp"
+	"slices"
 	"strings"
 	"testing"
 	"time"
@@ -17,11 +21,13 @@ import (
 	"github.com/charmbracelet/log"
 	"github.com/numtide/treefmt/v2/cmd"
 	formatCmd "github.com/numtide/treefmt/v2/cmd/format"
+	initCmd "github.com/numtide/treefmt/v2/cmd/init"
 	"github.com/numtide/treefmt/v2/config"
 	"github.com/numtide/treefmt/v2/format"
 	"github.com/numtide/treefmt/v2/stats"
 	"github.com/numtide/treefmt/v2/test"
 	"github.com/numtide/treefmt/v2/walk"
+	"github.com/numtide/treefmt/v2/walk/cache"
 	cp "github.com/otiai10/copy"
 	"github.com/stretchr/testify/require"
 )
@@ -131,6 +137,73 @@ func TestOnUnmatched(t *testing.T) {
 	})
 }
 
+func TestColor(t *testing.T) {
+	as := require.New(t)
+
+	tempDir := test.TempExamples(t)
+
+	test.ChangeWorkDir(t, tempDir)
+
+	// allow missing formatter
+	t.Setenv("TREEFMT_ALLOW_MISSING_FORMATTER", "true")
+
+	// stderr is not a terminal, so there is no color by default
+	treefmt(t,
+		withArgs("--on-unmatched", "warn"),
+		withNoError(t),
+		withStderr(func(out []byte) {
+			as.Contains(string(out), "no formatter for path")
+			as.NotContains(string(out), "\x1b[")
+		}),
+	)
+
+	// unless it is forced
+	treefmt(t,
+		withArgs("--on-unmatched", "warn", "--color", "always"),
+		withNoError(t),
+		withStderr(func(out []byte) {
+			as.Contains(string(out), "\x1b[")
+		}),
+	)
+
+	t.Setenv("CLICOLOR_FORCE", "1")
+
+	treefmt(t,
+		withArgs("--on-unmatched", "warn"),
+		withNoError(t),
+		withStderr(func(out []byte) {
+			as.Contains(string(out), "\x1b[")
+		}),
+	)
+
+	// which NO_COLOR takes precedence over
+	t.Setenv("NO_COLOR", "1")
+
+	treefmt(t,
+		withArgs("--on-unmatched", "warn"),
+		withNoError(t),
+		withStderr(func(out []byte) {
+			as.NotContains(string(out), "\x1b[")
+		}),
+	)
+
+	// whereas an explicit mode always wins
+	treefmt(t,
+		withArgs("--on-unmatched", "warn", "--color", "always"),
+		withNoError(t),
+		withStderr(func(out []byte) {
+			as.Contains(string(out), "\x1b[")
+		}),
+	)
+
+	treefmt(t,
+		withArgs("--color", "sometimes"),
+		withError(func(as *require.Assertions, err error) {
+			as.ErrorContains(err, `invalid color mode "sometimes", must be one of auto, always or never`)
+		}),
+	)
+}
+
 func TestQuiet(t *testing.T) {
 	as := require.New(t)
 	tempDir := test.TempExamples(t)
@@ -150,6 +223,11 @@ func TestQuiet(t *testing.T) {
 	t.Setenv("TREEFMT_QUIET", "true")
 	treefmt(t, withNoError(t), withStdout(noOutput), withStderr(noOutput))
 
+	// quiet and verbose cannot be combined
+	treefmt(t, withArgs("-q", "-v"), withError(func(as *require.Assertions, err error) {
+		as.ErrorContains(err, "[quiet verbose] were all set")
+	}))
+
 	t.Setenv("TREEFMT_ALLOW_MISSING_FORMATTER", "false")
 
 	// check it doesn't suppress errors
@@ -158,6 +236,24 @@ func TestQuiet(t *testing.T) {
 	}))
 }
 
+func TestNoSummary(t *testing.T) {
+	as := require.New(t)
+	tempDir := test.TempExamples(t)
+
+	test.ChangeWorkDir(t, tempDir)
+
+	// allow missing formatter
+	t.Setenv("TREEFMT_ALLOW_MISSING_FORMATTER", "true")
+
+	treefmt(t, withNoError(t), withStderr(func(out []byte) {
+		as.Contains(string(out), "traversed 33 files")
+	}))
+
+	treefmt(t, withArgs("--no-summary"), withNoError(t), withStderr(func(out []byte) {
+		as.Empty(out)
+	}))
+}
+
 func TestCpuProfile(t *testing.T) {
 	as := require.New(t)
 	tempDir := test.TempExamples(t)
@@ -182,6 +278,130 @@ func TestCpuProfile(t *testing.T) {
 	as.FileExists(filepath.Join(tempDir, "env.pprof"))
 }
 
+func TestProfile(t *testing.T) {
+	as := require.New(t)
+	tempDir := test.TempExamples(t)
+
+	test.ChangeWorkDir(t, tempDir)
+
+	// allow missing formatter
+	t.Setenv("TREEFMT_ALLOW_MISSING_FORMATTER", "true")
+
+	treefmt(t,
+		withArgs("--profile", "--profile-file", "profile.json"),
+		withNoError(t),
+		withStderr(func(out []byte) {
+			as.Contains(string(out), "load config")
+			as.Contains(string(out), "walk and format")
+		}),
+	)
+
+	data, err := os.ReadFile(filepath.Join(tempDir, "profile.json"))
+	as.NoError(err)
+
+	var report struct {
+		Total       int64         `json:"total_ns"`
+		Phases      []stats.Phase `json:"phases"`
+		CacheHits   int           `json:"cache_hits"`
+		CacheMisses int           `json:"cache_misses"`
+	}
+
+	as.NoError(json.Unmarshal(data, &report))
+	as.Positive(report.Total)
+	as.Zero(report.CacheHits)
+	as.Positive(report.CacheMisses)
+
+	names := make([]string, 0, len(report.Phases))
+
+	for _, phase := range report.Phases {
+		names = append(names, phase.Name)
+	}
+
+	as.Equal([]string{"load config", "open cache", "walk and format", "finalise formatting", "close walker"}, names)
+}
+
+func TestAllowCommands(t *testing.T) {
+	as := require.New(t)
+
+	tempDir := test.TempExamples(t)
+	configPath := filepath.Join(tempDir, "treefmt.toml")
+
+	test.ChangeWorkDir(t, tempDir)
+
+	cfg := &config.Config{
+		FormatterConfigs: map[string]*config.Formatter{
+			"echo": {
+				Command:  "echo",
+				Includes: []string{"*.go"},
+			},
+			"append": {
+				Command:  "test-fmt-append",
+				Options:  []string{"   "},
+				Includes: []string{"*.py"},
+			},
+		},
+	}
+
+	// by default, any command is allowed
+	treefmt(t,
+		withConfig(configPath, cfg),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Matched: 3,
+		}),
+	)
+
+	// commands missing from the allowlist are rejected, even when validating
+	cfg.AllowCommands = []string{"echo"}
+
+	for _, args := range [][]string{nil, {"--validate"}} {
+		treefmt(t,
+			withConfig(configPath, cfg),
+			withArgs(args...),
+			withError(func(as *require.Assertions, err error) {
+				as.ErrorContains(err,
+					"formatter append resolves to executable test-fmt-append, which is not listed in allow-commands",
+				)
+			}),
+		)
+	}
+
+	// the allowlist can name every command in use
+	cfg.AllowCommands = []string{"echo", "test-fmt-append"}
+
+	treefmt(t,
+		withConfig(configPath, cfg),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Matched: 3,
+		}),
+	)
+
+	// an executable within the tree root cannot pass for the tool of the same name
+	as.NoError(os.WriteFile(filepath.Join(tempDir, "test-fmt-append"), []byte("#!/bin/sh\n"), 0o755)) //nolint:gosec
+
+	treefmt(t,
+		withConfig(configPath, cfg),
+		withError(func(as *require.Assertions, err error) {
+			as.ErrorContains(err,
+				"formatter append resolves to executable test-fmt-append within the tree root, which is not listed by "+
+					"its path in allow-commands",
+			)
+		}),
+	)
+
+	// it must be listed by its path instead
+	cfg.AllowCommands = []string{"echo", "./test-fmt-append"}
+
+	treefmt(t,
+		withConfig(configPath, cfg),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Matched: 3,
+		}),
+	)
+}
+
 func TestAllowMissingFormatter(t *testing.T) {
 	tempDir := test.TempExamples(t)
 	configPath := filepath.Join(tempDir, "treefmt.toml")
@@ -223,1320 +443,5114 @@ func TestAllowMissingFormatter(t *testing.T) {
 	})
 }
 
-func TestSpecifyingFormatters(t *testing.T) {
-	// we use the test formatter to append some whitespace
+func TestValidate(t *testing.T) {
+	as := require.New(t)
+
+	tempDir := test.TempExamples(t)
+	configPath := filepath.Join(tempDir, "treefmt.toml")
+
+	test.ChangeWorkDir(t, tempDir)
+
 	cfg := &config.Config{
 		FormatterConfigs: map[string]*config.Formatter{
-			"elm": {
-				Command:  "test-fmt-append",
-				Options:  []string{"   "},
-				Includes: []string{"*.elm"},
-			},
-			"nix": {
-				Command:  "test-fmt-append",
-				Options:  []string{"   "},
-				Includes: []string{"*.nix"},
-			},
-			"ruby": {
-				Command:  "test-fmt-append",
-				Options:  []string{"   "},
-				Includes: []string{"*.rb"},
+			"echo": {
+				Command:  "echo",
+				Includes: []string{"*"},
 			},
 		},
 	}
 
-	tempDir := test.TempExamples(t)
-	configPath := filepath.Join(tempDir, "treefmt.toml")
+	// a valid config does not format anything
+	treefmt(t,
+		withConfig(configPath, cfg),
+		withArgs("--validate"),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 0,
+			stats.Matched:   0,
+			stats.Formatted: 0,
+			stats.Changed:   0,
+		}),
+		withStdout(func(out []byte) {
+			as.Contains(string(out), "treefmt.toml is valid")
+		}),
+	)
 
-	test.WriteConfig(t, configPath, cfg)
-	test.ChangeWorkDir(t, tempDir)
+	// invalid include globs are reported
+	cfg.FormatterConfigs["echo"].Includes = []string{"[*"}
 
-	t.Run("default", func(t *testing.T) {
-		treefmt(t,
-			withNoError(t),
-			withModtimeBump(tempDir, time.Second),
-			withStats(t, map[stats.Type]int{
-				stats.Traversed: 33,
-				stats.Matched:   3,
-				stats.Formatted: 3,
-				stats.Changed:   3,
-			}),
-		)
-	})
+	treefmt(t,
+		withConfig(configPath, cfg),
+		withArgs("--validate"),
+		withError(func(as *require.Assertions, err error) {
+			as.ErrorContains(err, "formatter 'echo': invalid include pattern '[*'")
+		}),
+	)
 
-	t.Run("args", func(t *testing.T) {
+	// as are invalid exclude globs, both global and per formatter
+	cfg.FormatterConfigs["echo"].Includes = []string{"*"}
+	cfg.FormatterConfigs["echo"].Excludes = []string{"{*.go"}
+
+	treefmt(t,
+		withConfig(configPath, cfg),
+		withArgs("--validate"),
+		withError(func(as *require.Assertions, err error) {
+			as.ErrorContains(err, "formatter 'echo': invalid exclude pattern '{*.go'")
+		}),
+	)
+
+	cfg.FormatterConfigs["echo"].Excludes = nil
+	cfg.Excludes = []string{"[*"}
+
+	treefmt(t,
+		withConfig(configPath, cfg),
+		withArgs("--validate"),
+		withError(func(as *require.Assertions, err error) {
+			as.ErrorContains(err, "invalid global exclude pattern '[*'")
+		}),
+	)
+
+	cfg.Excludes = nil
+
+	// missing commands are reported unless allowed
+	cfg.FormatterConfigs["echo"].Includes = []string{"*"}
+	cfg.FormatterConfigs["foo-fmt"] = &config.Formatter{
+		Command:  "foo-fmt",
+		Includes: []string{"*"},
+	}
+
+	treefmt(t,
+		withConfig(configPath, cfg),
+		withArgs("--validate"),
+		withError(func(as *require.Assertions, err error) {
+			as.ErrorIs(err, format.ErrCommandNotFound)
+		}),
+	)
+
+	// when several commands are missing, they are all reported
+	cfg.FormatterConfigs["bar-fmt"] = &config.Formatter{
+		Command:  "bar-fmt",
+		Includes: []string{"*"},
+	}
+
+	treefmt(t,
+		withConfig(configPath, cfg),
+		withArgs("--validate"),
+		withError(func(as *require.Assertions, err error) {
+			as.ErrorIs(err, format.ErrCommandNotFound)
+			as.Regexp("(?s)failed to initialise formatter bar-fmt.*\\nfailed to initialise formatter foo-fmt", err.Error())
+		}),
+	)
+
+	// unless strict-load is enabled, in which case the first by name is reported every time
+	for range 5 {
 		treefmt(t,
-			withArgs("--formatters", "elm,nix"),
-			withModtimeBump(tempDir, time.Second),
-			withNoError(t),
-			withStats(t, map[stats.Type]int{
-				stats.Traversed: 33,
-				stats.Matched:   2,
-				stats.Formatted: 2,
-				stats.Changed:   2,
+			withConfig(configPath, cfg),
+			withArgs("--validate", "--strict-load"),
+			withError(func(as *require.Assertions, err error) {
+				as.ErrorIs(err, format.ErrCommandNotFound)
+				as.ErrorContains(err, "failed to initialise formatter bar-fmt")
+				as.NotContains(err.Error(), "foo-fmt")
 			}),
 		)
+	}
 
-		treefmt(t,
-			withArgs("--formatters", "ruby,nix"),
-			withModtimeBump(tempDir, time.Second),
-			withNoError(t),
-			withStats(t, map[stats.Type]int{
-				stats.Traversed: 33,
-				stats.Matched:   2,
-				stats.Formatted: 2,
-				stats.Changed:   2,
-			}),
-		)
-
-		treefmt(t,
-			withArgs("--formatters", "nix"),
-			withModtimeBump(tempDir, time.Second),
-			withNoError(t),
-			withStats(t, map[stats.Type]int{
-				stats.Traversed: 33,
-				stats.Matched:   1,
-				stats.Formatted: 1,
-				stats.Changed:   1,
-			}),
-		)
+	cfg.StrictLoad = true
 
-		// bad name
-		treefmt(t,
-			withArgs("--formatters", "foo"),
-			withError(func(as *require.Assertions, err error) {
-				as.ErrorContains(err, "formatter foo not found in config")
-			}),
-		)
-	})
-
-	t.Run("env", func(t *testing.T) {
-		t.Setenv("TREEFMT_FORMATTERS", "ruby,nix")
+	treefmt(t,
+		withConfig(configPath, cfg),
+		withArgs("--validate"),
+		withError(func(as *require.Assertions, err error) {
+			as.NotContains(err.Error(), "foo-fmt")
+		}),
+	)
 
-		treefmt(t,
-			withNoError(t),
-			withModtimeBump(tempDir, time.Second),
-			withStats(t, map[stats.Type]int{
-				stats.Traversed: 33,
-				stats.Matched:   2,
-				stats.Formatted: 2,
-				stats.Changed:   2,
-			}),
-		)
+	cfg.StrictLoad = false
 
-		t.Setenv("TREEFMT_FORMATTERS", "bar,foo")
+	treefmt(t,
+		withConfig(configPath, cfg),
+		withArgs("--validate", "--allow-missing-formatter"),
+		withNoError(t),
+	)
+}
 
-		treefmt(t,
-			withError(func(as *require.Assertions, err error) {
-				as.ErrorContains(err, "formatter bar not found in config")
-			}),
-		)
-	})
+func TestExplain(t *testing.T) {
+	as := require.New(t)
 
-	t.Run("bad names", func(t *testing.T) {
-		for _, name := range []string{"foo$", "/bar", "baz%"} {
-			treefmt(t,
-				withArgs("--formatters", name),
-				withError(func(as *require.Assertions, err error) {
-					as.ErrorContains(err, fmt.Sprintf("formatter name %q is invalid", name))
-				}),
-			)
+	tempDir := test.TempExamples(t)
+	configPath := filepath.Join(tempDir, "treefmt.toml")
 
-			t.Setenv("TREEFMT_FORMATTERS", name)
+	test.ChangeWorkDir(t, tempDir)
 
-			treefmt(t,
-				withError(func(as *require.Assertions, err error) {
-					as.ErrorContains(err, fmt.Sprintf("formatter name %q is invalid", name))
-				}),
-			)
+	cfg := &config.Config{
+		Excludes: []string{"nix/*"},
+		FormatterConfigs: map[string]*config.Formatter{
+			"append": {
+				Command:  "test-fmt-append",
+				Options:  []string{"   "},
+				Includes: []string{"*"},
+				Excludes: []string{"go/*"},
+			},
+			"echo": {
+				Command:  "echo",
+				Includes: []string{"*.go", "*.nix"},
+			},
+		},
+	}
 
-			t.Setenv("TREEFMT_FORMATTERS", "")
+	// a matching file is reported along with the formatters which would be applied
+	treefmt(t,
+		withConfig(configPath, cfg),
+		withArgs("--explain", "go/main.go"),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 0,
+			stats.Formatted: 0,
+		}),
+		withStdout(func(out []byte) {
+			as.Contains(string(out), `formatter append: included by "*" but excluded by "go/*"`)
+			as.Contains(string(out), `formatter echo: included by "*.go"`)
+			as.Contains(string(out), "verdict: formatted by echo\n")
+		}),
+	)
 
-			cfg.FormatterConfigs[name] = &config.Formatter{
-				Command:  "echo",
-				Includes: []string{"*"},
-			}
+	// global excludes take precedence over formatter includes
+	treefmt(t,
+		withArgs("--explain", "nix/sources.nix"),
+		withNoError(t),
+		withStdout(func(out []byte) {
+			as.Contains(string(out), `global excludes: excluded by "nix/*"`)
+			as.Contains(string(out), `verdict: skipped because it matches the global exclude "nix/*"`)
+		}),
+	)
 
-			test.WriteConfig(t, configPath, cfg)
+	// ignoring the global excludes leaves only the formatter excludes
+	treefmt(t,
+		withArgs("--explain", "nix/sources.nix", "--no-global-excludes"),
+		withNoError(t),
+		withStdout(func(out []byte) {
+			as.Contains(string(out), "global excludes: ignored because of --no-global-excludes")
+			as.Contains(string(out), "verdict: formatted by append, then echo\n")
+		}),
+	)
 
-			treefmt(t,
-				withError(func(as *require.Assertions, err error) {
-					as.ErrorContains(err, fmt.Sprintf("formatter name %q is invalid", name))
-				}),
-			)
+	// files outside the max depth are not traversed
+	treefmt(t,
+		withArgs("--explain", "go/main.go", "--max-depth", "1"),
+		withNoError(t),
+		withStdout(func(out []byte) {
+			as.Contains(string(out), "verdict: skipped because it is nested beyond the max depth")
+		}),
+	)
 
-			delete(cfg.FormatterConfigs, name)
+	// paths outside the tree root are never formatted
+	treefmt(t,
+		withArgs("--explain", filepath.Join(t.TempDir(), "foo.go")),
+		withNoError(t),
+		withStdout(func(out []byte) {
+			as.Contains(string(out), "verdict: skipped because it is outside the tree root")
+		}),
+	)
 
-			test.WriteConfig(t, configPath, cfg)
-		}
-	})
+	// explain works with a single path only
+	treefmt(t,
+		withArgs("--explain", "go/main.go", "go"),
+		withError(func(as *require.Assertions, err error) {
+			as.ErrorContains(err, "--explain cannot be used with path arguments")
+		}),
+	)
 }
 
-func TestIncludesAndExcludes(t *testing.T) {
+func TestListFiles(t *testing.T) {
+	as := require.New(t)
+
 	tempDir := test.TempExamples(t)
 	configPath := filepath.Join(tempDir, "treefmt.toml")
 
 	test.ChangeWorkDir(t, tempDir)
 
-	// test without any excludes
 	cfg := &config.Config{
+		Excludes: []string{"haskell-frontend/*"},
 		FormatterConfigs: map[string]*config.Formatter{
-			"echo": {
-				Command:  "echo",
-				Includes: []string{"*"},
+			"haskell": {
+				Command:  "test-fmt-append",
+				Options:  []string{"   "},
+				Includes: []string{"*.hs"},
+				Excludes: []string{"haskell/Nested/*"},
+			},
+			"python": {
+				Command:     "test-fmt-append",
+				Description: "appends whitespace",
+				Options:     []string{"   "},
+				Includes:    []string{"*.py"},
 			},
 		},
 	}
 
+	// files are grouped by formatter, along with any description, and nothing is formatted
 	treefmt(t,
 		withConfig(configPath, cfg),
+		withArgs("--list-files"),
 		withNoError(t),
 		withStats(t, map[stats.Type]int{
 			stats.Traversed: 33,
-			stats.Matched:   33,
-			stats.Formatted: 33,
+			stats.Formatted: 0,
 			stats.Changed:   0,
 		}),
+		withStdout(func(out []byte) {
+			as.Equal(`haskell:
+  haskell/Foo.hs
+  haskell/Main.hs
+  haskell/Setup.hs
+python: # appends whitespace
+  python/main.py
+  python/virtualenv_proxy.py
+`, string(out))
+		}),
 	)
 
-	// globally exclude nix files
-	cfg.Excludes = []string{"*.nix"}
-
+	// the cache is not populated, so a regular run formats everything
 	treefmt(t,
-		withArgs("-c"),
-		withConfig(configPath, cfg),
 		withNoError(t),
 		withStats(t, map[stats.Type]int{
 			stats.Traversed: 33,
-			stats.Matched:   32,
-			stats.Formatted: 32,
-			stats.Changed:   0,
+			stats.Matched:   5,
+			stats.Formatted: 5,
+			stats.Changed:   5,
 		}),
 	)
+}
 
-	// add haskell files to the global exclude
-	cfg.Excludes = []string{"*.nix", "*.hs"}
+func TestDumpMatches(t *testing.T) {
+	as := require.New(t)
+
+	tempDir := test.TempExamples(t)
+	configPath := filepath.Join(tempDir, "treefmt.toml")
+
+	test.ChangeWorkDir(t, tempDir)
+
+	cfg := &config.Config{
+		Excludes: []string{"haskell-frontend/*"},
+		FormatterConfigs: map[string]*config.Formatter{
+			"haskell": {
+				Command:  "test-fmt-append",
+				Options:  []string{"   "},
+				Includes: []string{"*.hs"},
+				Excludes: []string{"haskell/Nested/*"},
+			},
+			"python": {
+				Command:  "test-fmt-append",
+				Options:  []string{"   "},
+				Includes: []string{"*.py"},
+			},
+			"ruby": {
+				Command:  "test-fmt-append",
+				Options:  []string{"   "},
+				Includes: []string{"*.no-such-extension"},
+			},
+		},
+	}
 
+	// the matches are printed as sorted json, formatters matching nothing are omitted, and nothing is formatted
 	treefmt(t,
-		withArgs("-c"),
 		withConfig(configPath, cfg),
+		withArgs("--dump-matches"),
 		withNoError(t),
 		withStats(t, map[stats.Type]int{
 			stats.Traversed: 33,
-			stats.Matched:   26,
-			stats.Formatted: 26,
+			stats.Formatted: 0,
 			stats.Changed:   0,
 		}),
+		withStdout(func(out []byte) {
+			var matches map[string][]string
+			as.NoError(json.Unmarshal(out, &matches))
+			as.Equal(map[string][]string{
+				"haskell": {"haskell/Foo.hs", "haskell/Main.hs", "haskell/Setup.hs"},
+				"python":  {"python/main.py", "python/virtualenv_proxy.py"},
+			}, matches)
+		}),
 	)
 
-	echo := cfg.FormatterConfigs["echo"]
-
-	// remove python files from the echo formatter
-	echo.Excludes = []string{"*.py"}
-
+	// it can be limited to the given paths
 	treefmt(t,
-		withArgs("-c"),
-		withConfig(configPath, cfg),
+		withArgs("--dump-matches", "python"),
 		withNoError(t),
-		withStats(t, map[stats.Type]int{
-			stats.Traversed: 33,
-			stats.Matched:   24,
-			stats.Formatted: 24,
-			stats.Changed:   0,
+		withStdout(func(out []byte) {
+			as.JSONEq(`{"python": ["python/main.py", "python/virtualenv_proxy.py"]}`, string(out))
 		}),
 	)
 
-	// remove go files from the echo formatter via env
-	t.Setenv("TREEFMT_FORMATTER_ECHO_EXCLUDES", "*.py,*.go")
+	// it cannot be combined with list-files
+	treefmt(t,
+		withArgs("--dump-matches", "--list-files"),
+		withError(func(as *require.Assertions, err error) {
+			as.ErrorContains(err, "none of the others can be")
+		}),
+	)
 
+	// the cache is not populated, so a regular run formats everything
 	treefmt(t,
-		withArgs("-c"),
-		withConfig(configPath, cfg),
 		withNoError(t),
 		withStats(t, map[stats.Type]int{
 			stats.Traversed: 33,
-			stats.Matched:   23,
-			stats.Formatted: 23,
-			stats.Changed:   0,
+			stats.Matched:   5,
+			stats.Formatted: 5,
+			stats.Changed:   5,
 		}),
 	)
+}
 
-	t.Setenv("TREEFMT_FORMATTER_ECHO_EXCLUDES", "") // reset
+func TestBenchWalk(t *testing.T) {
+	as := require.New(t)
 
-	// adjust the includes for echo to only include elm files
-	echo.Includes = []string{"*.elm"}
+	tempDir := test.TempExamples(t)
+	configPath := filepath.Join(tempDir, "treefmt.toml")
+
+	test.ChangeWorkDir(t, tempDir)
+
+	cfg := &config.Config{
+		FormatterConfigs: map[string]*config.Formatter{
+			"python": {
+				Command:  "test-fmt-append",
+				Options:  []string{"   "},
+				Includes: []string{"*.py"},
+			},
+		},
+	}
 
+	// timings are printed, and nothing is formatted
 	treefmt(t,
-		withArgs("-c"),
 		withConfig(configPath, cfg),
+		withArgs("--bench-walk", "3"),
 		withNoError(t),
 		withStats(t, map[stats.Type]int{
-			stats.Traversed: 33,
-			stats.Matched:   1,
-			stats.Formatted: 1,
+			stats.Formatted: 0,
 			stats.Changed:   0,
 		}),
+		withStdout(func(out []byte) {
+			as.Contains(string(out), "walked 3 times, traversing 33 files of which 2 matched a formatter\n")
+			as.Contains(string(out), "median:")
+			as.Contains(string(out), "files/sec:")
+		}),
 	)
 
-	// add js files to echo formatter via env
-	t.Setenv("TREEFMT_FORMATTER_ECHO_INCLUDES", "*.elm,*.js")
-
+	// the cache is not populated, so a regular run formats everything
 	treefmt(t,
-		withArgs("-c"),
-		withConfig(configPath, cfg),
 		withNoError(t),
 		withStats(t, map[stats.Type]int{
 			stats.Traversed: 33,
 			stats.Matched:   2,
 			stats.Formatted: 2,
-			stats.Changed:   0,
+			stats.Changed:   2,
+		}),
+	)
+
+	// benchmarking requires at least one run, and a tree to walk
+	treefmt(t,
+		withArgs("--bench-walk", "-1"),
+		withError(func(as *require.Assertions, err error) {
+			as.ErrorContains(err, "--bench-walk must be a positive number of runs")
+		}),
+	)
+
+	treefmt(t,
+		withArgs("--bench-walk", "3", "--stdin", "main.py"),
+		withError(func(as *require.Assertions, err error) {
+			as.ErrorContains(err, "--bench-walk cannot be used with --stdin")
 		}),
 	)
 }
 
-func TestConfigFile(t *testing.T) {
+func TestCommandRelativeToTreeRoot(t *testing.T) {
 	as := require.New(t)
 
-	for _, name := range []string{"treefmt.toml", ".treefmt.toml"} {
-		t.Run(name, func(t *testing.T) {
-			tempDir := test.TempExamples(t)
-
-			// use a config file in a different temp directory
-			configPath := filepath.Join(t.TempDir(), name)
-
-			// if we don't specify a tree root, we default to the directory containing the config file
-			treefmt(t,
-				withConfig(configPath, &config.Config{
-					FormatterConfigs: map[string]*config.Formatter{
-						"echo": {
-							Command:  "echo",
-							Includes: []string{"*"},
-						},
-					},
-				}),
-				withArgs("--config-file", configPath),
-				withNoError(t),
-				withStats(t, map[stats.Type]int{
-					stats.Traversed: 1,
-					stats.Matched:   1,
-					stats.Formatted: 1,
-					stats.Changed:   0,
-				}),
-			)
-
-			treefmt(t,
-				withArgs("--config-file", configPath, "--tree-root", tempDir),
-				withNoError(t),
-				withStats(t, map[stats.Type]int{
-					stats.Traversed: 33,
-					stats.Matched:   33,
-					stats.Formatted: 33,
-					stats.Changed:   0,
-				}),
-			)
+	tempDir := test.TempExamples(t)
+	configPath := filepath.Join(tempDir, "treefmt.toml")
 
-			// use env variable
-			treefmt(t,
-				withEnv(map[string]string{
-					// TREEFMT_CONFIG takes precedence
-					"TREEFMT_CONFIG": configPath,
-					"PRJ_ROOT":       tempDir,
-				}),
-				withNoError(t),
-				withStats(t, map[stats.Type]int{
-					stats.Traversed: 1,
-					stats.Matched:   1,
-					stats.Formatted: 0,
-					stats.Changed:   0,
-				}),
-			)
+	test.ChangeWorkDir(t, tempDir)
 
-			// should fallback to PRJ_ROOT
-			treefmt(t,
-				withArgs("--tree-root", tempDir),
-				withEnv(map[string]string{
-					"PRJ_ROOT": filepath.Dir(configPath),
-				}),
-				withNoError(t),
-				withStats(t, map[stats.Type]int{
-					stats.Traversed: 33,
-					stats.Matched:   33,
-					stats.Formatted: 0,
-					stats.Changed:   0,
-				}),
-			)
+	// a project local formatter, referenced by a bare name which is not on the PATH
+	echoPath, err := exec.LookPath("echo")
+	as.NoError(err)
+	as.NoError(os.Symlink(echoPath, filepath.Join(tempDir, "project-echo")))
 
-			// should not search upwards if using PRJ_ROOT
-			configSubDir := filepath.Join(filepath.Dir(configPath), "sub")
-			as.NoError(os.MkdirAll(configSubDir, 0o600))
+	// and one within a subdirectory
+	as.NoError(os.Mkdir(filepath.Join(tempDir, "scripts"), 0o755))
+	as.NoError(os.Symlink(echoPath, filepath.Join(tempDir, "scripts", "project-echo")))
 
-			treefmt(t,
-				withArgs("--tree-root", tempDir),
-				withEnv(map[string]string{
-					"PRJ_ROOT": configSubDir,
-				}),
-				withError(func(as *require.Assertions, err error) {
-					as.ErrorContains(err, "failed to find treefmt config file")
-				}),
-			)
-		})
+	for _, command := range []string{"project-echo", "scripts/project-echo"} {
+		treefmt(t,
+			withConfig(configPath, &config.Config{
+				FormatterConfigs: map[string]*config.Formatter{
+					"echo": {
+						Command:  command,
+						Includes: []string{"*.go"},
+					},
+				},
+			}),
+			withArgs("-c"),
+			withNoError(t),
+			withStats(t, map[stats.Type]int{
+				stats.Matched:   1,
+				stats.Formatted: 1,
+			}),
+		)
 	}
 }
 
-func TestCache(t *testing.T) {
+func TestShellFormatter(t *testing.T) {
+	as := require.New(t)
+
 	tempDir := test.TempExamples(t)
 	configPath := filepath.Join(tempDir, "treefmt.toml")
 
 	test.ChangeWorkDir(t, tempDir)
 
-	// test without any excludes
 	cfg := &config.Config{
 		FormatterConfigs: map[string]*config.Formatter{
-			"append": {
-				Command:  "test-fmt-append",
-				Options:  []string{"   "},
-				Includes: []string{"*"},
+			"shell": {
+				Command:  `value="$1"; shift; for file in "$@"; do echo "$value" >> "$file"; done`,
+				Options:  []string{"formatted by shell"},
+				Includes: []string{"*.py"},
+				Shell:    true,
 			},
 		},
 	}
 
-	test.WriteConfig(t, configPath, cfg)
-
-	// first run
+	// options and paths are passed to the snippet as positional parameters
 	treefmt(t,
+		withConfig(configPath, cfg),
 		withNoError(t),
 		withStats(t, map[stats.Type]int{
 			stats.Traversed: 33,
-			stats.Matched:   33,
-			stats.Formatted: 33,
-			stats.Changed:   33,
+			stats.Matched:   2,
+			stats.Formatted: 2,
+			stats.Changed:   2,
 		}),
 	)
 
-	// cached run with no changes to underlying files
+	contents, err := os.ReadFile(filepath.Join(tempDir, "python", "main.py"))
+	as.NoError(err)
+	as.True(strings.HasSuffix(string(contents), "formatted by shell\n"))
+
+	// changing the snippet invalidates the cache
+	cfg.FormatterConfigs["shell"].Command = `for file in "$@"; do :; done`
+
 	treefmt(t,
+		withConfig(configPath, cfg),
 		withNoError(t),
 		withStats(t, map[stats.Type]int{
 			stats.Traversed: 33,
-			stats.Matched:   33,
-			stats.Formatted: 0,
+			stats.Matched:   2,
+			stats.Formatted: 2,
 			stats.Changed:   0,
 		}),
 	)
+}
 
-	// clear cache
+func TestCaptureOutput(t *testing.T) {
+	as := require.New(t)
+
+	tempDir := test.TempExamples(t)
+	configPath := filepath.Join(tempDir, "treefmt.toml")
+	reportPath := filepath.Join(tempDir, "reports", "lint.txt")
+
+	test.ChangeWorkDir(t, tempDir)
+
+	cfg := &config.Config{
+		FormatterConfigs: map[string]*config.Formatter{
+			"lint": {
+				Command:       `for file in "$@"; do echo "checked $file"; done; echo "1 warning" >&2`,
+				Includes:      []string{"*.py", "*.txt"},
+				Shell:         true,
+				CaptureOutput: "reports/lint.txt",
+			},
+		},
+	}
+
+	// stdout and stderr are both captured
 	treefmt(t,
-		withArgs("-c"),
+		withConfig(configPath, cfg),
+		withArgs("--no-cache"),
 		withNoError(t),
-		withStats(t, map[stats.Type]int{
-			stats.Traversed: 33,
-			stats.Matched:   33,
-			stats.Formatted: 33,
-			stats.Changed:   33,
-		}),
 	)
 
-	// cached run with no changes to underlying files
+	report, err := os.ReadFile(reportPath)
+	as.NoError(err)
+	as.Contains(string(report), "checked python/main.py\n")
+	as.Contains(string(report), "checked python/requirements.txt\n")
+	as.Contains(string(report), "1 warning\n")
+
+	// the report is replaced on each run, and is not formatted itself
 	treefmt(t,
+		withArgs("--no-cache"),
 		withNoError(t),
 		withStats(t, map[stats.Type]int{
-			stats.Traversed: 33,
-			stats.Matched:   33,
-			stats.Formatted: 0,
-			stats.Changed:   0,
+			stats.Traversed: 34,
+			stats.Matched:   3,
+			stats.Formatted: 3,
 		}),
 	)
 
-	// bump underlying files
+	report, err = os.ReadFile(reportPath)
+	as.NoError(err)
+	as.Equal(1, strings.Count(string(report), "checked python/main.py\n"))
+	as.NotContains(string(report), "reports/lint.txt")
+}
+
+func TestHooks(t *testing.T) {
+	as := require.New(t)
+
+	tempDir := test.TempExamples(t)
+	configPath := filepath.Join(tempDir, "treefmt.toml")
+	logPath := filepath.Join(t.TempDir(), "hooks.log")
+
+	test.ChangeWorkDir(t, tempDir)
+
+	readLog := func() string {
+		contents, err := os.ReadFile(logPath)
+		if errors.Is(err, os.ErrNotExist) {
+			return ""
+		}
+
+		as.NoError(err)
+		as.NoError(os.Remove(logPath))
+
+		return string(contents)
+	}
+
+	cfg := &config.Config{
+		PreHook:  "echo pre >> " + logPath,
+		PostHook: "echo post >> " + logPath,
+		FormatterConfigs: map[string]*config.Formatter{
+			"shell": {
+				Command:  "echo format >> " + logPath,
+				Includes: []string{"*.go"},
+				Shell:    true,
+			},
+		},
+	}
+
+	// the hooks run either side of the formatters
 	treefmt(t,
+		withConfig(configPath, cfg),
 		withNoError(t),
-		withModtimeBump(tempDir, time.Second),
 		withStats(t, map[stats.Type]int{
-			stats.Traversed: 33,
-			stats.Matched:   33,
-			stats.Formatted: 33,
-			stats.Changed:   33,
+			stats.Formatted: 1,
 		}),
 	)
 
-	// no cache
+	as.Equal("pre\nformat\npost\n", readLog())
+
+	// they do not run when there is nothing to format
 	treefmt(t,
-		withArgs("--no-cache"),
+		withConfig(configPath, cfg),
 		withNoError(t),
 		withStats(t, map[stats.Type]int{
-			stats.Traversed: 33,
-			stats.Matched:   33,
-			stats.Formatted: 33,
-			stats.Changed:   33,
+			stats.Formatted: 0,
 		}),
 	)
 
-	// update the config with a failing formatter
-	cfg = &config.Config{
-		FormatterConfigs: map[string]*config.Formatter{
-			// fails to execute
-			"fail": {
-				Command:  "touch",
-				Options:  []string{"--bad-arg"},
-				Includes: []string{"*.hs"},
-			},
-		},
-	}
-	test.WriteConfig(t, configPath, cfg)
-
-	// test that formatting errors are not cached
+	as.Empty(readLog())
 
-	// running should match but not format anything
+	// unless they should always run
+	cfg.HooksAlways = true
 
 	treefmt(t,
-		withError(func(as *require.Assertions, err error) {
-			as.ErrorIs(err, format.ErrFormattingFailures)
-		}),
+		withConfig(configPath, cfg),
+		withNoError(t),
 		withStats(t, map[stats.Type]int{
-			stats.Traversed: 33,
-			stats.Matched:   6,
 			stats.Formatted: 0,
-			stats.Changed:   0,
 		}),
 	)
 
-	// running again should provide the same result
+	as.Equal("pre\npost\n", readLog())
+
+	// a failing pre-hook prevents formatting
+	cfg.HooksAlways = false
+	cfg.PreHook = "exit 1"
+
 	treefmt(t,
+		withConfig(configPath, cfg),
+		withArgs("--no-cache"),
 		withError(func(as *require.Assertions, err error) {
-			as.ErrorIs(err, format.ErrFormattingFailures)
-		}),
-		withStats(t, map[stats.Type]int{
-			stats.Traversed: 33,
-			stats.Matched:   6,
-			stats.Formatted: 0,
-			stats.Changed:   0,
+			as.ErrorContains(err, "pre-hook failed")
 		}),
 	)
 
-	// let's fix the haskell config so it no longer fails
-	cfg.FormatterConfigs["fail"] = &config.Formatter{
-		Command:  "test-fmt-append",
-		Options:  []string{"   "},
-		Includes: []string{"*.hs"},
-	}
+	as.Empty(readLog())
 
-	test.WriteConfig(t, configPath, cfg)
+	// a failing post-hook fails the run
+	cfg.PreHook = ""
+	cfg.PostHook = "exit 1"
 
-	// we should now format the haskell files
 	treefmt(t,
-		withNoError(t),
-		withStats(t, map[stats.Type]int{
-			stats.Traversed: 33,
-			stats.Matched:   6,
-			stats.Formatted: 6,
-			stats.Changed:   6,
+		withConfig(configPath, cfg),
+		withArgs("--no-cache"),
+		withError(func(as *require.Assertions, err error) {
+			as.ErrorContains(err, "post-hook failed")
 		}),
 	)
+
+	as.Equal("format\n", readLog())
 }
 
-func TestChangeWorkingDirectory(t *testing.T) {
+func TestStrictScope(t *testing.T) {
 	as := require.New(t)
 
+	tempDir := test.TempExamples(t)
+	configPath := filepath.Join(tempDir, "treefmt.toml")
+
+	test.ChangeWorkDir(t, tempDir)
+
 	cfg := &config.Config{
 		FormatterConfigs: map[string]*config.Formatter{
-			"append": {
-				Command:  "test-fmt-append",
-				Options:  []string{"   "},
-				Includes: []string{"*"},
+			// modifies the go.mod alongside each file it is given
+			"sloppy": {
+				Command:  `for file in "$@"; do echo >> "$(dirname "$file")/go.mod"; done`,
+				Includes: []string{"*.go"},
+				Shell:    true,
 			},
 		},
 	}
 
-	t.Run("default", func(t *testing.T) {
-		// capture current cwd, so we can replace it after the test is finished
-		cwd, err := os.Getwd()
-		as.NoError(err)
+	// nothing is checked by default
+	treefmt(t,
+		withConfig(configPath, cfg),
+		withArgs("--no-cache"),
+		withNoError(t),
+		withStderr(func(out []byte) {
+			as.NotContains(string(out), "which it was not given")
+		}),
+	)
 
-		t.Cleanup(func() {
-			// return to the previous working directory
-			as.NoError(os.Chdir(cwd))
-		})
+	// files the formatter was not given are reported when they change
+	treefmt(t,
+		withConfig(configPath, cfg),
+		withArgs("--no-cache", "--strict-scope"),
+		withNoError(t),
+		withStderr(func(out []byte) {
+			as.Contains(string(out), "formatter | sloppy: modified go/go.mod, which it was not given")
+		}),
+	)
 
-		tempDir := test.TempExamples(t)
-		configPath := filepath.Join(tempDir, "treefmt.toml")
+	// well-behaved formatters are not reported
+	cfg.FormatterConfigs["sloppy"].Command = `for file in "$@"; do echo >> "$file"; done`
 
-		// change to an empty temp dir and try running without specifying a working directory
-		as.NoError(os.Chdir(t.TempDir()))
+	treefmt(t,
+		withConfig(configPath, cfg),
+		withArgs("--no-cache", "--strict-scope"),
+		withNoError(t),
+		withStderr(func(out []byte) {
+			as.NotContains(string(out), "which it was not given")
+		}),
+	)
+}
 
-		treefmt(t,
-			withConfig(configPath, cfg),
-			withError(func(as *require.Assertions, err error) {
-				as.ErrorContains(err, "failed to find treefmt config file")
-			}),
-		)
+func TestWriteOnlyOnChange(t *testing.T) {
+	as := require.New(t)
 
-		// now change to the examples temp directory
-		as.NoError(os.Chdir(tempDir), "failed to change to temp directory")
+	tempDir := test.TempExamples(t)
+	configPath := filepath.Join(tempDir, "treefmt.toml")
 
-		treefmt(t,
-			withConfig(configPath, cfg),
-			withNoError(t),
-			withStats(t, map[stats.Type]int{
-				stats.Traversed: 33,
-			}),
-		)
-	})
+	test.ChangeWorkDir(t, tempDir)
 
-	execute := func(t *testing.T, configFile string, env bool) {
-		t.Helper()
-		t.Run(configFile, func(t *testing.T) {
-			// capture current cwd, so we can replace it after the test is finished
-			cwd, err := os.Getwd()
-			as.NoError(err)
+	cfg := &config.Config{
+		FormatterConfigs: map[string]*config.Formatter{
+			// leaves the content alone, but rewinds the modification time
+			"touch": {
+				Command:  "touch",
+				Options:  []string{"-t", "200001010000"},
+				Includes: []string{"*.hs"},
+			},
+		},
+	}
 
-			t.Cleanup(func() {
-				// return to the previous working directory
-				as.NoError(os.Chdir(cwd))
-			})
+	// by default, the formatter changes the modification time of every file
+	treefmt(t,
+		withConfig(configPath, cfg),
+		withArgs("--no-cache"),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 33,
+			stats.Matched:   6,
+			stats.Formatted: 6,
+			stats.Changed:   6,
+		}),
+	)
 
-			tempDir := test.TempExamples(t)
-			configPath := filepath.Join(tempDir, configFile)
+	// only files whose content changes are written
+	as.NoError(os.Chtimes(filepath.Join(tempDir, "haskell", "Foo.hs"), time.Now(), time.Now()))
 
-			// delete treefmt.toml that comes with the example folder
-			as.NoError(os.Remove(filepath.Join(tempDir, "treefmt.toml")))
+	info, err := os.Stat(filepath.Join(tempDir, "haskell", "Foo.hs"))
+	as.NoError(err)
 
-			var args []string
+	treefmt(t,
+		withArgs("--no-cache", "--write-only-on-change"),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 33,
+			stats.Matched:   6,
+			stats.Formatted: 6,
+			stats.Changed:   0,
+		}),
+	)
 
-			if env {
-				t.Setenv("TREEFMT_WORKING_DIR", tempDir)
-			} else {
-				args = []string{"-C", tempDir}
-			}
+	current, err := os.Stat(filepath.Join(tempDir, "haskell", "Foo.hs"))
+	as.NoError(err)
+	as.Equal(info.ModTime(), current.ModTime())
 
-			treefmt(t,
-				withArgs(args...),
-				withConfig(configPath, cfg),
-				withNoError(t),
-				withStats(t, map[stats.Type]int{
-					stats.Traversed: 33,
-				}),
-			)
-		})
+	// files whose content does change are replaced, and no copies are left behind
+	cfg.FormatterConfigs["touch"] = &config.Formatter{
+		Command:  "test-fmt-append",
+		Options:  []string{"   "},
+		Includes: []string{"*.hs"},
 	}
 
-	// by default, we look for a config file at ./treefmt.toml or ./.treefmt.toml in the current working directory
-	configFiles := []string{"treefmt.toml", ".treefmt.toml"}
+	entries, err := os.ReadDir(filepath.Join(tempDir, "haskell"))
+	as.NoError(err)
 
-	t.Run("arg", func(t *testing.T) {
-		for _, configFile := range configFiles {
-			execute(t, configFile, false)
-		}
-	})
+	treefmt(t,
+		withConfig(configPath, cfg),
+		withArgs("--no-cache", "--write-only-on-change"),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 33,
+			stats.Matched:   6,
+			stats.Formatted: 6,
+			stats.Changed:   6,
+		}),
+	)
 
-	t.Run("env", func(t *testing.T) {
-		for _, configFile := range configFiles {
-			execute(t, configFile, true)
-		}
-	})
+	content, err := os.ReadFile(filepath.Join(tempDir, "haskell", "Foo.hs"))
+	as.NoError(err)
+	as.True(strings.HasSuffix(string(content), "   \n"))
+
+	after, err := os.ReadDir(filepath.Join(tempDir, "haskell"))
+	as.NoError(err)
+	as.Len(after, len(entries))
 }
 
-func TestFailOnChange(t *testing.T) {
-	t.Run("change size", func(t *testing.T) {
-		tempDir := test.TempExamples(t)
-		configPath := filepath.Join(tempDir, "treefmt.toml")
+func TestInteractive(t *testing.T) {
+	tempDir := test.TempExamples(t)
+	configPath := filepath.Join(tempDir, "treefmt.toml")
 
-		test.ChangeWorkDir(t, tempDir)
+	test.ChangeWorkDir(t, tempDir)
 
-		cfg := &config.Config{
-			FormatterConfigs: map[string]*config.Formatter{
-				"append": {
-					// test-fmt-append is a helper defined in nix/packages/treefmt/formatters.nix which lets us append
-					// an arbitrary value to a list of files
-					Command:  "test-fmt-append",
-					Options:  []string{"hello"},
-					Includes: []string{"elm/*"},
-				},
+	cfg := &config.Config{
+		FormatterConfigs: map[string]*config.Formatter{
+			"append": {
+				Command:  "test-fmt-append",
+				Options:  []string{"   "},
+				Includes: []string{"*.hs"},
 			},
-		}
+		},
+	}
 
-		// running with a cold cache, we should see the elm files being formatted, resulting in changes, which should
-		// trigger an error
-		treefmt(t,
-			withArgs("--fail-on-change"),
-			withConfig(configPath, cfg),
-			withError(func(as *require.Assertions, err error) {
-				as.ErrorIs(err, formatCmd.ErrFailOnChange)
-			}),
-			withStats(t, map[stats.Type]int{
-				stats.Traversed: 33,
-				stats.Matched:   2,
-				stats.Formatted: 2,
-				stats.Changed:   2,
-			}),
-		)
+	// without a terminal to answer the prompt, we fail rather than wait for an answer
+	treefmt(t,
+		withConfig(configPath, cfg),
+		withArgs("--interactive"),
+		withError(func(as *require.Assertions, err error) {
+			as.ErrorContains(err, "--interactive requires stdin to be a terminal")
+		}),
+	)
 
-		// running with a hot cache, we should see matches for the elm files, but no attempt to format them as the
-		// underlying files have not changed since we last ran
-		treefmt(t,
-			withArgs("--fail-on-change"),
-			withNoError(t),
-			withStats(t, map[stats.Type]int{
-				stats.Traversed: 33,
-				stats.Matched:   2,
-				stats.Formatted: 0,
-				stats.Changed:   0,
-			}),
-		)
-	})
+	treefmt(t,
+		withArgs("--interactive", "--stdin", "Foo.hs"),
+		withError(func(as *require.Assertions, err error) {
+			as.ErrorContains(err, "--interactive cannot be used with --stdin")
+		}),
+	)
+}
 
-	t.Run("change modtime", func(t *testing.T) {
-		tempDir := test.TempExamples(t)
-		configPath := filepath.Join(tempDir, "treefmt.toml")
+func TestMaxFileSize(t *testing.T) {
+	as := require.New(t)
 
-		test.ChangeWorkDir(t, tempDir)
+	tempDir := test.TempExamples(t)
+	configPath := filepath.Join(tempDir, "treefmt.toml")
 
-		dateFormat := "2006 01 02 15:04.05"
-		replacer := strings.NewReplacer(" ", "", ":", "")
+	test.ChangeWorkDir(t, tempDir)
 
-		formatTime := func(t time.Time) string {
-			// go date formats are stupid
-			return replacer.Replace(t.Format(dateFormat))
-		}
+	// one file just under the threshold, the other just over it
+	as.NoError(os.WriteFile(filepath.Join(tempDir, "small.bin"), bytes.Repeat([]byte("a"), 1000), 0o600))
+	as.NoError(os.WriteFile(filepath.Join(tempDir, "large.bin"), bytes.Repeat([]byte("a"), 1001), 0o600))
 
-		// running with a cold cache, we should see the haskell files being formatted, resulting in changes, which should
-		// trigger an error
-		treefmt(t,
-			withArgs("--fail-on-change"),
-			withConfigFunc(configPath, func() *config.Config {
-				// new mod time is in the next second
-				modTime := time.Now().Truncate(time.Second).Add(time.Second)
+	cfg := &config.Config{
+		MaxFileSize: "1KB",
+		FormatterConfigs: map[string]*config.Formatter{
+			"echo": {
+				Command:  "echo",
+				Includes: []string{"*.bin"},
+			},
+		},
+	}
 
-				return &config.Config{
-					FormatterConfigs: map[string]*config.Formatter{
-						"append": {
-							// test-fmt-modtime is a helper defined in nix/packages/treefmt/formatters.nix which lets us set
-							// a file's modtime to an arbitrary date.
-							// in this case, we move it forward more than a second so that our second level modtime comparison
-							// will detect it as a change.
-							Command:  "test-fmt-modtime",
-							Options:  []string{formatTime(modTime)},
-							Includes: []string{"haskell/*"},
-						},
-					},
-				}
-			}),
-			withError(func(as *require.Assertions, err error) {
-				as.ErrorIs(err, formatCmd.ErrFailOnChange)
-			}),
-			withStats(t, map[stats.Type]int{
-				stats.Traversed: 33,
-				stats.Matched:   7,
-				stats.Formatted: 7,
-				stats.Changed:   7,
-			}),
-		)
+	treefmt(t,
+		withConfig(configPath, cfg),
+		withArgs("--no-cache"),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 35,
+			stats.Matched:   1,
+			stats.Formatted: 1,
+		}),
+	)
 
-		// running with a hot cache, we should see matches for the haskell files, but no attempt to format them as the
-		// underlying files have not changed since we last ran
-		treefmt(t,
-			withArgs("--fail-on-change"),
-			withNoError(t),
-			withStats(t, map[stats.Type]int{
-				stats.Traversed: 33,
-				stats.Matched:   7,
-				stats.Formatted: 0,
-				stats.Changed:   0,
-			}),
-		)
-	})
+	// files over the limit are neither listed nor explained as formatted
+	treefmt(t,
+		withArgs("--list-files"),
+		withNoError(t),
+		withStdout(func(out []byte) {
+			as.Equal("echo:\n  small.bin\n", string(out))
+		}),
+	)
+
+	treefmt(t,
+		withArgs("--explain", "large.bin"),
+		withNoError(t),
+		withStdout(func(out []byte) {
+			as.Contains(string(out), "max file size: 1001 bytes, larger than the max-file-size of 1000 bytes")
+			as.Contains(string(out), "verdict: skipped because it is larger than the max-file-size of 1000 bytes\n")
+		}),
+	)
+
+	treefmt(t,
+		withArgs("--explain", "small.bin"),
+		withNoError(t),
+		withStdout(func(out []byte) {
+			as.Contains(string(out), "max file size: 1000 bytes, within the max-file-size of 1000 bytes")
+			as.Contains(string(out), "verdict: formatted by echo\n")
+		}),
+	)
+
+	// the flag takes precedence over the config file
+	treefmt(t,
+		withArgs("--no-cache", "--max-file-size", "2KB"),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 35,
+			stats.Matched:   2,
+			stats.Formatted: 2,
+		}),
+	)
+
+	// a formatter can have a limit of its own
+	cfg.MaxFileSize = ""
+	cfg.FormatterConfigs["echo"].MaxFileSize = "1000B"
+
+	treefmt(t,
+		withConfig(configPath, cfg),
+		withArgs("--no-cache"),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 35,
+			stats.Matched:   1,
+			stats.Formatted: 1,
+		}),
+	)
+
+	treefmt(t,
+		withArgs("--explain", "large.bin"),
+		withNoError(t),
+		withStdout(func(out []byte) {
+			as.Contains(string(out), `formatter echo: included by "*.bin" but larger than its max-file-size of 1000 bytes`)
+			as.Contains(string(out), "verdict: skipped because no formatter matches it\n")
+		}),
+	)
+
+	// invalid sizes are rejected
+	treefmt(t,
+		withArgs("--max-file-size", "lots"),
+		withError(func(as *require.Assertions, err error) {
+			as.ErrorContains(err, `max-file-size: invalid size "lots"`)
+		}),
+	)
 }
 
-func TestCacheBusting(t *testing.T) {
+func TestSelfTest(t *testing.T) {
 	as := require.New(t)
 
-	t.Run("formatter_change_config", func(t *testing.T) {
-		tempDir := test.TempExamples(t)
-		configPath := filepath.Join(tempDir, "treefmt.toml")
+	tempDir := test.TempExamples(t)
+	configPath := filepath.Join(tempDir, "treefmt.toml")
 
-		test.ChangeWorkDir(t, tempDir)
+	test.ChangeWorkDir(t, tempDir)
 
-		// basic config
-		cfg := &config.Config{
-			FormatterConfigs: map[string]*config.Formatter{
-				"python": {
-					Command:  "echo", // this is non-destructive, will match but cause no changes
-					Includes: []string{"*.py"},
-				},
-				"haskell": {
-					Command:  "test-fmt-append",
-					Options:  []string{"   "},
-					Includes: []string{"*.hs"},
-				},
+	cfg := &config.Config{
+		FormatterConfigs: map[string]*config.Formatter{
+			// appends every time it is applied, so it is not idempotent
+			"append": {
+				Command:  "test-fmt-append",
+				Options:  []string{"   "},
+				Includes: []string{"*.hs"},
 			},
-		}
+			// prints the file rather than formatting it in place
+			"cat": {
+				Command:  "cat",
+				Includes: []string{"*.rs"},
+			},
+			"echo": {
+				Command:     "echo",
+				Description: "leaves files alone",
+				Includes:    []string{"*.go"},
+			},
+			"missing": {
+				Command:  "does-not-exist",
+				Includes: []string{"*.py"},
+			},
+			"unmatched": {
+				Command:  "echo",
+				Includes: []string{"*.nope"},
+			},
+		},
+	}
 
-		// initial run
-		treefmt(t,
-			withConfig(configPath, cfg),
-			withNoError(t),
-			withStats(t, map[stats.Type]int{
-				stats.Traversed: 33,
-				stats.Matched:   8,
-				stats.Formatted: 8,
-				stats.Changed:   6,
-			}))
+	original, err := os.ReadFile(filepath.Join(tempDir, "go", "main.go"))
+	as.NoError(err)
 
-		// change formatter options
-		cfg.FormatterConfigs["haskell"].Options = []string{""}
+	treefmt(t,
+		withConfig(configPath, cfg),
+		withArgs("--self-test"),
+		withError(func(as *require.Assertions, err error) {
+			as.ErrorContains(err, "3 of 5 formatter(s) failed the self-test")
+		}),
+		withStdout(func(out []byte) {
+			as.Regexp(`append\s+fail: formatting a second time changed the file\s+haskell/`, string(out))
+			as.Regexp(`cat\s+warn: seems to print to stdout instead of formatting in place`, string(out))
+			as.Regexp(`echo\s+pass\s+go/main.go\s+leaves files alone`, string(out))
+			as.Regexp(`missing\s+fail: formatter command not found in PATH: does-not-exist`, string(out))
+			as.Regexp(`unmatched\s+fail: no file matches its includes`, string(out))
+		}),
+	)
 
-		// cache entries for haskell files should be invalidated
+	// the tree is left untouched
+	content, err := os.ReadFile(filepath.Join(tempDir, "go", "main.go"))
+	as.NoError(err)
+	as.Equal(original, content)
+
+	// passing formatters make for a passing self-test
+	treefmt(t,
+		withArgs("--self-test", "--formatters", "echo"),
+		withNoError(t),
+	)
+}
+
+func TestAtomicWrites(t *testing.T) {
+	as := require.New(t)
+
+	tempDir := test.TempExamples(t)
+	configPath := filepath.Join(tempDir, "treefmt.toml")
+
+	test.ChangeWorkDir(t, tempDir)
+
+	cfg := &config.Config{
+		FormatterConfigs: map[string]*config.Formatter{
+			// truncates every file it is given before failing
+			"destructive": {
+				Command:  `for f in "$@"; do : > "$f"; done; exit 1`,
+				Shell:    true,
+				Includes: []string{"*.hs"},
+			},
+		},
+	}
+
+	fooPath := filepath.Join(tempDir, "haskell", "Foo.hs")
+
+	original, err := os.ReadFile(fooPath)
+	as.NoError(err)
+
+	entries, err := os.ReadDir(filepath.Join(tempDir, "haskell"))
+	as.NoError(err)
+
+	// with atomic writes, the files are restored once the formatter fails
+	treefmt(t,
+		withConfig(configPath, cfg),
+		withArgs("--no-cache", "--atomic-writes"),
+		withError(func(as *require.Assertions, err error) {
+			as.ErrorIs(err, format.ErrFormattingFailures)
+		}),
+	)
+
+	content, err := os.ReadFile(fooPath)
+	as.NoError(err)
+	as.Equal(original, content)
+
+	// no backups are left behind
+	after, err := os.ReadDir(filepath.Join(tempDir, "haskell"))
+	as.NoError(err)
+	as.Len(after, len(entries))
+
+	// without, the files are left as the formatter left them
+	treefmt(t,
+		withArgs("--no-cache"),
+		withError(func(as *require.Assertions, err error) {
+			as.ErrorIs(err, format.ErrFormattingFailures)
+		}),
+	)
+
+	content, err = os.ReadFile(fooPath)
+	as.NoError(err)
+	as.Empty(content)
+}
+
+func TestSpecifyingFormatters(t *testing.T) {
+	// we use the test formatter to append some whitespace
+	cfg := &config.Config{
+		FormatterConfigs: map[string]*config.Formatter{
+			"elm": {
+				Command:  "test-fmt-append",
+				Options:  []string{"   "},
+				Includes: []string{"*.elm"},
+			},
+			"nix": {
+				Command:  "test-fmt-append",
+				Options:  []string{"   "},
+				Includes: []string{"*.nix"},
+			},
+			"ruby": {
+				Command:  "test-fmt-append",
+				Options:  []string{"   "},
+				Includes: []string{"*.rb"},
+			},
+		},
+	}
+
+	tempDir := test.TempExamples(t)
+	configPath := filepath.Join(tempDir, "treefmt.toml")
+
+	test.WriteConfig(t, configPath, cfg)
+	test.ChangeWorkDir(t, tempDir)
+
+	t.Run("default", func(t *testing.T) {
 		treefmt(t,
-			withConfig(configPath, cfg),
 			withNoError(t),
+			withModtimeBump(tempDir, time.Second),
 			withStats(t, map[stats.Type]int{
 				stats.Traversed: 33,
-				stats.Matched:   8,
-				stats.Formatted: 6,
-				stats.Changed:   6,
-			}))
+				stats.Matched:   3,
+				stats.Formatted: 3,
+				stats.Changed:   3,
+			}),
+		)
+	})
 
-		// run again, nothing should be formatted
+	t.Run("args", func(t *testing.T) {
 		treefmt(t,
-			withConfig(configPath, cfg),
+			withArgs("--formatters", "elm,nix"),
+			withModtimeBump(tempDir, time.Second),
 			withNoError(t),
 			withStats(t, map[stats.Type]int{
 				stats.Traversed: 33,
-				stats.Matched:   8,
-				stats.Formatted: 0,
-				stats.Changed:   0,
-			}))
-
-		// change the formatter's command
-		cfg.FormatterConfigs["haskell"].Command = "echo"
+				stats.Matched:   2,
+				stats.Formatted: 2,
+				stats.Changed:   2,
+			}),
+		)
 
-		// cache entries for haskell files should be invalidated
 		treefmt(t,
-			withConfig(configPath, cfg),
+			withArgs("--formatters", "ruby,nix"),
+			withModtimeBump(tempDir, time.Second),
 			withNoError(t),
 			withStats(t, map[stats.Type]int{
 				stats.Traversed: 33,
-				stats.Matched:   8,
-				stats.Formatted: 6,
-				stats.Changed:   0, // echo doesn't affect the files so no changes expected
-			}))
+				stats.Matched:   2,
+				stats.Formatted: 2,
+				stats.Changed:   2,
+			}),
+		)
 
-		// run again, nothing should be formatted
 		treefmt(t,
-			withConfig(configPath, cfg),
+			withArgs("--formatters", "nix"),
+			withModtimeBump(tempDir, time.Second),
 			withNoError(t),
 			withStats(t, map[stats.Type]int{
 				stats.Traversed: 33,
-				stats.Matched:   8,
-				stats.Formatted: 0,
-				stats.Changed:   0,
-			}))
-
-		// change the formatters includes
-		cfg.FormatterConfigs["haskell"].Includes = []string{"haskell/*.hs"}
-
-		// we should match on fewer files, but no formatting should occur as includes are not part of the formatting
-		// signature
+				stats.Matched:   1,
+				stats.Formatted: 1,
+				stats.Changed:   1,
+			}),
+		)
+
+		// bad name
 		treefmt(t,
-			withConfig(configPath, cfg),
-			withNoError(t),
-			withStats(t, map[stats.Type]int{
-				stats.Traversed: 33,
-				stats.Matched:   6,
-				stats.Formatted: 0,
-				stats.Changed:   0,
-			}))
+			withArgs("--formatters", "foo"),
+			withError(func(as *require.Assertions, err error) {
+				as.ErrorContains(err, "formatter foo not found in config")
+			}),
+		)
+	})
 
-		// change the formatters excludes
-		cfg.FormatterConfigs["haskell"].Excludes = []string{"haskell/Foo.hs"}
+	t.Run("env", func(t *testing.T) {
+		t.Setenv("TREEFMT_FORMATTERS", "ruby,nix")
 
-		// we should match on fewer files, but no formatting should occur as excludes are not part of the formatting
-		// signature
 		treefmt(t,
-			withConfig(configPath, cfg),
 			withNoError(t),
+			withModtimeBump(tempDir, time.Second),
 			withStats(t, map[stats.Type]int{
 				stats.Traversed: 33,
-				stats.Matched:   5,
-				stats.Formatted: 0,
-				stats.Changed:   0,
-			}))
+				stats.Matched:   2,
+				stats.Formatted: 2,
+				stats.Changed:   2,
+			}),
+		)
+
+		t.Setenv("TREEFMT_FORMATTERS", "bar,foo")
+
+		treefmt(t,
+			withError(func(as *require.Assertions, err error) {
+				as.ErrorContains(err, "formatter bar not found in config")
+			}),
+		)
+	})
+
+	t.Run("bad names", func(t *testing.T) {
+		for _, name := range []string{"foo$", "/bar", "baz%"} {
+			treefmt(t,
+				withArgs("--formatters", name),
+				withError(func(as *require.Assertions, err error) {
+					as.ErrorContains(err, fmt.Sprintf("formatter name %q is invalid", name))
+				}),
+			)
+
+			t.Setenv("TREEFMT_FORMATTERS", name)
+
+			treefmt(t,
+				withError(func(as *require.Assertions, err error) {
+					as.ErrorContains(err, fmt.Sprintf("formatter name %q is invalid", name))
+				}),
+			)
+
+			t.Setenv("TREEFMT_FORMATTERS", "")
+
+			cfg.FormatterConfigs[name] = &config.Formatter{
+				Command:  "echo",
+				Includes: []string{"*"},
+			}
+
+			test.WriteConfig(t, configPath, cfg)
+
+			treefmt(t,
+				withError(func(as *require.Assertions, err error) {
+					as.ErrorContains(err, fmt.Sprintf("formatter name %q is invalid", name))
+				}),
+			)
+
+			delete(cfg.FormatterConfigs, name)
+
+			test.WriteConfig(t, configPath, cfg)
+		}
 	})
+}
+
+func TestIncludesAndExcludes(t *testing.T) {
+	tempDir := test.TempExamples(t)
+	configPath := filepath.Join(tempDir, "treefmt.toml")
+
+	test.ChangeWorkDir(t, tempDir)
+
+	// test without any excludes
+	cfg := &config.Config{
+		FormatterConfigs: map[string]*config.Formatter{
+			"echo": {
+				Command:  "echo",
+				Includes: []string{"*"},
+			},
+		},
+	}
+
+	treefmt(t,
+		withConfig(configPath, cfg),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 33,
+			stats.Matched:   33,
+			stats.Formatted: 33,
+			stats.Changed:   0,
+		}),
+	)
+
+	// globally exclude nix files
+	cfg.Excludes = []string{"*.nix"}
+
+	treefmt(t,
+		withArgs("-c"),
+		withConfig(configPath, cfg),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 33,
+			stats.Matched:   32,
+			stats.Formatted: 32,
+			stats.Changed:   0,
+		}),
+	)
+
+	// add haskell files to the global exclude
+	cfg.Excludes = []string{"*.nix", "*.hs"}
+
+	treefmt(t,
+		withArgs("-c"),
+		withConfig(configPath, cfg),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 33,
+			stats.Matched:   26,
+			stats.Formatted: 26,
+			stats.Changed:   0,
+		}),
+	)
+
+	echo := cfg.FormatterConfigs["echo"]
+
+	// remove python files from the echo formatter
+	echo.Excludes = []string{"*.py"}
+
+	treefmt(t,
+		withArgs("-c"),
+		withConfig(configPath, cfg),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 33,
+			stats.Matched:   24,
+			stats.Formatted: 24,
+			stats.Changed:   0,
+		}),
+	)
+
+	// remove go files from the echo formatter via env
+	t.Setenv("TREEFMT_FORMATTER_ECHO_EXCLUDES", "*.py,*.go")
+
+	treefmt(t,
+		withArgs("-c"),
+		withConfig(configPath, cfg),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 33,
+			stats.Matched:   23,
+			stats.Formatted: 23,
+			stats.Changed:   0,
+		}),
+	)
+
+	t.Setenv("TREEFMT_FORMATTER_ECHO_EXCLUDES", "") // reset
+
+	// adjust the includes for echo to only include elm files
+	echo.Includes = []string{"*.elm"}
+
+	treefmt(t,
+		withArgs("-c"),
+		withConfig(configPath, cfg),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 33,
+			stats.Matched:   1,
+			stats.Formatted: 1,
+			stats.Changed:   0,
+		}),
+	)
+
+	// add js files to echo formatter via env
+	t.Setenv("TREEFMT_FORMATTER_ECHO_INCLUDES", "*.elm,*.js")
+
+	treefmt(t,
+		withArgs("-c"),
+		withConfig(configPath, cfg),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 33,
+			stats.Matched:   2,
+			stats.Formatted: 2,
+			stats.Changed:   0,
+		}),
+	)
+}
+
+func TestReportUnmatchedFormatters(t *testing.T) {
+	as := require.New(t)
+
+	tempDir := test.TempExamples(t)
+	configPath := filepath.Join(tempDir, "treefmt.toml")
+
+	test.ChangeWorkDir(t, tempDir)
+
+	cfg := &config.Config{
+		FormatterConfigs: map[string]*config.Formatter{
+			"python": {
+				Command:  "echo",
+				Includes: []string{"*.py"},
+				Excludes: []string{"*.py"},
+			},
+			"kotlin": {
+				Command:  "echo",
+				Includes: []string{"*.kt"},
+			},
+			"go": {
+				Command:  "echo",
+				Includes: []string{"*.go"},
+			},
+		},
+	}
+
+	treefmt(t,
+		withConfig(configPath, cfg),
+		withArgs("-v"),
+		withNoError(t),
+		withStderr(func(out []byte) {
+			as.Contains(
+				string(out),
+				"formatter | python: matched no files: all 2 file(s) matching its includes were rejected by its excludes",
+			)
+			as.Contains(string(out), "formatter | kotlin: no files match its includes")
+			as.NotContains(string(out), "formatter | go: no files")
+		}),
+	)
+
+	// nothing is reported when formatting specific paths
+	treefmt(t,
+		withArgs("-v", "go"),
+		withNoError(t),
+		withStderr(func(out []byte) {
+			as.NotContains(string(out), "matched no files")
+			as.NotContains(string(out), "no files match its includes")
+		}),
+	)
+}
+
+func TestRespectGitattributes(t *testing.T) {
+	as := require.New(t)
+
+	tempDir := test.TempExamples(t)
+	configPath := filepath.Join(tempDir, "treefmt.toml")
+
+	test.ChangeWorkDir(t, tempDir)
+
+	as.NoError(os.WriteFile(filepath.Join(tempDir, ".gitattributes"), []byte(`
+/haskell-frontend/** linguist-generated
+Setup.hs linguist-generated
+haskell/Setup.hs -linguist-generated
+`), 0o600))
+
+	cfg := &config.Config{
+		FormatterConfigs: map[string]*config.Formatter{
+			"echo": {
+				Command:  "echo",
+				Includes: []string{"*.hs"},
+			},
+		},
+	}
+
+	// .gitattributes is ignored by default
+	treefmt(t,
+		withConfig(configPath, cfg),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Matched: 6,
+		}),
+	)
+
+	// generated files are skipped when requested
+	treefmt(t,
+		withConfig(configPath, cfg),
+		withArgs("--respect-gitattributes"),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Matched: 4,
+		}),
+	)
+
+	// the same can be enabled in config
+	cfg.RespectGitattributes = true
+
+	treefmt(t,
+		withConfig(configPath, cfg),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Matched: 4,
+		}),
+	)
+}
+
+func TestInit(t *testing.T) {
+	as := require.New(t)
+
+	tempDir := t.TempDir()
+	configPath := filepath.Join(tempDir, "treefmt.toml")
+
+	// a sample config is generated in the working directory
+	treefmt(t,
+		withArgs("-C", tempDir, "--init"),
+		withNoError(t),
+		withStdout(func(out []byte) {
+			as.Equal("Generated treefmt.toml. Now it's your turn to edit it.\n", string(out))
+		}),
+	)
+
+	sample, err := os.ReadFile(configPath)
+	as.NoError(err)
+	as.NotEmpty(sample)
+
+	// an existing config is preserved
+	existing := []byte("[formatter.echo]\ncommand = \"echo\"\n")
+	as.NoError(os.WriteFile(configPath, existing, 0o600))
+
+	treefmt(t,
+		withArgs("-C", tempDir, "--init"),
+		withError(func(as *require.Assertions, err error) {
+			as.ErrorIs(err, initCmd.ErrConfigExists)
+			as.ErrorContains(err, "use --overwrite to replace it")
+		}),
+	)
+
+	contents, err := os.ReadFile(configPath)
+	as.NoError(err)
+	as.Equal(existing, contents)
+
+	// including a .treefmt.toml, which treefmt.toml would otherwise take precedence over
+	as.NoError(os.Rename(configPath, filepath.Join(tempDir, ".treefmt.toml")))
+
+	treefmt(t,
+		withArgs("-C", tempDir, "--init"),
+		withError(func(as *require.Assertions, err error) {
+			as.ErrorIs(err, initCmd.ErrConfigExists)
+		}),
+	)
+
+	as.NoFileExists(configPath)
+
+	// unless overwriting is requested
+	as.NoError(os.Rename(filepath.Join(tempDir, ".treefmt.toml"), configPath))
+
+	treefmt(t,
+		withArgs("-C", tempDir, "--init", "--overwrite"),
+		withNoError(t),
+	)
+
+	contents, err = os.ReadFile(configPath)
+	as.NoError(err)
+	as.Equal(sample, contents)
+
+	// which only applies to init
+	treefmt(t,
+		withArgs("-C", tempDir, "--overwrite"),
+		withError(func(as *require.Assertions, err error) {
+			as.EqualError(err, "--overwrite can only be used with --init")
+		}),
+	)
+}
+
+func TestConfigFile(t *testing.T) {
+	as := require.New(t)
+
+	for _, name := range []string{"treefmt.toml", ".treefmt.toml"} {
+		t.Run(name, func(t *testing.T) {
+			tempDir := test.TempExamples(t)
+
+			// use a config file in a different temp directory
+			configPath := filepath.Join(t.TempDir(), name)
+
+			// if we don't specify a tree root, we default to the directory containing the config file
+			treefmt(t,
+				withConfig(configPath, &config.Config{
+					FormatterConfigs: map[string]*config.Formatter{
+						"echo": {
+							Command:  "echo",
+							Includes: []string{"*"},
+						},
+					},
+				}),
+				withArgs("--config-file", configPath),
+				withNoError(t),
+				withStats(t, map[stats.Type]int{
+					stats.Traversed: 1,
+					stats.Matched:   1,
+					stats.Formatted: 1,
+					stats.Changed:   0,
+				}),
+			)
+
+			treefmt(t,
+				withArgs("--config-file", configPath, "--tree-root", tempDir),
+				withNoError(t),
+				withStats(t, map[stats.Type]int{
+					stats.Traversed: 33,
+					stats.Matched:   33,
+					stats.Formatted: 33,
+					stats.Changed:   0,
+				}),
+			)
+
+			// the config file need not be inside the tree root, but the tree root must be a directory
+			treefmt(t,
+				withArgs("--config-file", configPath, "--tree-root", filepath.Join(tempDir, "missing")),
+				withError(func(as *require.Assertions, err error) {
+					as.ErrorContains(err, "invalid tree root")
+					as.ErrorIs(err, fs.ErrNotExist)
+				}),
+			)
+
+			treefmt(t,
+				withArgs("--config-file", configPath, "--tree-root", filepath.Join(tempDir, "go", "main.go")),
+				withError(func(as *require.Assertions, err error) {
+					as.ErrorContains(err, "main.go is not a directory")
+				}),
+			)
+
+			// the tree root can still be discovered when using an explicit config file
+			treefmt(t,
+				withArgs(
+					"--config-file", configPath,
+					"--tree-root-file", "go.mod",
+					"-C", filepath.Join(tempDir, "go"),
+				),
+				withNoError(t),
+				withStats(t, map[stats.Type]int{
+					stats.Traversed: 2,
+					stats.Matched:   2,
+				}),
+			)
+
+			// a relative config file is resolved against the working directory, with the tree root still defaulting
+			// to the directory containing it
+			configDir := filepath.Dir(configPath)
+
+			treefmt(t,
+				withArgs(
+					"-C", filepath.Dir(configDir),
+					"--config-file", filepath.Join(filepath.Base(configDir), name),
+				),
+				withNoError(t),
+				withStats(t, map[stats.Type]int{
+					stats.Traversed: 1,
+					stats.Matched:   1,
+				}),
+			)
+
+			// use env variable
+			treefmt(t,
+				withEnv(map[string]string{
+					// TREEFMT_CONFIG takes precedence
+					"TREEFMT_CONFIG": configPath,
+					"PRJ_ROOT":       tempDir,
+				}),
+				withNoError(t),
+				withStats(t, map[stats.Type]int{
+					stats.Traversed: 1,
+					stats.Matched:   1,
+					stats.Formatted: 0,
+					stats.Changed:   0,
+				}),
+			)
+
+			// should fallback to PRJ_ROOT
+			treefmt(t,
+				withArgs("--tree-root", tempDir),
+				withEnv(map[string]string{
+					"PRJ_ROOT": filepath.Dir(configPath),
+				}),
+				withNoError(t),
+				withStats(t, map[stats.Type]int{
+					stats.Traversed: 33,
+					stats.Matched:   33,
+					stats.Formatted: 0,
+					stats.Changed:   0,
+				}),
+			)
+
+			// should not search upwards if using PRJ_ROOT
+			configSubDir := filepath.Join(filepath.Dir(configPath), "sub")
+			as.NoError(os.MkdirAll(configSubDir, 0o600))
+
+			treefmt(t,
+				withArgs("--tree-root", tempDir),
+				withEnv(map[string]string{
+					"PRJ_ROOT": configSubDir,
+				}),
+				withError(func(as *require.Assertions, err error) {
+					as.ErrorContains(err, "failed to find treefmt config file")
+				}),
+			)
+		})
+	}
+}
+
+func TestEmbeddedConfig(t *testing.T) {
+	as := require.New(t)
+
+	tempDir := test.TempExamples(t)
+
+	// replace treefmt.toml with a [tool.treefmt] table in pyproject.toml
+	as.NoError(os.Remove(filepath.Join(tempDir, "treefmt.toml")))
+	as.NoError(os.WriteFile(filepath.Join(tempDir, "pyproject.toml"), []byte(`
+[project]
+name = "examples"
+
+[tool.treefmt.formatter.echo]
+command = "echo"
+includes = ["*.py"]
+`), 0o600))
+
+	// it is found when searching upwards, with the tree root defaulting to the directory containing it
+	treefmt(t,
+		withArgs("-C", filepath.Join(tempDir, "python")),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 33,
+			stats.Matched:   2,
+			stats.Formatted: 2,
+			stats.Changed:   0,
+		}),
+	)
+
+	// a treefmt.toml alongside it takes precedence, with a warning that the embedded config is ignored
+	treefmt(t,
+		withConfig(filepath.Join(tempDir, "treefmt.toml"), &config.Config{
+			FormatterConfigs: map[string]*config.Formatter{
+				"echo": {
+					Command:  "echo",
+					Includes: []string{"*.go"},
+				},
+			},
+		}),
+		withArgs("-C", tempDir),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 34,
+			stats.Matched:   1,
+			stats.Formatted: 1,
+			stats.Changed:   0,
+		}),
+		withStderr(func(out []byte) {
+			as.Contains(string(out), "ignoring the config embedded in "+filepath.Join(tempDir, "pyproject.toml"))
+		}),
+	)
+}
+
+func TestInvalidConfigFile(t *testing.T) {
+	as := require.New(t)
+
+	tempDir := test.TempExamples(t)
+	configPath := filepath.Join(tempDir, "treefmt.toml")
+
+	test.ChangeWorkDir(t, tempDir)
+
+	// an empty config has no formatters
+	as.NoError(os.WriteFile(configPath, nil, 0o600))
+
+	treefmt(t,
+		withError(func(as *require.Assertions, err error) {
+			as.ErrorContains(err, "no formatters defined in")
+			as.ErrorContains(err, "add a [formatter.<name>] section")
+		}),
+	)
+
+	// syntax errors report the position of the problem
+	as.NoError(os.WriteFile(configPath, []byte("[formatter.go]\ncommand = \n"), 0o600))
+
+	treefmt(t,
+		withError(func(as *require.Assertions, err error) {
+			as.ErrorContains(err, "failed to parse")
+			as.ErrorContains(err, "treefmt.toml: line 2")
+		}),
+	)
+}
+
+func TestFormattersDir(t *testing.T) {
+	as := require.New(t)
+
+	tempDir := test.TempExamples(t)
+	configPath := filepath.Join(tempDir, "treefmt.toml")
+	formattersDir := filepath.Join(tempDir, "treefmt.d")
+
+	test.ChangeWorkDir(t, tempDir)
+
+	as.NoError(os.Mkdir(formattersDir, 0o755))
+
+	test.WriteConfig(t, configPath, &config.Config{
+		FormattersDir: "treefmt.d",
+		FormatterConfigs: map[string]*config.Formatter{
+			"python": {
+				Command:  "echo",
+				Includes: []string{"*.py"},
+			},
+		},
+	})
+
+	test.WriteConfig(t, filepath.Join(formattersDir, "go.toml"), &config.Config{
+		FormatterConfigs: map[string]*config.Formatter{
+			"go": {
+				Command:  "echo",
+				Includes: []string{"*.go"},
+			},
+		},
+	})
+
+	// formatters from both files are applied
+	treefmt(t,
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 34,
+			stats.Matched:   3,
+			stats.Formatted: 3,
+			stats.Changed:   0,
+		}),
+	)
+
+	// defining the same formatter again merges the definitions, appending to the includes
+	test.WriteConfig(t, filepath.Join(formattersDir, "python.toml"), &config.Config{
+		FormatterConfigs: map[string]*config.Formatter{
+			"python": {
+				Command:  "echo",
+				Includes: []string{"*.py", "*.txt"},
+			},
+		},
+	})
+
+	treefmt(t,
+		withArgs("--no-cache"),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 35,
+			stats.Matched:   4,
+			stats.Formatted: 4,
+			stats.Changed:   0,
+		}),
+	)
+
+	// unless the later definition replaces them
+	test.WriteConfig(t, filepath.Join(formattersDir, "python.toml"), &config.Config{
+		FormatterConfigs: map[string]*config.Formatter{
+			"python": {
+				Command:         "echo",
+				Includes:        []string{"*.txt"},
+				ReplaceIncludes: true,
+			},
+		},
+	})
+
+	treefmt(t,
+		withArgs("--no-cache"),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 35,
+			stats.Matched:   2,
+			stats.Formatted: 2,
+			stats.Changed:   0,
+		}),
+	)
+
+	// a missing directory is an error, rather than quietly dropping the formatters defined within it
+	treefmt(t,
+		withArgs("--formatters-dir", "treefmt.dd"),
+		withError(func(as *require.Assertions, err error) {
+			as.ErrorIs(err, fs.ErrNotExist)
+			as.ErrorContains(err, "formatters-dir")
+		}),
+	)
+}
+
+func TestConfigFiles(t *testing.T) {
+	as := require.New(t)
+
+	tempDir := test.TempExamples(t)
+	configPath := filepath.Join(tempDir, "treefmt.toml")
+	fmtrcPath := filepath.Join(tempDir, ".fmtrc")
+
+	test.ChangeWorkDir(t, tempDir)
+
+	cfg := &config.Config{
+		FormatterConfigs: map[string]*config.Formatter{
+			"python": {
+				Command:     "test-fmt-append",
+				Options:     []string{"   "},
+				Includes:    []string{"*.py"},
+				ConfigFiles: []string{".fmtrc"},
+			},
+		},
+	}
+
+	formatted := func(count int, hits int) option {
+		return withStats(t, map[stats.Type]int{
+			stats.Matched:   2,
+			stats.Formatted: count,
+			stats.CacheHits: hits,
+		})
+	}
+
+	// the config file does not exist yet, which is fine
+	treefmt(t, withConfig(configPath, cfg), withNoError(t), formatted(2, 0))
+	treefmt(t, withNoError(t), formatted(0, 2))
+
+	// creating it invalidates the cache for the files the formatter matches
+	as.NoError(os.WriteFile(fmtrcPath, []byte("indent = 2\n"), 0o600))
+
+	treefmt(t, withNoError(t), formatted(2, 0))
+	treefmt(t, withNoError(t), formatted(0, 2))
+
+	// as does changing it
+	as.NoError(os.WriteFile(fmtrcPath, []byte("indent = 4\nwidth = 80\n"), 0o600))
+
+	treefmt(t, withNoError(t), formatted(2, 0))
+	treefmt(t, withNoError(t), formatted(0, 2))
+
+	// or removing it
+	as.NoError(os.Remove(fmtrcPath))
+
+	treefmt(t, withNoError(t), formatted(2, 0))
+	treefmt(t, withNoError(t), formatted(0, 2))
+}
+
+func TestCache(t *testing.T) {
+	tempDir := test.TempExamples(t)
+	configPath := filepath.Join(tempDir, "treefmt.toml")
+
+	test.ChangeWorkDir(t, tempDir)
+
+	// test without any excludes
+	cfg := &config.Config{
+		FormatterConfigs: map[string]*config.Formatter{
+			"append": {
+				Command:  "test-fmt-append",
+				Options:  []string{"   "},
+				Includes: []string{"*"},
+			},
+		},
+	}
+
+	test.WriteConfig(t, configPath, cfg)
+
+	// first run
+	treefmt(t,
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 33,
+			stats.Matched:   33,
+			stats.Formatted: 33,
+			stats.Changed:   33,
+		}),
+	)
+
+	// cached run with no changes to underlying files
+	treefmt(t,
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 33,
+			stats.Matched:   33,
+			stats.Formatted: 0,
+			stats.Changed:   0,
+			stats.CacheHits: 33,
+		}),
+		withStderr(func(out []byte) {
+			require.Contains(t, string(out), "matched 33 files (33 cache hits, 0 cache misses, 100% hit rate)")
+		}),
+	)
+
+	// without the cache, there are no hits or misses to report
+	treefmt(t,
+		withArgs("--no-cache"),
+		withNoError(t),
+		withStderr(func(out []byte) {
+			require.Contains(t, string(out), "matched 33 files\n")
+			require.NotContains(t, string(out), "cache")
+		}),
+	)
+
+	// clear cache
+	treefmt(t,
+		withArgs("-c"),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 33,
+			stats.Matched:   33,
+			stats.Formatted: 33,
+			stats.Changed:   33,
+		}),
+	)
+
+	// cached run with no changes to underlying files
+	treefmt(t,
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 33,
+			stats.Matched:   33,
+			stats.Formatted: 0,
+			stats.Changed:   0,
+		}),
+	)
+
+	// bump underlying files
+	treefmt(t,
+		withNoError(t),
+		withModtimeBump(tempDir, time.Second),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 33,
+			stats.Matched:   33,
+			stats.Formatted: 33,
+			stats.Changed:   33,
+			stats.CacheHits: 0,
+		}),
+	)
+
+	// force a directory, which should not include the similarly named haskell-frontend directory
+	// the remaining files are served from the cache, so the hits and misses add up to the matched files
+	treefmt(t,
+		withArgs("--force", "haskell"),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 33,
+			stats.Matched:   33,
+			stats.Formatted: 6,
+			stats.Changed:   6,
+			stats.CacheHits: 27,
+		}),
+		withStderr(func(out []byte) {
+			require.Contains(t, string(out), "matched 33 files (27 cache hits, 6 cache misses, 82% hit rate)")
+		}),
+	)
+
+	// forcing the tree root bypasses the cache for every file
+	treefmt(t,
+		withArgs("--force", "haskell/Main.hs", "--force", "."),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 33,
+			stats.Matched:   33,
+			stats.Formatted: 33,
+			stats.Changed:   33,
+		}),
+	)
+
+	// forced paths must exist within the tree root
+	treefmt(t,
+		withArgs("--force", "foo"),
+		withError(func(as *require.Assertions, err error) {
+			as.ErrorContains(err, "path foo not found")
+		}),
+	)
+
+	// no cache
+	treefmt(t,
+		withArgs("--no-cache"),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 33,
+			stats.Matched:   33,
+			stats.Formatted: 33,
+			stats.Changed:   33,
+		}),
+	)
+
+	// update the config with a failing formatter
+	cfg = &config.Config{
+		FormatterConfigs: map[string]*config.Formatter{
+			// fails to execute
+			"fail": {
+				Command:  "touch",
+				Options:  []string{"--bad-arg"},
+				Includes: []string{"*.hs"},
+			},
+		},
+	}
+	test.WriteConfig(t, configPath, cfg)
+
+	// test that formatting errors are not cached
+
+	// running should match but not format anything
+
+	treefmt(t,
+		withError(func(as *require.Assertions, err error) {
+			as.ErrorIs(err, format.ErrFormattingFailures)
+		}),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 33,
+			stats.Matched:   6,
+			stats.Formatted: 0,
+			stats.Changed:   0,
+		}),
+	)
+
+	// running again should provide the same result
+	treefmt(t,
+		withError(func(as *require.Assertions, err error) {
+			as.ErrorIs(err, format.ErrFormattingFailures)
+		}),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 33,
+			stats.Matched:   6,
+			stats.Formatted: 0,
+			stats.Changed:   0,
+		}),
+	)
+
+	// let's fix the haskell config so it no longer fails
+	cfg.FormatterConfigs["fail"] = &config.Formatter{
+		Command:  "test-fmt-append",
+		Options:  []string{"   "},
+		Includes: []string{"*.hs"},
+	}
+
+	test.WriteConfig(t, configPath, cfg)
+
+	// we should now format the haskell files
+	treefmt(t,
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 33,
+			stats.Matched:   6,
+			stats.Formatted: 6,
+			stats.Changed:   6,
+		}),
+	)
+}
+
+func TestFormatterArg(t *testing.T) {
+	tempDir := test.TempExamples(t)
+	configPath := filepath.Join(tempDir, "treefmt.toml")
+
+	test.ChangeWorkDir(t, tempDir)
+
+	test.WriteConfig(t, configPath, &config.Config{
+		FormatterConfigs: map[string]*config.Formatter{
+			"echo": {
+				Command:  "echo",
+				Options:  []string{"foo"},
+				Includes: []string{"*.go"},
+			},
+		},
+	})
+
+	treefmt(t,
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Matched:   1,
+			stats.Formatted: 1,
+		}),
+	)
+
+	treefmt(t,
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Matched:   1,
+			stats.Formatted: 0,
+		}),
+	)
+
+	// an extra argument changes the formatter's signature, so the cache is bypassed
+	treefmt(t,
+		withArgs("--formatter-arg", "echo=bar"),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Matched:   1,
+			stats.Formatted: 1,
+		}),
+	)
+
+	// as it is for the next run without it
+	treefmt(t,
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Matched:   1,
+			stats.Formatted: 1,
+		}),
+	)
+
+	treefmt(t,
+		withArgs("--formatter-arg", "missing=bar"),
+		withError(func(as *require.Assertions, err error) {
+			as.ErrorContains(err, "formatter-arg: formatter missing not found in config")
+		}),
+	)
+}
+
+func TestFormatterArgConcurrentBatches(t *testing.T) {
+	as := require.New(t)
+
+	tempDir := t.TempDir()
+	configPath := filepath.Join(tempDir, "treefmt.toml")
+
+	test.ChangeWorkDir(t, tempDir)
+
+	// a formatter which writes the args it was given into each file it was given, kept outside the tree root
+	script := "#!/bin/sh\nfor f in \"$@\"; do case \"$f\" in -*) ;; *) echo \"$*\" > \"$f\" ;; esac; done\n"
+	command := filepath.Join(t.TempDir(), "args-fmt")
+	as.NoError(os.WriteFile(command, []byte(script), 0o755)) //nolint:gosec
+
+	for i := range 32 {
+		as.NoError(os.WriteFile(filepath.Join(tempDir, fmt.Sprintf("%d.txt", i)), nil, 0o600))
+	}
+
+	test.WriteConfig(t, configPath, &config.Config{
+		FormatterConfigs: map[string]*config.Formatter{
+			"args": {
+				Command: command,
+				// the extra arg is appended to these, which leaves spare capacity behind it
+				Options:   []string{"-a", "-b"},
+				Includes:  []string{"*.txt"},
+				BatchSize: 1,
+			},
+		},
+	})
+
+	// every batch is run at the same time, and must only be given its own file
+	treefmt(t,
+		withArgs("--no-cache", "--formatter-arg", "args=-c"),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Matched:   32,
+			stats.Formatted: 32,
+			stats.Changed:   32,
+		}),
+	)
+
+	for i := range 32 {
+		content, err := os.ReadFile(filepath.Join(tempDir, fmt.Sprintf("%d.txt", i)))
+		as.NoError(err)
+		as.Equal(fmt.Sprintf("-a -b -c %d.txt\n", i), string(content))
+	}
+}
+
+func TestCacheReadOnly(t *testing.T) {
+	tempDir := test.TempExamples(t)
+	configPath := filepath.Join(tempDir, "treefmt.toml")
+
+	test.ChangeWorkDir(t, tempDir)
+
+	test.WriteConfig(t, configPath, &config.Config{
+		FormatterConfigs: map[string]*config.Formatter{
+			"append": {
+				Command:  "test-fmt-append",
+				Options:  []string{"   "},
+				Includes: []string{"*"},
+			},
+		},
+	})
+
+	// without an existing cache, everything is formatted
+	treefmt(t,
+		withArgs("--cache-mode", "read-only"),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 33,
+			stats.Matched:   33,
+			stats.Formatted: 33,
+			stats.Changed:   33,
+		}),
+	)
+
+	// nothing was written to the cache, so a regular run formats everything again, populating the cache
+	treefmt(t,
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 33,
+			stats.Matched:   33,
+			stats.Formatted: 33,
+			stats.Changed:   33,
+		}),
+	)
+
+	// a read-only cache is used for filtering
+	treefmt(t,
+		withArgs("--cache-mode", "read-only"),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 33,
+			stats.Matched:   33,
+			stats.Formatted: 0,
+			stats.Changed:   0,
+		}),
+	)
+
+	// but it is never updated
+	treefmt(t,
+		withArgs("--cache-mode", "read-only"),
+		withModtimeBump(tempDir, time.Second),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 33,
+			stats.Matched:   33,
+			stats.Formatted: 33,
+			stats.Changed:   33,
+		}),
+	)
+
+	treefmt(t,
+		withArgs("--cache-mode", "read-only"),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 33,
+			stats.Matched:   33,
+			stats.Formatted: 33,
+			stats.Changed:   33,
+		}),
+	)
+
+	// a read-only cache cannot be cleared
+	treefmt(t,
+		withArgs("--cache-mode", "read-only", "--clear-cache"),
+		withError(func(as *require.Assertions, err error) {
+			as.ErrorContains(err, "--clear-cache cannot be used with a read-only cache")
+		}),
+	)
+
+	// unknown modes are rejected
+	treefmt(t,
+		withArgs("--cache-mode", "write-only"),
+		withError(func(as *require.Assertions, err error) {
+			as.ErrorContains(err, `cache-mode must be one of "read-write" or "read-only", got "write-only"`)
+		}),
+	)
+}
+
+func TestGC(t *testing.T) {
+	as := require.New(t)
+
+	tempDir := test.TempExamples(t)
+	configPath := filepath.Join(tempDir, "treefmt.toml")
+	cacheDir := t.TempDir()
+
+	test.ChangeWorkDir(t, tempDir)
+
+	cfg := &config.Config{
+		FormatterConfigs: map[string]*config.Formatter{
+			"echo": {
+				Command:  "echo",
+				Includes: []string{"*"},
+			},
+		},
+	}
+
+	// create a cache for the tree
+	treefmt(t,
+		withConfig(configPath, cfg),
+		withArgs("--cache-dir", cacheDir),
+		withNoError(t),
+	)
+
+	matches, err := filepath.Glob(filepath.Join(cacheDir, "*.db"))
+	as.NoError(err)
+	as.Len(matches, 1)
+
+	cacheFile := matches[0]
+	info, err := os.Stat(cacheFile)
+	as.NoError(err)
+
+	// a cache which is held open by a running treefmt is skipped rather than aborting the gc
+	heldFile := cache.File(cacheDir, filepath.Join(tempDir, "held"))
+
+	held, err := cache.Open(cacheDir, filepath.Join(tempDir, "held"), configPath)
+	as.NoError(err)
+
+	defer held.Close()
+
+	// while the tree exists, its cache is kept
+	treefmt(t,
+		withArgs("-C", cacheDir, "--gc", "--cache-dir", cacheDir),
+		withNoError(t),
+		withStdout(func(out []byte) {
+			as.Contains(string(out), "removed 0 stale cache file(s), reclaimed 0 bytes")
+		}),
+	)
+
+	as.FileExists(cacheFile)
+
+	// once the tree is removed, its cache is collected
+	as.NoError(os.RemoveAll(tempDir))
+
+	treefmt(t,
+		withArgs("-C", cacheDir, "--gc", "--cache-dir", cacheDir),
+		withNoError(t),
+		withStdout(func(out []byte) {
+			as.Contains(string(out), fmt.Sprintf("removed 1 stale cache file(s), reclaimed %d bytes", info.Size()))
+		}),
+	)
+
+	as.NoFileExists(cacheFile)
+	as.FileExists(heldFile)
+
+	// caches stored within a tree are removed along with it
+	treefmt(t,
+		withArgs("-C", cacheDir, "--gc", "--cache-dir", "repo"),
+		withError(func(as *require.Assertions, err error) {
+			as.ErrorContains(err, "--gc requires an absolute cache-dir")
+		}),
+	)
+}
+
+func TestCacheDir(t *testing.T) {
+	as := require.New(t)
+
+	tempDir := test.TempExamples(t)
+	configPath := filepath.Join(tempDir, "treefmt.toml")
+
+	test.ChangeWorkDir(t, tempDir)
+
+	cfg := &config.Config{
+		CacheDir: "repo",
+		FormatterConfigs: map[string]*config.Formatter{
+			"append": {
+				Command:  "test-fmt-append",
+				Options:  []string{"   "},
+				Includes: []string{"*"},
+			},
+		},
+	}
+
+	// the cache is stored within the tree root, and is not formatted itself
+	treefmt(t,
+		withConfig(configPath, cfg),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Matched:   33,
+			stats.Formatted: 33,
+			stats.Changed:   33,
+		}),
+	)
+
+	matches, err := filepath.Glob(filepath.Join(tempDir, config.RepoCacheDir, "*.db"))
+	as.NoError(err)
+	as.Len(matches, 1)
+
+	// and is used on the next run
+	treefmt(t,
+		withConfig(configPath, cfg),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Matched:   33,
+			stats.Formatted: 0,
+			stats.Changed:   0,
+		}),
+	)
+
+	// the flag takes precedence over the config, with relative paths resolved against the tree root
+	treefmt(t,
+		withConfig(configPath, cfg),
+		withArgs("--cache-dir", "build/cache"),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Matched:   33,
+			stats.Formatted: 33,
+			stats.Changed:   33,
+		}),
+	)
+
+	matches, err = filepath.Glob(filepath.Join(tempDir, "build", "cache", "*.db"))
+	as.NoError(err)
+	as.Len(matches, 1)
+
+	// a custom cache directory within the tree root is never traversed, even without the global excludes
+	treefmt(t,
+		withConfig(configPath, cfg),
+		withArgs("--cache-dir", "build/cache", "--no-global-excludes"),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 33,
+			stats.Matched:   33,
+			stats.Formatted: 0,
+		}),
+	)
+
+	treefmt(t,
+		withArgs("--cache-dir", "build/cache", "--explain", matches[0]),
+		withNoError(t),
+		withStdout(func(out []byte) {
+			as.Contains(string(out), "verdict: skipped because it belongs to treefmt itself\n")
+		}),
+	)
+
+	// reading the cache never creates a missing cache directory
+	treefmt(t,
+		withConfig(configPath, cfg),
+		withArgs("--cache-dir", "missing", "--cache-mode", "read-only"),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Matched:   33,
+			stats.Formatted: 33,
+			stats.Changed:   33,
+		}),
+	)
+
+	as.NoDirExists(filepath.Join(tempDir, "missing"))
+}
+
+func TestPrintCachePath(t *testing.T) {
+	as := require.New(t)
+
+	tempDir := test.TempExamples(t)
+	configPath := filepath.Join(tempDir, "treefmt.toml")
+
+	test.ChangeWorkDir(t, tempDir)
+
+	cfg := &config.Config{
+		CacheDir: "build/cache",
+		FormatterConfigs: map[string]*config.Formatter{
+			"append": {
+				Command:  "test-fmt-append",
+				Options:  []string{"   "},
+				Includes: []string{"*"},
+			},
+		},
+	}
+
+	var printed []string
+
+	// the paths are printed without formatting anything
+	treefmt(t,
+		withConfig(configPath, cfg),
+		withArgs("--print-cache-path"),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 0,
+			stats.Matched:   0,
+			stats.Formatted: 0,
+			stats.Changed:   0,
+		}),
+		withStdout(func(out []byte) {
+			printed = strings.Split(strings.TrimSpace(string(out)), "\n")
+		}),
+	)
+
+	cacheDir := filepath.Join(tempDir, "build", "cache")
+
+	as.Len(printed, 2)
+	as.Equal(cacheDir, printed[0])
+	as.Equal(cacheDir, filepath.Dir(printed[1]))
+	as.NoFileExists(printed[1])
+
+	// and match the cache file used when formatting
+	treefmt(t,
+		withConfig(configPath, cfg),
+		withNoError(t),
+	)
+
+	as.FileExists(printed[1])
+}
+
+func TestChangeWorkingDirectory(t *testing.T) {
+	as := require.New(t)
+
+	cfg := &config.Config{
+		FormatterConfigs: map[string]*config.Formatter{
+			"append": {
+				Command:  "test-fmt-append",
+				Options:  []string{"   "},
+				Includes: []string{"*"},
+			},
+		},
+	}
+
+	t.Run("default", func(t *testing.T) {
+		// capture current cwd, so we can replace it after the test is finished
+		cwd, err := os.Getwd()
+		as.NoError(err)
+
+		t.Cleanup(func() {
+			// return to the previous working directory
+			as.NoError(os.Chdir(cwd))
+		})
+
+		tempDir := test.TempExamples(t)
+		configPath := filepath.Join(tempDir, "treefmt.toml")
+
+		// change to an empty temp dir and try running without specifying a working directory
+		as.NoError(os.Chdir(t.TempDir()))
+
+		treefmt(t,
+			withConfig(configPath, cfg),
+			withError(func(as *require.Assertions, err error) {
+				as.ErrorContains(err, "failed to find treefmt config file")
+			}),
+		)
+
+		// now change to the examples temp directory
+		as.NoError(os.Chdir(tempDir), "failed to change to temp directory")
+
+		treefmt(t,
+			withConfig(configPath, cfg),
+			withNoError(t),
+			withStats(t, map[stats.Type]int{
+				stats.Traversed: 33,
+			}),
+		)
+	})
+
+	execute := func(t *testing.T, configFile string, env bool) {
+		t.Helper()
+		t.Run(configFile, func(t *testing.T) {
+			// capture current cwd, so we can replace it after the test is finished
+			cwd, err := os.Getwd()
+			as.NoError(err)
+
+			t.Cleanup(func() {
+				// return to the previous working directory
+				as.NoError(os.Chdir(cwd))
+			})
+
+			tempDir := test.TempExamples(t)
+			configPath := filepath.Join(tempDir, configFile)
+
+			// delete treefmt.toml that comes with the example folder
+			as.NoError(os.Remove(filepath.Join(tempDir, "treefmt.toml")))
+
+			var args []string
+
+			if env {
+				t.Setenv("TREEFMT_WORKING_DIR", tempDir)
+			} else {
+				args = []string{"-C", tempDir}
+			}
+
+			treefmt(t,
+				withArgs(args...),
+				withConfig(configPath, cfg),
+				withNoError(t),
+				withStats(t, map[stats.Type]int{
+					stats.Traversed: 33,
+				}),
+			)
+		})
+	}
+
+	// by default, we look for a config file at ./treefmt.toml or ./.treefmt.toml in the current working directory
+	configFiles := []string{"treefmt.toml", ".treefmt.toml"}
+
+	t.Run("arg", func(t *testing.T) {
+		for _, configFile := range configFiles {
+			execute(t, configFile, false)
+		}
+	})
+
+	t.Run("env", func(t *testing.T) {
+		for _, configFile := range configFiles {
+			execute(t, configFile, true)
+		}
+	})
+}
+
+func TestFailOnChange(t *testing.T) {
+	t.Run("change size", func(t *testing.T) {
+		tempDir := test.TempExamples(t)
+		configPath := filepath.Join(tempDir, "treefmt.toml")
+
+		test.ChangeWorkDir(t, tempDir)
+
+		cfg := &config.Config{
+			FormatterConfigs: map[string]*config.Formatter{
+				"append": {
+					// test-fmt-append is a helper defined in nix/packages/treefmt/formatters.nix which lets us append
+					// an arbitrary value to a list of files
+					Command:  "test-fmt-append",
+					Options:  []string{"hello"},
+					Includes: []string{"elm/*"},
+				},
+			},
+		}
+
+		// running with a cold cache, we should see the elm files being formatted, resulting in changes, which should
+		// trigger an error
+		treefmt(t,
+			withArgs("--fail-on-change"),
+			withConfig(configPath, cfg),
+			withError(func(as *require.Assertions, err error) {
+				as.ErrorIs(err, formatCmd.ErrFailOnChange)
+			}),
+			withStats(t, map[stats.Type]int{
+				stats.Traversed: 33,
+				stats.Matched:   2,
+				stats.Formatted: 2,
+				stats.Changed:   2,
+			}),
+		)
+
+		// running with a hot cache, we should see matches for the elm files, but no attempt to format them as the
+		// underlying files have not changed since we last ran
+		treefmt(t,
+			withArgs("--fail-on-change"),
+			withNoError(t),
+			withStats(t, map[stats.Type]int{
+				stats.Traversed: 33,
+				stats.Matched:   2,
+				stats.Formatted: 0,
+				stats.Changed:   0,
+			}),
+		)
+	})
+
+	t.Run("change modtime", func(t *testing.T) {
+		tempDir := test.TempExamples(t)
+		configPath := filepath.Join(tempDir, "treefmt.toml")
+
+		test.ChangeWorkDir(t, tempDir)
+
+		dateFormat := "2006 01 02 15:04.05"
+		replacer := strings.NewReplacer(" ", "", ":", "")
+
+		formatTime := func(t time.Time) string {
+			// go date formats are stupid
+			return replacer.Replace(t.Format(dateFormat))
+		}
+
+		// running with a cold cache, we should see the haskell files being formatted, resulting in changes, which should
+		// trigger an error
+		treefmt(t,
+			withArgs("--fail-on-change"),
+			withConfigFunc(configPath, func() *config.Config {
+				// new mod time is in the next second
+				modTime := time.Now().Truncate(time.Second).Add(time.Second)
+
+				return &config.Config{
+					FormatterConfigs: map[string]*config.Formatter{
+						"append": {
+							// test-fmt-modtime is a helper defined in nix/packages/treefmt/formatters.nix which lets us set
+							// a file's modtime to an arbitrary date.
+							// in this case, we move it forward more than a second so that our second level modtime comparison
+							// will detect it as a change.
+							Command:  "test-fmt-modtime",
+							Options:  []string{formatTime(modTime)},
+							Includes: []string{"haskell/*"},
+						},
+					},
+				}
+			}),
+			withError(func(as *require.Assertions, err error) {
+				as.ErrorIs(err, formatCmd.ErrFailOnChange)
+			}),
+			withStats(t, map[stats.Type]int{
+				stats.Traversed: 33,
+				stats.Matched:   7,
+				stats.Formatted: 7,
+				stats.Changed:   7,
+			}),
+		)
+
+		// running with a hot cache, we should see matches for the haskell files, but no attempt to format them as the
+		// underlying files have not changed since we last ran
+		treefmt(t,
+			withArgs("--fail-on-change"),
+			withNoError(t),
+			withStats(t, map[stats.Type]int{
+				stats.Traversed: 33,
+				stats.Matched:   7,
+				stats.Formatted: 0,
+				stats.Changed:   0,
+			}),
+		)
+	})
+}
+
+func TestFailOnChangeFormatters(t *testing.T) {
+	tempDir := test.TempExamples(t)
+	configPath := filepath.Join(tempDir, "treefmt.toml")
+
+	test.ChangeWorkDir(t, tempDir)
+
+	cfg := &config.Config{
+		FormatterConfigs: map[string]*config.Formatter{
+			// changes the files it is given
+			"code": {
+				Command:  "test-fmt-append",
+				Options:  []string{"   "},
+				Includes: []string{"*.hs"},
+			},
+			// leaves the files it is given alone, including those it shares with code
+			"docs": {
+				Command:  "echo",
+				Includes: []string{"*.md", "*.hs"},
+				Priority: 1,
+			},
+		},
+	}
+
+	// changes made by other formatters do not cause a failure
+	cfg.FailOnChangeFormatters = []string{"docs"}
+
+	treefmt(t,
+		withConfig(configPath, cfg),
+		withArgs("--no-cache"),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Changed: 6,
+		}),
+	)
+
+	// changes made by the given formatters do
+	cfg.FailOnChangeFormatters = []string{"code", "docs"}
+
+	treefmt(t,
+		withConfig(configPath, cfg),
+		withArgs("--no-cache"),
+		withError(func(as *require.Assertions, err error) {
+			as.ErrorIs(err, formatCmd.ErrFailOnChange)
+			as.ErrorContains(err, "files were changed by code")
+		}),
+	)
+
+	// the flag takes precedence over the config
+	treefmt(t,
+		withConfig(configPath, cfg),
+		withArgs("--no-cache", "--fail-on-change-formatters", "docs"),
+		withNoError(t),
+	)
+
+	// formatters must exist
+	cfg.FailOnChangeFormatters = []string{"foo"}
+
+	treefmt(t,
+		withConfig(configPath, cfg),
+		withError(func(as *require.Assertions, err error) {
+			as.ErrorContains(err, "fail-on-change-formatters: formatter foo not found in config")
+		}),
+	)
+}
+
+func TestChangedFiles(t *testing.T) {
+	as := require.New(t)
+
+	tempDir := test.TempExamples(t)
+	configPath := filepath.Join(tempDir, "treefmt.toml")
+
+	test.ChangeWorkDir(t, tempDir)
+
+	cfg := &config.Config{
+		FormatterConfigs: map[string]*config.Formatter{
+			"append": {
+				Command:  "test-fmt-append",
+				Options:  []string{"hello"},
+				Includes: []string{"elm/*"},
+			},
+		},
+	}
+
+	// changed files are listed in sorted order, relative to the tree root
+	treefmt(t,
+		withArgs("--changed-files"),
+		withConfig(configPath, cfg),
+		withNoError(t),
+		withStdout(func(out []byte) {
+			as.Equal("elm/elm.json\nelm/src/Main.elm\n", string(out))
+		}),
+	)
+
+	// nothing is listed when nothing changed
+	treefmt(t,
+		withArgs("--changed-files"),
+		withNoError(t),
+		withStdout(func(out []byte) {
+			as.Empty(out)
+		}),
+	)
+}
+
+func TestPrint0(t *testing.T) {
+	as := require.New(t)
+
+	tempDir := test.TempExamples(t)
+	configPath := filepath.Join(tempDir, "treefmt.toml")
+
+	test.ChangeWorkDir(t, tempDir)
+
+	cfg := &config.Config{
+		FormatterConfigs: map[string]*config.Formatter{
+			"append": {
+				Command:  "test-fmt-append",
+				Options:  []string{"hello"},
+				Includes: []string{"elm/*"},
+			},
+		},
+	}
+
+	// a path containing a newline would be split in two by --changed-files
+	as.NoError(os.WriteFile(filepath.Join(tempDir, "elm", "with\nnewline.elm"), []byte("module Main\n"), 0o600))
+
+	// changed files are listed in sorted order, each terminated by a NUL character
+	treefmt(t,
+		withArgs("--print0"),
+		withConfig(configPath, cfg),
+		withNoError(t),
+		withStdout(func(out []byte) {
+			as.Equal("elm/elm.json\x00elm/src/Main.elm\x00elm/with\nnewline.elm\x00", string(out))
+		}),
+	)
+
+	// nothing is listed when nothing changed
+	treefmt(t,
+		withArgs("--print0"),
+		withNoError(t),
+		withStdout(func(out []byte) {
+			as.Empty(out)
+		}),
+	)
+
+	// it cannot be mixed with other output on stdout
+	for _, flag := range []string{"--changed-files", "--porcelain", "--diff"} {
+		treefmt(t,
+			withArgs("--print0", flag),
+			withError(func(as *require.Assertions, err error) {
+				as.EqualError(err, "--print0 cannot be used with "+flag)
+			}),
+		)
+	}
+}
+
+func TestPorcelain(t *testing.T) {
+	as := require.New(t)
+
+	tempDir := test.TempExamples(t)
+	configPath := filepath.Join(tempDir, "treefmt.toml")
+
+	test.ChangeWorkDir(t, tempDir)
+
+	cfg := &config.Config{
+		FormatterConfigs: map[string]*config.Formatter{
+			"append": {
+				Command:  "test-fmt-append",
+				Options:  []string{"hello"},
+				Includes: []string{"elm/*"},
+			},
+			"append-again": {
+				Command:  "test-fmt-append",
+				Options:  []string{"world"},
+				Includes: []string{"elm/src/*"},
+				Priority: 1,
+			},
+			"echo": {
+				Command:  "echo",
+				Includes: []string{"elm/*"},
+			},
+		},
+	}
+
+	// each changed file is listed with the formatters which changed it, in the order they were applied
+	treefmt(t,
+		withArgs("--porcelain"),
+		withConfig(configPath, cfg),
+		withNoError(t),
+		withStdout(func(out []byte) {
+			as.Equal("M\tappend\telm/elm.json\nM\tappend,append-again\telm/src/Main.elm\n", string(out))
+		}),
+		withStderr(func(out []byte) {
+			as.NotContains(string(out), "traversed")
+		}),
+	)
+
+	// nothing is listed when nothing changed
+	treefmt(t,
+		withArgs("--porcelain"),
+		withNoError(t),
+		withStdout(func(out []byte) {
+			as.Empty(out)
+		}),
+	)
+
+	treefmt(t,
+		withArgs("--porcelain", "--changed-files"),
+		withError(func(as *require.Assertions, err error) {
+			as.ErrorContains(err, "--porcelain cannot be used with --changed-files")
+		}),
+	)
+}
+
+func TestDiff(t *testing.T) {
+	as := require.New(t)
+
+	tempDir := test.TempExamples(t)
+	configPath := filepath.Join(tempDir, "treefmt.toml")
+
+	test.ChangeWorkDir(t, tempDir)
+
+	test.WriteConfig(t, configPath, &config.Config{
+		FormatterConfigs: map[string]*config.Formatter{
+			"append": {
+				Command:  "test-fmt-append",
+				Options:  []string{"formatted"},
+				Includes: []string{"python/main.py"},
+			},
+			"echo": {
+				Command:  "echo",
+				Includes: []string{"*.go"},
+			},
+		},
+	})
+
+	// combined with fail-on-change, the diff explains the failure
+	treefmt(t,
+		withArgs("--fail-on-change", "--diff"),
+		withError(func(as *require.Assertions, err error) {
+			as.ErrorIs(err, formatCmd.ErrFailOnChange)
+		}),
+		withStdout(func(out []byte) {
+			as.Equal(`--- a/python/main.py
++++ b/python/main.py
+@@ -10,3 +10,4 @@
+ 
+ if __name__ == "__main__":
+     app.run()
++formatted
+`, string(out))
+		}),
+	)
+
+	// files which were not changed produce no diff
+	treefmt(t,
+		withArgs("--diff", "--formatters", "echo", "--no-cache"),
+		withNoError(t),
+		withStdout(func(out []byte) {
+			as.Empty(out)
+		}),
+	)
+
+	treefmt(t,
+		withArgs("--diff", "--porcelain"),
+		withError(func(as *require.Assertions, err error) {
+			as.ErrorContains(err, "--diff cannot be used with --porcelain")
+		}),
+	)
+}
+
+func TestCacheBusting(t *testing.T) {
+	as := require.New(t)
+
+	t.Run("formatter_change_config", func(t *testing.T) {
+		tempDir := test.TempExamples(t)
+		configPath := filepath.Join(tempDir, "treefmt.toml")
+
+		test.ChangeWorkDir(t, tempDir)
+
+		// basic config
+		cfg := &config.Config{
+			FormatterConfigs: map[string]*config.Formatter{
+				"python": {
+					Command:  "echo", // this is non-destructive, will match but cause no changes
+					Includes: []string{"*.py"},
+				},
+				"haskell": {
+					Command:  "test-fmt-append",
+					Options:  []string{"   "},
+					Includes: []string{"*.hs"},
+				},
+			},
+		}
+
+		// initial run
+		treefmt(t,
+			withConfig(configPath, cfg),
+			withNoError(t),
+			withStats(t, map[stats.Type]int{
+				stats.Traversed: 33,
+				stats.Matched:   8,
+				stats.Formatted: 8,
+				stats.Changed:   6,
+			}))
+
+		// change formatter options
+		cfg.FormatterConfigs["haskell"].Options = []string{""}
+
+		// cache entries for haskell files should be invalidated
+		treefmt(t,
+			withConfig(configPath, cfg),
+			withNoError(t),
+			withStats(t, map[stats.Type]int{
+				stats.Traversed: 33,
+				stats.Matched:   8,
+				stats.Formatted: 6,
+				stats.Changed:   6,
+			}))
+
+		// run again, nothing should be formatted
+		treefmt(t,
+			withConfig(configPath, cfg),
+			withNoError(t),
+			withStats(t, map[stats.Type]int{
+				stats.Traversed: 33,
+				stats.Matched:   8,
+				stats.Formatted: 0,
+				stats.Changed:   0,
+			}))
+
+		// change the formatter's command
+		cfg.FormatterConfigs["haskell"].Command = "echo"
+
+		// cache entries for haskell files should be invalidated
+		treefmt(t,
+			withConfig(configPath, cfg),
+			withNoError(t),
+			withStats(t, map[stats.Type]int{
+				stats.Traversed: 33,
+				stats.Matched:   8,
+				stats.Formatted: 6,
+				stats.Changed:   0, // echo doesn't affect the files so no changes expected
+			}))
+
+		// run again, nothing should be formatted
+		treefmt(t,
+			withConfig(configPath, cfg),
+			withNoError(t),
+			withStats(t, map[stats.Type]int{
+				stats.Traversed: 33,
+				stats.Matched:   8,
+				stats.Formatted: 0,
+				stats.Changed:   0,
+			}))
+
+		// change the formatters includes
+		cfg.FormatterConfigs["haskell"].Includes = []string{"haskell/*.hs"}
+
+		// we should match on fewer files, but no formatting should occur as includes are not part of the formatting
+		// signature
+		treefmt(t,
+			withConfig(configPath, cfg),
+			withNoError(t),
+			withStats(t, map[stats.Type]int{
+				stats.Traversed: 33,
+				stats.Matched:   6,
+				stats.Formatted: 0,
+				stats.Changed:   0,
+			}))
+
+		// change the formatters excludes
+		cfg.FormatterConfigs["haskell"].Excludes = []string{"haskell/Foo.hs"}
+
+		// we should match on fewer files, but no formatting should occur as excludes are not part of the formatting
+		// signature
+		treefmt(t,
+			withConfig(configPath, cfg),
+			withNoError(t),
+			withStats(t, map[stats.Type]int{
+				stats.Traversed: 33,
+				stats.Matched:   5,
+				stats.Formatted: 0,
+				stats.Changed:   0,
+			}))
+	})
+
+	t.Run("formatter_change_binary", func(t *testing.T) {
+		tempDir := test.TempExamples(t)
+		configPath := filepath.Join(tempDir, "treefmt.toml")
+
+		test.ChangeWorkDir(t, tempDir)
+
+		// find test-fmt-append in PATH
+		sourcePath, err := exec.LookPath("test-fmt-append")
+		as.NoError(err, "failed to find test-fmt-append in PATH")
+
+		// copy it into the temp dir so we can mess with its size and modtime
+		binPath := filepath.Join(tempDir, "bin")
+		as.NoError(os.Mkdir(binPath, 0o755))
+
+		scriptPath := filepath.Join(binPath, "test-fmt-append")
+		as.NoError(cp.Copy(sourcePath, scriptPath, cp.Options{AddPermission: 0o755}))
+
+		// prepend our test bin directory to PATH
+		t.Setenv("PATH", binPath+":"+os.Getenv("PATH"))
+
+		// basic config
+		cfg := &config.Config{
+			FormatterConfigs: map[string]*config.Formatter{
+				"python": {
+					Command:  "echo", // this is non-destructive, will match but cause no changes
+					Includes: []string{"*.py"},
+				},
+				"elm": {
+					Command:  "test-fmt-append",
+					Options:  []string{"   "},
+					Includes: []string{"*.elm"},
+				},
+			},
+		}
+
+		// initial run
+		treefmt(t,
+			withConfig(configPath, cfg),
+			withNoError(t),
+			withStats(t, map[stats.Type]int{
+				stats.Traversed: 34,
+				stats.Matched:   3,
+				stats.Formatted: 3,
+				stats.Changed:   1,
+			}))
+
+		// tweak mod time of elm formatter
+		newTime := time.Now().Add(-time.Minute)
+		as.NoError(os.Chtimes(scriptPath, newTime, newTime))
+
+		// cache entries for elm files should be invalidated
+		treefmt(t,
+			withConfig(configPath, cfg),
+			withNoError(t),
+			withStats(t, map[stats.Type]int{
+				stats.Traversed: 34,
+				stats.Matched:   3,
+				stats.Formatted: 1,
+				stats.Changed:   1,
+			}))
+
+		// running again with a hot cache, we should see nothing be formatted
+		treefmt(t,
+			withConfig(configPath, cfg),
+			withNoError(t),
+			withStats(t, map[stats.Type]int{
+				stats.Traversed: 34,
+				stats.Matched:   3,
+				stats.Formatted: 0,
+				stats.Changed:   0,
+			}),
+		)
+
+		// tweak the size of elm formatter
+		formatter, err := os.OpenFile(scriptPath, os.O_WRONLY|os.O_APPEND, 0o755)
+		as.NoError(err, "failed to open elm formatter")
+
+		_, err = formatter.WriteString(" ") // add some whitespace
+		as.NoError(err, "failed to append to elm formatter")
+		as.NoError(formatter.Close(), "failed to close elm formatter")
+
+		// cache entries for elm files should be invalidated
+		treefmt(t,
+			withConfig(configPath, cfg),
+			withNoError(t),
+			withStats(t, map[stats.Type]int{
+				stats.Traversed: 34,
+				stats.Matched:   3,
+				stats.Formatted: 1,
+				stats.Changed:   1,
+			}))
+
+		// running again with a hot cache, we should see nothing be formatted
+		treefmt(t,
+			withConfig(configPath, cfg),
+			withNoError(t),
+			withStats(t, map[stats.Type]int{
+				stats.Traversed: 34,
+				stats.Matched:   3,
+				stats.Formatted: 0,
+				stats.Changed:   0,
+			}),
+		)
+	})
+
+	t.Run("formatter_add_remove", func(t *testing.T) {
+		tempDir := test.TempExamples(t)
+		configPath := filepath.Join(tempDir, "treefmt.toml")
+
+		test.ChangeWorkDir(t, tempDir)
+
+		cfg := &config.Config{
+			FormatterConfigs: map[string]*config.Formatter{
+				"python": {
+					Command:  "test-fmt-append",
+					Options:  []string{"   "},
+					Includes: []string{"*.py"},
+				},
+			},
+		}
+
+		// initial run
+		treefmt(t,
+			withConfig(configPath, cfg),
+			withNoError(t),
+			withStats(t, map[stats.Type]int{
+				stats.Traversed: 33,
+				stats.Matched:   2,
+				stats.Formatted: 2,
+				stats.Changed:   2,
+			}),
+		)
+
+		// cached run
+		treefmt(t,
+			withConfig(configPath, cfg),
+			withNoError(t),
+			withStats(t, map[stats.Type]int{
+				stats.Traversed: 33,
+				stats.Matched:   2,
+				stats.Formatted: 0,
+				stats.Changed:   0,
+			}),
+		)
+
+		// add a formatter
+		cfg.FormatterConfigs["elm"] = &config.Formatter{
+			Command:  "test-fmt-append",
+			Options:  []string{"   "},
+			Includes: []string{"*.elm"},
+		}
+
+		// only the elm files should be formatted
+		treefmt(t,
+			withConfig(configPath, cfg),
+			withNoError(t),
+			withStats(t, map[stats.Type]int{
+				stats.Traversed: 33,
+				stats.Matched:   3,
+				stats.Formatted: 1,
+				stats.Changed:   1,
+			}),
+		)
+
+		// let's add a second python formatter
+		cfg.FormatterConfigs["python_secondary"] = &config.Formatter{
+			Command:  "test-fmt-append",
+			Options:  []string{" "},
+			Includes: []string{"*.py"},
+		}
+
+		// python files should be formatted as their pipeline has changed
+		treefmt(t,
+			withConfig(configPath, cfg),
+			withNoError(t),
+			withStats(t, map[stats.Type]int{
+				stats.Traversed: 33,
+				stats.Matched:   3,
+				stats.Formatted: 2,
+				stats.Changed:   2,
+			}),
+		)
+
+		// cached run
+		treefmt(t,
+			withConfig(configPath, cfg),
+			withNoError(t),
+			withStats(t, map[stats.Type]int{
+				stats.Traversed: 33,
+				stats.Matched:   3,
+				stats.Formatted: 0,
+				stats.Changed:   0,
+			}),
+		)
+
+		// change ordering within a pipeline
+		cfg.FormatterConfigs["python"].Priority = 2
+		cfg.FormatterConfigs["python_secondary"].Priority = 1
+
+		// python files should be formatted as their pipeline has changed
+		treefmt(t,
+			withConfig(configPath, cfg),
+			withNoError(t),
+			withStats(t, map[stats.Type]int{
+				stats.Traversed: 33,
+				stats.Matched:   3,
+				stats.Formatted: 2,
+				stats.Changed:   2,
+			}),
+		)
+
+		// cached run
+		treefmt(t,
+			withConfig(configPath, cfg),
+			withNoError(t),
+			withStats(t, map[stats.Type]int{
+				stats.Traversed: 33,
+				stats.Matched:   3,
+				stats.Formatted: 0,
+				stats.Changed:   0,
+			}),
+		)
+
+		// remove secondary python formatter
+		delete(cfg.FormatterConfigs, "python_secondary")
+
+		// python files should be formatted as their pipeline has changed
+		treefmt(t,
+			withConfig(configPath, cfg),
+			withNoError(t),
+			withStats(t, map[stats.Type]int{
+				stats.Traversed: 33,
+				stats.Matched:   3,
+				stats.Formatted: 2,
+				stats.Changed:   2,
+			}),
+		)
+
+		// cached run
+		treefmt(t,
+			withConfig(configPath, cfg),
+			withNoError(t),
+			withStats(t, map[stats.Type]int{
+				stats.Traversed: 33,
+				stats.Matched:   3,
+				stats.Formatted: 0,
+				stats.Changed:   0,
+			}),
+		)
+
+		// remove the elm formatter
+		delete(cfg.FormatterConfigs, "elm")
+
+		// only python files should match, but no formatting should occur as not formatting signatures have been
+		// affected
+		treefmt(t,
+			withConfig(configPath, cfg),
+			withNoError(t),
+			withStats(t, map[stats.Type]int{
+				stats.Traversed: 33,
+				stats.Matched:   2,
+				stats.Formatted: 0,
+				stats.Changed:   0,
+			}),
+		)
+	})
+}
+
+func TestGit(t *testing.T) {
+	as := require.New(t)
+
+	tempDir := test.TempExamples(t)
+	configPath := filepath.Join(tempDir, "/treefmt.toml")
+
+	test.ChangeWorkDir(t, tempDir)
+
+	// basic config
+	cfg := &config.Config{
+		FormatterConfigs: map[string]*config.Formatter{
+			"echo": {
+				Command:  "echo", // will not generate any underlying changes in the file
+				Includes: []string{"*"},
+			},
+		},
+	}
+
+	test.WriteConfig(t, configPath, cfg)
+
+	// init a git repo
+	gitCmd := exec.Command("git", "init")
+	as.NoError(gitCmd.Run(), "failed to init git repository")
+
+	// run before adding anything to the index
+	treefmt(t,
+		withConfig(configPath, cfg),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 0,
+		}),
+	)
+
+	// add everything to the index
+	gitCmd = exec.Command("git", "add", ".")
+	as.NoError(gitCmd.Run(), "failed to add everything to the index")
+
+	treefmt(t,
+		withConfig(configPath, cfg),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 33,
+			stats.Matched:   33,
+			stats.Formatted: 33,
+			stats.Changed:   0,
+		}),
+	)
+
+	// remove python directory from the index
+	gitCmd = exec.Command("git", "rm", "--cached", "python/*")
+	as.NoError(gitCmd.Run(), "failed to remove python directory from the index")
+
+	// we should traverse and match against fewer files, but no formatting should occur as no formatting signatures
+	// are impacted
+	treefmt(t,
+		withConfig(configPath, cfg),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 30,
+			stats.Matched:   30,
+			stats.Formatted: 0,
+			stats.Changed:   0,
+		}),
+	)
+
+	// remove nixpkgs.toml from the filesystem but leave it in the index
+	as.NoError(os.Remove(filepath.Join(tempDir, "nixpkgs.toml")))
+
+	// walk with filesystem instead of with git
+	// the .git folder contains 50 additional files
+	// when added to the 32 we started with (34 minus nixpkgs.toml which we removed from the filesystem), we should
+	// traverse 82 files.
+	treefmt(t,
+		withArgs("--walk", "filesystem"),
+		withConfig(configPath, cfg),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 82,
+			stats.Matched:   82,
+			stats.Formatted: 50, // the echo formatter should only be applied to the new files
+			stats.Changed:   0,
+		}),
+	)
+
+	// format specific sub paths
+	// we should traverse and match against those files, but without any underlying change to their files or their
+	// formatting config, we will not format them
+
+	treefmt(t,
+		withArgs("go"),
+		withConfig(configPath, cfg),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 2,
+			stats.Matched:   2,
+			stats.Formatted: 0,
+			stats.Changed:   0,
+		}),
+	)
+
+	treefmt(t,
+		withArgs("go", "haskell"),
+		withConfig(configPath, cfg),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 9,
+			stats.Matched:   9,
+			stats.Formatted: 0,
+			stats.Changed:   0,
+		}),
+	)
+
+	treefmt(t,
+		withArgs("-C", tempDir, "go", "haskell", "ruby"),
+		withConfig(configPath, cfg),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 10,
+			stats.Matched:   10,
+			stats.Formatted: 0,
+			stats.Changed:   0,
+		}),
+	)
+
+	// try with a bad path
+	treefmt(t,
+		withArgs("-C", tempDir, "haskell", "foo"),
+		withConfig(configPath, cfg),
+		withError(func(as *require.Assertions, err error) {
+			as.ErrorContains(err, "path foo not found")
+		}),
+	)
+
+	// try with a path not in the git index
+	_, err := os.Create(filepath.Join(tempDir, "foo.txt"))
+	as.NoError(err)
+
+	treefmt(t,
+		withArgs("haskell", "foo.txt", "-vv"),
+		withConfig(configPath, cfg),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 8,
+			stats.Matched:   8,
+			stats.Formatted: 1, // we only format foo.txt, which is new to the cache
+			stats.Changed:   0,
+		}),
+	)
+
+	treefmt(t,
+		withArgs("go", "foo.txt"),
+		withConfig(configPath, cfg),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 3,
+			stats.Matched:   3,
+			stats.Formatted: 0,
+			stats.Changed:   0,
+		}),
+	)
+
+	treefmt(t,
+		withArgs("foo.txt"),
+		withConfig(configPath, cfg),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 1,
+			stats.Matched:   1,
+			stats.Formatted: 0,
+			stats.Changed:   0,
+		}),
+	)
+}
+
+func TestNoVCS(t *testing.T) {
+	as := require.New(t)
+
+	tempDir := test.TempExamples(t)
+	configPath := filepath.Join(tempDir, "/treefmt.toml")
+
+	test.ChangeWorkDir(t, tempDir)
+
+	cfg := &config.Config{
+		Walk: "git",
+		FormatterConfigs: map[string]*config.Formatter{
+			"echo": {
+				Command:  "echo",
+				Includes: []string{"*"},
+			},
+		},
+	}
+
+	test.WriteConfig(t, configPath, cfg)
+
+	// init a git repo without adding anything to the index
+	gitCmd := exec.Command("git", "init")
+	as.NoError(gitCmd.Run(), "failed to init git repository")
+
+	treefmt(t,
+		withArgs("go"),
+		withConfig(configPath, cfg),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 0,
+		}),
+	)
+
+	// ignoring git, the filesystem is walked instead, regardless of the walk type in the config
+	treefmt(t,
+		withArgs("--no-vcs", "go"),
+		withConfig(configPath, cfg),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 2,
+			stats.Matched:   2,
+			stats.Formatted: 2,
+			stats.Changed:   0,
+		}),
+	)
+
+	t.Setenv("TREEFMT_NO_VCS", "true")
+
+	treefmt(t,
+		withArgs("haskell"),
+		withConfig(configPath, cfg),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 7,
+			stats.Matched:   7,
+		}),
+	)
+
+	// the tree root is always the directory containing the config file
+	treefmt(t, withArgs("--no-vcs", "--tree-root", "go"), withError(func(as *require.Assertions, err error) {
+		as.ErrorContains(err, "[no-vcs tree-root] were all set")
+	}))
+
+	treefmt(t, withArgs("--no-vcs", "--staged"), withError(func(as *require.Assertions, err error) {
+		as.ErrorContains(err, "[no-vcs staged] were all set")
+	}))
+
+	// wherever else the tree root is set
+	treefmt(t,
+		withEnv(map[string]string{"TREEFMT_TREE_ROOT_FILE": "go.mod"}),
+		withError(func(as *require.Assertions, err error) {
+			as.ErrorContains(err, "--no-vcs cannot be used with tree-root-file")
+		}),
+	)
+
+	cfg.TreeRoot = "go"
+
+	treefmt(t,
+		withConfig(configPath, cfg),
+		withError(func(as *require.Assertions, err error) {
+			as.ErrorContains(err, "--no-vcs cannot be used with tree-root")
+		}),
+	)
+}
+
+func TestSince(t *testing.T) {
+	as := require.New(t)
+
+	tempDir := test.TempExamples(t)
+	configPath := filepath.Join(tempDir, "treefmt.toml")
+
+	test.ChangeWorkDir(t, tempDir)
+
+	test.WriteConfig(t, configPath, &config.Config{
+		FormatterConfigs: map[string]*config.Formatter{
+			"echo": {
+				Command:  "echo",
+				Includes: []string{"*"},
+			},
+		},
+	})
+
+	git := func(args ...string) {
+		args = append([]string{"-c", "user.name=test", "-c", "user.email=test@example.com"}, args...)
+		as.NoError(exec.Command("git", args...).Run(), "failed to run git %v", args)
+	}
+
+	git("init")
+	git("add", ".")
+	git("commit", "-m", "initial")
+
+	// change one file, stage a change to another, and delete a third
+	for _, path := range []string{"python/main.py", "haskell/Foo.hs"} {
+		f, err := os.OpenFile(filepath.Join(tempDir, path), os.O_APPEND|os.O_WRONLY, 0o644)
+		as.NoError(err)
+		_, err = f.WriteString("\n")
+		as.NoError(err)
+		as.NoError(f.Close())
+	}
+
+	git("add", "haskell/Foo.hs")
+	as.NoError(os.Remove(filepath.Join(tempDir, "go", "main.go")))
+
+	// only the files which still exist and differ from the ref are read
+	treefmt(t,
+		withArgs("--since", "HEAD"),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 2,
+			stats.Matched:   2,
+		}),
+	)
+
+	// path arguments narrow it down further
+	treefmt(t,
+		withArgs("--since", "HEAD", "--no-cache", "python"),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 1,
+			stats.Matched:   1,
+		}),
+	)
+
+	treefmt(t,
+		withArgs("--since", "does-not-exist"),
+		withError(func(as *require.Assertions, err error) {
+			as.ErrorContains(err, "invalid --since: does-not-exist does not name a commit")
+		}),
+	)
+
+	treefmt(t,
+		withArgs("--since", "HEAD", "--walk", "filesystem"),
+		withError(func(as *require.Assertions, err error) {
+			as.ErrorContains(err, "--since cannot be used with the filesystem walk")
+		}),
+	)
+}
+
+func TestOnlyChanged(t *testing.T) {
+	as := require.New(t)
+
+	tempDir := test.TempExamples(t)
+	configPath := filepath.Join(tempDir, "treefmt.toml")
+
+	test.ChangeWorkDir(t, tempDir)
+
+	test.WriteConfig(t, configPath, &config.Config{
+		FormatterConfigs: map[string]*config.Formatter{
+			"echo": {
+				Command:  "echo",
+				Includes: []string{"*"},
+			},
+		},
+	})
+
+	git := func(args ...string) {
+		args = append([]string{"-c", "user.name=test", "-c", "user.email=test@example.com"}, args...)
+		as.NoError(exec.Command("git", args...).Run(), "failed to run git %v", args)
+	}
+
+	as.NoError(os.WriteFile(filepath.Join(tempDir, ".gitignore"), []byte("*.log\n"), 0o600))
+
+	git("init")
+
+	// before the first commit, everything which is not ignored counts as changed
+	as.NoError(os.WriteFile(filepath.Join(tempDir, "debug.log"), []byte("ignored\n"), 0o600))
+
+	treefmt(t,
+		withArgs("--only-changed", "--no-cache"),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 34,
+		}),
+	)
+
+	git("add", ".")
+	git("commit", "-m", "initial")
+
+	// nothing has changed since
+	treefmt(t,
+		withArgs("--only-changed"),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 0,
+		}),
+	)
+
+	// change one file, stage a change to another, add an untracked file and delete a tracked one
+	for _, path := range []string{"python/main.py", "haskell/Foo.hs"} {
+		f, err := os.OpenFile(filepath.Join(tempDir, path), os.O_APPEND|os.O_WRONLY, 0o644)
+		as.NoError(err)
+		_, err = f.WriteString("\n")
+		as.NoError(err)
+		as.NoError(f.Close())
+	}
+
+	git("add", "haskell/Foo.hs")
+	as.NoError(os.WriteFile(filepath.Join(tempDir, "new.txt"), []byte("new\n"), 0o600))
+	as.NoError(os.Remove(filepath.Join(tempDir, "go", "main.go")))
+
+	treefmt(t,
+		withArgs("--only-changed"),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 3,
+			stats.Matched:   3,
+		}),
+	)
+
+	treefmt(t,
+		withArgs("--only-changed", "--since", "HEAD"),
+		withError(func(as *require.Assertions, err error) {
+			as.ErrorContains(err, "--only-changed cannot be used with --since")
+		}),
+	)
+}
+
+func TestStaged(t *testing.T) {
+	as := require.New(t)
+
+	tempDir := test.TempExamples(t)
+	configPath := filepath.Join(tempDir, "treefmt.toml")
+
+	test.ChangeWorkDir(t, tempDir)
+
+	cfg := &config.Config{
+		FormatterConfigs: map[string]*config.Formatter{
+			"python": {
+				Command:  "test-fmt-append",
+				Options:  []string{"   "},
+				Includes: []string{"*.py"},
+			},
+		},
+	}
+
+	test.WriteConfig(t, configPath, cfg)
+
+	git := func(args ...string) string {
+		out, err := exec.Command("git", args...).Output()
+		as.NoError(err, "failed to run git %v", args)
+
+		return string(out)
+	}
+
+	git("init")
+	git("add", "python/main.py", "python/virtualenv_proxy.py", "haskell/Foo.hs")
+
+	// unstaged changes must survive formatting the staged content
+	proxyPath := filepath.Join(tempDir, "python", "virtualenv_proxy.py")
+
+	content, err := os.ReadFile(proxyPath)
+	as.NoError(err)
+	as.NoError(os.WriteFile(proxyPath, append(content, "# unstaged\n"...), 0o644))
+
+	treefmt(t,
+		withArgs("--staged"),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 3,
+			stats.Matched:   2,
+			stats.Formatted: 2,
+			stats.Changed:   2,
+		}),
+	)
+
+	// the staged content is formatted
+	as.True(strings.HasSuffix(git("show", ":python/main.py"), "   \n"))
+	as.True(strings.HasSuffix(git("show", ":python/virtualenv_proxy.py"), "   \n"))
+
+	// as is the working tree, unless it has unstaged changes
+	content, err = os.ReadFile(filepath.Join(tempDir, "python", "main.py"))
+	as.NoError(err)
+	as.Equal(git("show", ":python/main.py"), string(content))
+
+	content, err = os.ReadFile(proxyPath)
+	as.NoError(err)
+	as.True(strings.HasSuffix(string(content), "# unstaged\n"))
+
+	// no temporary files are left behind
+	as.NotContains(git("status", "--porcelain", "--untracked-files=all"), walk.TempFilePrefix)
+
+	// staged content can only be formatted once it has been walked
+	treefmt(t,
+		withArgs("--staged", "--list-files"),
+		withError(func(as *require.Assertions, err error) {
+			as.ErrorContains(err, "--list-files cannot be used with --staged")
+		}),
+	)
+
+	// selecting the staged walk type directly is the same as --staged, which never uses the cache
+	cacheDir := t.TempDir()
+
+	treefmt(t,
+		withArgs("--walk", "staged", "--cache-dir", cacheDir),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 3,
+			stats.Matched:   2,
+			stats.Formatted: 2,
+			stats.Changed:   2,
+		}),
+	)
+
+	content, err = os.ReadFile(filepath.Join(tempDir, "python", "main.py"))
+	as.NoError(err)
+	as.Equal(git("show", ":python/main.py"), string(content))
+
+	entries, err := os.ReadDir(cacheDir)
+	as.NoError(err)
+	as.Empty(entries)
+
+	treefmt(t,
+		withArgs("--walk", "staged", "--list-files"),
+		withError(func(as *require.Assertions, err error) {
+			as.ErrorContains(err, "--list-files cannot be used with --staged")
+		}),
+	)
+
+	treefmt(t,
+		withArgs("--walk", "staged", "--no-vcs"),
+		withError(func(as *require.Assertions, err error) {
+			as.ErrorContains(err, "--no-vcs cannot be used with --staged")
+		}),
+	)
+}
+
+func TestStagedNestedTreeRoot(t *testing.T) {
+	as := require.New(t)
+
+	// the tree root is a subdirectory of the repository
+	repoDir := t.TempDir()
+	treeRoot := filepath.Join(repoDir, "project")
+
+	test.TempExamplesInDir(t, treeRoot)
+	test.ChangeWorkDir(t, treeRoot)
+
+	// an include of an exact path only matches if the path in the index is used, rather than the temporary file
+	cfg := &config.Config{
+		FormatterConfigs: map[string]*config.Formatter{
+			"python": {
+				Command:  "test-fmt-append",
+				Options:  []string{"   "},
+				Includes: []string{"python/main.py"},
+			},
+		},
+	}
+
+	test.WriteConfig(t, filepath.Join(treeRoot, "treefmt.toml"), cfg)
+
+	git := func(args ...string) string {
+		cmd := exec.Command("git", args...)
+		cmd.Dir = repoDir
+
+		out, err := cmd.Output()
+		as.NoError(err, "failed to run git %v", args)
+
+		return string(out)
+	}
+
+	git("init")
+	git("add", "project/python/main.py", "project/python/virtualenv_proxy.py")
+
+	treefmt(t,
+		withArgs("--staged"),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 2,
+			stats.Matched:   1,
+			stats.Formatted: 1,
+			stats.Changed:   1,
+		}),
+	)
+
+	// the formatted content is written back to the path of the file within the repository
+	as.True(strings.HasSuffix(git("show", ":project/python/main.py"), "   \n"))
+	as.False(strings.HasSuffix(git("show", ":project/python/virtualenv_proxy.py"), "   \n"))
+	as.Equal("project/python/main.py\nproject/python/virtualenv_proxy.py\n", git("ls-files"))
+
+	content, err := os.ReadFile(filepath.Join(treeRoot, "python", "main.py"))
+	as.NoError(err)
+	as.Equal(git("show", ":project/python/main.py"), string(content))
+}
+
+func TestPathsArg(t *testing.T) {
+	as := require.New(t)
+
+	// capture current cwd, so we can replace it after the test is finished
+	cwd, err := os.Getwd()
+	as.NoError(err)
+
+	t.Cleanup(func() {
+		// return to the previous working directory
+		as.NoError(os.Chdir(cwd))
+	})
+
+	// create a project root under a temp dir to verify behaviour with files inside the temp dir, but outside the
+	// project root
+	tempDir := t.TempDir()
+	treeRoot := filepath.Join(tempDir, "tree-root")
+
+	test.TempExamplesInDir(t, treeRoot)
+
+	configPath := filepath.Join(treeRoot, "/treefmt.toml")
+
+	// create a file outside the treeRoot
+	externalFile, err := os.Create(filepath.Join(tempDir, "outside_tree.go"))
+	as.NoError(err)
+
+	// change working directory to project root
+	as.NoError(os.Chdir(treeRoot))
+
+	// basic config
+	cfg := &config.Config{
+		FormatterConfigs: map[string]*config.Formatter{
+			"echo": {
+				Command:  "echo",
+				Includes: []string{"*"},
+			},
+		},
+	}
+
+	test.WriteConfig(t, configPath, cfg)
+
+	// without any path args
+	treefmt(t,
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 33,
+			stats.Matched:   33,
+			stats.Formatted: 33,
+			stats.Changed:   0,
+		}),
+	)
+
+	// specify some explicit paths
+	treefmt(t,
+		withArgs("elm/elm.json", "haskell/Nested/Foo.hs"),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 2,
+			stats.Matched:   2,
+			stats.Formatted: 0,
+			stats.Changed:   0,
+		}),
+	)
+
+	// specify an absolute path
+	absoluteInternalPath, err := filepath.Abs("elm/elm.json")
+	as.NoError(err)
+
+	treefmt(t,
+		withArgs(absoluteInternalPath),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 1,
+			stats.Matched:   1,
+			stats.Formatted: 0,
+			stats.Changed:   0,
+		}),
+	)
+
+	// specify a bad path
+	treefmt(t,
+		withArgs("elm/elm.json", "haskell/Nested/Bar.hs"),
+		withError(func(as *require.Assertions, err error) {
+			as.ErrorContains(err, "path haskell/Nested/Bar.hs not found")
+		}),
+	)
+
+	// specify an absolute path outside the tree root
+	absoluteExternalPath, err := filepath.Abs(externalFile.Name())
+	as.NoError(err)
+	as.FileExists(absoluteExternalPath, "external file must exist")
+
+	treefmt(t,
+		withArgs(absoluteExternalPath),
+		withError(func(as *require.Assertions, err error) {
+			as.ErrorContains(err, fmt.Sprintf("path %s not inside the tree root", absoluteExternalPath))
+		}),
+	)
+
+	// specify a relative path outside the tree root
+	relativeExternalPath := "../outside_tree.go"
+	as.FileExists(relativeExternalPath, "exernal file must exist")
+
+	treefmt(t,
+		withArgs(relativeExternalPath),
+		withError(func(as *require.Assertions, err error) {
+			as.ErrorContains(err, fmt.Sprintf("path %s not inside the tree root", relativeExternalPath))
+		}),
+	)
+}
+
+func TestSymlinkPathsArg(t *testing.T) {
+	as := require.New(t)
+
+	tempDir := t.TempDir()
+	treeRoot := filepath.Join(tempDir, "tree-root")
+
+	test.TempExamplesInDir(t, treeRoot)
+
+	configPath := filepath.Join(treeRoot, "treefmt.toml")
+
+	test.ChangeWorkDir(t, treeRoot)
+
+	cfg := &config.Config{
+		FormatterConfigs: map[string]*config.Formatter{
+			"append": {
+				Command:  "test-fmt-append",
+				Options:  []string{"// formatted"},
+				Includes: []string{"*.go"},
+			},
+		},
+	}
+
+	// a symlink within the tree, and one which points outside of it
+	as.NoError(os.Symlink(filepath.Join("go", "main.go"), filepath.Join(treeRoot, "link.go")))
+
+	externalFile := filepath.Join(tempDir, "outside_tree.go")
+	as.NoError(os.WriteFile(externalFile, []byte("package main\n"), 0o600))
+	as.NoError(os.Symlink(externalFile, filepath.Join(treeRoot, "external.go")))
+
+	// symlinks found while walking are skipped
+	treefmt(t,
+		withConfig(configPath, cfg),
+		withArgs("--no-cache"),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 33,
+			stats.Matched:   1,
+			stats.Formatted: 1,
+			stats.Changed:   1,
+		}),
+	)
+
+	// a symlink named explicitly is formatted via its target
+	treefmt(t,
+		withConfig(configPath, cfg),
+		withArgs("--no-cache", "link.go"),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 1,
+			stats.Matched:   1,
+			stats.Formatted: 1,
+			stats.Changed:   1,
+		}),
+	)
+
+	contents, err := os.ReadFile(filepath.Join(treeRoot, "go", "main.go"))
+	as.NoError(err)
+	as.True(strings.HasSuffix(string(contents), "// formatted\n// formatted\n"))
+
+	// unless its target is outside the tree root
+	treefmt(t,
+		withConfig(configPath, cfg),
+		withArgs("external.go"),
+		withError(func(as *require.Assertions, err error) {
+			as.ErrorContains(err, "symlink external.go resolves to")
+			as.ErrorContains(err, "which is not inside the tree root")
+		}),
+	)
+}
+
+func TestStdin(t *testing.T) {
+	as := require.New(t)
+	tempDir := test.TempExamples(t)
+
+	test.ChangeWorkDir(t, tempDir)
+
+	// capture current stdin and replace it on test cleanup
+	prevStdIn := os.Stdin
+
+	t.Cleanup(func() {
+		os.Stdin = prevStdIn
+	})
+
+	// omit the required filename parameter
+	contents := `{ foo, ... }: "hello"`
+	os.Stdin = test.TempFile(t, "", "stdin", &contents)
+
+	// for convenience so we don't have to specify it in the args
+	t.Setenv("TREEFMT_ALLOW_MISSING_FORMATTER", "true")
+
+	// we get an error about the missing filename parameter.
+	treefmt(t,
+		withArgs("--stdin"),
+		withError(func(as *require.Assertions, err error) {
+			as.EqualError(err, "exactly one path should be specified when using the --stdin flag")
+		}),
+		withStderr(func(out []byte) {
+			as.Equal("Error: exactly one path should be specified when using the --stdin flag\n", string(out))
+		}),
+	)
+
+	// the path must name a file, not a directory
+	treefmt(t,
+		withArgs("--stdin", "nix"),
+		withError(func(as *require.Assertions, err error) {
+			as.EqualError(err, "--stdin expects a file path, got a directory: nix")
+		}),
+	)
+
+	treefmt(t,
+		withArgs("--stdin", "missing/"),
+		withError(func(as *require.Assertions, err error) {
+			as.EqualError(err, "--stdin expects a file path, got a directory: missing/")
+		}),
+	)
+
+	// now pass along the filename parameter
+	os.Stdin = test.TempFile(t, "", "stdin", &contents)
+
+	treefmt(t,
+		withArgs("--stdin", "test.nix"),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 1,
+			stats.Matched:   1,
+			stats.Formatted: 1,
+			stats.Changed:   1,
+		}),
+		withStdout(func(out []byte) {
+			as.Equal(`{ ...}: "hello"
+`, string(out))
+		}),
+	)
+
+	// the nix formatters should have reduced the example to the following
+
+	// try a file that's outside of the project root
+	os.Stdin = test.TempFile(t, "", "stdin", &contents)
+
+	treefmt(t,
+		withArgs("--stdin", "../test.nix"),
+		withError(func(as *require.Assertions, err error) {
+			as.ErrorContains(err, "path ../test.nix not inside the tree root "+tempDir)
+		}),
+		withStderr(func(out []byte) {
+			as.Contains(string(out), "Error: path ../test.nix not inside the tree root")
+		}),
+	)
+
+	// try some markdown instead
+	contents = `
+| col1 | col2 |
+| ---- | ---- |
+| nice | fits |
+| oh no! | it's ugly |
+`
+	os.Stdin = test.TempFile(t, "", "stdin", &contents)
+
+	treefmt(t,
+		withArgs("--stdin", "test.md"),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 1,
+			stats.Matched:   1,
+			stats.Formatted: 1,
+			stats.Changed:   1,
+		}),
+		withStdout(func(out []byte) {
+			as.Equal(`| col1   | col2      |
+| ------ | --------- |
+| nice   | fits      |
+| oh no! | it's ugly |
+`, string(out))
+		}),
+	)
+}
+
+func TestStdinReadOnlyTree(t *testing.T) {
+	if os.Geteuid() == 0 {
+		t.Skip("permissions are not enforced for root")
+	}
+
+	as := require.New(t)
+
+	tempDir := test.TempExamples(t)
+	configPath := filepath.Join(tempDir, "treefmt.toml")
+
+	test.ChangeWorkDir(t, tempDir)
+
+	// capture current stdin and replace it on test cleanup
+	prevStdIn := os.Stdin
+
+	t.Cleanup(func() {
+		os.Stdin = prevStdIn
+	})
+
+	// the include is anchored, so it must still be matched against the path rather than the temporary file
+	cfg := &config.Config{
+		FormatterConfigs: map[string]*config.Formatter{
+			"append": {
+				Command:  "test-fmt-append",
+				Options:  []string{"   "},
+				Includes: []string{"nix/*.nix"},
+			},
+		},
+	}
+
+	test.WriteConfig(t, configPath, cfg)
+
+	// no temporary file can be created alongside the path
+	nixDir := filepath.Join(tempDir, "nix")
+	as.NoError(os.Chmod(nixDir, 0o555))
+
+	t.Cleanup(func() {
+		_ = os.Chmod(nixDir, 0o755)
+	})
+
+	contents := `{ foo, ... }: "hello"`
+	os.Stdin = test.TempFile(t, "", "stdin", &contents)
+
+	// so the system temp directory is used instead
+	treefmt(t,
+		withArgs("--stdin", "nix/test.nix"),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 1,
+			stats.Matched:   1,
+			stats.Formatted: 1,
+		}),
+		withStdout(func(out []byte) {
+			as.Equal(contents+"   \n", string(out))
+		}),
+	)
+}
+
+func TestStdinConfigDiscovery(t *testing.T) {
+	as := require.New(t)
+
+	tempDir := test.TempExamples(t)
+	configPath := filepath.Join(tempDir, "treefmt.toml")
+
+	test.ChangeWorkDir(t, tempDir)
+
+	// capture current stdin and replace it on test cleanup
+	prevStdIn := os.Stdin
+
+	t.Cleanup(func() {
+		os.Stdin = prevStdIn
+	})
+
+	// a formatter which, like prettier, discovers its config by walking up from each file's directory, and appends it
+	cfg := &config.Config{
+		FormatterConfigs: map[string]*config.Formatter{
+			"discover": {
+				Command: `for file in "$@"; do
+  dir=$(dirname "$file")
+  while [ ! -f "$dir/.fmtrc" ] && [ "$dir" != . ]; do dir=$(dirname "$dir"); done
+  cat "$dir/.fmtrc" >> "$file"
+done`,
+				Includes: []string{"*.nix"},
+				Shell:    true,
+			},
+		},
+	}
+
+	test.WriteConfig(t, configPath, cfg)
+
+	as.NoError(os.WriteFile(filepath.Join(tempDir, ".fmtrc"), []byte("root\n"), 0o600))
+	as.NoError(os.WriteFile(filepath.Join(tempDir, "nix", ".fmtrc"), []byte("nix\n"), 0o600))
+
+	contents := "{ ... }: null\n"
+
+	for path, expected := range map[string]string{
+		"test.nix":     "root\n",
+		"nix/test.nix": "nix\n",
+		// the directory does not exist, but the config in its nearest existing parent is still found
+		"nix/new/deeper/test.nix": "nix\n",
+		"new/test.nix":            "root\n",
+	} {
+		os.Stdin = test.TempFile(t, "", "stdin", &contents)
+
+		treefmt(t,
+			withArgs("--stdin", path),
+			withNoError(t),
+			withStats(t, map[stats.Type]int{
+				stats.Traversed: 1,
+				stats.Matched:   1,
+				stats.Formatted: 1,
+				stats.Changed:   1,
+			}),
+			withStdout(func(out []byte) {
+				as.Equal(contents+expected, string(out), "formatting %s", path)
+			}),
+		)
+	}
+
+	// the missing directories were not created
+	as.NoDirExists(filepath.Join(tempDir, "nix", "new"))
+	as.NoDirExists(filepath.Join(tempDir, "new"))
+}
+
+func TestStdinFormatters(t *testing.T) {
+	as := require.New(t)
+
+	tempDir := test.TempExamples(t)
+	configPath := filepath.Join(tempDir, "treefmt.toml")
+
+	test.ChangeWorkDir(t, tempDir)
+
+	// capture current stdin and replace it on test cleanup
+	prevStdIn := os.Stdin
+
+	t.Cleanup(func() {
+		os.Stdin = prevStdIn
+	})
+
+	cfg := &config.Config{
+		FormatterConfigs: map[string]*config.Formatter{
+			"append": {
+				Command:  "test-fmt-append",
+				Options:  []string{"   "},
+				Includes: []string{"*.nix"},
+			},
+			"missing": {
+				Command:  "missing-fmt",
+				Includes: []string{"*.nix"},
+			},
+		},
+	}
+
+	contents := `{ foo, ... }: "hello"`
+
+	// every configured formatter is resolved by default
+	os.Stdin = test.TempFile(t, "", "stdin", &contents)
+
+	treefmt(t,
+		withConfig(configPath, cfg),
+		withArgs("--stdin", "test.nix"),
+		withError(func(as *require.Assertions, err error) {
+			as.ErrorIs(err, format.ErrCommandNotFound)
+		}),
+	)
+
+	// whereas formatters which have not been selected are never resolved
+	os.Stdin = test.TempFile(t, "", "stdin", &contents)
+
+	treefmt(t,
+		withArgs("--stdin", "--formatters", "append", "test.nix"),
+		withNoError(t),
+		withStdout(func(out []byte) {
+			as.Equal(contents+"   \n", string(out))
+		}),
+	)
+}
+
+func TestStdinReport(t *testing.T) {
+	as := require.New(t)
+
+	tempDir := test.TempExamples(t)
+	configPath := filepath.Join(tempDir, "treefmt.toml")
+
+	test.ChangeWorkDir(t, tempDir)
+
+	// capture current stdin and replace it on test cleanup
+	prevStdIn := os.Stdin
+
+	t.Cleanup(func() {
+		os.Stdin = prevStdIn
+	})
+
+	test.WriteConfig(t, configPath, &config.Config{
+		FormatterConfigs: map[string]*config.Formatter{
+			"append": {
+				Command:  "test-fmt-append",
+				Options:  []string{"formatted"},
+				Includes: []string{"*.py"},
+			},
+			"echo": {
+				Command:  "echo",
+				Includes: []string{"*.go"},
+			},
+		},
+	})
+
+	contents := "hello\n"
+
+	// the report replaces the summary
+	os.Stdin = test.TempFile(t, "", "stdin", &contents)
+
+	treefmt(t,
+		withArgs("--stdin", "test.py", "--stdin-report"),
+		withNoError(t),
+		withStdout(func(out []byte) {
+			as.Equal("hello\nformatted\n", string(out))
+		}),
+		withStderr(func(out []byte) {
+			as.Equal("changed\n", string(out))
+		}),
+	)
+
+	os.Stdin = test.TempFile(t, "", "stdin", &contents)
+
+	treefmt(t,
+		withArgs("--stdin", "test.go", "--stdin-report"),
+		withNoError(t),
+		withStdout(func(out []byte) {
+			as.Equal(contents, string(out))
+		}),
+		withStderr(func(out []byte) {
+			as.Equal("unchanged\n", string(out))
+		}),
+	)
+
+	// the report requires stdin
+	treefmt(t,
+		withArgs("--stdin-report"),
+		withError(func(as *require.Assertions, err error) {
+			as.ErrorContains(err, "--stdin-report can only be used with --stdin")
+		}),
+	)
+}
+
+func TestStdinJSON(t *testing.T) {
+	as := require.New(t)
+
+	tempDir := test.TempExamples(t)
+	configPath := filepath.Join(tempDir, "treefmt.toml")
+
+	test.ChangeWorkDir(t, tempDir)
+
+	// capture current stdin and replace it on test cleanup
+	prevStdIn := os.Stdin
+
+	t.Cleanup(func() {
+		os.Stdin = prevStdIn
+	})
+
+	test.WriteConfig(t, configPath, &config.Config{
+		FormatterConfigs: map[string]*config.Formatter{
+			"append": {
+				Command:  "test-fmt-append",
+				Options:  []string{"formatted"},
+				Includes: []string{"*.py"},
+			},
+			"echo": {
+				Command:  "echo",
+				Includes: []string{"*.go"},
+			},
+			"fail": {
+				Command:  "false",
+				Includes: []string{"*.sh"},
+			},
+		},
+	})
+
+	request := `{"filename": "test.py", "content": "hello\n"}`
+	os.Stdin = test.TempFile(t, "", "stdin", &request)
+
+	treefmt(t,
+		withArgs("--stdin", "--stdin-json"),
+		withNoError(t),
+		withStdout(func(out []byte) {
+			as.JSONEq(`{"formatted": "hello\nformatted\n", "changed": true, "formatter": "append"}`, string(out))
+		}),
+		withStderr(func(out []byte) {
+			as.Empty(out)
+		}),
+	)
+
+	request = `{"filename": "test.go", "content": "hello\n"}`
+	os.Stdin = test.TempFile(t, "", "stdin", &request)
+
+	treefmt(t,
+		withArgs("--stdin", "--stdin-json"),
+		withNoError(t),
+		withStdout(func(out []byte) {
+			as.JSONEq(`{"formatted": "hello\n", "changed": false}`, string(out))
+		}),
+	)
+
+	// errors are reported in the response as well as the exit code
+	request = `{"filename": "test.sh", "content": "hello\n"}`
+	os.Stdin = test.TempFile(t, "", "stdin", &request)
+
+	treefmt(t,
+		withArgs("--stdin", "--stdin-json"),
+		withError(func(as *require.Assertions, err error) {
+			as.ErrorIs(err, format.ErrFormattingFailures)
+		}),
+		withStdout(func(out []byte) {
+			as.Contains(string(out), `"error":`)
+		}),
+	)
+
+	request = `not json`
+	os.Stdin = test.TempFile(t, "", "stdin", &request)
+
+	treefmt(t,
+		withArgs("--stdin", "--stdin-json"),
+		withError(func(as *require.Assertions, err error) {
+			as.ErrorContains(err, "failed to decode request")
+		}),
+		withStdout(func(out []byte) {
+			as.Contains(string(out), `"error":`)
+		}),
+	)
+
+	// the path is read from the request
+	treefmt(t,
+		withArgs("--stdin", "--stdin-json", "test.py"),
+		withError(func(as *require.Assertions, err error) {
+			as.ErrorContains(err, "--stdin-json reads the path from the request")
+		}),
+	)
+
+	treefmt(t,
+		withArgs("--stdin-json"),
+		withError(func(as *require.Assertions, err error) {
+			as.ErrorContains(err, "--stdin-json can only be used with --stdin")
+		}),
+	)
+}
+
+func TestStdinOutput(t *testing.T) {
+	as := require.New(t)
+
+	tempDir := test.TempExamples(t)
+
+	test.ChangeWorkDir(t, tempDir)
+
+	// capture current stdin and replace it on test cleanup
+	prevStdIn := os.Stdin
+
+	t.Cleanup(func() {
+		os.Stdin = prevStdIn
+	})
+
+	t.Setenv("TREEFMT_ALLOW_MISSING_FORMATTER", "true")
+
+	contents := `{ foo, ... }: "hello"`
+	os.Stdin = test.TempFile(t, "", "stdin", &contents)
+
+	outputPath := filepath.Join(t.TempDir(), "out.nix")
+
+	// the formatted content is written to the output file instead of stdout
+	treefmt(t,
+		withArgs("--stdin", "test.nix", "--output", outputPath),
+		withNoError(t),
+		withStdout(func(out []byte) {
+			as.Empty(out)
+		}),
+	)
 
-	t.Run("formatter_change_binary", func(t *testing.T) {
-		tempDir := test.TempExamples(t)
-		configPath := filepath.Join(tempDir, "treefmt.toml")
+	out, err := os.ReadFile(outputPath)
+	as.NoError(err)
+	as.Equal(`{ ...}: "hello"
+`, string(out))
 
-		test.ChangeWorkDir(t, tempDir)
+	// output is only supported with stdin
+	treefmt(t,
+		withArgs("--output", outputPath),
+		withError(func(as *require.Assertions, err error) {
+			as.ErrorContains(err, "--output can only be used with --stdin")
+		}),
+	)
+}
 
-		// find test-fmt-append in PATH
-		sourcePath, err := exec.LookPath("test-fmt-append")
-		as.NoError(err, "failed to find test-fmt-append in PATH")
+func TestStdinPriority(t *testing.T) {
+	as := require.New(t)
 
-		// copy it into the temp dir so we can mess with its size and modtime
-		binPath := filepath.Join(tempDir, "bin")
-		as.NoError(os.Mkdir(binPath, 0o755))
+	tempDir := test.TempExamples(t)
+	configPath := filepath.Join(tempDir, "treefmt.toml")
 
-		scriptPath := filepath.Join(binPath, "test-fmt-append")
-		as.NoError(cp.Copy(sourcePath, scriptPath, cp.Options{AddPermission: 0o755}))
+	test.ChangeWorkDir(t, tempDir)
 
-		// prepend our test bin directory to PATH
-		t.Setenv("PATH", binPath+":"+os.Getenv("PATH"))
+	// capture current stdin and replace it on test cleanup
+	prevStdIn := os.Stdin
 
-		// basic config
-		cfg := &config.Config{
+	t.Cleanup(func() {
+		os.Stdin = prevStdIn
+	})
+
+	contents := "print(\"hello\")\n"
+	os.Stdin = test.TempFile(t, "", "stdin", &contents)
+
+	// all matching formatters are applied in sequence, in the same order as a full run
+	treefmt(t,
+		withConfig(configPath, &config.Config{
 			FormatterConfigs: map[string]*config.Formatter{
-				"python": {
-					Command:  "echo", // this is non-destructive, will match but cause no changes
+				"fmt-b": {
+					Command:  "test-fmt-append",
+					Options:  []string{"fmt-b"},
 					Includes: []string{"*.py"},
 				},
-				"elm": {
+				"fmt-a": {
 					Command:  "test-fmt-append",
-					Options:  []string{"   "},
-					Includes: []string{"*.elm"},
+					Options:  []string{"fmt-a"},
+					Includes: []string{"*.py"},
+				},
+				"fmt-c": {
+					Command:  "test-fmt-append",
+					Options:  []string{"fmt-c"},
+					Includes: []string{"*.py"},
+					Priority: -1,
 				},
 			},
-		}
+		}),
+		withArgs("--stdin", "test.py"),
+		withNoError(t),
+		withStdout(func(out []byte) {
+			as.Equal("print(\"hello\")\nfmt-c\nfmt-a\nfmt-b\n", string(out))
+		}),
+	)
+}
 
-		// initial run
-		treefmt(t,
-			withConfig(configPath, cfg),
-			withNoError(t),
-			withStats(t, map[stats.Type]int{
-				stats.Traversed: 34,
-				stats.Matched:   3,
-				stats.Formatted: 3,
-				stats.Changed:   1,
-			}))
+func TestStdinLanguage(t *testing.T) {
+	as := require.New(t)
 
-		// tweak mod time of elm formatter
-		newTime := time.Now().Add(-time.Minute)
-		as.NoError(os.Chtimes(scriptPath, newTime, newTime))
+	tempDir := test.TempExamples(t)
+	configPath := filepath.Join(tempDir, "treefmt.toml")
 
-		// cache entries for elm files should be invalidated
-		treefmt(t,
-			withConfig(configPath, cfg),
-			withNoError(t),
-			withStats(t, map[stats.Type]int{
-				stats.Traversed: 34,
-				stats.Matched:   3,
-				stats.Formatted: 1,
-				stats.Changed:   1,
-			}))
+	test.ChangeWorkDir(t, tempDir)
 
-		// running again with a hot cache, we should see nothing be formatted
-		treefmt(t,
-			withConfig(configPath, cfg),
-			withNoError(t),
-			withStats(t, map[stats.Type]int{
-				stats.Traversed: 34,
-				stats.Matched:   3,
-				stats.Formatted: 0,
-				stats.Changed:   0,
-			}),
-		)
+	// capture current stdin and replace it on test cleanup
+	prevStdIn := os.Stdin
 
-		// tweak the size of elm formatter
-		formatter, err := os.OpenFile(scriptPath, os.O_WRONLY|os.O_APPEND, 0o755)
-		as.NoError(err, "failed to open elm formatter")
+	t.Cleanup(func() {
+		os.Stdin = prevStdIn
+	})
 
-		_, err = formatter.WriteString(" ") // add some whitespace
-		as.NoError(err, "failed to append to elm formatter")
-		as.NoError(formatter.Close(), "failed to close elm formatter")
+	test.WriteConfig(t, configPath, &config.Config{
+		FormatterConfigs: map[string]*config.Formatter{
+			"markdown": {
+				Command:   "test-fmt-append",
+				Options:   []string{"markdown"},
+				Languages: []string{"markdown"},
+			},
+			"python": {
+				Command:   "test-fmt-append",
+				Options:   []string{"python"},
+				Includes:  []string{"*.py"},
+				Languages: []string{"python"},
+			},
+		},
+	})
 
-		// cache entries for elm files should be invalidated
-		treefmt(t,
-			withConfig(configPath, cfg),
-			withNoError(t),
-			withStats(t, map[stats.Type]int{
-				stats.Traversed: 34,
-				stats.Matched:   3,
-				stats.Formatted: 1,
-				stats.Changed:   1,
-			}))
+	contents := "hello\n"
 
-		// running again with a hot cache, we should see nothing be formatted
-		treefmt(t,
-			withConfig(configPath, cfg),
-			withNoError(t),
-			withStats(t, map[stats.Type]int{
-				stats.Traversed: 34,
-				stats.Matched:   3,
-				stats.Formatted: 0,
-				stats.Changed:   0,
-			}),
-		)
-	})
+	// no path is required, and formatters are selected by language alone
+	os.Stdin = test.TempFile(t, "", "stdin", &contents)
 
-	t.Run("formatter_add_remove", func(t *testing.T) {
-		tempDir := test.TempExamples(t)
-		configPath := filepath.Join(tempDir, "treefmt.toml")
+	treefmt(t,
+		withArgs("--stdin", "--language", "markdown"),
+		withNoError(t),
+		withStdout(func(out []byte) {
+			as.Equal("hello\nmarkdown\n", string(out))
+		}),
+	)
 
-		test.ChangeWorkDir(t, tempDir)
+	// the language takes precedence over the provided path
+	os.Stdin = test.TempFile(t, "", "stdin", &contents)
 
-		cfg := &config.Config{
-			FormatterConfigs: map[string]*config.Formatter{
-				"python": {
-					Command:  "test-fmt-append",
-					Options:  []string{"   "},
-					Includes: []string{"*.py"},
-				},
-			},
-		}
+	treefmt(t,
+		withArgs("--stdin", "--language", "markdown", "test.py"),
+		withNoError(t),
+		withStdout(func(out []byte) {
+			as.Equal("hello\nmarkdown\n", string(out))
+		}),
+	)
 
-		// initial run
-		treefmt(t,
-			withConfig(configPath, cfg),
-			withNoError(t),
-			withStats(t, map[stats.Type]int{
-				stats.Traversed: 33,
-				stats.Matched:   2,
-				stats.Formatted: 2,
-				stats.Changed:   2,
-			}),
-		)
+	// unknown languages leave the content unchanged
+	os.Stdin = test.TempFile(t, "", "stdin", &contents)
 
-		// cached run
-		treefmt(t,
-			withConfig(configPath, cfg),
-			withNoError(t),
-			withStats(t, map[stats.Type]int{
-				stats.Traversed: 33,
-				stats.Matched:   2,
-				stats.Formatted: 0,
-				stats.Changed:   0,
-			}),
-		)
+	treefmt(t,
+		withArgs("--stdin", "--language", "rust"),
+		withNoError(t),
+		withStdout(func(out []byte) {
+			as.Equal(contents, string(out))
+		}),
+	)
 
-		// add a formatter
-		cfg.FormatterConfigs["elm"] = &config.Formatter{
-			Command:  "test-fmt-append",
-			Options:  []string{"   "},
-			Includes: []string{"*.elm"},
-		}
+	// a language requires stdin
+	treefmt(t,
+		withArgs("--language", "python"),
+		withError(func(as *require.Assertions, err error) {
+			as.ErrorContains(err, "--language can only be used with --stdin")
+		}),
+	)
+}
 
-		// only the elm files should be formatted
-		treefmt(t,
-			withConfig(configPath, cfg),
-			withNoError(t),
-			withStats(t, map[stats.Type]int{
-				stats.Traversed: 33,
-				stats.Matched:   3,
-				stats.Formatted: 1,
-				stats.Changed:   1,
-			}),
-		)
+func TestStdinDetect(t *testing.T) {
+	as := require.New(t)
 
-		// let's add a second python formatter
-		cfg.FormatterConfigs["python_secondary"] = &config.Formatter{
-			Command:  "test-fmt-append",
-			Options:  []string{" "},
-			Includes: []string{"*.py"},
-		}
+	tempDir := test.TempExamples(t)
+	configPath := filepath.Join(tempDir, "treefmt.toml")
 
-		// python files should be formatted as their pipeline has changed
-		treefmt(t,
-			withConfig(configPath, cfg),
-			withNoError(t),
-			withStats(t, map[stats.Type]int{
-				stats.Traversed: 33,
-				stats.Matched:   3,
-				stats.Formatted: 2,
-				stats.Changed:   2,
-			}),
-		)
+	test.ChangeWorkDir(t, tempDir)
 
-		// cached run
-		treefmt(t,
-			withConfig(configPath, cfg),
-			withNoError(t),
-			withStats(t, map[stats.Type]int{
-				stats.Traversed: 33,
-				stats.Matched:   3,
-				stats.Formatted: 0,
-				stats.Changed:   0,
-			}),
-		)
+	// capture current stdin and replace it on test cleanup
+	prevStdIn := os.Stdin
+
+	t.Cleanup(func() {
+		os.Stdin = prevStdIn
+	})
+
+	test.WriteConfig(t, configPath, &config.Config{
+		FormatterConfigs: map[string]*config.Formatter{
+			"json": {
+				Command:  "test-fmt-append",
+				Options:  []string{"json"},
+				Includes: []string{"*.json"},
+			},
+			"python": {
+				Command:  "test-fmt-append",
+				Options:  []string{"python"},
+				Includes: []string{"*.py"},
+			},
+		},
+	})
 
-		// change ordering within a pipeline
-		cfg.FormatterConfigs["python"].Priority = 2
-		cfg.FormatterConfigs["python_secondary"].Priority = 1
+	// the content is matched against includes using a detected name
+	contents := `{"hello": "world"}` + "\n"
+	os.Stdin = test.TempFile(t, "", "stdin", &contents)
 
-		// python files should be formatted as their pipeline has changed
-		treefmt(t,
-			withConfig(configPath, cfg),
-			withNoError(t),
-			withStats(t, map[stats.Type]int{
-				stats.Traversed: 33,
-				stats.Matched:   3,
-				stats.Formatted: 2,
-				stats.Changed:   2,
-			}),
-		)
+	treefmt(t,
+		withArgs("--stdin", "--stdin-detect"),
+		withNoError(t),
+		withStdout(func(out []byte) {
+			as.Equal(contents+"json\n", string(out))
+		}),
+	)
 
-		// cached run
-		treefmt(t,
-			withConfig(configPath, cfg),
-			withNoError(t),
-			withStats(t, map[stats.Type]int{
-				stats.Traversed: 33,
-				stats.Matched:   3,
-				stats.Formatted: 0,
-				stats.Changed:   0,
-			}),
-		)
+	contents = "#!/usr/bin/env python3\nprint('hello')\n"
+	os.Stdin = test.TempFile(t, "", "stdin", &contents)
 
-		// remove secondary python formatter
-		delete(cfg.FormatterConfigs, "python_secondary")
+	treefmt(t,
+		withArgs("--stdin", "--stdin-detect"),
+		withNoError(t),
+		withStdout(func(out []byte) {
+			as.Equal(contents+"python\n", string(out))
+		}),
+	)
 
-		// python files should be formatted as their pipeline has changed
-		treefmt(t,
-			withConfig(configPath, cfg),
-			withNoError(t),
-			withStats(t, map[stats.Type]int{
-				stats.Traversed: 33,
-				stats.Matched:   3,
-				stats.Formatted: 2,
-				stats.Changed:   2,
-			}),
-		)
+	// a provided path takes precedence over detection
+	os.Stdin = test.TempFile(t, "", "stdin", &contents)
 
-		// cached run
-		treefmt(t,
-			withConfig(configPath, cfg),
-			withNoError(t),
-			withStats(t, map[stats.Type]int{
-				stats.Traversed: 33,
-				stats.Matched:   3,
-				stats.Formatted: 0,
-				stats.Changed:   0,
-			}),
-		)
+	treefmt(t,
+		withArgs("--stdin", "--stdin-detect", "test.json"),
+		withNoError(t),
+		withStdout(func(out []byte) {
+			as.Equal(contents+"json\n", string(out))
+		}),
+	)
 
-		// remove the elm formatter
-		delete(cfg.FormatterConfigs, "elm")
+	// content which cannot be identified is written out unchanged
+	contents = "hello world\n"
+	os.Stdin = test.TempFile(t, "", "stdin", &contents)
 
-		// only python files should match, but no formatting should occur as not formatting signatures have been
-		// affected
-		treefmt(t,
-			withConfig(configPath, cfg),
-			withNoError(t),
-			withStats(t, map[stats.Type]int{
-				stats.Traversed: 33,
-				stats.Matched:   2,
-				stats.Formatted: 0,
-				stats.Changed:   0,
-			}),
-		)
-	})
+	treefmt(t,
+		withArgs("--stdin", "--stdin-detect"),
+		withNoError(t),
+		withStdout(func(out []byte) {
+			as.Equal(contents, string(out))
+		}),
+		withStderr(func(out []byte) {
+			as.Contains(string(out), "could not detect the type of content on stdin")
+		}),
+	)
+
+	// detection requires stdin
+	treefmt(t,
+		withArgs("--stdin-detect"),
+		withError(func(as *require.Assertions, err error) {
+			as.ErrorContains(err, "--stdin-detect can only be used with --stdin")
+		}),
+	)
 }
 
-func TestGit(t *testing.T) {
+func TestStdinMatchesProvidedPath(t *testing.T) {
 	as := require.New(t)
 
 	tempDir := test.TempExamples(t)
-	configPath := filepath.Join(tempDir, "/treefmt.toml")
+	configPath := filepath.Join(tempDir, "treefmt.toml")
 
 	test.ChangeWorkDir(t, tempDir)
 
-	// basic config
-	cfg := &config.Config{
+	// capture current stdin and replace it on test cleanup
+	prevStdIn := os.Stdin
+
+	t.Cleanup(func() {
+		os.Stdin = prevStdIn
+	})
+
+	contents := "fn main() {}\n"
+
+	test.WriteConfig(t, configPath, &config.Config{
 		FormatterConfigs: map[string]*config.Formatter{
-			"echo": {
-				Command:  "echo", // will not generate any underlying changes in the file
-				Includes: []string{"*"},
+			"append": {
+				Command:  "test-fmt-append",
+				Options:  []string{"formatted"},
+				Includes: []string{"rust/src/*.rs"},
 			},
 		},
-	}
-
-	test.WriteConfig(t, configPath, cfg)
+	})
 
-	// init a git repo
-	gitCmd := exec.Command("git", "init")
-	as.NoError(gitCmd.Run(), "failed to init git repository")
+	// an include anchored to a directory matches a path within that directory
+	os.Stdin = test.TempFile(t, "", "stdin", &contents)
 
-	// run before adding anything to the index
 	treefmt(t,
-		withConfig(configPath, cfg),
+		withArgs("--stdin", "rust/src/main.rs"),
 		withNoError(t),
-		withStats(t, map[stats.Type]int{
-			stats.Traversed: 0,
+		withStdout(func(out []byte) {
+			as.Equal(contents+"formatted\n", string(out))
 		}),
 	)
 
-	// add everything to the index
-	gitCmd = exec.Command("git", "add", ".")
-	as.NoError(gitCmd.Run(), "failed to add everything to the index")
+	// the same file name elsewhere in the tree does not match
+	os.Stdin = test.TempFile(t, "", "stdin", &contents)
 
 	treefmt(t,
-		withConfig(configPath, cfg),
+		withArgs("--stdin", "rust/main.rs"),
 		withNoError(t),
 		withStats(t, map[stats.Type]int{
-			stats.Traversed: 33,
-			stats.Matched:   33,
-			stats.Formatted: 33,
-			stats.Changed:   0,
+			stats.Traversed: 1,
+			stats.Matched:   0,
 		}),
 	)
 
-	// remove python directory from the index
-	gitCmd = exec.Command("git", "rm", "--cached", "python/*")
-	as.NoError(gitCmd.Run(), "failed to remove python directory from the index")
+	// excludes are applied relative to the provided path too
+	test.WriteConfig(t, configPath, &config.Config{
+		Excludes: []string{"rust/src/*"},
+		FormatterConfigs: map[string]*config.Formatter{
+			"append": {
+				Command:  "test-fmt-append",
+				Options:  []string{"formatted"},
+				Includes: []string{"*.rs"},
+			},
+		},
+	})
 
-	// we should traverse and match against fewer files, but no formatting should occur as no formatting signatures
-	// are impacted
+	os.Stdin = test.TempFile(t, "", "stdin", &contents)
+
+	// the content is passed through unchanged
 	treefmt(t,
-		withConfig(configPath, cfg),
+		withArgs("--stdin", "rust/src/main.rs"),
 		withNoError(t),
 		withStats(t, map[stats.Type]int{
-			stats.Traversed: 30,
-			stats.Matched:   30,
-			stats.Formatted: 0,
-			stats.Changed:   0,
+			stats.Traversed: 1,
+			stats.Matched:   0,
+		}),
+		withStdout(func(out []byte) {
+			as.Equal(contents, string(out))
 		}),
 	)
 
-	// remove nixpkgs.toml from the filesystem but leave it in the index
-	as.NoError(os.Remove(filepath.Join(tempDir, "nixpkgs.toml")))
+	// patterns never match the name of the temporary file, e.g. `.treefmt-tmp-123-fix.patch`, which would otherwise
+	// be excluded along with the numbered patches from git format-patch
+	test.WriteConfig(t, configPath, &config.Config{
+		Excludes: []string{"*[0-9]*.patch"},
+		FormatterConfigs: map[string]*config.Formatter{
+			"append": {
+				Command:  "test-fmt-append",
+				Options:  []string{"formatted"},
+				Includes: []string{"*.patch"},
+			},
+		},
+	})
+
+	os.Stdin = test.TempFile(t, "", "stdin", &contents)
 
-	// walk with filesystem instead of with git
-	// the .git folder contains 50 additional files
-	// when added to the 32 we started with (34 minus nixpkgs.toml which we removed from the filesystem), we should
-	// traverse 82 files.
 	treefmt(t,
-		withArgs("--walk", "filesystem"),
-		withConfig(configPath, cfg),
+		withArgs("--stdin", "fix.patch"),
 		withNoError(t),
 		withStats(t, map[stats.Type]int{
-			stats.Traversed: 82,
-			stats.Matched:   82,
-			stats.Formatted: 50, // the echo formatter should only be applied to the new files
-			stats.Changed:   0,
+			stats.Traversed: 1,
+			stats.Matched:   1,
+		}),
+		withStdout(func(out []byte) {
+			as.Equal(contents+"formatted\n", string(out))
 		}),
 	)
 
-	// format specific sub paths
-	// we should traverse and match against those files, but without any underlying change to their files or their
-	// formatting config, we will not format them
+	os.Stdin = test.TempFile(t, "", "stdin", &contents)
 
 	treefmt(t,
-		withArgs("go"),
-		withConfig(configPath, cfg),
+		withArgs("--stdin", "0001-fix.patch"),
 		withNoError(t),
 		withStats(t, map[stats.Type]int{
-			stats.Traversed: 2,
-			stats.Matched:   2,
-			stats.Formatted: 0,
-			stats.Changed:   0,
+			stats.Traversed: 1,
+			stats.Matched:   0,
+		}),
+		withStdout(func(out []byte) {
+			as.Equal(contents, string(out))
 		}),
 	)
 
+	// a pattern with a directory is anchored to the tree root, while one without floats to match at any depth
+	test.WriteConfig(t, configPath, &config.Config{
+		FormatterConfigs: map[string]*config.Formatter{
+			"anchored": {
+				Command:  "test-fmt-append",
+				Options:  []string{"anchored"},
+				Includes: []string{"patches/*.patch"},
+			},
+			"floating": {
+				Command:  "test-fmt-append",
+				Options:  []string{"floating"},
+				Includes: []string{"*.patch"},
+			},
+		},
+	})
+
+	os.Stdin = test.TempFile(t, "", "stdin", &contents)
+
 	treefmt(t,
-		withArgs("go", "haskell"),
-		withConfig(configPath, cfg),
+		withArgs("--stdin", "patches/fix.patch"),
 		withNoError(t),
-		withStats(t, map[stats.Type]int{
-			stats.Traversed: 9,
-			stats.Matched:   9,
-			stats.Formatted: 0,
-			stats.Changed:   0,
+		withStdout(func(out []byte) {
+			as.Equal(contents+"anchored\nfloating\n", string(out))
 		}),
 	)
 
+	os.Stdin = test.TempFile(t, "", "stdin", &contents)
+
 	treefmt(t,
-		withArgs("-C", tempDir, "go", "haskell", "ruby"),
-		withConfig(configPath, cfg),
+		withArgs("--stdin", "vendor/patches/fix.patch"),
 		withNoError(t),
-		withStats(t, map[stats.Type]int{
-			stats.Traversed: 10,
-			stats.Matched:   10,
-			stats.Formatted: 0,
-			stats.Changed:   0,
+		withStdout(func(out []byte) {
+			as.Equal(contents+"floating\n", string(out))
 		}),
 	)
+}
 
-	// try with a bad path
-	treefmt(t,
-		withArgs("-C", tempDir, "haskell", "foo"),
-		withConfig(configPath, cfg),
-		withError(func(as *require.Assertions, err error) {
-			as.ErrorContains(err, "path foo not found")
-		}),
-	)
+func TestFilenamesStdinAndStaged(t *testing.T) {
+	as := require.New(t)
+
+	tempDir := test.TempExamples(t)
+	configPath := filepath.Join(tempDir, "treefmt.toml")
+
+	test.ChangeWorkDir(t, tempDir)
+
+	// capture current stdin and replace it on test cleanup
+	prevStdIn := os.Stdin
+
+	t.Cleanup(func() {
+		os.Stdin = prevStdIn
+	})
+
+	test.WriteConfig(t, configPath, &config.Config{
+		FormatterConfigs: map[string]*config.Formatter{
+			"docker": {
+				Command:   "test-fmt-append",
+				Options:   []string{"formatted"},
+				Filenames: []string{"Dockerfile"},
+			},
+		},
+	})
+
+	contents := "FROM scratch\n"
 
-	// try with a path not in the git index
-	_, err := os.Create(filepath.Join(tempDir, "foo.txt"))
-	as.NoError(err)
+	// filenames match the provided path, not the temporary file standing in for it
+	os.Stdin = test.TempFile(t, "", "stdin", &contents)
 
 	treefmt(t,
-		withArgs("haskell", "foo.txt", "-vv"),
-		withConfig(configPath, cfg),
+		withArgs("--stdin", "services/api/Dockerfile"),
 		withNoError(t),
 		withStats(t, map[stats.Type]int{
-			stats.Traversed: 8,
-			stats.Matched:   8,
-			stats.Formatted: 1, // we only format foo.txt, which is new to the cache
-			stats.Changed:   0,
+			stats.Traversed: 1,
+			stats.Matched:   1,
+		}),
+		withStdout(func(out []byte) {
+			as.Equal(contents+"formatted\n", string(out))
 		}),
 	)
 
+	os.Stdin = test.TempFile(t, "", "stdin", &contents)
+
 	treefmt(t,
-		withArgs("go", "foo.txt"),
-		withConfig(configPath, cfg),
+		withArgs("--stdin", "Dockerfile.dev"),
 		withNoError(t),
 		withStats(t, map[stats.Type]int{
-			stats.Traversed: 3,
-			stats.Matched:   3,
-			stats.Formatted: 0,
-			stats.Changed:   0,
+			stats.Traversed: 1,
+			stats.Matched:   0,
+		}),
+		withStdout(func(out []byte) {
+			as.Equal(contents, string(out))
 		}),
 	)
 
+	// and the path in the index when formatting staged content
+	dockerfile := filepath.Join(tempDir, "Dockerfile")
+	as.NoError(os.WriteFile(dockerfile, []byte(contents), 0o600))
+
+	git := func(args ...string) string {
+		out, err := exec.Command("git", args...).Output()
+		as.NoError(err, "failed to run git %v", args)
+
+		return string(out)
+	}
+
+	git("init")
+	git("add", "Dockerfile", "python/main.py")
+
 	treefmt(t,
-		withArgs("foo.txt"),
-		withConfig(configPath, cfg),
+		withArgs("--staged"),
 		withNoError(t),
 		withStats(t, map[stats.Type]int{
-			stats.Traversed: 1,
+			stats.Traversed: 2,
 			stats.Matched:   1,
-			stats.Formatted: 0,
-			stats.Changed:   0,
+			stats.Formatted: 1,
+			stats.Changed:   1,
 		}),
 	)
+
+	as.Equal(contents+"formatted\n", git("show", ":Dockerfile"))
 }
 
-func TestPathsArg(t *testing.T) {
+func TestSequential(t *testing.T) {
 	as := require.New(t)
 
-	// capture current cwd, so we can replace it after the test is finished
-	cwd, err := os.Getwd()
-	as.NoError(err)
+	tempDir := test.TempExamples(t)
+	configPath := filepath.Join(tempDir, "treefmt.toml")
 
-	t.Cleanup(func() {
-		// return to the previous working directory
-		as.NoError(os.Chdir(cwd))
-	})
+	test.ChangeWorkDir(t, tempDir)
 
-	// create a project root under a temp dir to verify behaviour with files inside the temp dir, but outside the
-	// project root
-	tempDir := t.TempDir()
-	treeRoot := filepath.Join(tempDir, "tree-root")
+	// each formatter records its name in a log file outside the tree root when it is applied
+	logPath := filepath.Join(t.TempDir(), "order.log")
 
-	test.TempExamplesInDir(t, treeRoot)
+	formatters := make(map[string]*config.Formatter)
+	for _, name := range []string{"d", "b", "c", "a"} {
+		formatters[name] = &config.Formatter{
+			Command: `echo "$0" >> "$1"`,
+			Options: []string{logPath},
+			Shell:   true,
+		}
+	}
 
-	configPath := filepath.Join(treeRoot, "/treefmt.toml")
+	// each formatter matches different files, so they are applied in separate batches
+	formatters["a"].Includes = []string{"*.py"}
+	formatters["b"].Includes = []string{"*.hs"}
+	formatters["c"].Includes = []string{"*.go"}
+	formatters["d"].Includes = []string{"*.rs"}
 
-	// create a file outside the treeRoot
-	externalFile, err := os.Create(filepath.Join(tempDir, "outside_tree.go"))
+	treefmt(t,
+		withConfig(configPath, &config.Config{FormatterConfigs: formatters}),
+		withArgs("--sequential"),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 33,
+			stats.Matched:   10,
+		}),
+	)
+
+	// batches are applied one at a time, ordered by the sequence of formatters they require
+	out, err := os.ReadFile(logPath)
 	as.NoError(err)
+	as.Equal("a\nb\nc\nd\n", string(out))
+}
 
-	// change working directory to project root
-	as.NoError(os.Chdir(treeRoot))
+func TestSortFiles(t *testing.T) {
+	as := require.New(t)
+
+	tempDir := test.TempExamples(t)
+	configPath := filepath.Join(tempDir, "treefmt.toml")
+
+	test.ChangeWorkDir(t, tempDir)
+
+	// the formatter records the paths it is given in a log file outside the tree root, one invocation at a time
+	logPath := filepath.Join(t.TempDir(), "paths.log")
 
-	// basic config
 	cfg := &config.Config{
 		FormatterConfigs: map[string]*config.Formatter{
-			"echo": {
-				Command:  "echo",
+			"record": {
+				Command:  `log="$1"; shift; printf '%s\n' "$@" >> "$log"; echo -- >> "$log"`,
+				Options:  []string{logPath},
+				Shell:    true,
 				Includes: []string{"*"},
 			},
 		},
@@ -1544,164 +5558,190 @@ func TestPathsArg(t *testing.T) {
 
 	test.WriteConfig(t, configPath, cfg)
 
-	// without any path args
+	// every file in the tree, sorted by its path relative to the tree root
+	var expected []string
+
+	as.NoError(filepath.WalkDir(tempDir, func(path string, entry fs.DirEntry, err error) error {
+		if err != nil || !entry.Type().IsRegular() {
+			return err
+		}
+
+		relPath, err := filepath.Rel(tempDir, path)
+		expected = append(expected, relPath)
+
+		return err
+	}))
+
+	slices.Sort(expected)
+
 	treefmt(t,
+		withArgs("--sort-files", "--sequential", "--batch-size", "5"),
 		withNoError(t),
 		withStats(t, map[stats.Type]int{
 			stats.Traversed: 33,
 			stats.Matched:   33,
 			stats.Formatted: 33,
-			stats.Changed:   0,
 		}),
 	)
 
-	// specify some explicit paths
+	out, err := os.ReadFile(logPath)
+	as.NoError(err)
+
+	invocations := strings.Split(strings.TrimSuffix(string(out), "--\n"), "--\n")
+
+	// the sorted files are split into batches of the given size, which are applied in order
+	var actual []string
+
+	for idx, invocation := range invocations {
+		paths := strings.Split(strings.TrimSuffix(invocation, "\n"), "\n")
+		if idx < len(invocations)-1 {
+			as.Len(paths, 5)
+		}
+
+		actual = append(actual, paths...)
+	}
+
+	as.Len(invocations, 7)
+	as.Equal(expected, actual)
+}
+
+func TestDeadline(t *testing.T) {
+	tempDir := test.TempExamples(t)
+	configPath := filepath.Join(tempDir, "treefmt.toml")
+
+	test.ChangeWorkDir(t, tempDir)
+
+	// the batches are applied one at a time, in the order of the formatters they require, with the first taking
+	// longer than the deadline
+	cfg := &config.Config{
+		FormatterConfigs: map[string]*config.Formatter{
+			"a": {
+				Command:  "sleep 2",
+				Shell:    true,
+				Includes: []string{"*.py"},
+			},
+			"b": {
+				Command:  "echo",
+				Includes: []string{"*.hs", "*.go", "*.rs"},
+			},
+		},
+	}
+
+	// the first batch finishes, but the rest are left for the next run
 	treefmt(t,
-		withArgs("elm/elm.json", "haskell/Nested/Foo.hs"),
-		withNoError(t),
+		withConfig(configPath, cfg),
+		withArgs("--sequential", "--deadline", "1"),
+		withError(func(as *require.Assertions, err error) {
+			as.ErrorIs(err, format.ErrDeadlineReached)
+			as.ErrorContains(err, "8 file(s) were left unformatted")
+		}),
 		withStats(t, map[stats.Type]int{
-			stats.Traversed: 2,
-			stats.Matched:   2,
-			stats.Formatted: 0,
-			stats.Changed:   0,
+			stats.Traversed: 33,
+			stats.Matched:   10,
+			stats.Formatted: 2,
 		}),
 	)
 
-	// specify an absolute path
-	absoluteInternalPath, err := filepath.Abs("elm/elm.json")
-	as.NoError(err)
-
+	// running again continues where we left off
 	treefmt(t,
-		withArgs(absoluteInternalPath),
+		withArgs("--sequential"),
 		withNoError(t),
 		withStats(t, map[stats.Type]int{
-			stats.Traversed: 1,
-			stats.Matched:   1,
-			stats.Formatted: 0,
-			stats.Changed:   0,
+			stats.Traversed: 33,
+			stats.Matched:   10,
+			stats.Formatted: 8,
 		}),
 	)
 
-	// specify a bad path
+	// the deadline must not be negative
 	treefmt(t,
-		withArgs("elm/elm.json", "haskell/Nested/Bar.hs"),
+		withArgs("--deadline", "-1"),
 		withError(func(as *require.Assertions, err error) {
-			as.ErrorContains(err, "path haskell/Nested/Bar.hs not found")
+			as.ErrorContains(err, "deadline must not be negative")
 		}),
 	)
+}
 
-	// specify an absolute path outside the tree root
-	absoluteExternalPath, err := filepath.Abs(externalFile.Name())
-	as.NoError(err)
-	as.FileExists(absoluteExternalPath, "external file must exist")
+func TestSingleThreaded(t *testing.T) {
+	tempDir := test.TempExamples(t)
+	configPath := filepath.Join(tempDir, "treefmt.toml")
 
-	treefmt(t,
-		withArgs(absoluteExternalPath),
-		withError(func(as *require.Assertions, err error) {
-			as.ErrorContains(err, fmt.Sprintf("path %s not inside the tree root", absoluteExternalPath))
-		}),
-	)
+	test.ChangeWorkDir(t, tempDir)
 
-	// specify a relative path outside the tree root
-	relativeExternalPath := "../outside_tree.go"
-	as.FileExists(relativeExternalPath, "exernal file must exist")
+	// each formatter fails if the other is running at the same time
+	lock := fmt.Sprintf("mkdir %s || exit 1; sleep 1; rmdir %[1]s", filepath.Join(t.TempDir(), "lock"))
+
+	cfg := &config.Config{
+		FormatterConfigs: map[string]*config.Formatter{
+			"a": {
+				Command:        lock,
+				Shell:          true,
+				Includes:       []string{"*.py"},
+				SingleThreaded: true,
+			},
+			"b": {
+				Command:  lock,
+				Shell:    true,
+				Includes: []string{"*.go"},
+			},
+		},
+	}
 
 	treefmt(t,
-		withArgs(relativeExternalPath),
-		withError(func(as *require.Assertions, err error) {
-			as.ErrorContains(err, fmt.Sprintf("path %s not inside the tree root", relativeExternalPath))
+		withConfig(configPath, cfg),
+		withNoError(t),
+		withStats(t, map[stats.Type]int{
+			stats.Traversed: 33,
+			stats.Matched:   3,
+			stats.Formatted: 3,
 		}),
 	)
 }
 
-func TestStdin(t *testing.T) {
-	as := require.New(t)
+func TestIgnoreExitCodes(t *testing.T) {
 	tempDir := test.TempExamples(t)
+	configPath := filepath.Join(tempDir, "treefmt.toml")
 
 	test.ChangeWorkDir(t, tempDir)
 
-	// capture current stdin and replace it on test cleanup
-	prevStdIn := os.Stdin
-
-	t.Cleanup(func() {
-		os.Stdin = prevStdIn
-	})
-
-	// omit the required filename parameter
-	contents := `{ foo, ... }: "hello"`
-	os.Stdin = test.TempFile(t, "", "stdin", &contents)
-
-	// for convenience so we don't have to specify it in the args
-	t.Setenv("TREEFMT_ALLOW_MISSING_FORMATTER", "true")
+	// formats the files, then reports having found issues through its exit code
+	cfg := &config.Config{
+		FormatterConfigs: map[string]*config.Formatter{
+			"lint": {
+				Command:  `for f in "$@"; do echo linted >> "$f"; done; exit 3`,
+				Shell:    true,
+				Includes: []string{"*.py"},
+			},
+		},
+	}
 
-	// we get an error about the missing filename parameter.
 	treefmt(t,
-		withArgs("--stdin"),
+		withConfig(configPath, cfg),
 		withError(func(as *require.Assertions, err error) {
-			as.EqualError(err, "exactly one path should be specified when using the --stdin flag")
-		}),
-		withStderr(func(out []byte) {
-			as.Equal("Error: exactly one path should be specified when using the --stdin flag\n", string(out))
+			as.ErrorIs(err, format.ErrFormattingFailures)
 		}),
 	)
 
-	// now pass along the filename parameter
-	os.Stdin = test.TempFile(t, "", "stdin", &contents)
+	cfg.FormatterConfigs["lint"].IgnoreExitCodes = []int{1, 3}
 
+	// the files are cached as formatted, so they are not formatted again by the next run
 	treefmt(t,
-		withArgs("--stdin", "test.nix"),
+		withConfig(configPath, cfg),
 		withNoError(t),
 		withStats(t, map[stats.Type]int{
-			stats.Traversed: 1,
-			stats.Matched:   1,
-			stats.Formatted: 1,
-			stats.Changed:   1,
-		}),
-		withStdout(func(out []byte) {
-			as.Equal(`{ ...}: "hello"
-`, string(out))
-		}),
-	)
-
-	// the nix formatters should have reduced the example to the following
-
-	// try a file that's outside of the project root
-	os.Stdin = test.TempFile(t, "", "stdin", &contents)
-
-	treefmt(t,
-		withArgs("--stdin", "../test.nix"),
-		withError(func(as *require.Assertions, err error) {
-			as.ErrorContains(err, "path ../test.nix not inside the tree root "+tempDir)
-		}),
-		withStderr(func(out []byte) {
-			as.Contains(string(out), "Error: path ../test.nix not inside the tree root")
+			stats.Matched:   2,
+			stats.Formatted: 2,
+			stats.Changed:   2,
 		}),
 	)
 
-	// try some markdown instead
-	contents = `
-| col1 | col2 |
-| ---- | ---- |
-| nice | fits |
-| oh no! | it's ugly |
-`
-	os.Stdin = test.TempFile(t, "", "stdin", &contents)
-
 	treefmt(t,
-		withArgs("--stdin", "test.md"),
+		withConfig(configPath, cfg),
 		withNoError(t),
 		withStats(t, map[stats.Type]int{
-			stats.Traversed: 1,
-			stats.Matched:   1,
-			stats.Formatted: 1,
-			stats.Changed:   1,
-		}),
-		withStdout(func(out []byte) {
-			as.Equal(`| col1   | col2      |
-| ------ | --------- |
-| nice   | fits      |
-| oh no! | it's ugly |
-`, string(out))
+			stats.Matched:   2,
+			stats.Formatted: 0,
+			stats.CacheHits: 2,
 		}),
 	)
 }
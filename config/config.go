@@ -1,35 +1,115 @@
 package config
 
 import (
+	"errors"
 	"fmt"
+	"maps"
 	"os"
 	"path/filepath"
 	"regexp"
+	"slices"
 	"strings"
 
 	"github.com/numtide/treefmt/v2/walk"
+	"github.com/pelletier/go-toml/v2"
+	"github.com/pelletier/go-toml/v2/unstable"
 	"github.com/spf13/pflag"
 	"github.com/spf13/viper"
 )
 
+const (
+	// CacheModeReadWrite reads the cache and updates it with the outcome of formatting.
+	CacheModeReadWrite = "read-write"
+	// CacheModeReadOnly reads the cache but never writes to it.
+	CacheModeReadOnly = "read-only"
+
+	// CacheDirXDG stores the cache within the user's XDG cache directory.
+	CacheDirXDG = "xdg"
+	// CacheDirRepo stores the cache within RepoCacheDir in the tree root.
+	CacheDirRepo = "repo"
+	// RepoCacheDir is the directory, relative to the tree root, in which the cache is stored with CacheDirRepo.
+	RepoCacheDir = walk.RepoCacheDir
+
+	// ColorAuto colors log output when stderr is a terminal, honouring NO_COLOR and CLICOLOR_FORCE.
+	ColorAuto = "auto"
+	// ColorAlways colors log output regardless of where it is written.
+	ColorAlways = "always"
+	// ColorNever disables colors in log output.
+	ColorNever = "never"
+)
+
 // Config is used to represent the list of configured Formatters.
 type Config struct {
-	AllowMissingFormatter bool     `mapstructure:"allow-missing-formatter" toml:"allow-missing-formatter,omitempty"`
-	CI                    bool     `mapstructure:"ci"                      toml:"-"` // not allowed in config
-	ClearCache            bool     `mapstructure:"clear-cache"             toml:"-"` // not allowed in config
-	CPUProfile            string   `mapstructure:"cpu-profile"             toml:"cpu-profile,omitempty"`
-	Excludes              []string `mapstructure:"excludes"                toml:"excludes,omitempty"`
-	FailOnChange          bool     `mapstructure:"fail-on-change"          toml:"fail-on-change,omitempty"`
-	Formatters            []string `mapstructure:"formatters"              toml:"formatters,omitempty"`
-	NoCache               bool     `mapstructure:"no-cache"                toml:"-"` // not allowed in config
-	OnUnmatched           string   `mapstructure:"on-unmatched"            toml:"on-unmatched,omitempty"`
-	Quiet                 bool     `mapstructure:"quiet"                   toml:"-"` // not allowed in config
-	TreeRoot              string   `mapstructure:"tree-root"               toml:"tree-root,omitempty"`
-	TreeRootFile          string   `mapstructure:"tree-root-file"          toml:"tree-root-file,omitempty"`
-	Verbose               uint8    `mapstructure:"verbose"                 toml:"verbose,omitempty"`
-	Walk                  string   `mapstructure:"walk"                    toml:"walk,omitempty"`
-	WorkingDirectory      string   `mapstructure:"working-dir"             toml:"-"`
-	Stdin                 bool     `mapstructure:"stdin"                   toml:"-"` // not allowed in config
+	AllowCommands          []string `mapstructure:"allow-commands"            toml:"allow-commands,omitempty"`
+	AllowMissingFormatter  bool     `mapstructure:"allow-missing-formatter"   toml:"allow-missing-formatter,omitempty"`
+	AtomicWrites           bool     `mapstructure:"atomic-writes"             toml:"atomic-writes,omitempty"`
+	BatchSize              int      `mapstructure:"batch-size"                toml:"batch-size,omitempty"`
+	BenchWalk              int      `mapstructure:"bench-walk"                toml:"-"` // not allowed in config
+	Cache                  bool     `mapstructure:"cache"                     toml:"cache,omitempty"`
+	CacheDir               string   `mapstructure:"cache-dir"                 toml:"cache-dir,omitempty"`
+	CacheMode              string   `mapstructure:"cache-mode"                toml:"cache-mode,omitempty"`
+	CaseInsensitive        bool     `mapstructure:"case-insensitive"          toml:"case-insensitive,omitempty"`
+	ChangedFiles           bool     `mapstructure:"changed-files"             toml:"-"` // not allowed in config
+	CI                     bool     `mapstructure:"ci"                        toml:"-"` // not allowed in config
+	ClearCache             bool     `mapstructure:"clear-cache"               toml:"-"` // not allowed in config
+	Color                  string   `mapstructure:"color"                     toml:"color,omitempty"`
+	ConfigOrder            bool     `mapstructure:"config-order"              toml:"config-order,omitempty"`
+	ConfigProfile          string   `mapstructure:"config-profile"            toml:"-"` // not allowed in config
+	CPUProfile             string   `mapstructure:"cpu-profile"               toml:"cpu-profile,omitempty"`
+	Deadline               int      `mapstructure:"deadline"                  toml:"-"` // not allowed in config
+	Diff                   bool     `mapstructure:"diff"                      toml:"diff,omitempty"`
+	DumpMatches            bool     `mapstructure:"dump-matches"              toml:"-"` // not allowed in config
+	Excludes               []string `mapstructure:"excludes"                  toml:"excludes,omitempty"`
+	ExcludesFrom           string   `mapstructure:"excludes-from"             toml:"-"` // not allowed in config
+	Explain                string   `mapstructure:"explain"                   toml:"-"` // not allowed in config
+	FailOnChange           bool     `mapstructure:"fail-on-change"            toml:"fail-on-change,omitempty"`
+	FailOnChangeFormatters []string `mapstructure:"fail-on-change-formatters" toml:"fail-on-change-formatters,omitempty"`
+	Force                  []string `mapstructure:"force"                     toml:"-"` // not allowed in config
+	FormatterArgs          []string `mapstructure:"formatter-arg"             toml:"-"` // not allowed in config
+	Formatters             []string `mapstructure:"formatters"                toml:"formatters,omitempty"`
+	FormattersDir          string   `mapstructure:"formatters-dir"            toml:"formatters-dir,omitempty"`
+	HooksAlways            bool     `mapstructure:"hooks-always"              toml:"hooks-always,omitempty"`
+	IgnoreExtensionCase    bool     `mapstructure:"ignore-extension-case"     toml:"ignore-extension-case,omitempty"`
+	Interactive            bool     `mapstructure:"interactive"               toml:"-"` // not allowed in config
+	Language               string   `mapstructure:"language"                  toml:"-"` // not allowed in config
+	ListFiles              bool     `mapstructure:"list-files"                toml:"-"` // not allowed in config
+	MaxDepth               int      `mapstructure:"max-depth"                 toml:"max-depth,omitempty"`
+	MaxFileSize            string   `mapstructure:"max-file-size"             toml:"max-file-size,omitempty"`
+	NoCache                bool     `mapstructure:"no-cache"                  toml:"-"` // not allowed in config
+	NoGlobalExcludes       bool     `mapstructure:"no-global-excludes"        toml:"-"` // not allowed in config
+	NoSummary              bool     `mapstructure:"no-summary"                toml:"-"` // not allowed in config
+	NoVCS                  bool     `mapstructure:"no-vcs"                    toml:"-"` // not allowed in config
+	OnUnmatched            string   `mapstructure:"on-unmatched"              toml:"on-unmatched,omitempty"`
+	OnlyChanged            bool     `mapstructure:"only-changed"              toml:"-"` // not allowed in config
+	Output                 string   `mapstructure:"output"                    toml:"-"` // not allowed in config
+	Porcelain              bool     `mapstructure:"porcelain"                 toml:"-"` // not allowed in config
+	PostHook               string   `mapstructure:"post-hook"                 toml:"post-hook,omitempty"`
+	PreHook                string   `mapstructure:"pre-hook"                  toml:"pre-hook,omitempty"`
+	PrintCachePath         bool     `mapstructure:"print-cache-path"          toml:"-"` // not allowed in config
+	Print0                 bool     `mapstructure:"print0"                    toml:"-"` // not allowed in config
+	Profile                bool     `mapstructure:"profile"                   toml:"-"` // not allowed in config
+	ProfileFile            string   `mapstructure:"profile-file"              toml:"profile-file,omitempty"`
+	Quiet                  bool     `mapstructure:"quiet"                     toml:"-"` // not allowed in config
+	RespectGitattributes   bool     `mapstructure:"respect-gitattributes"     toml:"respect-gitattributes,omitempty"`
+	SelfTest               bool     `mapstructure:"self-test"                 toml:"-"` // not allowed in config
+	Sequential             bool     `mapstructure:"sequential"                toml:"sequential,omitempty"`
+	Since                  string   `mapstructure:"since"                     toml:"-"` // not allowed in config
+	SortFiles              bool     `mapstructure:"sort-files"                toml:"sort-files,omitempty"`
+	StrictLoad             bool     `mapstructure:"strict-load"               toml:"strict-load,omitempty"`
+	StrictScope            bool     `mapstructure:"strict-scope"              toml:"strict-scope,omitempty"`
+	TreeRoot               string   `mapstructure:"tree-root"                 toml:"tree-root,omitempty"`
+	TreeRootFile           string   `mapstructure:"tree-root-file"            toml:"tree-root-file,omitempty"`
+	Validate               bool     `mapstructure:"validate"                  toml:"-"` // not allowed in config
+	Verbose                uint8    `mapstructure:"verbose"                   toml:"verbose,omitempty"`
+	Walk                   string   `mapstructure:"walk"                      toml:"walk,omitempty"`
+	Watch                  bool     `mapstructure:"watch"                     toml:"-"` // not allowed in config
+	WorkingDirectory       string   `mapstructure:"working-dir"               toml:"-"`
+	WriteOnlyOnChange      bool     `mapstructure:"write-only-on-change"      toml:"write-only-on-change,omitempty"`
+	Staged                 bool     `mapstructure:"staged"                    toml:"-"` // not allowed in config
+	Stdin                  bool     `mapstructure:"stdin"                     toml:"-"` // not allowed in config
+	StdinDetect            bool     `mapstructure:"stdin-detect"              toml:"-"` // not allowed in config
+	StdinJSON              bool     `mapstructure:"stdin-json"                toml:"-"` // not allowed in config
+	StdinReport            bool     `mapstructure:"stdin-report"              toml:"-"` // not allowed in config
 
 	FormatterConfigs map[string]*Formatter `mapstructure:"formatter" toml:"formatter,omitempty"`
 
@@ -42,14 +122,79 @@ type Config struct {
 type Formatter struct {
 	// Command is the command to invoke when applying this Formatter.
 	Command string `mapstructure:"command" toml:"command"`
+	// Description is an optional note on what this Formatter does or why it is set up the way it is. It is purely
+	// documentation, shown alongside the Formatter by --list-files and --self-test.
+	Description string `mapstructure:"description,omitempty" toml:"description,omitempty"`
 	// Options are an optional list of args to be passed to Command.
 	Options []string `mapstructure:"options,omitempty" toml:"options,omitempty"`
 	// Includes is a list of glob patterns used to determine whether this Formatter should be applied against a path.
 	Includes []string `mapstructure:"includes,omitempty" toml:"includes,omitempty"`
 	// Excludes is an optional list of glob patterns used to exclude certain files from this Formatter.
 	Excludes []string `mapstructure:"excludes,omitempty" toml:"excludes,omitempty"`
+	// Filenames is an optional list of base names, such as Dockerfile, which are matched exactly and case-sensitively
+	// to determine whether this Formatter should be applied against a path, in addition to Includes.
+	Filenames []string `mapstructure:"filenames,omitempty" toml:"filenames,omitempty"`
+	// IncludesFrom is an optional file, relative to the config file, listing glob patterns one per line which are
+	// appended to Includes. Blank lines and lines starting with '#' are ignored.
+	IncludesFrom string `mapstructure:"includes-from,omitempty" toml:"includes-from,omitempty"`
+	// ExcludesFrom is an optional file, relative to the config file, listing glob patterns one per line which are
+	// appended to Excludes. Blank lines and lines starting with '#' are ignored.
+	ExcludesFrom string `mapstructure:"excludes-from,omitempty" toml:"excludes-from,omitempty"`
 	// Indicates the order of precedence when executing this Formatter in a sequence of Formatters.
 	Priority int `mapstructure:"priority,omitempty" toml:"priority,omitempty"`
+	// BatchSize is an optional limit on the number of paths passed to a single invocation of Command.
+	// When 0, each batch scheduled by the global batch size is passed in a single invocation.
+	BatchSize int `mapstructure:"batch-size,omitempty" toml:"batch-size,omitempty"`
+	// Languages is an optional list of language ids, such as `python`, used to select this Formatter when formatting
+	// stdin with --language instead of matching a path against Includes.
+	Languages []string `mapstructure:"languages,omitempty" toml:"languages,omitempty"`
+	// Shell indicates Command is a shell snippet to be run with `sh -c`, rather than an executable.
+	// Options and paths are passed to the snippet as positional parameters, available via "$@".
+	Shell bool `mapstructure:"shell,omitempty" toml:"shell,omitempty"`
+	// CaseInsensitive overrides the top-level case-insensitive setting for Includes and Excludes when set.
+	CaseInsensitive *bool `mapstructure:"case-insensitive,omitempty" toml:"case-insensitive,omitempty"`
+	// IgnoreExtensionCase overrides the top-level ignore-extension-case setting for Includes and Excludes when set.
+	IgnoreExtensionCase *bool `mapstructure:"ignore-extension-case,omitempty" toml:"ignore-extension-case,omitempty"`
+	// IgnoreExitCodes is an optional list of non-zero exit codes of Command which are treated as success, for tools
+	// which report the issues they find through their exit code, even though they formatted the files.
+	IgnoreExitCodes []int `mapstructure:"ignore-exit-codes,omitempty" toml:"ignore-exit-codes,omitempty"`
+	// CaptureOutput is an optional file, relative to the tree root, to which the combined stdout and stderr of Command
+	// is written each time it is applied.
+	CaptureOutput string `mapstructure:"capture-output,omitempty" toml:"capture-output,omitempty"`
+	// ConfigFiles is an optional list of files, relative to the tree root, which Command reads its own config from,
+	// such as .prettierrc. Their sizes and modification times are part of the Formatter's signature, so changing one
+	// invalidates the cache for the files the Formatter matches.
+	ConfigFiles []string `mapstructure:"config-files,omitempty" toml:"config-files,omitempty"`
+	// MaxFileSize is an optional size, such as 512KB, above which files are not passed to Command. It only further
+	// restricts the top-level max-file-size, as files exceeding that are skipped entirely.
+	MaxFileSize string `mapstructure:"max-file-size,omitempty" toml:"max-file-size,omitempty"`
+	// MinVersion is an optional version, such as 1.2.0, which the first version number in the output of Command, run
+	// with VersionArgs, must be at least. The formatter fails to load otherwise, or is skipped with
+	// allow-missing-formatter.
+	MinVersion string `mapstructure:"min-version,omitempty" toml:"min-version,omitempty"`
+	// VersionArgs are the args passed to Command to print its version when checking MinVersion, defaulting to
+	// --version.
+	VersionArgs []string `mapstructure:"version-args,omitempty" toml:"version-args,omitempty"`
+	// Order is the position at which this Formatter is declared in the config file, which breaks ties in Priority
+	// when config-order is enabled. It is zero otherwise, leaving ties to be broken by name.
+	Order int `mapstructure:"-" toml:"-"`
+	// SingleThreaded indicates Command must not run at the same time as any other formatter, including another
+	// invocation of itself, e.g. because it uses a lot of memory or takes a global lock.
+	SingleThreaded bool `mapstructure:"single-threaded,omitempty" toml:"single-threaded,omitempty"`
+	// ReplaceIncludes, ReplaceExcludes and ReplaceOptions determine whether this definition replaces, rather than
+	// appends to, the Includes, Excludes and Options of an earlier definition of the same Formatter in formatters-dir.
+	ReplaceIncludes bool `mapstructure:"replace-includes,omitempty" toml:"replace-includes,omitempty"`
+	ReplaceExcludes bool `mapstructure:"replace-excludes,omitempty" toml:"replace-excludes,omitempty"`
+	ReplaceOptions  bool `mapstructure:"replace-options,omitempty"  toml:"replace-options,omitempty"`
+	// Profiles are optional overrides, by profile name, which are applied when running with --config-profile.
+	Profiles map[string]*FormatterProfile `mapstructure:"profiles,omitempty" toml:"profiles,omitempty"`
+}
+
+// FormatterProfile overrides the Command and Options of a Formatter when its profile has been selected.
+// Fields which are not set leave those of the Formatter as they are.
+type FormatterProfile struct {
+	Command string   `mapstructure:"command,omitempty" toml:"command,omitempty"`
+	Options []string `mapstructure:"options,omitempty" toml:"options,omitempty"`
 }
 
 // SetFlags appends our flags to the provided flag set.
@@ -57,10 +202,55 @@ type Formatter struct {
 // mapstructure tag.
 // We rely on a flag's default value being provided in the event the same value was not specified in the config file.
 func SetFlags(fs *pflag.FlagSet) {
+	fs.StringSlice(
+		"allow-commands", nil,
+		"Only allow formatters whose resolved executable has one of the given names, or paths for executables "+
+			"within the tree root. Defaults to allowing any command. (env $TREEFMT_ALLOW_COMMANDS)",
+	)
 	fs.Bool(
 		"allow-missing-formatter", false,
 		"Do not exit with error if a configured formatter is missing. (env $TREEFMT_ALLOW_MISSING_FORMATTER)",
 	)
+	fs.Bool(
+		"atomic-writes", false,
+		"Back up files before applying a formatter, restoring them if it fails so they are never left half "+
+			"written. (env $TREEFMT_ATOMIC_WRITES)",
+	)
+	fs.Int(
+		"batch-size", 1024,
+		"The maximum number of paths passed to a formatter in a single invocation. Use 0 to pass all matching "+
+			"paths at once. (env $TREEFMT_BATCH_SIZE)",
+	)
+	fs.Int(
+		"bench-walk", 0,
+		"Walk the tree the given number of times, matching files against the formatters, and print timing "+
+			"statistics. Nothing is formatted and the cache is not used. (env $TREEFMT_BENCH_WALK)",
+	)
+	fs.Bool(
+		"cache", true,
+		"Use the evaluation cache. Set to false in the config file to disable caching by default, which can be "+
+			"overridden with --cache. (env $TREEFMT_CACHE)",
+	)
+	fs.String(
+		"cache-dir", CacheDirXDG,
+		"Where the evaluation cache is stored. One of '"+CacheDirXDG+"', '"+CacheDirRepo+"' to use "+RepoCacheDir+
+			" in the tree root, or a directory path relative to the tree root. (env $TREEFMT_CACHE_DIR)",
+	)
+	fs.String(
+		"cache-mode", CacheModeReadWrite,
+		"How the evaluation cache is used. One of '"+CacheModeReadWrite+"' or '"+CacheModeReadOnly+"', which reads "+
+			"an existing cache but never writes to it. (env $TREEFMT_CACHE_MODE)",
+	)
+	fs.Bool(
+		"case-insensitive", false,
+		"Match include and exclude patterns regardless of case, e.g. so that *.PY matches main.py. Can be "+
+			"overridden per formatter. (env $TREEFMT_CASE_INSENSITIVE)",
+	)
+	fs.Bool(
+		"changed-files", false,
+		"Print a sorted list of the files changed by formatting to stdout, one per line, relative to the tree "+
+			"root. (env $TREEFMT_CHANGED_FILES)",
+	)
 	fs.Bool(
 		"ci", false,
 		"Runs treefmt in a CI mode, enabling --no-cache, --fail-on-change and adjusting some other settings "+
@@ -70,35 +260,242 @@ func SetFlags(fs *pflag.FlagSet) {
 		"clear-cache", "c", false,
 		"Reset the evaluation cache. Use in case the cache is not precise enough. (env $TREEFMT_CLEAR_CACHE)",
 	)
+	fs.String(
+		"color", ColorAuto,
+		"When to color log output. Possible values are <auto|always|never>, where auto honours NO_COLOR and "+
+			"CLICOLOR_FORCE. (env $TREEFMT_COLOR)",
+	)
+	fs.Bool(
+		"config-order", false,
+		"Apply formatters with the same priority in the order they are declared in the config file, rather than "+
+			"in order of their names. (env $TREEFMT_CONFIG_ORDER)",
+	)
+	fs.String(
+		"config-profile", "",
+		"Apply the overrides of the given profile, defined by formatters in [formatter.<name>.profiles.<profile>]. "+
+			"(env $TREEFMT_CONFIG_PROFILE)",
+	)
 	fs.String(
 		"cpu-profile", "",
 		"The file into which a cpu profile will be written. (env $TREEFMT_CPU_PROFILE)",
 	)
+	fs.Int(
+		"deadline", 0,
+		"Stop starting new formatter batches once the given number of seconds has elapsed, letting those in flight "+
+			"finish. Files left unformatted are reported, and formatted by the next run. (env $TREEFMT_DEADLINE)",
+	)
+	fs.Bool(
+		"diff", false,
+		"Print a unified diff of each file changed by formatting to stdout, e.g. to explain a failure with "+
+			"--fail-on-change. (env $TREEFMT_DIFF)",
+	)
+	fs.Bool(
+		"dump-matches", false,
+		"Print a JSON object mapping each formatter to the paths of the files it would be passed, without "+
+			"formatting anything. (env $TREEFMT_DUMP_MATCHES)",
+	)
 	fs.StringSlice(
 		"excludes", nil,
 		"Exclude files or directories matching the specified globs. (env $TREEFMT_EXCLUDES)",
 	)
+	fs.String(
+		"excludes-from", "",
+		"Exclude files or directories matching the globs listed in the given file, one per line, in addition to "+
+			"excludes. (env $TREEFMT_EXCLUDES_FROM)",
+	)
+	fs.String(
+		"explain", "",
+		"Explain whether the given path would be formatted, and why, without formatting anything. "+
+			"(env $TREEFMT_EXPLAIN)",
+	)
 	fs.Bool(
 		"fail-on-change", false,
 		"Exit with error if any changes were made. Useful for CI. (env $TREEFMT_FAIL_ON_CHANGE)",
 	)
+	fs.StringSlice(
+		"fail-on-change-formatters", nil,
+		"Exit with error only if files were changed by the specified formatters. Other formatters still format "+
+			"files without failing. (env $TREEFMT_FAIL_ON_CHANGE_FORMATTERS)",
+	)
+	fs.StringSlice(
+		"force", nil,
+		"Ignore the evaluation cache for files within the specified paths, formatting them even if they have not "+
+			"changed. (env $TREEFMT_FORCE)",
+	)
+	fs.StringArray(
+		"formatter-arg", nil,
+		"Append an argument to the options of a formatter for this run only, given as <formatter>=<arg>. "+
+			"Can be repeated. (env $TREEFMT_FORMATTER_ARG)",
+	)
 	fs.StringSliceP(
 		"formatters", "f", nil,
 		"Specify formatters to apply. Defaults to all configured formatters. (env $TREEFMT_FORMATTERS)",
 	)
+	fs.String(
+		"formatters-dir", "",
+		"A directory of *.toml files containing additional [formatter.<name>] definitions, relative to the config "+
+			"file. (env $TREEFMT_FORMATTERS_DIR)",
+	)
+	fs.Bool(
+		"hooks-always", false,
+		"Run the pre and post hooks even when no files are formatted. (env $TREEFMT_HOOKS_ALWAYS)",
+	)
+	fs.Bool(
+		"ignore-extension-case", false,
+		"Match the final extension of each path regardless of case, e.g. so that *.py matches FILE.PY, while the "+
+			"rest of the path is matched case-sensitively. Can be overridden per formatter. "+
+			"(env $TREEFMT_IGNORE_EXTENSION_CASE)",
+	)
+	fs.Bool(
+		"interactive", false,
+		"Format copies of the files first, then summarise the changes and ask before applying them. Requires stdin "+
+			"to be a terminal. (env $TREEFMT_INTERACTIVE)",
+	)
+	fs.String(
+		"language", "",
+		"When using --stdin, select formatters by the given language id, matched against each formatter's "+
+			"languages, instead of by path. The path argument becomes optional. (env $TREEFMT_LANGUAGE)",
+	)
+	fs.Bool(
+		"list-files", false,
+		"Print every file which would be passed to each formatter, grouped by formatter, without formatting "+
+			"anything. (env $TREEFMT_LIST_FILES)",
+	)
+	fs.Int(
+		"max-depth", 0,
+		"Limit how deep treefmt descends into directories, with 1 meaning only the files directly within the "+
+			"tree root or given directories. Defaults to no limit. (env $TREEFMT_MAX_DEPTH)",
+	)
+	fs.String(
+		"max-file-size", "",
+		"Skip files larger than the given size, such as 1024, 512KB or 5MiB. Defaults to no limit. "+
+			"(env $TREEFMT_MAX_FILE_SIZE)",
+	)
 	fs.Bool(
 		"no-cache", false,
 		"Ignore the evaluation cache entirely. Useful for CI. (env $TREEFMT_NO_CACHE)",
 	)
+	fs.Bool(
+		"no-global-excludes", false,
+		"Ignore the global excludes, e.g. to find out with --explain whether they are why a file is skipped. "+
+			"Formatter excludes still apply. (env $TREEFMT_NO_GLOBAL_EXCLUDES)",
+	)
+	fs.Bool(
+		"no-summary", false,
+		"Do not print the summary of traversed, matched and formatted files. (env $TREEFMT_NO_SUMMARY)",
+	)
+	fs.Bool(
+		"no-vcs", false,
+		"Ignore any version control, using the directory containing the config file as the tree root and walking "+
+			"the filesystem. (env $TREEFMT_NO_VCS)",
+	)
 	fs.StringP(
 		"on-unmatched", "u", "info",
 		"Log paths that did not match any formatters at the specified log level. Possible values are "+
 			"<debug|info|warn|error|fatal>. (env $TREEFMT_ON_UNMATCHED)",
 	)
+	fs.Bool(
+		"only-changed", false,
+		"Only read files with uncommitted changes, whether staged or not, and untracked files which are not "+
+			"ignored, instead of walking the whole tree. (env $TREEFMT_ONLY_CHANGED)",
+	)
+	fs.String(
+		"output", "",
+		"Write the formatted content to the given file instead of stdout when using --stdin. The file is "+
+			"replaced atomically.",
+	)
+	fs.Bool(
+		"porcelain", false,
+		"Print a tab separated line of status, formatters and path to stdout for each file changed by formatting, "+
+			"in place of the summary. The format is stable across releases. (env $TREEFMT_PORCELAIN)",
+	)
+	fs.String(
+		"post-hook", "",
+		"A shell command run in the tree root once formatting has completed successfully, provided at least one "+
+			"file was formatted. (env $TREEFMT_POST_HOOK)",
+	)
+	fs.String(
+		"pre-hook", "",
+		"A shell command run in the tree root before the first formatter is applied. (env $TREEFMT_PRE_HOOK)",
+	)
+	fs.Bool(
+		"print-cache-path", false,
+		"Print the absolute path of the cache directory, followed by the cache file for the current tree root, "+
+			"then exit. Useful for caching it in CI. (env $TREEFMT_PRINT_CACHE_PATH)",
+	)
+	fs.Bool(
+		"print0", false,
+		"Like --changed-files, but each path is terminated by a NUL character instead of a newline, for use with "+
+			"xargs -0. (env $TREEFMT_PRINT0)",
+	)
+	fs.Bool(
+		"profile", false,
+		"Print how long each phase of execution took. (env $TREEFMT_PROFILE)",
+	)
+	fs.String(
+		"profile-file", "",
+		"The file into which a JSON report of how long each phase of execution took will be written. "+
+			"(env $TREEFMT_PROFILE_FILE)",
+	)
+	fs.Bool(
+		"respect-gitattributes", false,
+		"Skip files marked with the linguist-generated attribute in the .gitattributes file at the tree root. "+
+			"(env $TREEFMT_RESPECT_GITATTRIBUTES)",
+	)
+	fs.Bool(
+		"self-test", false,
+		"Check each formatter can be found, succeeds and is idempotent, by applying it twice to a copy of the smallest "+
+			"file it matches, then print the results. (env $TREEFMT_SELF_TEST)",
+	)
+	fs.Bool(
+		"sequential", false,
+		"Apply formatters to one batch of files at a time, in a deterministic order, so their output does not "+
+			"interleave. (env $TREEFMT_SEQUENTIAL)",
+	)
+	fs.String(
+		"since", "",
+		"Only read tracked files which differ from the given git ref, including uncommitted changes, instead of "+
+			"walking the whole tree. (env $TREEFMT_SINCE)",
+	)
+	fs.Bool(
+		"sort-files", false,
+		"Wait until every file has been read, then pass the paths to each formatter in sorted order, so each "+
+			"invocation is given the same files in the same order on every run. (env $TREEFMT_SORT_FILES)",
+	)
+	fs.Bool(
+		"staged", false,
+		"Format the content staged in the git index rather than the working tree, writing the result back into the "+
+			"index. Files without unstaged changes are updated in the working tree too. (env $TREEFMT_STAGED)",
+	)
 	fs.Bool(
 		"stdin", false,
 		"Format the context passed in via stdin.",
 	)
+	fs.Bool(
+		"stdin-detect", false,
+		"When using --stdin without a path or --language, guess the type of content from its shebang or structure. "+
+			"Content which cannot be identified is written out unchanged. (env $TREEFMT_STDIN_DETECT)",
+	)
+	fs.Bool(
+		"stdin-json", false,
+		"When using --stdin, read a JSON request of the form {\"filename\": ..., \"content\": ...} and write a JSON "+
+			"response with the formatted content, for use by editors. (env $TREEFMT_STDIN_JSON)",
+	)
+	fs.Bool(
+		"stdin-report", false,
+		"When using --stdin, print 'changed' or 'unchanged' to stderr once the formatted content has been written, "+
+			"in place of the summary. (env $TREEFMT_STDIN_REPORT)",
+	)
+	fs.Bool(
+		"strict-load", false,
+		"Stop at the first formatter, by name, which cannot be loaded and report only that one, rather than reporting "+
+			"every formatter which cannot be loaded. (env $TREEFMT_STRICT_LOAD)",
+	)
+	fs.Bool(
+		"strict-scope", false,
+		"Warn if a formatter modifies files in the tree root, or alongside the files it was given, which it was not "+
+			"given. Implies --sequential. (env $TREEFMT_STRICT_SCOPE)",
+	)
 	fs.String(
 		"tree-root", "",
 		"The root directory from which treefmt will start walking the filesystem (defaults to the directory "+
@@ -108,6 +505,11 @@ func SetFlags(fs *pflag.FlagSet) {
 		"tree-root-file", "",
 		"File to search for to find the tree root (if --tree-root is not passed). (env $TREEFMT_TREE_ROOT_FILE)",
 	)
+	fs.Bool(
+		"validate", false,
+		"Validate the config without formatting anything: resolve each formatter's command and compile all "+
+			"include and exclude globs. (env $TREEFMT_VALIDATE)",
+	)
 	fs.CountP(
 		"verbose", "v",
 		"Set the verbosity of logs e.g. -vv. (env $TREEFMT_VERBOSE)",
@@ -118,13 +520,23 @@ func SetFlags(fs *pflag.FlagSet) {
 	fs.String(
 		"walk", "auto",
 		"The method used to traverse the files within the tree root. Currently supports "+
-			"<auto|git|filesystem>. (env $TREEFMT_WALK)",
+			"<auto|git|filesystem|staged>, where staged is the same as --staged. (env $TREEFMT_WALK)",
+	)
+	fs.Bool(
+		"watch", false,
+		"Keep running after formatting, watching the tree root and formatting files as they change. "+
+			"(env $TREEFMT_WATCH)",
 	)
 	fs.StringP(
 		"working-dir", "C", ".",
 		"Run as if treefmt was started in the specified working directory instead of the current working "+
 			"directory. (env $TREEFMT_WORKING_DIR)",
 	)
+	fs.Bool(
+		"write-only-on-change", false,
+		"Apply formatters to temporary copies of files, only overwriting those whose content changed, so that "+
+			"files which are already formatted keep their modification time. (env $TREEFMT_WRITE_ONLY_ON_CHANGE)",
+	)
 }
 
 // NewViper creates a Viper instance pre-configured with the following options:
@@ -151,14 +563,178 @@ func NewViper() (*viper.Viper, error) {
 	return v, nil
 }
 
+// Read reads the config file at path into v.
+// If path is a project file such as pyproject.toml, the config is read from the table embedded within it.
+// If the file cannot be parsed, the returned error includes the line and column of the problem where possible.
+func Read(v *viper.Viper, path string) error {
+	if p, ok := lookupProjectFile(path); ok {
+		return readEmbedded(v, p, path)
+	}
+
+	v.SetConfigFile(path)
+
+	err := v.ReadInConfig()
+	if err == nil {
+		return nil
+	}
+
+	var parseErr viper.ConfigParseError
+	if !errors.As(err, &parseErr) {
+		return fmt.Errorf("failed to read %s: %w", path, err)
+	}
+
+	var decodeErr *toml.DecodeError
+	if errors.As(err, &decodeErr) {
+		row, col := decodeErr.Position()
+
+		return fmt.Errorf("failed to parse %s: line %d, column %d: %w", path, row, col, decodeErr)
+	}
+
+	return fmt.Errorf("failed to parse %s: %w", path, err)
+}
+
+// ReadFormattersDir merges the formatter definitions from each *.toml file within formatters-dir, if one has been
+// configured, into v.
+// Only [formatter.<name>] tables are read from these files, which are merged in order of their names, with a formatter
+// defined more than once being merged field by field, see mergeFormatter.
+func ReadFormattersDir(v *viper.Viper) error {
+	dir := v.GetString("formatters-dir")
+	if dir == "" {
+		return nil
+	}
+
+	// relative paths are resolved against the directory containing the config file
+	if !filepath.IsAbs(dir) {
+		dir = filepath.Join(filepath.Dir(v.ConfigFileUsed()), dir)
+	}
+
+	// a misspelled directory would otherwise quietly drop every formatter defined within it
+	if info, err := os.Stat(dir); err != nil {
+		return fmt.Errorf("could not find %s: %w", dir, err)
+	} else if !info.IsDir() {
+		return fmt.Errorf("formatters-dir %s is not a directory", dir)
+	}
+
+	paths, err := filepath.Glob(filepath.Join(dir, "*.toml"))
+	if err != nil {
+		return fmt.Errorf("failed to list formatter files in %s: %w", dir, err)
+	}
+
+	for _, path := range paths {
+		fragment := viper.New()
+
+		if err = Read(fragment, path); err != nil {
+			return err
+		}
+
+		formatters := fragment.GetStringMap("formatter")
+		defined := v.GetStringMap("formatter")
+
+		for name, value := range formatters {
+			parent, parentOk := defined[name].(map[string]any)
+			child, childOk := value.(map[string]any)
+
+			if parentOk && childOk {
+				formatters[name] = mergeFormatter(parent, child)
+			}
+		}
+
+		if err = v.MergeConfigMap(map[string]any{"formatter": formatters}); err != nil {
+			return fmt.Errorf("failed to merge formatter file %s: %w", path, err)
+		}
+	}
+
+	return nil
+}
+
+// mergeFormatter merges child, a later definition of a formatter, into parent.
+// Fields set by child override those of parent, except for includes, excludes and options, which are appended to
+// those of parent unless child sets replace-includes, replace-excludes or replace-options respectively.
+// Appended includes and excludes skip any patterns parent already has.
+func mergeFormatter(parent, child map[string]any) map[string]any {
+	merged := make(map[string]any, len(parent)+len(child))
+	maps.Copy(merged, parent)
+
+	for key, value := range child {
+		switch key {
+		case "includes", "excludes", "options":
+			if replace, _ := child["replace-"+key].(bool); replace {
+				merged[key] = value
+
+				continue
+			}
+
+			merged[key] = appendValues(merged[key], value, key != "options")
+		default:
+			merged[key] = value
+		}
+	}
+
+	return merged
+}
+
+// appendValues appends the values of next to those of prev, where either may be a list or a single value.
+// When dedup is true, values of next already present are skipped.
+func appendValues(prev, next any, dedup bool) []any {
+	toList := func(value any) []any {
+		switch value := value.(type) {
+		case nil:
+			return nil
+		case []any:
+			return value
+		default:
+			return []any{value}
+		}
+	}
+
+	result := slices.Clone(toList(prev))
+
+	for _, value := range toList(next) {
+		if dedup && slices.Contains(result, value) {
+			continue
+		}
+
+		result = append(result, value)
+	}
+
+	return result
+}
+
 // FromViper takes a viper instance and produces a Config instance.
 func FromViper(v *viper.Viper) (*Config, error) {
 	configReset := map[string]any{
-		"ci":          false,
-		"clear-cache": false,
-		"no-cache":    false,
-		"stdin":       false,
-		"working-dir": ".",
+		"bench-walk":         0,
+		"changed-files":      false,
+		"ci":                 false,
+		"clear-cache":        false,
+		"config-profile":     "",
+		"deadline":           0,
+		"dump-matches":       false,
+		"excludes-from":      "",
+		"explain":            "",
+		"force":              []string{},
+		"formatter-arg":      []string{},
+		"interactive":        false,
+		"language":           "",
+		"list-files":         false,
+		"no-cache":           false,
+		"no-global-excludes": false,
+		"no-vcs":             false,
+		"only-changed":       false,
+		"output":             "",
+		"porcelain":          false,
+		"print-cache-path":   false,
+		"print0":             false,
+		"self-test":          false,
+		"since":              "",
+		"staged":             false,
+		"stdin":              false,
+		"stdin-detect":       false,
+		"stdin-json":         false,
+		"stdin-report":       false,
+		"validate":           false,
+		"watch":              false,
+		"working-dir":        ".",
 	}
 
 	// reset certain values which are not allowed to be specified in the config file
@@ -166,6 +742,17 @@ func FromViper(v *viper.Viper) (*Config, error) {
 		return nil, fmt.Errorf("failed to overwrite config values: %w", err)
 	}
 
+	// formatter includes and excludes may be given as a single pattern instead of a list
+	// we normalise them here, otherwise viper would split the pattern on commas, breaking globs such as *.{js,ts}
+	for name := range v.GetStringMap("formatter") {
+		for _, field := range []string{"includes", "excludes"} {
+			key := fmt.Sprintf("formatter.%s.%s", name, field)
+			if pattern, ok := v.Get(key).(string); ok {
+				v.Set(key, []string{pattern})
+			}
+		}
+	}
+
 	// read config from viper
 	var err error
 
@@ -181,11 +768,50 @@ func FromViper(v *viper.Viper) (*Config, error) {
 		return nil, fmt.Errorf("failed to get absolute path for working directory: %w", err)
 	}
 
+	// resolve the stdin output file to an absolute path
+	if cfg.Output != "" {
+		if cfg.Output, err = filepath.Abs(cfg.Output); err != nil {
+			return nil, fmt.Errorf("failed to get absolute path for output: %w", err)
+		}
+	}
+
 	// if the stdin flag was passed, we force the stdin walk type
 	if cfg.Stdin {
 		cfg.Walk = walk.Stdin.String()
 	}
 
+	// likewise for the staged flag, with the staged walk type
+	if cfg.Staged {
+		cfg.Walk = walk.Staged.String()
+	}
+
+	// and selecting the staged walk type directly is the same as passing the staged flag
+	if walkType, err := walk.TypeString(cfg.Walk); err == nil && walkType == walk.Staged {
+		cfg.Staged = true
+	}
+
+	// without version control, the tree is everything beneath the config file, and is walked as plain files
+	if cfg.NoVCS {
+		if cfg.Staged {
+			return nil, errors.New("--no-vcs cannot be used with --staged")
+		}
+
+		// the tree root is always the directory containing the config file, wherever else it has been set
+		for _, key := range []string{"tree-root", "tree-root-file"} {
+			if v.IsSet(key) {
+				return nil, fmt.Errorf("--no-vcs cannot be used with %s, which is set in the config file or env", key)
+			}
+		}
+
+		cfg.TreeRoot = filepath.Dir(v.ConfigFileUsed())
+		cfg.TreeRootFile = ""
+		cfg.RespectGitattributes = false
+
+		if !cfg.Stdin {
+			cfg.Walk = walk.Filesystem.String()
+		}
+	}
+
 	// determine the tree root
 	if cfg.TreeRoot == "" {
 		// if none was specified, we first try with tree-root-file
@@ -206,25 +832,130 @@ func FromViper(v *viper.Viper) (*Config, error) {
 		return nil, fmt.Errorf("failed to get absolute path for tree root: %w", err)
 	}
 
+	if cfg.BatchSize < 0 {
+		return nil, fmt.Errorf("batch-size must not be negative, got %d", cfg.BatchSize)
+	}
+
+	if _, err = ParseSize(cfg.MaxFileSize); err != nil {
+		return nil, fmt.Errorf("max-file-size: %w", err)
+	}
+
+	if cfg.Deadline < 0 {
+		return nil, fmt.Errorf("deadline must not be negative, got %d", cfg.Deadline)
+	}
+
+	if cfg.MaxDepth < 0 {
+		return nil, fmt.Errorf("max-depth must not be negative, got %d", cfg.MaxDepth)
+	}
+
 	// prefer top level excludes, falling back to global.excludes for backwards compatibility
 	if len(cfg.Excludes) == 0 {
 		cfg.Excludes = cfg.Global.Excludes
 	}
 
+	// append any patterns listed in a file for this run, which is resolved relative to the working directory
+	if cfg.ExcludesFrom != "" {
+		patterns, readErr := readPatterns(cfg.WorkingDirectory, cfg.ExcludesFrom)
+		if readErr != nil {
+			return nil, fmt.Errorf("excludes-from: %w", readErr)
+		}
+
+		cfg.Excludes = append(cfg.Excludes, patterns...)
+	}
+
+	if cfg.NoGlobalExcludes {
+		cfg.Excludes = nil
+	}
+
 	// validate formatter names do not contain invalid characters
 
 	nameRegex := regexp.MustCompile("^[a-zA-Z0-9_-]+$")
 
-	for name := range cfg.FormatterConfigs {
+	for name, formatterCfg := range cfg.FormatterConfigs {
 		if !nameRegex.MatchString(name) {
 			return nil, fmt.Errorf(
 				"formatter name %q is invalid, must be of the form %s",
 				name, nameRegex.String(),
 			)
 		}
+
+		if formatterCfg.BatchSize < 0 {
+			return nil, fmt.Errorf("formatter %v batch-size must not be negative, got %d", name, formatterCfg.BatchSize)
+		}
+
+		if _, err = ParseSize(formatterCfg.MaxFileSize); err != nil {
+			return nil, fmt.Errorf("formatter %v max-file-size: %w", name, err)
+		}
+	}
+
+	// apply the overrides of the selected profile, before placeholders are expanded in the options it provides
+	if cfg.ConfigProfile != "" {
+		if err = applyProfile(cfg.FormatterConfigs, cfg.ConfigProfile); err != nil {
+			return nil, err
+		}
+	}
+
+	// expand placeholders in formatter options, allowing them to refer to files within the tree independent of
+	// where it has been checked out
+	configDir, err := filepath.Abs(filepath.Dir(v.ConfigFileUsed()))
+	if err != nil {
+		return nil, fmt.Errorf("failed to get absolute path for config directory: %w", err)
+	}
+
+	placeholders := strings.NewReplacer(
+		"${tree_root}", cfg.TreeRoot,
+		"${config_dir}", configDir,
+		"${work_dir}", cfg.WorkingDirectory,
+	)
+
+	for _, formatterCfg := range cfg.FormatterConfigs {
+		for i, option := range formatterCfg.Options {
+			formatterCfg.Options[i] = placeholders.Replace(option)
+		}
+	}
+
+	// formatters inherit the top-level case sensitivity unless they override it
+	for _, formatterCfg := range cfg.FormatterConfigs {
+		if formatterCfg.CaseInsensitive == nil {
+			formatterCfg.CaseInsensitive = &cfg.CaseInsensitive
+		}
+
+		if formatterCfg.IgnoreExtensionCase == nil {
+			formatterCfg.IgnoreExtensionCase = &cfg.IgnoreExtensionCase
+		}
 	}
 
-	// filter formatters based on provided names
+	if cfg.ConfigOrder {
+		if err = setDeclarationOrder(cfg.FormatterConfigs, v.ConfigFileUsed()); err != nil {
+			return nil, err
+		}
+	}
+
+	// fail-on-change-formatters must refer to formatters which exist, before any are filtered out below
+	for _, name := range cfg.FailOnChangeFormatters {
+		if _, ok := cfg.FormatterConfigs[name]; !ok {
+			return nil, fmt.Errorf("fail-on-change-formatters: formatter %v not found in config", name)
+		}
+	}
+
+	// append any arguments given for this run, which changes the formatter's signature and so bypasses the cache for
+	// the files it matches
+	for _, formatterArg := range cfg.FormatterArgs {
+		name, arg, ok := strings.Cut(formatterArg, "=")
+		if !ok || name == "" {
+			return nil, fmt.Errorf("formatter-arg: %q must be of the form <formatter>=<arg>", formatterArg)
+		}
+
+		formatterCfg, ok := cfg.FormatterConfigs[name]
+		if !ok {
+			return nil, fmt.Errorf("formatter-arg: formatter %v not found in config", name)
+		}
+
+		formatterCfg.Options = append(slices.Clone(formatterCfg.Options), arg)
+	}
+
+	// filter formatters based on provided names, before any are created, so the commands of the formatters which were
+	// not selected are never resolved
 	if len(cfg.Formatters) > 0 {
 		filtered := make(map[string]*Formatter)
 
@@ -249,6 +980,85 @@ func FromViper(v *viper.Viper) (*Config, error) {
 		cfg.FormatterConfigs = filtered
 	}
 
+	// append any patterns listed in files, which are resolved relative to the config file
+	for name, formatterCfg := range cfg.FormatterConfigs {
+		if formatterCfg.IncludesFrom != "" {
+			patterns, readErr := readPatterns(configDir, formatterCfg.IncludesFrom)
+			if readErr != nil {
+				return nil, fmt.Errorf("formatter %v: failed to read includes-from: %w", name, readErr)
+			}
+
+			formatterCfg.Includes = append(formatterCfg.Includes, patterns...)
+		}
+
+		if formatterCfg.ExcludesFrom != "" {
+			patterns, readErr := readPatterns(configDir, formatterCfg.ExcludesFrom)
+			if readErr != nil {
+				return nil, fmt.Errorf("formatter %v: failed to read excludes-from: %w", name, readErr)
+			}
+
+			formatterCfg.Excludes = append(formatterCfg.Excludes, patterns...)
+		}
+	}
+
+	if cfg.CacheMode != CacheModeReadWrite && cfg.CacheMode != CacheModeReadOnly {
+		return nil, fmt.Errorf(
+			"cache-mode must be one of %q or %q, got %q", CacheModeReadWrite, CacheModeReadOnly, cfg.CacheMode,
+		)
+	}
+
+	// resolve the cache directory, with an empty value meaning the user's XDG cache directory
+	switch cfg.CacheDir {
+	case "", CacheDirXDG:
+		cfg.CacheDir = ""
+	case CacheDirRepo:
+		cfg.CacheDir = filepath.Join(cfg.TreeRoot, RepoCacheDir)
+	default:
+		if !filepath.IsAbs(cfg.CacheDir) {
+			cfg.CacheDir = filepath.Join(cfg.TreeRoot, cfg.CacheDir)
+		}
+	}
+
+	// files which formatter output is captured in must not be formatted themselves
+	names := make([]string, 0, len(cfg.FormatterConfigs))
+	for name := range cfg.FormatterConfigs {
+		names = append(names, name)
+	}
+
+	slices.Sort(names)
+
+	for _, name := range names {
+		capturePath := cfg.FormatterConfigs[name].CaptureOutput
+		if capturePath == "" {
+			continue
+		}
+
+		if !filepath.IsAbs(capturePath) {
+			capturePath = filepath.Join(cfg.TreeRoot, capturePath)
+		}
+
+		relPath, relErr := filepath.Rel(cfg.TreeRoot, capturePath)
+		if relErr == nil && !strings.HasPrefix(relPath, "..") {
+			cfg.Excludes = append(cfg.Excludes, filepath.ToSlash(relPath))
+		}
+	}
+
+	// caching can be disabled by default in the config file
+	if !cfg.Cache {
+		cfg.NoCache = true
+	}
+
+	// listing or dumping matches, benchmarking the walk or self-testing never reads or writes the cache, and staged
+	// content is extracted into new temporary files each time, so there is nothing worth caching
+	if cfg.ListFiles || cfg.DumpMatches || cfg.BenchWalk > 0 || cfg.Staged || cfg.SelfTest {
+		cfg.NoCache = true
+	}
+
+	// porcelain output, or a json response to stdin, replaces the summary
+	if cfg.Porcelain || cfg.StdinJSON {
+		cfg.NoSummary = true
+	}
+
 	// ci mode
 	if cfg.CI {
 		cfg.NoCache = true
@@ -328,3 +1138,128 @@ func fileExists(path string) bool {
 
 	return fi.Mode().IsRegular()
 }
+
+// readPatterns reads newline separated glob patterns from path, which is resolved relative to dir.
+// Surrounding whitespace is trimmed, and blank lines and lines starting with '#' are ignored.
+func readPatterns(dir, path string) ([]string, error) {
+	if !filepath.IsAbs(path) {
+		path = filepath.Join(dir, path)
+	}
+
+	content, err := os.ReadFile(path)
+	if err != nil {
+		return nil, fmt.Errorf("failed to read patterns file: %w", err)
+	}
+
+	var patterns []string
+
+	for _, line := range strings.Split(string(content), "\n") {
+		line = strings.TrimSpace(line)
+		if line == "" || strings.HasPrefix(line, "#") {
+			continue
+		}
+
+		patterns = append(patterns, line)
+	}
+
+	return patterns, nil
+}
+
+// setDeclarationOrder sets the Order of each formatter to the position at which it is first declared in the config
+// file at path, whether by a [formatter.<name>] table or a dotted key.
+// Formatters which are not declared in the file, e.g. those defined in formatters-dir, are ordered after all others.
+func setDeclarationOrder(formatters map[string]*Formatter, path string) error {
+	// in a project file, the formatters are declared within the table embedding the config
+	var prefix []string
+
+	if p, ok := lookupProjectFile(path); ok {
+		if filepath.Ext(path) != ".toml" {
+			return fmt.Errorf("config-order is not supported in %s, as the order of keys in JSON is not significant",
+				p.name)
+		}
+
+		prefix = p.key
+	}
+
+	prefix = append(prefix, "formatter")
+
+	data, err := os.ReadFile(path)
+	if err != nil {
+		return fmt.Errorf("failed to read %s: %w", path, err)
+	}
+
+	var (
+		order  []string
+		table  []string
+		parser unstable.Parser
+	)
+
+	parser.Reset(data)
+
+	for parser.NextExpression() {
+		expr := parser.Expression()
+
+		var key []string
+		for it := expr.Key(); it.Next(); {
+			key = append(key, string(it.Node().Data))
+		}
+
+		//nolint:exhaustive
+		switch expr.Kind {
+		case unstable.Table, unstable.ArrayTable:
+			table = key
+		case unstable.KeyValue:
+			key = append(slices.Clone(table), key...)
+		default:
+			continue
+		}
+
+		if len(key) > len(prefix) && slices.Equal(key[:len(prefix)], prefix) &&
+			!slices.Contains(order, key[len(prefix)]) {
+			order = append(order, key[len(prefix)])
+		}
+	}
+
+	if err = parser.Error(); err != nil {
+		return fmt.Errorf("failed to parse %s: %w", path, err)
+	}
+
+	for name, formatterCfg := range formatters {
+		formatterCfg.Order = slices.Index(order, name)
+		if formatterCfg.Order < 0 {
+			formatterCfg.Order = len(order)
+		}
+	}
+
+	return nil
+}
+
+// applyProfile overrides the command and options of each formatter which defines the given profile.
+// It is an error if no formatter defines the profile, as it is most likely misspelled.
+func applyProfile(formatters map[string]*Formatter, profile string) error {
+	defined := false
+
+	for _, formatterCfg := range formatters {
+		// like all keys read by viper, profile names are case-insensitive
+		overrides, ok := formatterCfg.Profiles[strings.ToLower(profile)]
+		if !ok || overrides == nil {
+			continue
+		}
+
+		defined = true
+
+		if overrides.Command != "" {
+			formatterCfg.Command = overrides.Command
+		}
+
+		if overrides.Options != nil {
+			formatterCfg.Options = overrides.Options
+		}
+	}
+
+	if !defined {
+		return fmt.Errorf("config-profile %q is not defined by any formatter", profile)
+	}
+
+	return nil
+}
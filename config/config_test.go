@@ -6,6 +6,7 @@ import (
 	"fmt"
 	"os"
 	"path/filepath"
+	"strings"
 	"testing"
 
 	"github.com/BurntSushi/toml"
@@ -86,6 +87,71 @@ func TestAllowMissingFormatter(t *testing.T) {
 	checkValue(true)
 }
 
+func TestBatchSize(t *testing.T) {
+	as := require.New(t)
+
+	cfg := &config.Config{}
+	v, flags := newViper(t)
+
+	checkValue := func(expected int) {
+		readValue(t, v, cfg, func(cfg *config.Config) {
+			as.Equal(expected, cfg.BatchSize)
+		})
+	}
+
+	// default with no flag, env or config
+	checkValue(1024)
+
+	// set config value
+	cfg.BatchSize = 512
+	checkValue(512)
+
+	// env override
+	t.Setenv("TREEFMT_BATCH_SIZE", "64")
+	checkValue(64)
+
+	// flag override
+	as.NoError(flags.Set("batch-size", "0"))
+	checkValue(0)
+}
+
+func TestCache(t *testing.T) {
+	as := require.New(t)
+
+	cfg := &config.Config{}
+	v, flags := newViper(t)
+
+	checkValues := func(cache bool, noCache bool) {
+		readValue(t, v, cfg, func(cfg *config.Config) {
+			as.Equal(cache, cfg.Cache)
+			as.Equal(noCache, cfg.NoCache)
+		})
+	}
+
+	// default with no flag, env or config
+	checkValues(true, false)
+
+	// disable in config
+	as.NoError(v.ReadConfig(strings.NewReader("cache = false")))
+
+	decodedCfg, err := config.FromViper(v)
+	as.NoError(err)
+	as.False(decodedCfg.Cache)
+	as.True(decodedCfg.NoCache)
+
+	// env override
+	t.Setenv("TREEFMT_CACHE", "false")
+	checkValues(false, true)
+
+	// flag override
+	as.NoError(flags.Set("cache", "true"))
+	checkValues(true, false)
+
+	// --no-cache still takes effect
+	as.NoError(flags.Set("no-cache", "true"))
+	checkValues(true, true)
+}
+
 func TestCI(t *testing.T) {
 	as := require.New(t)
 
@@ -218,6 +284,58 @@ func TestExcludes(t *testing.T) {
 	checkValue([]string{"bleep", "bloop"})
 }
 
+func TestExcludesFrom(t *testing.T) {
+	as := require.New(t)
+
+	v, flags := newViper(t)
+	workingDir := t.TempDir()
+
+	as.NoError(os.WriteFile(filepath.Join(workingDir, "vendored.txt"), []byte(`
+# computed by CI
+third_party/*
+  vendor/*
+
+`), 0o600))
+
+	as.NoError(v.ReadConfig(strings.NewReader(`
+excludes = ["*.lock"]
+excludes-from = "vendored.txt"
+
+[formatter.echo]
+command = "echo"
+includes = ["*"]
+`)))
+
+	as.NoError(flags.Set("working-dir", workingDir))
+
+	// it cannot be set in the config file
+	cfg, err := config.FromViper(v)
+	as.NoError(err)
+	as.Equal([]string{"*.lock"}, cfg.Excludes)
+
+	// the patterns are appended to the excludes, relative to the working directory
+	as.NoError(flags.Set("excludes-from", "vendored.txt"))
+
+	cfg, err = config.FromViper(v)
+	as.NoError(err)
+	as.Equal([]string{"*.lock", "third_party/*", "vendor/*"}, cfg.Excludes)
+
+	// they are global excludes like any other
+	as.NoError(flags.Set("no-global-excludes", "true"))
+
+	cfg, err = config.FromViper(v)
+	as.NoError(err)
+	as.Empty(cfg.Excludes)
+
+	// a missing file is an error
+	as.NoError(flags.Set("no-global-excludes", "false"))
+	as.NoError(flags.Set("excludes-from", "missing.txt"))
+
+	_, err = config.FromViper(v)
+	as.ErrorContains(err, "excludes-from: failed to read patterns file")
+	as.ErrorIs(err, os.ErrNotExist)
+}
+
 func TestFailOnChange(t *testing.T) {
 	as := require.New(t)
 
@@ -293,6 +411,363 @@ func TestFormatters(t *testing.T) {
 	as.ErrorContains(err, "formatter foo not found in config")
 }
 
+func TestFormatterPatternsAsString(t *testing.T) {
+	as := require.New(t)
+
+	v, _ := newViper(t)
+
+	// a single pattern can be given instead of a list, and it should not be split on commas
+	as.NoError(v.ReadConfig(strings.NewReader(`
+[formatter.prettier]
+command = "prettier"
+includes = "*.{js,ts}"
+excludes = "vendor/*"
+`)))
+
+	cfg, err := config.FromViper(v)
+	as.NoError(err)
+
+	as.Equal([]string{"*.{js,ts}"}, cfg.FormatterConfigs["prettier"].Includes)
+	as.Equal([]string{"vendor/*"}, cfg.FormatterConfigs["prettier"].Excludes)
+}
+
+func TestFormatterOptionsPlaceholders(t *testing.T) {
+	as := require.New(t)
+
+	v, _ := newViper(t)
+
+	as.NoError(v.ReadConfig(strings.NewReader(`
+[formatter.prettier]
+command = "prettier"
+options = ["--config", "${tree_root}/.prettierrc", "--ignore-path=${config_dir}/.prettierignore", "${work_dir}"]
+includes = ["*.js"]
+
+[formatter.shfmt]
+command = "shfmt"
+options = ["${HOME}", "${unknown}"]
+includes = ["*.sh"]
+`)))
+
+	cfg, err := config.FromViper(v)
+	as.NoError(err)
+
+	configDir := filepath.Dir(v.ConfigFileUsed())
+
+	as.Equal([]string{
+		"--config", filepath.Join(cfg.TreeRoot, ".prettierrc"),
+		"--ignore-path=" + filepath.Join(configDir, ".prettierignore"),
+		cfg.WorkingDirectory,
+	}, cfg.FormatterConfigs["prettier"].Options)
+
+	// other placeholders are left as-is
+	as.Equal([]string{"${HOME}", "${unknown}"}, cfg.FormatterConfigs["shfmt"].Options)
+}
+
+func TestFormatterArg(t *testing.T) {
+	as := require.New(t)
+
+	v, flags := newViper(t)
+
+	as.NoError(v.ReadConfig(strings.NewReader(`
+[formatter.rustfmt]
+command = "rustfmt"
+options = ["--check"]
+includes = ["*.rs"]
+`)))
+
+	// arguments are appended in order, and may contain both = and ,
+	as.NoError(flags.Set("formatter-arg", "rustfmt=--edition=2021"))
+	as.NoError(flags.Set("formatter-arg", "rustfmt=--config=a,b"))
+
+	cfg, err := config.FromViper(v)
+	as.NoError(err)
+	as.Equal([]string{"--check", "--edition=2021", "--config=a,b"}, cfg.FormatterConfigs["rustfmt"].Options)
+
+	// the formatter must exist
+	as.NoError(flags.Set("formatter-arg", "prettier=--write"))
+
+	_, err = config.FromViper(v)
+	as.ErrorContains(err, "formatter-arg: formatter prettier not found in config")
+
+	// and be named
+	v, flags = newViper(t)
+	as.NoError(flags.Set("formatter-arg", "--write"))
+
+	_, err = config.FromViper(v)
+	as.ErrorContains(err, `formatter-arg: "--write" must be of the form <formatter>=<arg>`)
+}
+
+func TestCaseInsensitive(t *testing.T) {
+	as := require.New(t)
+
+	v, flags := newViper(t)
+
+	as.NoError(v.ReadConfig(strings.NewReader(`
+case-insensitive = true
+
+[formatter.python]
+command = "black"
+includes = ["*.PY"]
+
+[formatter.rust]
+command = "rustfmt"
+includes = ["*.rs"]
+case-insensitive = false
+`)))
+
+	cfg, err := config.FromViper(v)
+	as.NoError(err)
+
+	// formatters inherit the top-level setting unless they override it
+	as.True(cfg.CaseInsensitive)
+	as.True(*cfg.FormatterConfigs["python"].CaseInsensitive)
+	as.False(*cfg.FormatterConfigs["rust"].CaseInsensitive)
+
+	// the flag takes precedence over the config file
+	as.NoError(flags.Set("case-insensitive", "false"))
+
+	cfg, err = config.FromViper(v)
+	as.NoError(err)
+
+	as.False(cfg.CaseInsensitive)
+	as.False(*cfg.FormatterConfigs["python"].CaseInsensitive)
+
+	// ignore-extension-case is inherited in the same way
+	as.NoError(flags.Set("ignore-extension-case", "true"))
+
+	cfg, err = config.FromViper(v)
+	as.NoError(err)
+
+	as.True(cfg.IgnoreExtensionCase)
+	as.True(*cfg.FormatterConfigs["python"].IgnoreExtensionCase)
+	as.True(*cfg.FormatterConfigs["rust"].IgnoreExtensionCase)
+}
+
+func TestPatternsFrom(t *testing.T) {
+	as := require.New(t)
+
+	v, _ := newViper(t)
+	configDir := filepath.Dir(v.ConfigFileUsed())
+
+	as.NoError(os.WriteFile(filepath.Join(configDir, "includes.txt"), []byte(`
+# generated from the manifest
+*.py
+  src/*.pyi
+
+`), 0o600))
+	as.NoError(os.WriteFile(filepath.Join(configDir, "excludes.txt"), []byte("vendor/*\n"), 0o600))
+
+	as.NoError(v.ReadConfig(strings.NewReader(`
+[formatter.python]
+command = "black"
+includes = ["*.pyw"]
+includes-from = "includes.txt"
+excludes-from = "excludes.txt"
+`)))
+
+	cfg, err := config.FromViper(v)
+	as.NoError(err)
+
+	// patterns read from files are appended to those in the config
+	as.Equal([]string{"*.pyw", "*.py", "src/*.pyi"}, cfg.FormatterConfigs["python"].Includes)
+	as.Equal([]string{"vendor/*"}, cfg.FormatterConfigs["python"].Excludes)
+
+	// a missing file is an error
+	as.NoError(v.ReadConfig(strings.NewReader(`
+[formatter.python]
+command = "black"
+includes-from = "missing.txt"
+`)))
+
+	_, err = config.FromViper(v)
+	as.ErrorContains(err, "formatter python: failed to read includes-from")
+	as.ErrorIs(err, os.ErrNotExist)
+}
+
+func TestConfigOrder(t *testing.T) {
+	as := require.New(t)
+
+	v, flags := newViper(t)
+
+	contents := `
+formatter.yamlfmt.command = "yamlfmt"
+
+[formatter.shfmt]
+command = "shfmt"
+
+[formatter.deadnix]
+command = "deadnix"
+
+[formatter.shfmt.profiles.ci]
+options = ["-d"]
+
+[formatter.alejandra]
+command = "alejandra"
+`
+
+	as.NoError(os.WriteFile(v.ConfigFileUsed(), []byte(contents), 0o600))
+	as.NoError(v.ReadInConfig())
+
+	order := func() map[string]int {
+		cfg, err := config.FromViper(v)
+		as.NoError(err)
+
+		result := make(map[string]int)
+		for name, formatterCfg := range cfg.FormatterConfigs {
+			result[name] = formatterCfg.Order
+		}
+
+		return result
+	}
+
+	// by default, the order is left to the names
+	as.Equal(map[string]int{"yamlfmt": 0, "shfmt": 0, "deadnix": 0, "alejandra": 0}, order())
+
+	// otherwise it follows the first declaration of each formatter
+	as.NoError(flags.Set("config-order", "true"))
+	as.Equal(map[string]int{"yamlfmt": 0, "shfmt": 1, "deadnix": 2, "alejandra": 3}, order())
+}
+
+func TestEmbeddedConfig(t *testing.T) {
+	as := require.New(t)
+
+	dir := t.TempDir()
+	subDir := filepath.Join(dir, "src")
+	as.NoError(os.Mkdir(subDir, 0o755))
+
+	pyproject := filepath.Join(dir, "pyproject.toml")
+	packageJSON := filepath.Join(subDir, "package.json")
+
+	// project files without an embedded config are not config files
+	as.NoError(os.WriteFile(pyproject, []byte("[project]\nname = \"example\"\n"), 0o600))
+	as.NoError(os.WriteFile(packageJSON, []byte(`{"name": "example"}`), 0o600))
+
+	_, _, err := config.LocateUp(subDir)
+	as.ErrorContains(err, "no config file found")
+
+	// otherwise they are found when searching upwards, like treefmt.toml
+	as.NoError(os.WriteFile(pyproject, []byte(`
+[project]
+name = "example"
+
+[tool.treefmt]
+config-order = true
+
+[tool.treefmt.formatter.ruff]
+command = "ruff"
+includes = ["*.py"]
+
+[tool.treefmt.formatter.black]
+command = "black"
+includes = ["*.py"]
+`), 0o600))
+
+	path, ignored, err := config.LocateUp(subDir)
+	as.NoError(err)
+	as.Equal(pyproject, path)
+	as.Empty(ignored)
+
+	v, _ := newViper(t)
+	as.NoError(config.Read(v, path))
+
+	cfg, err := config.FromViper(v)
+	as.NoError(err)
+	as.Equal(pyproject, v.ConfigFileUsed())
+	as.Equal(0, cfg.FormatterConfigs["ruff"].Order)
+	as.Equal(1, cfg.FormatterConfigs["black"].Order)
+
+	// the nearest config wins
+	as.NoError(os.WriteFile(packageJSON, []byte(`{
+  "name": "example",
+  "treefmt": {
+    "formatter": {
+      "prettier": {"command": "prettier", "includes": ["*.js"]}
+    }
+  }
+}`), 0o600))
+
+	path, _, err = config.LocateUp(subDir)
+	as.NoError(err)
+	as.Equal(packageJSON, path)
+
+	v, _ = newViper(t)
+	as.NoError(config.Read(v, path))
+
+	cfg, err = config.FromViper(v)
+	as.NoError(err)
+	as.Equal("prettier", cfg.FormatterConfigs["prettier"].Command)
+	as.Equal([]string{"*.js"}, cfg.FormatterConfigs["prettier"].Includes)
+
+	// a dedicated config file in the same directory takes precedence, and the project file is reported as ignored
+	treefmtToml := filepath.Join(dir, "treefmt.toml")
+	as.NoError(os.WriteFile(treefmtToml, []byte("[formatter.ruff]\ncommand = \"ruff\"\n"), 0o600))
+
+	path, ignored, err = config.Locate(dir)
+	as.NoError(err)
+	as.Equal(treefmtToml, path)
+	as.Equal(pyproject, ignored)
+
+	// reading a project file without an embedded config is an error
+	as.NoError(os.WriteFile(pyproject, []byte("[project]\nname = \"example\"\n"), 0o600))
+
+	v, _ = newViper(t)
+	as.ErrorContains(config.Read(v, pyproject), "has no [tool.treefmt] table")
+}
+
+func TestConfigProfile(t *testing.T) {
+	as := require.New(t)
+
+	v, flags := newViper(t)
+
+	as.NoError(v.ReadConfig(strings.NewReader(`
+[formatter.nix]
+command = "nixfmt"
+options = ["--check"]
+includes = ["*.nix"]
+
+[formatter.nix.profiles.ci]
+options = ["--width", "100"]
+
+[formatter.nix.profiles.local]
+command = "alejandra"
+
+[formatter.python]
+command = "black"
+options = ["--check"]
+includes = ["*.py"]
+`)))
+
+	// without a profile, the base config is used
+	cfg, err := config.FromViper(v)
+	as.NoError(err)
+	as.Equal("nixfmt", cfg.FormatterConfigs["nix"].Command)
+	as.Equal([]string{"--check"}, cfg.FormatterConfigs["nix"].Options)
+
+	// only the fields set by the profile are overridden
+	as.NoError(flags.Set("config-profile", "ci"))
+
+	cfg, err = config.FromViper(v)
+	as.NoError(err)
+	as.Equal("nixfmt", cfg.FormatterConfigs["nix"].Command)
+	as.Equal([]string{"--width", "100"}, cfg.FormatterConfigs["nix"].Options)
+
+	// formatters which do not define the profile are left alone
+	as.Equal([]string{"--check"}, cfg.FormatterConfigs["python"].Options)
+
+	as.NoError(flags.Set("config-profile", "local"))
+
+	cfg, err = config.FromViper(v)
+	as.NoError(err)
+	as.Equal("alejandra", cfg.FormatterConfigs["nix"].Command)
+	as.Equal([]string{"--check"}, cfg.FormatterConfigs["nix"].Options)
+
+	// unknown profiles are an error
+	as.NoError(flags.Set("config-profile", "staging"))
+
+	_, err = config.FromViper(v)
+	as.ErrorContains(err, `config-profile "staging" is not defined by any formatter`)
+}
+
 func TestNoCache(t *testing.T) {
 	as := require.New(t)
 
@@ -717,3 +1192,87 @@ func TestSampleConfigFile(t *testing.T) {
 	as.True(ok, "foo formatter not found")
 	as.Equal("foo-fmt", foo.Command)
 }
+
+func TestKnownFormatters(t *testing.T) {
+	as := require.New(t)
+
+	templates := config.KnownFormatters()
+	as.NotEmpty(templates)
+
+	names := make([]string, len(templates))
+
+	for i, template := range templates {
+		names[i] = template.Name
+
+		as.Regexp("^[a-zA-Z0-9_-]+$", template.Name)
+		as.NotEmpty(template.Formatter.Command, template.Name)
+		as.NotEmpty(template.Formatter.Includes, template.Name)
+	}
+
+	as.IsIncreasing(names, "templates should be sorted by name, without duplicates")
+
+	// every call returns new values
+	templates[0].Formatter.Includes[0] = "*.foo"
+	as.NotEqual("*.foo", config.KnownFormatters()[0].Formatter.Includes[0])
+}
+
+func TestParseSize(t *testing.T) {
+	as := require.New(t)
+
+	for size, expected := range map[string]int64{
+		"":       0,
+		"1024":   1024,
+		"512B":   512,
+		"5KB":    5000,
+		"5 kb":   5000,
+		"5K":     5000,
+		"5KiB":   5 * 1024,
+		"2MB":    2 * 1000 * 1000,
+		"2MiB":   2 * 1024 * 1024,
+		"1GiB":   1024 * 1024 * 1024,
+		" 1GB  ": 1000 * 1000 * 1000,
+	} {
+		actual, err := config.ParseSize(size)
+		as.NoError(err, size)
+		as.Equal(expected, actual, size)
+	}
+
+	for _, size := range []string{"abc", "5XB", "-1", "1.5MB", "MB"} {
+		_, err := config.ParseSize(size)
+		as.ErrorContains(err, "invalid size", size)
+	}
+}
+
+func TestSchema(t *testing.T) {
+	as := require.New(t)
+
+	_, flags := newViper(t)
+
+	schema := config.Schema(flags)
+	properties, ok := schema["properties"].(map[string]any)
+	as.True(ok)
+
+	// top-level keys are described by their flags
+	excludes, ok := properties["excludes"].(map[string]any)
+	as.True(ok)
+	as.Equal("array", excludes["type"])
+	as.Contains(excludes["description"], "Exclude files or directories matching the specified globs")
+	as.NotContains(excludes["description"], "$TREEFMT_EXCLUDES")
+
+	// keys which are not allowed in the config file are left out
+	as.NotContains(properties, "no-cache")
+	as.NotContains(properties, "working-dir")
+
+	// formatters require a command, and accept a single include pattern
+	formatters, ok := properties["formatter"].(map[string]any)
+	as.True(ok)
+
+	formatter, ok := formatters["additionalProperties"].(map[string]any)
+	as.True(ok)
+	as.Equal([]string{"command"}, formatter["required"])
+
+	formatterProperties, ok := formatter["properties"].(map[string]any)
+	as.True(ok)
+	as.Contains(formatterProperties, "priority")
+	as.Contains(formatterProperties["includes"], "anyOf")
+}
@@ -0,0 +1,163 @@
+package config
+
+import (
+	"bytes"
+	"encoding/json"
+	"errors"
+	"fmt"
+	"os"
+	"path/filepath"
+	"strings"
+
+	"github.com/pelletier/go-toml/v2"
+	"github.com/spf13/viper"
+)
+
+// errNotFound is returned when no config file could be found.
+var errNotFound = errors.New("no config file found")
+
+// fileNames are the names of the dedicated config files, in order of preference.
+var fileNames = []string{"treefmt.toml", ".treefmt.toml"}
+
+// projectFile describes a file belonging to another ecosystem's tooling, which may embed the config in a nested table
+// in place of a dedicated config file.
+type projectFile struct {
+	name      string
+	key       []string
+	unmarshal func([]byte, any) error
+	marshal   func(any) ([]byte, error)
+}
+
+// projectFiles are the project files which may embed the config, in order of preference.
+var projectFiles = []projectFile{
+	{name: "pyproject.toml", key: []string{"tool", "treefmt"}, unmarshal: toml.Unmarshal, marshal: toml.Marshal},
+	{name: "package.json", key: []string{"treefmt"}, unmarshal: json.Unmarshal, marshal: json.Marshal},
+}
+
+// table describes the location of the embedded config, e.g. [tool.treefmt].
+func (p projectFile) table() string {
+	return "[" + strings.Join(p.key, ".") + "]"
+}
+
+// embedded returns the config embedded in the file at path, re-encoded in the format of the file, or nil if there is
+// none.
+func (p projectFile) embedded(path string) ([]byte, error) {
+	data, err := os.ReadFile(path)
+	if err != nil {
+		return nil, fmt.Errorf("failed to read %s: %w", path, err)
+	}
+
+	var value any
+
+	if err = p.unmarshal(data, &value); err != nil {
+		var decodeErr *toml.DecodeError
+		if errors.As(err, &decodeErr) {
+			row, col := decodeErr.Position()
+
+			return nil, fmt.Errorf("failed to parse %s: line %d, column %d: %w", path, row, col, decodeErr)
+		}
+
+		return nil, fmt.Errorf("failed to parse %s: %w", path, err)
+	}
+
+	for _, key := range p.key {
+		table, ok := value.(map[string]any)
+		if !ok {
+			return nil, nil
+		}
+
+		if value, ok = table[key]; !ok {
+			return nil, nil
+		}
+	}
+
+	if _, ok := value.(map[string]any); !ok {
+		return nil, fmt.Errorf("%s in %s must be a table", p.table(), path)
+	}
+
+	if data, err = p.marshal(value); err != nil {
+		return nil, fmt.Errorf("failed to encode %s from %s: %w", p.table(), path, err)
+	}
+
+	return data, nil
+}
+
+// lookupProjectFile returns the projectFile describing the file at path, if it is one.
+func lookupProjectFile(path string) (projectFile, bool) {
+	for _, p := range projectFiles {
+		if filepath.Base(path) == p.name {
+			return p, true
+		}
+	}
+
+	return projectFile{}, false
+}
+
+// readEmbedded reads the config embedded in the project file p at path into v.
+func readEmbedded(v *viper.Viper, p projectFile, path string) error {
+	data, err := p.embedded(path)
+	if err != nil {
+		return err
+	} else if data == nil {
+		return fmt.Errorf("%s has no %s table", path, p.table())
+	}
+
+	// the config file is still the project file, so that paths in the config are resolved relative to it
+	v.SetConfigFile(path)
+
+	if err = v.ReadConfig(bytes.NewReader(data)); err != nil {
+		return fmt.Errorf("failed to read %s from %s: %w", p.table(), path, err)
+	}
+
+	return nil
+}
+
+// Locate returns the config file in dir, preferring a dedicated config file over a project file which embeds the
+// config. If both exist, the path of the project file is also returned, so that the caller can warn it was ignored.
+func Locate(dir string) (path string, ignored string, err error) {
+	path, _ = Find(dir, fileNames...)
+
+	for _, p := range projectFiles {
+		candidate := filepath.Join(dir, p.name)
+		if !fileExists(candidate) {
+			continue
+		}
+
+		data, embedErr := p.embedded(candidate)
+
+		switch {
+		case embedErr != nil && path != "":
+			// the project file is not needed, so there is no reason to fail because of it
+			continue
+		case embedErr != nil:
+			return "", "", embedErr
+		case data == nil:
+			continue
+		case path != "":
+			return path, candidate, nil
+		default:
+			return candidate, "", nil
+		}
+	}
+
+	if path == "" {
+		return "", "", fmt.Errorf("%w in %s: looked for %s, or a project file embedding the config",
+			errNotFound, dir, fileNames)
+	}
+
+	return path, "", nil
+}
+
+// LocateUp searches for a config file with Locate, starting in searchDir and moving up through its parents.
+func LocateUp(searchDir string) (path string, ignored string, err error) {
+	for _, dir := range eachDir(searchDir) {
+		if path, ignored, err = Locate(dir); path != "" {
+			return path, ignored, nil
+		} else if !errors.Is(err, errNotFound) {
+			return "", "", err
+		}
+	}
+
+	return "", "", fmt.Errorf("%w in %s or its parents: looked for %s, or a project file embedding the config",
+		errNotFound, searchDir, fileNames)
+}
@@ -0,0 +1,123 @@
+package config
+
+// FormatterTemplate is a curated default configuration for a commonly used formatter, which can be used to scaffold a
+// config file.
+type FormatterTemplate struct {
+	// Name is the name the formatter is conventionally configured under, e.g. `[formatter.gofmt]`.
+	Name string
+	// Formatter is the default configuration, including the languages it speaks for use with --language.
+	Formatter Formatter
+}
+
+// KnownFormatters returns curated default configurations for commonly used formatters, sorted by name.
+// Each call returns new values, so callers are free to modify them.
+func KnownFormatters() []FormatterTemplate {
+	return []FormatterTemplate{
+		{
+			Name: "alejandra",
+			Formatter: Formatter{
+				Command:   "alejandra",
+				Includes:  []string{"*.nix"},
+				Languages: []string{"nix"},
+			},
+		},
+		{
+			Name: "black",
+			Formatter: Formatter{
+				Command:   "black",
+				Includes:  []string{"*.py", "*.pyi"},
+				Languages: []string{"python"},
+			},
+		},
+		{
+			Name: "cabal-fmt",
+			Formatter: Formatter{
+				Command:  "cabal-fmt",
+				Options:  []string{"--inplace"},
+				Includes: []string{"*.cabal"},
+			},
+		},
+		{
+			Name: "elm-format",
+			Formatter: Formatter{
+				Command:   "elm-format",
+				Options:   []string{"--yes"},
+				Includes:  []string{"*.elm"},
+				Languages: []string{"elm"},
+			},
+		},
+		{
+			Name: "gofmt",
+			Formatter: Formatter{
+				Command:   "gofmt",
+				Options:   []string{"-w"},
+				Includes:  []string{"*.go"},
+				Languages: []string{"go"},
+			},
+		},
+		{
+			Name: "ormolu",
+			Formatter: Formatter{
+				Command:   "ormolu",
+				Options:   []string{"--mode", "inplace"},
+				Includes:  []string{"*.hs"},
+				Languages: []string{"haskell"},
+			},
+		},
+		{
+			Name: "prettier",
+			Formatter: Formatter{
+				Command: "prettier",
+				Options: []string{"--write"},
+				Includes: []string{
+					"*.css", "*.html", "*.js", "*.json", "*.jsx", "*.md", "*.mdx", "*.scss", "*.ts", "*.tsx", "*.yaml",
+					"*.yml",
+				},
+				Languages: []string{"css", "html", "javascript", "json", "markdown", "typescript", "yaml"},
+			},
+		},
+		{
+			Name: "rufo",
+			Formatter: Formatter{
+				Command:   "rufo",
+				Options:   []string{"-x"},
+				Includes:  []string{"*.rb"},
+				Languages: []string{"ruby"},
+			},
+		},
+		{
+			Name: "rustfmt",
+			Formatter: Formatter{
+				Command:   "rustfmt",
+				Options:   []string{"--edition", "2021"},
+				Includes:  []string{"*.rs"},
+				Languages: []string{"rust"},
+			},
+		},
+		{
+			Name: "shfmt",
+			Formatter: Formatter{
+				Command:   "shfmt",
+				Options:   []string{"-w"},
+				Includes:  []string{"*.sh", "*.bash"},
+				Languages: []string{"shell"},
+			},
+		},
+		{
+			Name: "terraform",
+			Formatter: Formatter{
+				Command:   "terraform",
+				Options:   []string{"fmt"},
+				Includes:  []string{"*.tf", "*.tfvars"},
+				Languages: []string{"terraform"},
+			},
+		},
+		{
+			Name: "yamlfmt",
+			Formatter: Formatter{
+				Command:  "yamlfmt",
+				Includes: []string{"*.yaml", "*.yml"},
+			},
+		},
+	}
+}
@@ -0,0 +1,103 @@
+package config
+
+import (
+	"reflect"
+	"regexp"
+	"slices"
+	"strings"
+
+	"github.com/spf13/pflag"
+)
+
+// envSuffix matches the trailing reference to the env variable in the usage of a flag, which is of no use in a schema.
+var envSuffix = regexp.MustCompile(`\s*\(env \$\w+\)$`)
+
+// Schema returns a JSON Schema describing the config file, derived from the toml tags of Config, so that editors can
+// validate it and offer completion.
+// The top-level keys are described using the usage of their corresponding flags in fs.
+func Schema(fs *pflag.FlagSet) map[string]any {
+	schema := structSchema(reflect.TypeOf(Config{}), fs)
+
+	schema["$schema"] = "https://json-schema.org/draft/2020-12/schema"
+	schema["title"] = "treefmt.toml"
+
+	return schema
+}
+
+// structSchema describes a struct by the fields which have a toml tag, with those whose tag lacks omitempty being
+// required. fs may be nil, in which case the fields have no description.
+func structSchema(t reflect.Type, fs *pflag.FlagSet) map[string]any {
+	properties := make(map[string]any)
+
+	var required []string
+
+	for i := range t.NumField() {
+		field := t.Field(i)
+
+		name, options, _ := strings.Cut(field.Tag.Get("toml"), ",")
+		if name == "" || name == "-" {
+			continue
+		}
+
+		property := typeSchema(field.Type)
+
+		// a single pattern is accepted in place of a list, see FromViper
+		if t == reflect.TypeOf(Formatter{}) && (name == "includes" || name == "excludes") {
+			property = map[string]any{
+				"anyOf": []any{map[string]any{"type": "string"}, property},
+			}
+		}
+
+		if fs != nil {
+			if flag := fs.Lookup(name); flag != nil {
+				property["description"] = envSuffix.ReplaceAllString(flag.Usage, "")
+			}
+		}
+
+		if name == "global" {
+			property["deprecated"] = true
+		}
+
+		if !slices.Contains(strings.Split(options, ","), "omitempty") {
+			required = append(required, name)
+		}
+
+		properties[name] = property
+	}
+
+	schema := map[string]any{
+		"type":                 "object",
+		"properties":           properties,
+		"additionalProperties": false,
+	}
+
+	if len(required) > 0 {
+		schema["required"] = required
+	}
+
+	return schema
+}
+
+// typeSchema describes the values of type t.
+func typeSchema(t reflect.Type) map[string]any {
+	//nolint:exhaustive
+	switch t.Kind() {
+	case reflect.Pointer:
+		return typeSchema(t.Elem())
+	case reflect.Bool:
+		return map[string]any{"type": "boolean"}
+	case reflect.String:
+		return map[string]any{"type": "string"}
+	case reflect.Int, reflect.Int64, reflect.Uint8:
+		return map[string]any{"type": "integer"}
+	case reflect.Slice:
+		return map[string]any{"type": "array", "items": typeSchema(t.Elem())}
+	case reflect.Map:
+		return map[string]any{"type": "object", "additionalProperties": typeSchema(t.Elem())}
+	case reflect.Struct:
+		return structSchema(t, nil)
+	default:
+		// any value is allowed for types we do not know how to describe
+		return map[string]any{}
+	}
+}
@@ -0,0 +1,50 @@
+package format
+
+import (
+	"fmt"
+	"os"
+	"path/filepath"
+	"sync"
+)
+
+// outputCapture writes the output of a formatter to a file, see config.Formatter.CaptureOutput.
+// The file is replaced the first time output is written, after which the output of subsequent batches is appended.
+type outputCapture struct {
+	path string
+
+	lock    sync.Mutex
+	started bool
+}
+
+func (c *outputCapture) write(out []byte) error {
+	c.lock.Lock()
+	defer c.lock.Unlock()
+
+	flags := os.O_CREATE | os.O_WRONLY | os.O_APPEND
+	if !c.started {
+		flags |= os.O_TRUNC
+	}
+
+	if err := os.MkdirAll(filepath.Dir(c.path), 0o750); err != nil {
+		return fmt.Errorf("failed to create directory for %s: %w", c.path, err)
+	}
+
+	file, err := os.OpenFile(c.path, flags, 0o600)
+	if err != nil {
+		return fmt.Errorf("failed to open %s: %w", c.path, err)
+	}
+
+	c.started = true
+
+	if _, err = file.Write(out); err != nil {
+		_ = file.Close()
+
+		return fmt.Errorf("failed to write to %s: %w", c.path, err)
+	}
+
+	if err = file.Close(); err != nil {
+		return fmt.Errorf("failed to close %s: %w", c.path, err)
+	}
+
+	return nil
+}
@@ -6,13 +6,18 @@ import (
 	"errors"
 	"fmt"
 	"os"
+	"path/filepath"
 	"slices"
+	"strings"
+	"sync"
+	"time"
 
 	"github.com/charmbracelet/log"
 	"github.com/gobwas/glob"
 	"github.com/numtide/treefmt/v2/config"
 	"github.com/numtide/treefmt/v2/stats"
 	"github.com/numtide/treefmt/v2/walk"
+	"golang.org/x/sync/errgroup"
 	"mvdan.cc/sh/v3/expand"
 )
 
@@ -20,7 +25,10 @@ const (
 	batchKeySeparator = ":"
 )
 
-var ErrFormattingFailures = errors.New("formatting failures detected")
+var (
+	ErrFormattingFailures = errors.New("formatting failures detected")
+	ErrDeadlineReached    = errors.New("deadline reached")
+)
 
 // CompositeFormatter handles the application of multiple Formatter instances based on global excludes and individual
 // formatter configuration.
@@ -28,15 +36,28 @@ type CompositeFormatter struct {
 	cfg            *config.Config
 	stats          *stats.Stats
 	globalExcludes []glob.Glob
+	generated      generatedRules
+	// maxFileSize is the size in bytes above which files are skipped, with 0 meaning no limit.
+	maxFileSize int64
 
 	unmatchedLevel log.Level
 
 	scheduler  *scheduler
 	formatters map[string]*Formatter
+	// ordered contains the formatters in the order in which they should be applied, so that matching a file does not
+	// require sorting.
+	ordered []*Formatter
 }
 
 // match filters the file against global excludes and returns a list of formatters that want to process the file.
-func (c *CompositeFormatter) match(file *walk.File) (bool, []*Formatter) {
+// When count is true, the included and excluded counts of each formatter are updated, see ReportUnmatched, which must
+// only happen once for each file formatted.
+func (c *CompositeFormatter) match(file *walk.File, count bool) (bool, []*Formatter) {
+	// when a language has been specified, formatters are selected by language and globs are ignored entirely
+	if c.cfg.Language != "" {
+		return false, c.formattersForLanguage(c.cfg.Language)
+	}
+
 	// first check if this file has been globally excluded
 	if pathMatches(file.RelPath, c.globalExcludes) {
 		log.Debugf("path matched global excludes: %s", file.RelPath)
@@ -44,12 +65,36 @@ func (c *CompositeFormatter) match(file *walk.File) (bool, []*Formatter) {
 		return true, nil
 	}
 
+	// then check if it has been marked as generated in .gitattributes
+	if pattern := c.generated.match(file.RelPath); pattern != "" {
+		log.Debugf("path marked as generated by .gitattributes pattern %s: %s", pattern, file.RelPath)
+
+		return true, nil
+	}
+
+	// and finally whether it is too large, which is only known for files which have been walked
+	if c.maxFileSize > 0 && file.Info != nil && file.Info.Size() > c.maxFileSize {
+		log.Debugf("path exceeds max-file-size of %d bytes: %s", c.maxFileSize, file.RelPath)
+
+		return true, nil
+	}
+
 	// a list of formatters that match this file
 	var matches []*Formatter
 
-	// iterate the formatters, recording which are interested in this file
-	for _, formatter := range c.formatters {
-		if formatter.Wants(file) {
+	// iterate the formatters in the order in which they should be applied, recording which are interested in this file
+	for _, formatter := range c.ordered {
+		included, wanted := formatter.match(file)
+
+		if count && included {
+			formatter.included.Add(1)
+
+			if !wanted {
+				formatter.excluded.Add(1)
+			}
+		}
+
+		if wanted {
 			matches = append(matches, formatter)
 		}
 	}
@@ -57,16 +102,94 @@ func (c *CompositeFormatter) match(file *walk.File) (bool, []*Formatter) {
 	return false, matches
 }
 
+// checkAllowed returns an error unless executable, which the named formatter resolves to, is listed in allow.
+// An executable outside the tree root may be listed by its name or by its path. One within the tree root, such as a
+// script checked in alongside the config, must be listed by its path, either absolute or relative to the tree root, so
+// that it cannot pass for a tool of the same name.
+func checkAllowed(allow []string, treeRoot string, name string, executable string) error {
+	relPath, err := filepath.Rel(treeRoot, executable)
+	withinTree := err == nil && relPath != ".." && !strings.HasPrefix(relPath, ".."+string(filepath.Separator))
+
+	for _, entry := range allow {
+		if !strings.ContainsRune(entry, '/') && !strings.ContainsRune(entry, filepath.Separator) {
+			if !withinTree && entry == filepath.Base(executable) {
+				return nil
+			}
+
+			continue
+		}
+
+		path := entry
+		if !filepath.IsAbs(path) {
+			path = filepath.Join(treeRoot, path)
+		}
+
+		if filepath.Clean(path) == executable {
+			return nil
+		}
+	}
+
+	if withinTree {
+		return fmt.Errorf(
+			"formatter %v resolves to executable %s within the tree root, which is not listed by its path in "+
+				"allow-commands", name, relPath,
+		)
+	}
+
+	return fmt.Errorf(
+		"formatter %v resolves to executable %s, which is not listed in allow-commands", name, filepath.Base(executable),
+	)
+}
+
+// formattersForLanguage returns the formatters which speak the given language, in the order in which they should be
+// applied.
+func (c *CompositeFormatter) formattersForLanguage(language string) []*Formatter {
+	var matches []*Formatter
+
+	for _, formatter := range c.ordered {
+		if formatter.Speaks(language) {
+			matches = append(matches, formatter)
+		}
+	}
+
+	return matches
+}
+
+// FormattersFor returns the formatters which want to process the given file, in the order in which they would be
+// applied.
+// A file which matches the global excludes, or which exceeds max-file-size, has no formatters. The size of a file is
+// only checked when its Info is set.
+func (c *CompositeFormatter) FormattersFor(file *walk.File) []*Formatter {
+	_, matches := c.match(file, false)
+
+	return matches
+}
+
+// MaxFileSize returns the global max-file-size in bytes, with 0 meaning no limit.
+func (c *CompositeFormatter) MaxFileSize() int64 {
+	return c.maxFileSize
+}
+
 // Apply applies the configured formatters to the given files.
 func (c *CompositeFormatter) Apply(ctx context.Context, files []*walk.File) error {
+	// when hooks always run, the pre-hook does not wait for the first batch
+	if c.cfg.HooksAlways {
+		if err := c.scheduler.runPreHook(ctx); err != nil {
+			return err
+		}
+	}
+
 	var toRelease []*walk.File
 
 	for _, file := range files {
 		// match the file against the formatters
-		globalExclude, matches := c.match(file)
+		globalExclude, matches := c.match(file, true)
 
 		// if the file is globally excluded, we do not emit a warning
 		if globalExclude {
+			// no further processing to be done, append to the release list
+			toRelease = append(toRelease, file)
+
 			continue
 		}
 
@@ -115,16 +238,8 @@ func (c *CompositeFormatter) Apply(ctx context.Context, files []*walk.File) erro
 func (c *CompositeFormatter) signature() (signature, error) {
 	h := sha256.New()
 
-	// sort formatters deterministically
-	formatters := make([]*Formatter, 0, len(c.formatters))
-	for _, f := range c.formatters {
-		formatters = append(formatters, f)
-	}
-
-	slices.SortFunc(formatters, formatterSortFunc)
-
-	// apply them to the hash
-	for _, f := range formatters {
+	// apply the formatters to the hash in a deterministic order
+	for _, f := range c.ordered {
 		if err := f.Hash(h); err != nil {
 			return nil, fmt.Errorf("failed to hash formatter: %w", err)
 		}
@@ -134,10 +249,87 @@ func (c *CompositeFormatter) signature() (signature, error) {
 	return h.Sum(nil), nil
 }
 
+// ReportUnmatched logs each formatter which did not want any of the files it was offered, distinguishing between every
+// file matching its includes having been excluded, which usually indicates a mistake in the config, and there being no
+// matching files at all.
+// It should only be called after traversing the whole tree.
+func (c *CompositeFormatter) ReportUnmatched() {
+	names := make([]string, 0, len(c.formatters))
+	for name := range c.formatters {
+		names = append(names, name)
+	}
+
+	slices.Sort(names)
+
+	for _, name := range names {
+		formatter := c.formatters[name]
+
+		switch {
+		case len(formatter.includes) == 0 && len(formatter.config.Filenames) == 0:
+			// formatters which are only selected by language are never offered files from a walk
+			continue
+		case formatter.included.Load() == 0:
+			formatter.log.Info("no files match its includes")
+		case formatter.included.Load() == formatter.excluded.Load():
+			formatter.log.Warnf(
+				"matched no files: all %d file(s) matching its includes were rejected by its excludes",
+				formatter.excluded.Load(),
+			)
+		}
+	}
+}
+
+// Change describes a file which was changed by formatting.
+type Change struct {
+	// Path is the path of the file, relative to the tree root.
+	Path string
+	// Formatters are the names of the formatters which changed the file, in the order they were applied.
+	// They are only recorded with porcelain output, as it costs a stat of each file after every formatter.
+	Formatters []string
+	// Diff is a unified diff of the change, which is only recorded with diff enabled, as it costs reading each file
+	// before and after formatting.
+	Diff string
+}
+
+// Changed returns the files which were changed by formatting, sorted by their paths relative to the tree root.
+// It should only be called after Close.
+func (c *CompositeFormatter) Changed() []Change {
+	return c.scheduler.changes()
+}
+
+// ChangedBy returns a sorted list of the formatters named in fail-on-change-formatters which changed at least one
+// file.
+// It should only be called after Close.
+func (c *CompositeFormatter) ChangedBy() []string {
+	return c.scheduler.changedByTracked()
+}
+
+// Remaining returns how many files were matched by a formatter, but left unformatted because the deadline passed
+// before they could be processed.
+// It should only be called after Close.
+func (c *CompositeFormatter) Remaining() int {
+	return int(c.scheduler.remaining.Load())
+}
+
+// ConfirmWith has the CompositeFormatter apply formatters to copies of the files, only applying the changes once
+// confirm agrees to them after every file has been formatted.
+func (c *CompositeFormatter) ConfirmWith(confirm ConfirmFunc) {
+	c.scheduler.confirm = confirm
+}
+
 // Close finalizes the processing of the CompositeFormatter, ensuring that any remaining batches are applied and
 // all formatters have completed their tasks. It returns an error if any formatting failures were detected.
+// If formatting succeeded, the post-hook is then run, provided at least one file was processed or hooks always run.
 func (c *CompositeFormatter) Close(ctx context.Context) error {
-	return c.scheduler.close(ctx)
+	if err := c.scheduler.close(ctx); err != nil {
+		return err
+	}
+
+	if c.cfg.PostHook == "" || !(c.scheduler.scheduled || c.cfg.HooksAlways) {
+		return nil
+	}
+
+	return runHook(ctx, "post-hook", c.cfg.PostHook, c.cfg.TreeRoot)
 }
 
 func NewCompositeFormatter(
@@ -146,9 +338,23 @@ func NewCompositeFormatter(
 	batchSize int,
 ) (*CompositeFormatter, error) {
 	// compile global exclude globs
-	globalExcludes, err := compileGlobs(cfg.Excludes)
+	globalExcludes, err := compileGlobs("global exclude", cfg.Excludes, cfg.CaseInsensitive, cfg.IgnoreExtensionCase)
 	if err != nil {
-		return nil, fmt.Errorf("failed to compile global excludes: %w", err)
+		return nil, err
+	}
+
+	// read the generated markers from .gitattributes, if requested
+	var generated generatedRules
+
+	if cfg.RespectGitattributes {
+		if generated, err = readGeneratedRules(filepath.Join(cfg.TreeRoot, ".gitattributes")); err != nil {
+			return nil, fmt.Errorf("failed to read .gitattributes: %w", err)
+		}
+	}
+
+	maxFileSize, err := config.ParseSize(cfg.MaxFileSize)
+	if err != nil {
+		return nil, fmt.Errorf("invalid max-file-size: %w", err)
 	}
 
 	// parse unmatched log level
@@ -164,35 +370,152 @@ func NewCompositeFormatter(
 	}
 
 	// create formatters
+	// each one involves searching PATH and stat-ing its executable, so we create them in parallel
+	var (
+		eg             errgroup.Group
+		formattersLock sync.Mutex
+	)
+
 	formatters := make(map[string]*Formatter)
+	// failures records why each formatter could not be created, so we can report them in a stable order
+	failures := make(map[string]error)
 
 	env := expand.ListEnviron(os.Environ()...)
 
-	for name, formatterCfg := range cfg.FormatterConfigs {
-		formatter, err := newFormatter(name, cfg.TreeRoot, env, formatterCfg)
+	names := make([]string, 0, len(cfg.FormatterConfigs))
+	for name := range cfg.FormatterConfigs {
+		names = append(names, name)
+	}
 
-		if errors.Is(err, ErrCommandNotFound) && cfg.AllowMissingFormatter {
-			log.Debugf("formatter command not found: %v", name)
+	slices.Sort(names)
 
-			continue
-		} else if err != nil {
-			return nil, fmt.Errorf("failed to initialise formatter %v: %w", name, err)
+	// with strict-load, formatters are created one at a time by name, stopping at the first which cannot be created
+	if cfg.StrictLoad {
+		eg.SetLimit(1)
+	}
+
+	for _, name := range names {
+		formatterCfg := cfg.FormatterConfigs[name]
+
+		eg.Go(func() (err error) {
+			if cfg.StrictLoad {
+				formattersLock.Lock()
+				failed := len(failures) > 0
+				formattersLock.Unlock()
+
+				if failed {
+					return nil
+				}
+			}
+
+			defer func() {
+				if err != nil {
+					formattersLock.Lock()
+					defer formattersLock.Unlock()
+
+					failures[name] = err
+				}
+			}()
+
+			formatter, err := newFormatter(name, cfg.TreeRoot, env, formatterCfg)
+
+			// checking the version means running the formatter, which validating the config must never do
+			if err == nil && formatterCfg.MinVersion != "" && !cfg.Validate {
+				err = formatter.checkVersion()
+			}
+
+			if err != nil {
+				switch {
+				case errors.Is(err, ErrCommandNotFound) && cfg.AllowMissingFormatter:
+					log.Debugf("formatter command not found: %v", name)
+
+					return nil
+				case errors.Is(err, ErrVersionTooOld) && cfg.AllowMissingFormatter:
+					// unlike a missing formatter, an outdated one is likely to surprise, so it is always reported
+					log.Warnf("skipping formatter %v: %v", name, err)
+
+					return nil
+				default:
+					return fmt.Errorf("failed to initialise formatter %v: %w", name, err)
+				}
+			}
+
+			formatter.strictScope = cfg.StrictScope
+			formatter.writeOnlyOnChange = cfg.WriteOnlyOnChange
+			formatter.atomicWrites = cfg.AtomicWrites
+
+			// when an allowlist is configured, only the executables it names may be run
+			if len(cfg.AllowCommands) > 0 {
+				if err = checkAllowed(cfg.AllowCommands, cfg.TreeRoot, name, formatter.Executable()); err != nil {
+					return err
+				}
+			}
+
+			// store formatter by name
+			formattersLock.Lock()
+			defer formattersLock.Unlock()
+
+			formatters[name] = formatter
+
+			return nil
+		})
+	}
+
+	// report every formatter which could not be created, ordered by name rather than by whichever failed first
+	if err = eg.Wait(); err != nil {
+		errs := make([]error, 0, len(failures))
+
+		for _, name := range names {
+			if failure, ok := failures[name]; ok {
+				errs = append(errs, failure)
+			}
 		}
 
-		// store formatter by name
-		formatters[name] = formatter
+		return nil, errors.Join(errs...)
 	}
 
 	// create a scheduler for carrying out the actual formatting
-	scheduler := newScheduler(statz, batchSize, changeLevel, formatters)
+	var preHook func(ctx context.Context) error
+
+	if cfg.PreHook != "" {
+		preHook = func(ctx context.Context) error {
+			return runHook(ctx, "pre-hook", cfg.PreHook, cfg.TreeRoot)
+		}
+	}
+
+	// checking the scope of each batch relies on no other batches being processed at the same time
+	sequential := cfg.Sequential || cfg.StrictScope
+
+	scheduler := newScheduler(
+		statz, batchSize, sequential, changeLevel, formatters, preHook, cfg.FailOnChangeFormatters,
+	)
+
+	scheduler.attributeAll = cfg.Porcelain || cfg.StdinJSON
+	scheduler.diff = cfg.Diff
+	scheduler.sortFiles = cfg.SortFiles
+
+	if cfg.Deadline > 0 {
+		scheduler.deadline = time.Now().Add(time.Duration(cfg.Deadline) * time.Second)
+	}
+
+	// sort the formatters once, rather than every time a file is matched
+	ordered := make([]*Formatter, 0, len(formatters))
+	for _, formatter := range formatters {
+		ordered = append(ordered, formatter)
+	}
+
+	slices.SortFunc(ordered, formatterSortFunc)
 
 	return &CompositeFormatter{
 		cfg:            cfg,
 		stats:          statz,
 		globalExcludes: globalExcludes,
+		generated:      generated,
+		maxFileSize:    maxFileSize,
 		unmatchedLevel: unmatchedLevel,
 
 		scheduler:  scheduler,
 		formatters: formatters,
+		ordered:    ordered,
 	}, nil
 }
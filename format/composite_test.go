@@ -0,0 +1,238 @@
+package format //nolint:testpackage
+
+import (
+	"context"
+	"fmt"
+	"os"
+	"path/filepath"
+	"testing"
+
+	"github.com/numtide/treefmt/v2/config"
+	"github.com/numtide/treefmt/v2/stats"
+	"github.com/numtide/treefmt/v2/walk"
+	"github.com/stretchr/testify/require"
+)
+
+func TestFormattersFor(t *testing.T) {
+	as := require.New(t)
+
+	cfg := &config.Config{
+		OnUnmatched: "info",
+		Excludes:    []string{"vendor/*"},
+		FormatterConfigs: map[string]*config.Formatter{
+			"b": {
+				Command:  "echo",
+				Includes: []string{"*.nix"},
+			},
+			"a": {
+				Command:  "echo",
+				Includes: []string{"*.nix"},
+			},
+			"c": {
+				Command:  "echo",
+				Includes: []string{"*.nix", "*.go"},
+				Priority: -1,
+			},
+		},
+	}
+
+	statz := stats.New()
+
+	composite, err := NewCompositeFormatter(cfg, &statz, 1024)
+	as.NoError(err)
+
+	names := func(path string) []string {
+		var result []string
+
+		for _, f := range composite.FormattersFor(&walk.File{RelPath: path}) {
+			result = append(result, f.Name())
+		}
+
+		return result
+	}
+
+	// ordered by priority, then name
+	as.Equal([]string{"c", "a", "b"}, names("default.nix"))
+	as.Equal([]string{"c"}, names("main.go"))
+
+	// no matches
+	as.Empty(names("README.md"))
+
+	// globally excluded
+	as.Empty(names("vendor/default.nix"))
+
+	// with config-order, ties in priority are broken by the order in which formatters were declared
+	cfg.FormatterConfigs["b"].Order = 0
+	cfg.FormatterConfigs["a"].Order = 1
+	cfg.FormatterConfigs["c"].Order = 2
+
+	composite, err = NewCompositeFormatter(cfg, &statz, 1024)
+	as.NoError(err)
+
+	as.Equal([]string{"c", "b", "a"}, names("default.nix"))
+
+	// looking up formatters has no effect on the counts reported after a walk
+	as.Zero(composite.formatters["c"].included.Load())
+	as.Zero(composite.formatters["c"].excluded.Load())
+
+	// whereas matching a file to be formatted counts it once for each formatter whose includes it matches
+	composite.match(&walk.File{RelPath: "main.go"}, true)
+	composite.match(&walk.File{RelPath: "default.nix"}, true)
+
+	as.Equal(int64(2), composite.formatters["c"].included.Load())
+	as.Equal(int64(1), composite.formatters["a"].included.Load())
+	as.Zero(composite.formatters["c"].excluded.Load())
+}
+
+func TestFilenames(t *testing.T) {
+	as := require.New(t)
+
+	cfg := &config.Config{
+		OnUnmatched: "info",
+		FormatterConfigs: map[string]*config.Formatter{
+			"hadolint": {
+				Command:   "echo",
+				Includes:  []string{"*.dockerfile"},
+				Filenames: []string{"Dockerfile", "Containerfile"},
+				Excludes:  []string{"vendor/*"},
+			},
+			"just": {
+				Command:   "echo",
+				Filenames: []string{"justfile"},
+			},
+		},
+	}
+
+	statz := stats.New()
+
+	composite, err := NewCompositeFormatter(cfg, &statz, 1024)
+	as.NoError(err)
+
+	names := func(path string) []string {
+		var result []string
+
+		for _, f := range composite.FormattersFor(&walk.File{RelPath: path}) {
+			result = append(result, f.Name())
+		}
+
+		return result
+	}
+
+	// filenames match the base name exactly, in any directory, alongside the includes
+	as.Equal([]string{"hadolint"}, names("Dockerfile"))
+	as.Equal([]string{"hadolint"}, names("services/api/Containerfile"))
+	as.Equal([]string{"hadolint"}, names("app.dockerfile"))
+	as.Equal([]string{"just"}, names("justfile"))
+
+	// they are case-sensitive, and not patterns
+	as.Empty(names("dockerfile"))
+	as.Empty(names("Dockerfile.dev"))
+	as.Empty(names("Justfile"))
+
+	// excludes still apply
+	as.Empty(names("vendor/Dockerfile"))
+}
+
+func BenchmarkMatch(b *testing.B) {
+	cfg := &config.Config{
+		OnUnmatched:      "info",
+		Excludes:         []string{"vendor/*"},
+		FormatterConfigs: make(map[string]*config.Formatter),
+	}
+
+	// a config resembling a large monorepo, with many formatters for different languages
+	for i := range 50 {
+		cfg.FormatterConfigs[fmt.Sprintf("formatter-%d", i)] = &config.Formatter{
+			Command:  "echo",
+			Includes: []string{fmt.Sprintf("*.ext%d", i), fmt.Sprintf("dir%d/**/*.txt", i)},
+			Excludes: []string{"*_generated.*"},
+			Priority: i % 3,
+		}
+	}
+
+	statz := stats.New()
+
+	composite, err := NewCompositeFormatter(cfg, &statz, 1024)
+	if err != nil {
+		b.Fatal(err)
+	}
+
+	files := make([]*walk.File, 1000)
+	for i := range files {
+		files[i] = &walk.File{RelPath: fmt.Sprintf("dir%d/nested/file%d.ext%d", i%50, i, i%60)}
+	}
+
+	b.ReportAllocs()
+	b.ResetTimer()
+
+	for i := range b.N {
+		composite.match(files[i%len(files)], true)
+	}
+}
+
+func TestConfirmWith(t *testing.T) {
+	as := require.New(t)
+
+	tempDir := t.TempDir()
+	path := filepath.Join(tempDir, "hello.txt")
+
+	as.NoError(os.WriteFile(path, []byte("hello\n"), 0o600))
+
+	cfg := &config.Config{
+		OnUnmatched: "info",
+		TreeRoot:    tempDir,
+		FormatterConfigs: map[string]*config.Formatter{
+			"append": {
+				Command:  `for f in "$@"; do echo formatted >> "$f"; done`,
+				Shell:    true,
+				Includes: []string{"*.txt"},
+			},
+		},
+	}
+
+	run := func(answer bool) {
+		statz := stats.New()
+
+		composite, err := NewCompositeFormatter(cfg, &statz, 1024)
+		as.NoError(err)
+
+		composite.ConfirmWith(func(changedBy map[string]int, total int) (bool, error) {
+			as.Equal(map[string]int{"append": 1}, changedBy)
+			as.Equal(1, total)
+
+			// nothing has been written yet
+			content, err := os.ReadFile(path)
+			as.NoError(err)
+			as.Equal("hello\n", string(content))
+
+			return answer, nil
+		})
+
+		info, err := os.Stat(path)
+		as.NoError(err)
+
+		ctx := context.Background()
+
+		as.NoError(composite.Apply(ctx, []*walk.File{{Path: path, RelPath: "hello.txt", Info: info}}))
+		as.NoError(composite.Close(ctx))
+	}
+
+	// declining leaves the file as it was
+	run(false)
+
+	content, err := os.ReadFile(path)
+	as.NoError(err)
+	as.Equal("hello\n", string(content))
+
+	// accepting applies the changes
+	run(true)
+
+	content, err = os.ReadFile(path)
+	as.NoError(err)
+	as.Equal("hello\nformatted\n", string(content))
+
+	// no copies are left behind
+	entries, err := os.ReadDir(tempDir)
+	as.NoError(err)
+	as.Len(entries, 1)
+}
@@ -0,0 +1,167 @@
+package format
+
+import (
+	"bytes"
+	"context"
+	"errors"
+	"fmt"
+	"maps"
+	"os"
+
+	"github.com/numtide/treefmt/v2/walk"
+)
+
+// ConfirmFunc is asked whether to apply the changes made by formatting, given how many files each formatter changed,
+// by name, and the total number of files which would change.
+type ConfirmFunc func(changedBy map[string]int, total int) (bool, error)
+
+// pendingBatch is a batch whose formatted copies are held until the changes have been confirmed.
+type pendingBatch struct {
+	batch  []*walk.File
+	staged []stagedFile
+}
+
+// formatCopies applies the sequence of formatters for key to copies of the files in batch, counting the files each
+// formatter changes. The copies are held until close, when the changes are either applied or discarded.
+func (s *scheduler) formatCopies(ctx context.Context, key batchKey, batch []*walk.File) error {
+	staged, err := stageCopies(batch)
+	if err != nil {
+		return fmt.Errorf("failed to stage copies: %w", err)
+	}
+
+	copies := make([]*walk.File, len(staged))
+	contents := make([][]byte, len(staged))
+
+	for i := range staged {
+		copies[i] = staged[i].temp
+
+		if contents[i], err = os.ReadFile(copies[i].Path); err != nil {
+			removeStaged(staged)
+
+			return fmt.Errorf("failed to read copy of %s: %w", batch[i].RelPath, err)
+		}
+	}
+
+	hasErrors := false
+
+	for _, name := range key.sequence() {
+		if applyErr := s.apply(ctx, s.formatters[name], copies); applyErr != nil {
+			hasErrors = true
+		}
+
+		if err = s.countChanges(name, copies, contents); err != nil {
+			removeStaged(staged)
+
+			return err
+		}
+	}
+
+	// the changes made to a batch which failed to format are never applied
+	if hasErrors {
+		s.formatError.Store(true)
+		removeStaged(staged)
+
+		return s.finish(ctx, batch, true)
+	}
+
+	s.pendingLock.Lock()
+	defer s.pendingLock.Unlock()
+
+	s.pending = append(s.pending, pendingBatch{batch: batch, staged: staged})
+
+	return nil
+}
+
+// countChanges compares the content of each of files with contents, counting those the formatter with the given name
+// changed. contents is updated with the current content of each file, ready for the next formatter in the sequence.
+func (s *scheduler) countChanges(name string, files []*walk.File, contents [][]byte) error {
+	changed := 0
+
+	for i, file := range files {
+		content, err := os.ReadFile(file.Path)
+		if err != nil {
+			return fmt.Errorf("failed to read formatted copy: %w", err)
+		}
+
+		if !bytes.Equal(content, contents[i]) {
+			contents[i] = content
+			changed++
+		}
+	}
+
+	if changed == 0 {
+		return nil
+	}
+
+	if s.tracked[name] {
+		s.recordChangeBy(name)
+	}
+
+	s.pendingLock.Lock()
+	defer s.pendingLock.Unlock()
+
+	s.pendingChanges[name] += changed
+
+	return nil
+}
+
+// resolvePending asks whether to apply the changes made to the copies held in pending, then replaces the files with
+// their copies or discards the copies accordingly, before releasing the files.
+// When ask is false, because formatting failed, any changes are discarded without asking.
+// Files whose changes are discarded are released without updating the cache, so they are formatted again next time.
+func (s *scheduler) resolvePending(ctx context.Context, ask bool) error {
+	total := 0
+
+	for _, pending := range s.pending {
+		for _, staged := range pending.staged {
+			original, err := os.ReadFile(staged.original.Path)
+			if err != nil {
+				return fmt.Errorf("failed to read %s: %w", staged.original.RelPath, err)
+			}
+
+			formatted, err := os.ReadFile(staged.temp.Path)
+			if err != nil {
+				return fmt.Errorf("failed to read formatted copy of %s: %w", staged.original.RelPath, err)
+			}
+
+			if !bytes.Equal(original, formatted) {
+				total++
+			}
+		}
+	}
+
+	// with nothing to change, there is nothing to confirm
+	accept := total == 0
+
+	var confirmErr error
+
+	if ask && total > 0 {
+		if accept, confirmErr = s.confirm(maps.Clone(s.pendingChanges), total); confirmErr != nil {
+			accept = false
+			confirmErr = fmt.Errorf("failed to confirm changes: %w", confirmErr)
+		}
+	}
+
+	errs := []error{confirmErr}
+
+	for _, pending := range s.pending {
+		applied := accept
+
+		if applied {
+			if err := commitStaged(pending.staged); err != nil {
+				errs = append(errs, err)
+				applied = false
+			}
+		} else {
+			removeStaged(pending.staged)
+		}
+
+		if err := s.finish(ctx, pending.batch, !applied); err != nil {
+			errs = append(errs, err)
+		}
+	}
+
+	s.pending = nil
+
+	return errors.Join(errs...)
+}
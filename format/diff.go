@@ -0,0 +1,45 @@
+package format
+
+import (
+	"fmt"
+	"os"
+
+	"github.com/numtide/treefmt/v2/walk"
+	"github.com/pmezard/go-difflib/difflib"
+)
+
+// diffContext is the number of unchanged lines shown around each change in a unified diff, as with `diff -u`.
+const diffContext = 3
+
+// readContents reads the content of each of files, in the same order.
+func readContents(files []*walk.File) ([][]byte, error) {
+	contents := make([][]byte, len(files))
+
+	for idx, file := range files {
+		content, err := os.ReadFile(file.Path)
+		if err != nil {
+			return nil, fmt.Errorf("failed to read %s: %w", file.RelPath, err)
+		}
+
+		contents[idx] = content
+	}
+
+	return contents, nil
+}
+
+// unifiedDiff returns a unified diff from before to after, naming the file at path relative to the tree root with the
+// a/ and b/ prefixes used by git, so the result can be applied with `git apply` or `patch -p1`.
+func unifiedDiff(path string, before, after []byte) (string, error) {
+	diff, err := difflib.GetUnifiedDiffString(difflib.UnifiedDiff{
+		A:        difflib.SplitLines(string(before)),
+		B:        difflib.SplitLines(string(after)),
+		FromFile: "a/" + path,
+		ToFile:   "b/" + path,
+		Context:  diffContext,
+	})
+	if err != nil {
+		return "", fmt.Errorf("failed to diff %s: %w", path, err)
+	}
+
+	return diff, nil
+}
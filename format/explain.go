@@ -0,0 +1,64 @@
+package format
+
+import (
+	"slices"
+	"strings"
+
+	"github.com/numtide/treefmt/v2/walk"
+)
+
+// FormatterMatch describes how a path was matched against a formatter's includes and excludes.
+type FormatterMatch struct {
+	Formatter *Formatter
+	// Include is the first include pattern, or else the filename, which matched the path, if any.
+	Include string
+	// Exclude is the first exclude pattern which matched the path, if any.
+	Exclude string
+	// MaxFileSize is the formatter's max-file-size in bytes, if the file exceeds it.
+	MaxFileSize int64
+}
+
+// Wants returns true if the formatter would process the path.
+func (m FormatterMatch) Wants() bool {
+	return m.Include != "" && m.Exclude == "" && m.MaxFileSize == 0
+}
+
+// Explain matches file against the global excludes and each formatter, recording which patterns matched.
+// It returns the global exclude pattern which matched the path, if any, and a match for each formatter sorted by name.
+// A path marked as generated in .gitattributes is reported as matching a global exclude.
+// The size of the file is checked against each formatter's max-file-size when its Info is set.
+func (c *CompositeFormatter) Explain(file *walk.File) (string, []FormatterMatch) {
+	path := file.RelPath
+
+	globalExclude := matchingPattern(path, c.cfg.Excludes, c.globalExcludes)
+	if pattern := c.generated.match(path); globalExclude == "" && pattern != "" {
+		globalExclude = pattern + " (" + generatedAttr + " in .gitattributes)"
+	}
+
+	matches := make([]FormatterMatch, 0, len(c.formatters))
+
+	for _, formatter := range c.formatters {
+		include := matchingPattern(path, formatter.config.Includes, formatter.includes)
+		if include == "" {
+			include = matchingFilename(path, formatter.config.Filenames)
+		}
+
+		match := FormatterMatch{
+			Formatter: formatter,
+			Include:   include,
+			Exclude:   matchingPattern(path, formatter.config.Excludes, formatter.excludes),
+		}
+
+		if formatter.maxFileSize > 0 && file.Info != nil && file.Info.Size() > formatter.maxFileSize {
+			match.MaxFileSize = formatter.maxFileSize
+		}
+
+		matches = append(matches, match)
+	}
+
+	slices.SortFunc(matches, func(a, b FormatterMatch) int {
+		return strings.Compare(a.Formatter.Name(), b.Formatter.Name())
+	})
+
+	return globalExclude, matches
+}
@@ -5,11 +5,16 @@ import (
 	"errors"
 	"fmt"
 	"hash"
+	"io/fs"
 	"os"
 	"os/exec"
+	"path/filepath"
 	"regexp"
+	"runtime"
+	"slices"
 	"strconv"
 	"strings"
+	"sync/atomic"
 	"time"
 
 	"github.com/charmbracelet/log"
@@ -35,11 +40,31 @@ type Formatter struct {
 
 	log        *log.Logger
 	executable string // path to the executable described by Command
-	workingDir string
+	// executableInfo is the result of os.Lstat on executable, captured when the Formatter was created.
+	executableInfo os.FileInfo
+	workingDir     string
+	// strictScope enables checking that files outside each batch are not modified, see --strict-scope.
+	strictScope bool
+	// writeOnlyOnChange applies the formatter to copies of files, replacing only those it changes, see
+	// --write-only-on-change.
+	writeOnlyOnChange bool
+	// maxFileSize is the size in bytes above which files are not wanted, with 0 meaning no limit.
+	maxFileSize int64
+	// atomicWrites backs up files before applying the formatter, restoring them if it fails, see restoreBackups.
+	atomicWrites bool
+	// capture receives the output of every invocation when CaptureOutput is configured.
+	capture *outputCapture
+	// configFiles are the absolute paths of ConfigFiles, whose state is included in Hash.
+	configFiles []string
 
 	// internal, compiled versions of Includes and Excludes.
 	includes []glob.Glob
 	excludes []glob.Glob
+
+	// included counts the files formatted by CompositeFormatter.Apply which matched Includes, and excluded how many of
+	// those were then rejected by Excludes or max-file-size.
+	included atomic.Int64
+	excluded atomic.Int64
 }
 
 func (f *Formatter) Name() string {
@@ -50,6 +75,11 @@ func (f *Formatter) Priority() int {
 	return f.config.Priority
 }
 
+// SingleThreaded returns true if the Formatter must not be applied whilst any other formatter is being applied.
+func (f *Formatter) SingleThreaded() bool {
+	return f.config.SingleThreaded
+}
+
 // Executable returns the path to the executable defined by Command.
 func (f *Formatter) Executable() string {
 	return f.executable
@@ -64,33 +94,117 @@ func (f *Formatter) Hash(h hash.Hash) error {
 	// if priority changes, the outcome of applying a sequence of formatters might be different
 	h.Write([]byte(strconv.Itoa(f.config.Priority)))
 
-	// stat the formatter's executable
-	info, err := os.Lstat(f.executable)
-	if err != nil {
-		return fmt.Errorf("failed to stat formatter executable: %w", err)
+	// a shell snippet is not captured by the executable's info, which is the shell itself
+	if f.config.Shell {
+		h.Write([]byte(f.config.Command))
 	}
 
 	// include the executable's size and mod time
 	// if the formatter executable changes (e.g. new version) the outcome of applying the formatter might differ
+	info := f.executableInfo
 	h.Write([]byte(fmt.Sprintf("%d %d", info.Size(), info.ModTime().Unix())))
 
+	// likewise for any config the formatter reads, where a missing file is recorded so that creating it also counts
+	for _, path := range f.configFiles {
+		h.Write([]byte(path))
+
+		configInfo, err := os.Stat(path)
+
+		switch {
+		case errors.Is(err, fs.ErrNotExist):
+			h.Write([]byte("missing"))
+		case err != nil:
+			return fmt.Errorf("failed to stat config file: %w", err)
+		default:
+			h.Write([]byte(fmt.Sprintf("%d %d", configInfo.Size(), configInfo.ModTime().UnixNano())))
+		}
+	}
+
 	return nil
 }
 
 func (f *Formatter) Apply(ctx context.Context, files []*walk.File) error {
+	// exit early if nothing to process
+	if len(files) == 0 {
+		return nil
+	}
+
+	// a formatter specific batch size takes precedence, otherwise all files are passed in a single invocation
+	batchSize := f.config.BatchSize
+	if batchSize <= 0 {
+		batchSize = len(files)
+	}
+
+	for start := 0; start < len(files); start += batchSize {
+		end := min(start+batchSize, len(files))
+
+		if _, err := f.apply(ctx, files[start:end]); err != nil {
+			return err
+		}
+	}
+
+	return nil
+}
+
+// apply executes Command once with the given files appended to its Options, returning its combined stdout and stderr.
+func (f *Formatter) apply(ctx context.Context, files []*walk.File) ([]byte, error) {
 	start := time.Now()
 
+	// when only writing files whose content changes, the formatter is applied to copies of the files instead
+	targets := files
+
+	var staged []stagedFile
+
+	if f.writeOnlyOnChange {
+		var err error
+
+		if staged, err = stageCopies(files); err != nil {
+			return nil, fmt.Errorf("formatter '%s' failed to stage files: %w", f.name, err)
+		}
+
+		// removes any copies left behind if the formatter fails
+		defer removeStaged(staged)
+
+		targets = make([]*walk.File, len(staged))
+		for i := range staged {
+			targets[i] = staged[i].temp
+		}
+	}
+
+	// the copies above already protect the originals, otherwise we keep a backup to restore if the formatter fails
+	var backups []stagedFile
+
+	if f.atomicWrites && !f.writeOnlyOnChange {
+		var err error
+
+		if backups, err = stageCopies(files); err != nil {
+			return nil, fmt.Errorf("formatter '%s' failed to back up files: %w", f.name, err)
+		}
+
+		// removes the backups once they are no longer needed, provided they were not restored
+		defer removeStaged(backups)
+	}
+
 	// construct args, starting with config
-	args := f.config.Options
+	// the options are copied, as several batches of this formatter may be constructing their args at the same time
+	args := slices.Clone(f.config.Options)
 
-	// exit early if nothing to process
-	if len(files) == 0 {
-		return nil
+	// shell snippets receive the options and paths as positional parameters, with the formatter name as $0
+	if f.config.Shell {
+		args = append([]string{"-c", f.config.Command, f.name}, args...)
 	}
 
 	// append paths to the args
-	for _, file := range files {
-		args = append(args, file.RelPath)
+	// they are relative to the tree root, which is also the working directory, so the formatter never sees absolute paths
+	// they are taken from each file's location on disk, as a temporary file, e.g. for stdin, has the RelPath of the
+	// file it stands in for
+	for _, file := range targets {
+		arg, err := filepath.Rel(f.workingDir, file.Path)
+		if err != nil {
+			return nil, fmt.Errorf("formatter '%s' failed to get a relative path for %s: %w", f.name, file.Path, err)
+		}
+
+		args = append(args, arg)
 	}
 
 	// execute the command
@@ -101,34 +215,112 @@ func (f *Formatter) Apply(ctx context.Context, files []*walk.File) error {
 	}
 	cmd.Dir = f.workingDir
 
+	// snapshot the files around this batch, so we can check afterward that the formatter left them alone
+	var scope scopeSnapshot
+
+	if f.strictScope {
+		scope = snapshotScope(f.workingDir, targets)
+	}
+
 	// log out the command being executed
 	f.log.Debugf("executing: %s", cmd.String())
 
-	if out, err := cmd.CombinedOutput(); err != nil {
+	out, err := cmd.CombinedOutput()
+
+	if f.capture != nil {
+		if captureErr := f.capture.write(out); captureErr != nil {
+			f.log.Errorf("failed to capture output: %v", captureErr)
+		}
+	}
+
+	// some formatters exit with a non-zero status when they find issues, despite having formatted the files
+	var exitErr *exec.ExitError
+	if errors.As(err, &exitErr) && slices.Contains(f.config.IgnoreExitCodes, exitErr.ExitCode()) {
+		f.log.Debugf("ignoring exit code %d", exitErr.ExitCode())
+
+		err = nil
+	}
+
+	if err != nil {
 		f.log.Errorf("failed to apply with options '%v': %s", f.config.Options, err)
 
+		if restoreErr := restoreBackups(backups); restoreErr != nil {
+			f.log.Errorf("failed to restore files: %v", restoreErr)
+		}
+
 		if len(out) > 0 {
 			_, _ = fmt.Fprintf(os.Stderr, "\n%s\n", out)
 		}
 
-		return fmt.Errorf("formatter '%s' with options '%v' failed to apply: %w", f.config.Command, f.config.Options, err)
+		return out, fmt.Errorf(
+			"formatter '%s' with options '%v' failed to apply: %w", f.config.Command, f.config.Options, err,
+		)
+	}
+
+	for _, path := range scope.modified() {
+		f.log.Warnf("modified %s, which it was not given", path)
+	}
+
+	if staged != nil {
+		if err = commitStaged(staged); err != nil {
+			return nil, fmt.Errorf("formatter '%s' failed to write changes: %w", f.name, err)
+		}
 	}
 
 	f.log.Infof("%v file(s) processed in %v", len(files), time.Since(start))
 
-	return nil
+	return out, nil
 }
 
-// Wants is used to determine if a Formatter wants to process a path based on it's configured Includes and Excludes
-// patterns.
+// Wants is used to determine if a Formatter wants to process a path based on it's configured Includes, Filenames and
+// Excludes.
 // Returns true if the Formatter should be applied to file, false otherwise.
 func (f *Formatter) Wants(file *walk.File) bool {
-	match := !pathMatches(file.RelPath, f.excludes) && pathMatches(file.RelPath, f.includes)
-	if match {
-		f.log.Debugf("match: %v", file)
+	_, wanted := f.match(file)
+
+	return wanted
+}
+
+// match returns whether file matches the Includes or Filenames of the Formatter, and whether the Formatter wants to
+// process it, see Wants.
+func (f *Formatter) match(file *walk.File) (included bool, wanted bool) {
+	if !pathMatches(file.RelPath, f.includes) && matchingFilename(file.RelPath, f.config.Filenames) == "" {
+		return false, false
+	}
+
+	if pathMatches(file.RelPath, f.excludes) {
+		return true, false
 	}
 
-	return match
+	if f.maxFileSize > 0 && file.Info != nil && file.Info.Size() > f.maxFileSize {
+		f.log.Debugf("skipping %s, which exceeds max-file-size of %d bytes", file.RelPath, f.maxFileSize)
+
+		return true, false
+	}
+
+	f.log.Debugf("match: %v", file)
+
+	return true, true
+}
+
+// Speaks returns true if language is one of the Formatter's configured Languages.
+func (f *Formatter) Speaks(language string) bool {
+	return slices.Contains(f.config.Languages, language)
+}
+
+// lookPath resolves command to an executable.
+// An executable at command relative to the tree root is preferred, falling back to searching PATH.
+func lookPath(treeRoot string, env expand.Environ, command string) (string, error) {
+	if !filepath.IsAbs(command) {
+		path := filepath.Join(treeRoot, command)
+
+		info, err := os.Stat(path)
+		if err == nil && info.Mode().IsRegular() && info.Mode().Perm()&0o111 != 0 {
+			return path, nil
+		}
+	}
+
+	return interp.LookPathDir(treeRoot, env, command) //nolint:wrapcheck
 }
 
 // newFormatter is used to create a new Formatter.
@@ -152,14 +344,57 @@ func newFormatter(
 	f.config = cfg
 	f.workingDir = treeRoot
 
-	// test if the formatter is available
-	executable, err := interp.LookPathDir(treeRoot, env, cfg.Command)
+	// test if the formatter is available, which for a shell snippet means the shell itself
+	command := cfg.Command
+	if cfg.Shell {
+		command = "sh"
+	}
+
+	executable, err := lookPath(treeRoot, env, command)
 	if err != nil {
-		return nil, fmt.Errorf("%w: error looking up '%s'", ErrCommandNotFound, cfg.Command)
+		return nil, fmt.Errorf("%w: error looking up '%s'", ErrCommandNotFound, command)
 	}
 
 	f.executable = executable
 
+	// stat the executable once, rather than every time the formatter is hashed
+	f.executableInfo, err = os.Lstat(executable)
+	if err != nil {
+		return nil, fmt.Errorf("failed to stat formatter executable: %w", err)
+	}
+
+	// the version is checked against the executable which will be run, so a shell snippet has nothing to check
+	if cfg.MinVersion != "" {
+		if cfg.Shell {
+			return nil, errors.New("min-version cannot be used with a shell formatter")
+		}
+
+		if _, ok := parseVersion(cfg.MinVersion); !ok {
+			return nil, fmt.Errorf("invalid min-version %q", cfg.MinVersion)
+		}
+	}
+
+	if f.maxFileSize, err = config.ParseSize(cfg.MaxFileSize); err != nil {
+		return nil, fmt.Errorf("invalid max-file-size: %w", err)
+	}
+
+	if cfg.CaptureOutput != "" {
+		path := cfg.CaptureOutput
+		if !filepath.IsAbs(path) {
+			path = filepath.Join(treeRoot, path)
+		}
+
+		f.capture = &outputCapture{path: path}
+	}
+
+	for _, path := range cfg.ConfigFiles {
+		if !filepath.IsAbs(path) {
+			path = filepath.Join(treeRoot, path)
+		}
+
+		f.configFiles = append(f.configFiles, path)
+	}
+
 	// initialise internal state
 	if cfg.Priority > 0 {
 		f.log = log.WithPrefix(fmt.Sprintf("formatter | %s[%d]", name, cfg.Priority))
@@ -167,19 +402,26 @@ func newFormatter(
 		f.log = log.WithPrefix("formatter | " + name)
 	}
 
-	// check there is at least one include
-	if len(cfg.Includes) == 0 {
+	if cfg.Shell && runtime.GOOS == "windows" {
+		f.log.Warn("shell formatters require sh, which may not be available on Windows")
+	}
+
+	// check there is at least one include or filename, unless the formatter is only selected by language
+	if len(cfg.Includes) == 0 && len(cfg.Filenames) == 0 && len(cfg.Languages) == 0 {
 		return nil, fmt.Errorf("formatter '%v' has no includes", f.name)
 	}
 
-	f.includes, err = compileGlobs(cfg.Includes)
+	caseInsensitive := cfg.CaseInsensitive != nil && *cfg.CaseInsensitive
+	ignoreExtensionCase := cfg.IgnoreExtensionCase != nil && *cfg.IgnoreExtensionCase
+
+	f.includes, err = compileGlobs("include", cfg.Includes, caseInsensitive, ignoreExtensionCase)
 	if err != nil {
-		return nil, fmt.Errorf("failed to compile formatter '%v' includes: %w", f.name, err)
+		return nil, fmt.Errorf("formatter '%v': %w", f.name, err)
 	}
 
-	f.excludes, err = compileGlobs(cfg.Excludes)
+	f.excludes, err = compileGlobs("exclude", cfg.Excludes, caseInsensitive, ignoreExtensionCase)
 	if err != nil {
-		return nil, fmt.Errorf("failed to compile formatter '%v' excludes: %w", f.name, err)
+		return nil, fmt.Errorf("formatter '%v': %w", f.name, err)
 	}
 
 	return &f, nil
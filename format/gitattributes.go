@@ -0,0 +1,121 @@
+package format
+
+import (
+	"bufio"
+	"errors"
+	"fmt"
+	"io/fs"
+	"os"
+	"path/filepath"
+	"strings"
+
+	"github.com/gobwas/glob"
+)
+
+// generatedAttr is the attribute used in .gitattributes to mark files as generated.
+// See https://github.com/github-linguist/linguist/blob/main/docs/overrides.md.
+const generatedAttr = "linguist-generated"
+
+// generatedRule is a line from a .gitattributes file which sets or unsets generatedAttr.
+type generatedRule struct {
+	pattern   string
+	glob      glob.Glob
+	basename  bool // patterns without a slash are matched against the file name at any depth
+	generated bool
+}
+
+// generatedRules are the rules from a .gitattributes file concerning generatedAttr, in the order they were defined.
+type generatedRules []generatedRule
+
+// readGeneratedRules parses the .gitattributes file at path, returning the rules which set or unset generatedAttr.
+// A missing file results in no rules.
+func readGeneratedRules(path string) (generatedRules, error) {
+	file, err := os.Open(path)
+	if errors.Is(err, fs.ErrNotExist) {
+		return nil, nil
+	} else if err != nil {
+		return nil, fmt.Errorf("failed to open %s: %w", path, err)
+	}
+	defer file.Close()
+
+	var rules generatedRules
+
+	scanner := bufio.NewScanner(file)
+
+	for lineNumber := 1; scanner.Scan(); lineNumber++ {
+		fields := strings.Fields(scanner.Text())
+
+		// skip blank lines, comments, and quoted patterns which we do not support
+		if len(fields) < 2 || strings.HasPrefix(fields[0], "#") || strings.HasPrefix(fields[0], `"`) {
+			continue
+		}
+
+		pattern := fields[0]
+
+		// patterns matching directories do not apply to the files within them
+		if strings.HasSuffix(pattern, "/") {
+			continue
+		}
+
+		for _, attr := range fields[1:] {
+			var generated bool
+
+			switch attr {
+			case generatedAttr, generatedAttr + "=true":
+				generated = true
+			case "-" + generatedAttr, "!" + generatedAttr, generatedAttr + "=false":
+				generated = false
+			default:
+				continue
+			}
+
+			basename := !strings.Contains(pattern, "/")
+
+			g, err := glob.Compile(normalizePath(strings.TrimPrefix(pattern, "/")), '/')
+			if err != nil {
+				return nil, fmt.Errorf("failed to compile pattern '%s' on line %d of %s: %w", pattern, lineNumber, path, err)
+			}
+
+			rules = append(rules, generatedRule{
+				pattern:   pattern,
+				glob:      g,
+				basename:  basename,
+				generated: generated,
+			})
+		}
+	}
+
+	if err = scanner.Err(); err != nil {
+		return nil, fmt.Errorf("failed to read %s: %w", path, err)
+	}
+
+	return rules, nil
+}
+
+// match returns the pattern which marks the path, relative to the tree root, as generated.
+// As with git, the last matching rule wins, so an empty string is returned if the path is not matched or its last
+// matching rule unsets the attribute.
+func (r generatedRules) match(path string) string {
+	path = normalizePath(filepath.ToSlash(path))
+
+	for idx := len(r) - 1; idx >= 0; idx-- {
+		rule := r[idx]
+
+		target := path
+		if rule.basename {
+			target = filepath.Base(path)
+		}
+
+		if !rule.glob.Match(target) {
+			continue
+		}
+
+		if rule.generated {
+			return rule.pattern
+		}
+
+		return ""
+	}
+
+	return ""
+}
@@ -0,0 +1,54 @@
+//nolint:testpackage
+package format
+
+import (
+	"os"
+	"path/filepath"
+	"testing"
+
+	"github.com/stretchr/testify/require"
+)
+
+func TestGeneratedRules(t *testing.T) {
+	as := require.New(t)
+
+	path := filepath.Join(t.TempDir(), ".gitattributes")
+
+	// a missing file has no rules
+	rules, err := readGeneratedRules(path)
+	as.NoError(err)
+	as.Empty(rules.match("foo.pb.go"))
+
+	as.NoError(os.WriteFile(path, []byte(`
+# generated code
+*.pb.go linguist-generated
+/gen/** linguist-generated=true
+gen/keep.go -linguist-generated
+vendor/ linguist-generated
+*.lock linguist-vendored
+docs/api.md text linguist-generated eol=lf
+`), 0o600))
+
+	rules, err = readGeneratedRules(path)
+	as.NoError(err)
+
+	// patterns without a slash match the file name at any depth
+	as.Equal("*.pb.go", rules.match("foo.pb.go"))
+	as.Equal("*.pb.go", rules.match("api/v1/foo.pb.go"))
+	as.Empty(rules.match("foo.go"))
+
+	// patterns with a slash are anchored to the tree root
+	as.Equal("/gen/**", rules.match("gen/a.go"))
+	as.Equal("/gen/**", rules.match("gen/nested/b.go"))
+	as.Empty(rules.match("src/gen/a.go"))
+
+	// the last matching rule wins
+	as.Empty(rules.match("gen/keep.go"))
+
+	// directory patterns and other attributes are ignored
+	as.Empty(rules.match("vendor/foo.go"))
+	as.Empty(rules.match("Cargo.lock"))
+
+	// the attribute may appear alongside others
+	as.Equal("docs/api.md", rules.match("docs/api.md"))
+}
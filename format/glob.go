@@ -2,18 +2,83 @@ package format
 
 import (
 	"fmt"
+	"path/filepath"
+	"slices"
+	"strings"
 
 	"github.com/gobwas/glob"
+	"golang.org/x/text/unicode/norm"
 )
 
+// caseInsensitiveGlob matches paths regardless of case, by lower-casing them before delegating to a glob compiled
+// from a lower-cased pattern.
+type caseInsensitiveGlob struct {
+	glob.Glob
+}
+
+func (g caseInsensitiveGlob) Match(path string) bool {
+	return g.Glob.Match(strings.ToLower(path))
+}
+
+// ignoreExtensionCaseGlob matches paths regardless of the case of their final extension, by lower-casing only
+// the extension before delegating to a glob compiled from a pattern whose final extension was lower-cased.
+type ignoreExtensionCaseGlob struct {
+	glob.Glob
+}
+
+func (g ignoreExtensionCaseGlob) Match(path string) bool {
+	return g.Glob.Match(lowerExtension(path))
+}
+
+// lowerExtension lower-cases the final extension of path, as returned by filepath.Ext, leaving the rest unchanged.
+func lowerExtension(path string) string {
+	ext := filepath.Ext(path)
+	if ext == "" {
+		return path
+	}
+
+	return path[:len(path)-len(ext)] + strings.ToLower(ext)
+}
+
 // compileGlobs prepares the globs, where the patterns are all right-matching.
-func compileGlobs(patterns []string) ([]glob.Glob, error) {
+// A pattern ending in a slash, such as `src/`, matches everything beneath that directory.
+// Patterns are normalized to NFC, see normalizePath, and when caseInsensitive is true, they match regardless of case.
+// When only ignoreExtensionCase is true, the final extension matches regardless of case, e.g. `*.py` matches
+// `FILE.PY`, while the rest of the path is still matched case-sensitively.
+// The kind of pattern, such as include or exclude, is used to describe an invalid pattern in the returned error.
+func compileGlobs(
+	kind string,
+	patterns []string,
+	caseInsensitive, ignoreExtensionCase bool,
+) ([]glob.Glob, error) {
+	ignoreExtensionCase = ignoreExtensionCase && !caseInsensitive
+
 	globs := make([]glob.Glob, len(patterns))
 
-	for i, pattern := range patterns {
+	for i, original := range patterns {
+		pattern := norm.NFC.String(original)
+
+		switch {
+		case caseInsensitive:
+			pattern = strings.ToLower(pattern)
+		case ignoreExtensionCase:
+			pattern = lowerExtension(pattern)
+		}
+
+		if strings.HasSuffix(pattern, "/") {
+			pattern += "**"
+		}
+
 		g, err := glob.Compile(pattern)
 		if err != nil {
-			return nil, fmt.Errorf("failed to compile include pattern '%v': %w", pattern, err)
+			return nil, fmt.Errorf("invalid %s pattern '%v': %w", kind, original, err)
+		}
+
+		switch {
+		case caseInsensitive:
+			g = caseInsensitiveGlob{g}
+		case ignoreExtensionCase:
+			g = ignoreExtensionCaseGlob{g}
 		}
 
 		globs[i] = g
@@ -22,7 +87,41 @@ func compileGlobs(patterns []string) ([]glob.Glob, error) {
 	return globs, nil
 }
 
+// normalizePath converts path to Unicode Normalization Form C before it is matched against globs.
+// Some filesystems, such as HFS+ and APFS on macOS, may store names decomposed (NFD) whereas patterns are usually
+// written composed (NFC), so `café.txt` would otherwise fail to match depending on how the file was created.
+// Paths which are already NFC, including all ASCII paths, are returned unchanged without allocating.
+func normalizePath(path string) string {
+	return norm.NFC.String(path)
+}
+
+// matchingPattern returns the first pattern whose glob matches path, or an empty string if there is none.
+// The globs must have been compiled from patterns.
+func matchingPattern(path string, patterns []string, globs []glob.Glob) string {
+	path = normalizePath(path)
+
+	for idx := range globs {
+		if globs[idx].Match(path) {
+			return patterns[idx]
+		}
+	}
+
+	return ""
+}
+
+// matchingFilename returns the entry of filenames which is exactly the base name of path, if any.
+func matchingFilename(path string, filenames []string) string {
+	name := filepath.Base(path)
+	if slices.Contains(filenames, name) {
+		return name
+	}
+
+	return ""
+}
+
 func pathMatches(path string, globs []glob.Glob) bool {
+	path = normalizePath(path)
+
 	for idx := range globs {
 		if globs[idx].Match(path) {
 			return true
@@ -17,24 +17,108 @@ func TestGlobs(t *testing.T) {
 	)
 
 	// File extension
-	globs, err = compileGlobs([]string{"*.txt"})
+	globs, err = compileGlobs("include", []string{"*.txt"}, false, false)
 	r.NoError(err)
 	r.True(pathMatches("test/foo/bar.txt", globs))
 	r.False(pathMatches("test/foo/bar.txtz", globs))
 	r.False(pathMatches("test/foo/bar.flob", globs))
 
 	// Prefix matching
-	globs, err = compileGlobs([]string{"test/*"})
+	globs, err = compileGlobs("include", []string{"test/*"}, false, false)
 	r.NoError(err)
 	r.True(pathMatches("test/bar.txt", globs))
 	r.True(pathMatches("test/foo/bar.txt", globs))
 	r.False(pathMatches("/test/foo/bar.txt", globs))
 
+	// Directory trees
+	globs, err = compileGlobs("include", []string{"src/"}, false, false)
+	r.NoError(err)
+	r.True(pathMatches("src/a.rs", globs))
+	r.True(pathMatches("src/nested/b.rs", globs))
+	r.False(pathMatches("src2/c.rs", globs))
+	r.False(pathMatches("src", globs))
+	r.False(pathMatches("lib/src/a.rs", globs))
+
+	// Patterns starting with a directory are anchored to the tree root
+	globs, err = compileGlobs("exclude", []string{"docs/generated/**"}, false, false)
+	r.NoError(err)
+	r.True(pathMatches("docs/generated/api.md", globs))
+	r.True(pathMatches("docs/generated/nested/api.md", globs))
+	r.False(pathMatches("src/generated/api.rs", globs))
+	r.False(pathMatches("lib/docs/generated/api.md", globs))
+
+	// whereas those starting with a wildcard float
+	globs, err = compileGlobs("exclude", []string{"*/generated/**"}, false, false)
+	r.NoError(err)
+	r.True(pathMatches("docs/generated/api.md", globs))
+	r.True(pathMatches("src/generated/api.rs", globs))
+	r.True(pathMatches("lib/docs/generated/api.md", globs))
+	r.False(pathMatches("generated/api.rs", globs))
+
 	// Exact matches
-	// File extension
-	globs, err = compileGlobs([]string{"LICENSE"})
+	globs, err = compileGlobs("include", []string{"LICENSE"}, false, false)
 	r.NoError(err)
 	r.True(pathMatches("LICENSE", globs))
 	r.False(pathMatches("test/LICENSE", globs))
 	r.False(pathMatches("LICENSE.txt", globs))
+
+	// a bare name is not expanded to match in subdirectories, so one which should also pairs it with a wildcard
+	globs, err = compileGlobs("include", []string{"Makefile"}, false, false)
+	r.NoError(err)
+	r.True(pathMatches("Makefile", globs))
+	r.False(pathMatches("src/Makefile", globs))
+
+	globs, err = compileGlobs("include", []string{"Makefile", "*/Makefile"}, false, false)
+	r.NoError(err)
+	r.True(pathMatches("Makefile", globs))
+	r.True(pathMatches("src/Makefile", globs))
+	r.True(pathMatches("src/nested/Makefile", globs))
+	r.False(pathMatches("src/Makefile.am", globs))
+
+	// Unicode normalization
+	// "é" may be a single code point (NFC) or "e" followed by a combining acute accent (NFD)
+	nfc := "caf\u00e9.txt"
+	nfd := "cafe\u0301.txt"
+
+	globs, err = compileGlobs("include", []string{nfc}, false, false)
+	r.NoError(err)
+	r.True(pathMatches(nfc, globs))
+	r.True(pathMatches(nfd, globs))
+	r.Equal(nfc, matchingPattern(nfd, []string{nfc}, globs))
+
+	globs, err = compileGlobs("include", []string{"docs/" + nfd}, false, false)
+	r.NoError(err)
+	r.True(pathMatches("docs/"+nfc, globs))
+	r.True(pathMatches("docs/"+nfd, globs))
+
+	// Case sensitivity
+	globs, err = compileGlobs("include", []string{"*.PY", "Docs/"}, false, false)
+	r.NoError(err)
+	r.False(pathMatches("main.py", globs))
+	r.True(pathMatches("main.PY", globs))
+	r.False(pathMatches("docs/index.md", globs))
+
+	globs, err = compileGlobs("include", []string{"*.PY", "Docs/"}, true, false)
+	r.NoError(err)
+	r.True(pathMatches("main.py", globs))
+	r.True(pathMatches("src/Main.Py", globs))
+	r.True(pathMatches("docs/index.md", globs))
+	r.True(pathMatches("DOCS/index.md", globs))
+	r.False(pathMatches("main.rs", globs))
+
+	// Extension case
+	globs, err = compileGlobs("include", []string{"*.py", "Docs/*.MD"}, false, true)
+	r.NoError(err)
+	r.True(pathMatches("main.py", globs))
+	r.True(pathMatches("FILE.PY", globs))
+	r.True(pathMatches("src/Main.Py", globs))
+	r.False(pathMatches("main.pyc", globs))
+	r.True(pathMatches("Docs/index.md", globs))
+	r.True(pathMatches("Docs/index.Md", globs))
+	r.False(pathMatches("docs/index.md", globs))
+	r.False(pathMatches("py", globs))
+
+	// Invalid patterns are reported as written, even when compiled case-insensitively
+	_, err = compileGlobs("exclude", []string{"*.go", "SRC/[*"}, true, false)
+	r.ErrorContains(err, "invalid exclude pattern 'SRC/[*'")
 }
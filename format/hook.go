@@ -0,0 +1,29 @@
+package format
+
+import (
+	"context"
+	"fmt"
+	"os"
+	"os/exec"
+
+	"github.com/charmbracelet/log"
+)
+
+// runHook executes a pre or post hook as a shell snippet with `sh -c`, using the tree root as its working directory.
+// It returns an error if the hook exits with a non-zero status.
+func runHook(ctx context.Context, name string, command string, treeRoot string) error {
+	log.Debugf("running %s: %s", name, command)
+
+	cmd := exec.CommandContext(ctx, "sh", "-c", command)
+	cmd.Dir = treeRoot
+
+	// hooks log to stderr so their output cannot be confused with formatted content or --changed-files on stdout
+	cmd.Stdout = os.Stderr
+	cmd.Stderr = os.Stderr
+
+	if err := cmd.Run(); err != nil {
+		return fmt.Errorf("%s failed: %w", name, err)
+	}
+
+	return nil
+}
@@ -6,9 +6,12 @@ import (
 	"context"
 	"crypto/md5" //nolint:gosec
 	"fmt"
+	"io/fs"
+	"os"
 	"runtime"
 	"slices"
 	"strings"
+	"sync"
 	"sync/atomic"
 	"time"
 
@@ -52,15 +55,211 @@ type scheduler struct {
 	batchSize   int
 	changeLevel log.Level
 	formatters  map[string]*Formatter
+	// sortFiles indicates batches are only scheduled once every file has been submitted, with the files sorted by
+	// their relative paths, so each formatter invocation receives the same files in the same order regardless of the
+	// order in which they were read
+	sortFiles bool
 
 	eg    *errgroup.Group
 	stats *stats.Stats
 
+	// exclusive is held for writing whilst a single-threaded formatter is applied, and for reading whilst any other
+	// formatter is applied, see apply
+	exclusive sync.RWMutex
+
 	batches    map[batchKey]batch
 	signatures map[batchKey]signature
 
 	// formatError indicates if at least one formatting error occurred
 	formatError *atomic.Bool
+
+	// changed records the relative paths of files which were changed by formatting, along with the formatters which
+	// changed them when attributeAll is set
+	changedLock sync.Mutex
+	changed     map[string][]string
+	// attributeAll indicates changes are attributed to every formatter, rather than only those which are tracked
+	attributeAll bool
+	// diff indicates the content of each file is read before it is formatted, so that a unified diff can be recorded
+	// in diffs for each file which changed
+	diff  bool
+	diffs map[string]string
+	// tracked contains the names of formatters whose changes are attributed to them individually, and changedBy
+	// records which of them changed at least one file
+	tracked   map[string]bool
+	changedBy map[string]struct{}
+
+	// preHook, if set, is run once before any formatters are applied
+	preHook    func(ctx context.Context) error
+	preHookRan bool
+	// scheduled indicates if at least one batch has been scheduled for processing
+	scheduled bool
+
+	// deadline, if set, is the time after which no more batches are started, see skipBatch
+	deadline time.Time
+	// remaining counts the files in batches which were skipped because the deadline had passed
+	remaining atomic.Int64
+
+	// confirm, if set, is asked whether to apply the changes made by formatting once every batch has been formatted.
+	// Until then, formatters are applied to copies of the files, which are held in pending, see formatCopies.
+	confirm        ConfirmFunc
+	pendingLock    sync.Mutex
+	pending        []pendingBatch
+	pendingChanges map[string]int
+}
+
+// runPreHook runs the pre-hook, unless there is none or it has already been run.
+func (s *scheduler) runPreHook(ctx context.Context) error {
+	if s.preHook == nil || s.preHookRan {
+		return nil
+	}
+
+	s.preHookRan = true
+
+	return s.preHook(ctx)
+}
+
+// recordChange notes that the file at relPath was changed by formatting.
+func (s *scheduler) recordChange(relPath string) {
+	s.changedLock.Lock()
+	defer s.changedLock.Unlock()
+
+	if _, ok := s.changed[relPath]; !ok {
+		s.changed[relPath] = nil
+	}
+}
+
+// recordChangeTo notes that the file at relPath was changed by the formatter with the given name.
+func (s *scheduler) recordChangeTo(relPath string, name string) {
+	s.changedLock.Lock()
+	defer s.changedLock.Unlock()
+
+	s.changed[relPath] = append(s.changed[relPath], name)
+}
+
+// recordDiff notes the unified diff of the change made to the file at relPath.
+func (s *scheduler) recordDiff(relPath string, diff string) {
+	s.changedLock.Lock()
+	defer s.changedLock.Unlock()
+
+	s.diffs[relPath] = diff
+}
+
+// recordChangeBy notes that the formatter with the given name changed at least one file.
+func (s *scheduler) recordChangeBy(name string) {
+	s.changedLock.Lock()
+	defer s.changedLock.Unlock()
+
+	s.changedBy[name] = struct{}{}
+}
+
+// changedByTracked returns a sorted list of the tracked formatters which changed at least one file.
+func (s *scheduler) changedByTracked() []string {
+	s.changedLock.Lock()
+	defer s.changedLock.Unlock()
+
+	names := make([]string, 0, len(s.changedBy))
+	for name := range s.changedBy {
+		names = append(names, name)
+	}
+
+	slices.Sort(names)
+
+	return names
+}
+
+// tracksAny returns true if any of the formatters in the sequence are tracked, or all changes are attributed.
+func (s *scheduler) tracksAny(sequence []string) bool {
+	return s.attributeAll || slices.ContainsFunc(sequence, func(name string) bool {
+		return s.tracked[name]
+	})
+}
+
+// attributeChanges checks which files in batch were changed by the formatter with the given name, compared with their
+// previous state in infos, recording the change against the formatter if it is tracked, and against the file if all
+// changes are attributed.
+// infos is updated with the current state of each file, ready for the next formatter in the sequence.
+func (s *scheduler) attributeChanges(name string, batch []*walk.File, infos []fs.FileInfo) error {
+	for idx, file := range batch {
+		previous := &walk.File{Path: file.Path, Info: infos[idx]}
+
+		changed, current, err := previous.Stat()
+		if err != nil {
+			return fmt.Errorf("failed to stat file: %w", err)
+		}
+
+		if !changed {
+			continue
+		}
+
+		infos[idx] = current
+
+		if s.tracked[name] {
+			s.recordChangeBy(name)
+		}
+
+		if s.attributeAll {
+			s.recordChangeTo(file.RelPath, name)
+		}
+	}
+
+	return nil
+}
+
+// diffContents records a unified diff for each file in batch whose content differs from its original content, given in
+// the same order.
+func (s *scheduler) diffContents(batch []*walk.File, originals [][]byte) error {
+	for idx, file := range batch {
+		current, err := os.ReadFile(file.Path)
+		if err != nil {
+			return fmt.Errorf("failed to read %s: %w", file.RelPath, err)
+		}
+
+		if bytes.Equal(originals[idx], current) {
+			continue
+		}
+
+		diff, err := unifiedDiff(file.RelPath, originals[idx], current)
+		if err != nil {
+			return err
+		}
+
+		s.recordDiff(file.RelPath, diff)
+	}
+
+	return nil
+}
+
+// skipBatch releases the files in batch without formatting them, as the deadline has passed.
+// The files are released without updating the cache, so they are picked up again by the next run.
+func (s *scheduler) skipBatch(ctx context.Context, batch []*walk.File) error {
+	s.remaining.Add(int64(len(batch)))
+
+	releaseCtx := walk.SetNoCache(ctx, true)
+
+	for _, file := range batch {
+		if err := file.Release(releaseCtx); err != nil {
+			return fmt.Errorf("failed to release file: %w", err)
+		}
+	}
+
+	return nil
+}
+
+// changes returns the files which were changed by formatting, sorted by their relative paths.
+func (s *scheduler) changes() []Change {
+	s.changedLock.Lock()
+	defer s.changedLock.Unlock()
+
+	changes := make([]Change, 0, len(s.changed))
+	for path, formatters := range s.changed {
+		changes = append(changes, Change{Path: path, Formatters: formatters, Diff: s.diffs[path]})
+	}
+
+	slices.SortFunc(changes, func(a, b Change) int {
+		return cmp.Compare(a.Path, b.Path)
+	})
+
+	return changes
 }
 
 func (s *scheduler) formattersSignature(key batchKey, formatters []*Formatter) ([]byte, error) {
@@ -93,7 +292,7 @@ func (s *scheduler) submit(
 	file *walk.File,
 	matches []*Formatter,
 ) (accepted bool, err error) {
-	slices.SortFunc(matches, formatterSortFunc)
+	// matches are already in the order in which they should be applied, see CompositeFormatter.match
 
 	// construct a batch key based on the sequence of formatters
 	key := newBatchKey(matches)
@@ -116,6 +315,8 @@ func (s *scheduler) submit(
 		// We know from the hash signature that we have already applied this sequence of formatters (and their config) to
 		// this file.
 		// When we applied the formatters, the file had the same mod time and file size.
+		s.stats.Add(stats.CacheHits, 1)
+
 		return false, nil
 	}
 
@@ -126,9 +327,12 @@ func (s *scheduler) submit(
 	// append to the batch
 	s.batches[key] = append(s.batches[key], file)
 
-	// schedule the batch for processing if it's full
-	if len(s.batches[key]) == s.batchSize {
-		s.schedule(ctx, key, s.batches[key])
+	// schedule the batch for processing if it's full, a batch size of 0 means we wait until close, as does sorting
+	if s.batchSize > 0 && !s.sortFiles && len(s.batches[key]) == s.batchSize {
+		if err := s.schedule(ctx, key, s.batches[key]); err != nil {
+			return false, err
+		}
+
 		// reset the batch
 		s.batches[key] = make([]*walk.File, 0, s.batchSize)
 	}
@@ -137,17 +341,71 @@ func (s *scheduler) submit(
 }
 
 // schedule begins processing a batch in the background.
-func (s *scheduler) schedule(ctx context.Context, key batchKey, batch []*walk.File) {
+// The pre-hook is run before the first batch is scheduled.
+func (s *scheduler) schedule(ctx context.Context, key batchKey, batch []*walk.File) error {
+	s.scheduled = true
+
+	if err := s.runPreHook(ctx); err != nil {
+		return err
+	}
+
 	s.eg.Go(func() error {
-		var formatErrors []error
+		// batches which had to wait until after the deadline to start are left for the next run, whilst those already
+		// in flight are allowed to finish
+		if !s.deadline.IsZero() && time.Now().After(s.deadline) {
+			return s.skipBatch(ctx, batch)
+		}
+
+		// when changes must be confirmed, the formatters are applied to copies, which are kept until close
+		if s.confirm != nil {
+			return s.formatCopies(ctx, key, batch)
+		}
+
+		var (
+			formatErrors []error
+			// the state of each file before the next formatter is applied, used to attribute changes to tracked
+			// formatters
+			infos []fs.FileInfo
+		)
+
+		sequence := key.sequence()
+
+		// the original content is kept to diff against, once every formatter has been applied
+		var originals [][]byte
+
+		if s.diff {
+			var err error
+			if originals, err = readContents(batch); err != nil {
+				return err
+			}
+		}
+
+		if s.tracksAny(sequence) {
+			infos = make([]fs.FileInfo, len(batch))
+			for idx, file := range batch {
+				infos[idx] = file.Info
+			}
+		}
 
 		// apply the formatters in sequence
-		for _, name := range key.sequence() {
+		for _, name := range sequence {
 			formatter := s.formatters[name]
 
-			if err := formatter.Apply(ctx, batch); err != nil {
+			if err := s.apply(ctx, formatter, batch); err != nil {
 				formatErrors = append(formatErrors, err)
 			}
+
+			if infos != nil {
+				if err := s.attributeChanges(name, batch, infos); err != nil {
+					return err
+				}
+			}
+		}
+
+		if originals != nil {
+			if err := s.diffContents(batch, originals); err != nil {
+				return err
+			}
 		}
 
 		// record if a format error occurred
@@ -156,73 +414,136 @@ func (s *scheduler) schedule(ctx context.Context, key batchKey, batch []*walk.Fi
 		// update overall error tracking
 		s.formatError.CompareAndSwap(false, hasErrors)
 
-		if !hasErrors {
-			// record that the file was formatted
-			s.stats.Add(stats.Formatted, len(batch))
-		}
+		return s.finish(ctx, batch, hasErrors)
+	})
 
-		// Create a release context.
-		// We set no-cache based on whether any formatting errors occurred in this batch.
-		// This is to communicate with any caching layer, if used when reading files for this batch, that it should not
-		// update the state of any file in this batch, as we want to re-process them in later invocations.
-		releaseCtx := walk.SetNoCache(ctx, hasErrors)
-
-		// post-processing
-		for _, file := range batch {
-			// check if the file has changed
-			changed, newInfo, err := file.Stat()
-			if err != nil {
-				return fmt.Errorf("failed to stat file: %w", err)
-			}
+	return nil
+}
 
-			if changed {
-				// record the change
-				s.stats.Add(stats.Changed, 1)
-
-				// log the change (useful for diagnosing issues)
-				log.Log(
-					s.changeLevel, "file has changed",
-					"path", file.RelPath,
-					"prev_size", file.Info.Size(),
-					"prev_mod_time", file.Info.ModTime().Truncate(time.Second),
-					"current_size", newInfo.Size(),
-					"current_mod_time", newInfo.ModTime().Truncate(time.Second),
-				)
-
-				// record the new file info
-				file.FormattedInfo = newInfo
-			}
+// apply applies formatter to batch. Single-threaded formatters wait until no other formatter is being applied, and
+// no other formatter starts until they have finished, whilst all other formatters may be applied concurrently.
+func (s *scheduler) apply(ctx context.Context, formatter *Formatter, batch []*walk.File) error {
+	if formatter.SingleThreaded() {
+		s.exclusive.Lock()
+		defer s.exclusive.Unlock()
+	} else {
+		s.exclusive.RLock()
+		defer s.exclusive.RUnlock()
+	}
 
-			// release the file as there is no further processing to be done on it
-			if err := file.Release(releaseCtx); err != nil {
-				return fmt.Errorf("failed to release file: %w", err)
-			}
+	return formatter.Apply(ctx, batch)
+}
+
+// finish records the outcome of formatting batch, then releases its files.
+func (s *scheduler) finish(ctx context.Context, batch []*walk.File, hasErrors bool) error {
+	if !hasErrors {
+		// record that the file was formatted
+		s.stats.Add(stats.Formatted, len(batch))
+	}
+
+	// Create a release context.
+	// We set no-cache based on whether any formatting errors occurred in this batch.
+	// This is to communicate with any caching layer, if used when reading files for this batch, that it should not
+	// update the state of any file in this batch, as we want to re-process them in later invocations.
+	releaseCtx := walk.SetNoCache(ctx, hasErrors)
+
+	// post-processing
+	for _, file := range batch {
+		// check if the file has changed
+		changed, newInfo, err := file.Stat()
+		if err != nil {
+			return fmt.Errorf("failed to stat file: %w", err)
 		}
 
-		return nil
-	})
+		if changed {
+			// record the change
+			s.stats.Add(stats.Changed, 1)
+			s.recordChange(file.RelPath)
+
+			// log the change (useful for diagnosing issues)
+			log.Log(
+				s.changeLevel, "file has changed",
+				"path", file.RelPath,
+				"prev_size", file.Info.Size(),
+				"prev_mod_time", file.Info.ModTime().Truncate(time.Second),
+				"current_size", newInfo.Size(),
+				"current_mod_time", newInfo.ModTime().Truncate(time.Second),
+			)
+
+			// record the new file info
+			file.FormattedInfo = newInfo
+		}
+
+		// release the file as there is no further processing to be done on it
+		if err := file.Release(releaseCtx); err != nil {
+			return fmt.Errorf("failed to release file: %w", err)
+		}
+	}
+
+	return nil
 }
 
 func (s *scheduler) close(ctx context.Context) error {
-	// schedule any partial batches that remain
-	for key, batch := range s.batches {
-		if len(batch) > 0 {
-			s.schedule(ctx, key, batch)
+	// schedule any partial batches that remain, in a deterministic order
+	keys := make([]batchKey, 0, len(s.batches))
+	for key := range s.batches {
+		keys = append(keys, key)
+	}
+
+	slices.Sort(keys)
+
+	for _, key := range keys {
+		batch := s.batches[key]
+		if len(batch) == 0 {
+			continue
+		}
+
+		if !s.sortFiles {
+			if err := s.schedule(ctx, key, batch); err != nil {
+				return err
+			}
+
+			continue
+		}
+
+		// every file has been submitted, so the batches can be formed from the sorted files
+		slices.SortFunc(batch, func(a, b *walk.File) int {
+			return cmp.Compare(a.RelPath, b.RelPath)
+		})
+
+		size := s.batchSize
+		if size <= 0 {
+			size = len(batch)
+		}
+
+		for start := 0; start < len(batch); start += size {
+			if err := s.schedule(ctx, key, batch[start:min(start+size, len(batch))]); err != nil {
+				return err
+			}
 		}
 	}
 
 	// wait for processing to complete
 	if err := s.eg.Wait(); err != nil {
 		return fmt.Errorf("failed to wait for formatters: %w", err)
-	} else if s.formatError.Load() {
+	}
+
+	// changes to the copies are only applied if formatting succeeded and they are confirmed
+	if s.confirm != nil {
+		if err := s.resolvePending(ctx, !s.formatError.Load()); err != nil {
+			return err
+		}
+	}
+
+	if s.formatError.Load() {
 		return ErrFormattingFailures
 	}
 
 	return nil
 }
 
-// formatterSortFunc sorts formatters by their priority in ascending order; ties are resolved by lexicographic order of
-// names.
+// formatterSortFunc sorts formatters by their priority in ascending order; ties are resolved by the order in which they
+// were declared, when config-order is enabled, and then by lexicographic order of names.
 func formatterSortFunc(a, b *Formatter) int {
 	// sort by priority in ascending order
 	priorityA := a.Priority()
@@ -230,7 +551,12 @@ func formatterSortFunc(a, b *Formatter) int {
 
 	result := priorityA - priorityB
 	if result == 0 {
-		// formatters with the same priority are sorted lexicographically to ensure a deterministic outcome
+		// when config-order is enabled, formatters with the same priority are sorted in the order they were declared
+		result = a.config.Order - b.config.Order
+	}
+
+	if result == 0 {
+		// otherwise they are sorted lexicographically to ensure a deterministic outcome
 		result = cmp.Compare(a.Name(), b.Name())
 	}
 
@@ -240,12 +566,26 @@ func formatterSortFunc(a, b *Formatter) int {
 func newScheduler(
 	statz *stats.Stats,
 	batchSize int,
+	sequential bool,
 	changeLevel log.Level,
 	formatters map[string]*Formatter,
+	preHook func(ctx context.Context) error,
+	tracked []string,
 ) *scheduler {
+	trackedSet := make(map[string]bool, len(tracked))
+	for _, name := range tracked {
+		trackedSet[name] = true
+	}
+
 	eg := &errgroup.Group{}
-	// we use a simple heuristic to avoid too much contention by limiting the concurrency to runtime.NumCPU()
-	eg.SetLimit(runtime.NumCPU())
+
+	if sequential {
+		// process one batch at a time, so the output from formatters does not interleave
+		eg.SetLimit(1)
+	} else {
+		// we use a simple heuristic to avoid too much contention by limiting the concurrency to runtime.NumCPU()
+		eg.SetLimit(runtime.NumCPU())
+	}
 
 	return &scheduler{
 		batchSize:   batchSize,
@@ -258,5 +598,13 @@ func newScheduler(
 		batches:     make(map[batchKey]batch),
 		signatures:  make(map[batchKey]signature),
 		formatError: &atomic.Bool{},
+		changed:     make(map[string][]string),
+		diffs:       make(map[string]string),
+		tracked:     trackedSet,
+		changedBy:   make(map[string]struct{}),
+
+		pendingChanges: make(map[string]int),
+
+		preHook: preHook,
 	}
 }
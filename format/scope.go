@@ -0,0 +1,97 @@
+package format
+
+import (
+	"os"
+	"path/filepath"
+	"slices"
+	"time"
+
+	"github.com/charmbracelet/log"
+	"github.com/numtide/treefmt/v2/walk"
+)
+
+// maxScopeSample limits how many files outside a batch are snapshotted with --strict-scope.
+const maxScopeSample = 1024
+
+type scopeState struct {
+	modTime time.Time
+	size    int64
+}
+
+// scopeSnapshot records the state of a sample of files outside a batch, keyed by their path relative to the tree
+// root.
+type scopeSnapshot struct {
+	treeRoot string
+	files    map[string]scopeState
+}
+
+// snapshotScope records the modification time and size of the files which a formatter applied to files is most likely
+// to touch by mistake: those in the tree root, such as config files, and the siblings of files in the batch.
+// Files within the batch are ignored, as are subdirectories, and at most maxScopeSample files are recorded.
+func snapshotScope(treeRoot string, files []*walk.File) scopeSnapshot {
+	targets := make(map[string]bool, len(files))
+	dirs := []string{treeRoot}
+
+	for _, file := range files {
+		targets[file.Path] = true
+
+		if dir := filepath.Dir(file.Path); !slices.Contains(dirs, dir) {
+			dirs = append(dirs, dir)
+		}
+	}
+
+	snapshot := scopeSnapshot{
+		treeRoot: treeRoot,
+		files:    make(map[string]scopeState),
+	}
+
+	for _, dir := range dirs {
+		entries, err := os.ReadDir(dir)
+		if err != nil {
+			log.Debugf("failed to read directory %s for strict scope check: %v", dir, err)
+
+			continue
+		}
+
+		for _, entry := range entries {
+			if len(snapshot.files) == maxScopeSample {
+				return snapshot
+			}
+
+			path := filepath.Join(dir, entry.Name())
+			if entry.IsDir() || targets[path] {
+				continue
+			}
+
+			info, err := entry.Info()
+			if err != nil {
+				continue
+			}
+
+			relPath, err := filepath.Rel(treeRoot, path)
+			if err != nil {
+				continue
+			}
+
+			snapshot.files[relPath] = scopeState{modTime: info.ModTime(), size: info.Size()}
+		}
+	}
+
+	return snapshot
+}
+
+// modified returns a sorted list of the files in the snapshot which have since been modified or removed.
+func (s scopeSnapshot) modified() []string {
+	var result []string
+
+	for relPath, state := range s.files {
+		info, err := os.Stat(filepath.Join(s.treeRoot, relPath))
+		if err != nil || !info.ModTime().Equal(state.modTime) || info.Size() != state.size {
+			result = append(result, relPath)
+		}
+	}
+
+	slices.Sort(result)
+
+	return result
+}
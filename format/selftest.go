@@ -0,0 +1,126 @@
+package format
+
+import (
+	"bytes"
+	"context"
+	"errors"
+	"fmt"
+	"os"
+	"slices"
+
+	"github.com/numtide/treefmt/v2/walk"
+)
+
+var (
+	ErrNoSample      = errors.New("no file matches its includes")
+	ErrNotIdempotent = errors.New("formatting a second time changed the file")
+	// ErrPrintsToStdout warns of a formatter which left its sample unchanged whilst printing at least as much as the
+	// sample contains, which usually means it writes the formatted content to stdout rather than in place.
+	ErrPrintsToStdout = errors.New("seems to print to stdout instead of formatting in place; did you forget --write?")
+)
+
+// SelfTestResult is the outcome of testing a formatter with SelfTest.
+type SelfTestResult struct {
+	// Name is the name of the formatter.
+	Name string
+	// Sample is the path, relative to the tree root, of the file the formatter was tested with, if any.
+	Sample string
+	// Err is why the formatter failed the test, or nil if it passed.
+	Err error
+	// Warning is a likely misconfiguration which does not fail the test, such as ErrPrintsToStdout.
+	Warning error
+}
+
+// SelfTest checks each configured formatter can be found, succeeds when applied to a copy of its sample and leaves
+// the copy unchanged when applied a second time. samples maps the name of each formatter to the file it is tested
+// with, which itself is never modified.
+// The results are sorted by formatter name.
+func (c *CompositeFormatter) SelfTest(ctx context.Context, samples map[string]*walk.File) []SelfTestResult {
+	names := make([]string, 0, len(c.cfg.FormatterConfigs))
+	for name := range c.cfg.FormatterConfigs {
+		names = append(names, name)
+	}
+
+	slices.Sort(names)
+
+	results := make([]SelfTestResult, 0, len(names))
+
+	for _, name := range names {
+		result := SelfTestResult{Name: name}
+
+		formatter, ok := c.formatters[name]
+		sample := samples[name]
+
+		switch {
+		case !ok:
+			// formatters whose command could not be found are left out when missing formatters are allowed
+			result.Err = fmt.Errorf("%w: %s", ErrCommandNotFound, c.cfg.FormatterConfigs[name].Command)
+		case sample == nil:
+			result.Err = ErrNoSample
+		default:
+			result.Sample = sample.RelPath
+			result.Warning, result.Err = formatter.selfTest(ctx, sample)
+		}
+
+		results = append(results, result)
+	}
+
+	return results
+}
+
+// selfTest applies the formatter twice to a copy of sample, which is created alongside it so that any config the
+// formatter discovers relative to the file still applies.
+// A warning is returned if the first run looks like it printed the formatted content rather than writing it.
+func (f *Formatter) selfTest(ctx context.Context, sample *walk.File) (warning, err error) {
+	staged, err := stageCopies([]*walk.File{sample})
+	if err != nil {
+		return nil, err
+	}
+
+	defer removeStaged(staged)
+
+	target := []*walk.File{staged[0].temp}
+
+	original, err := os.ReadFile(target[0].Path)
+	if err != nil {
+		return nil, fmt.Errorf("failed to read copy: %w", err)
+	}
+
+	// the output is needed to tell whether the formatter prints rather than writes, so a single invocation is used
+	out, err := f.apply(ctx, target)
+	if err != nil {
+		return nil, err
+	}
+
+	first, err := os.ReadFile(target[0].Path)
+	if err != nil {
+		return nil, fmt.Errorf("failed to read formatted copy: %w", err)
+	}
+
+	if printsInsteadOfWrites(original, first, out) {
+		warning = ErrPrintsToStdout
+	}
+
+	if err = f.Apply(ctx, target); err != nil {
+		return warning, fmt.Errorf("second run: %w", err)
+	}
+
+	second, err := os.ReadFile(target[0].Path)
+	if err != nil {
+		return warning, fmt.Errorf("failed to read formatted copy: %w", err)
+	}
+
+	if !bytes.Equal(first, second) {
+		return warning, ErrNotIdempotent
+	}
+
+	return warning, nil
+}
+
+// printsInsteadOfWrites returns true if a non-empty file was left unchanged by a formatter which printed at least as
+// much as the file contains, as formatters such as prettier do when they are not told to write in place.
+func printsInsteadOfWrites(before, after, out []byte) bool {
+	content := bytes.TrimSpace(before)
+
+	return len(content) > 0 && bytes.Equal(before, after) && len(bytes.TrimSpace(out)) >= len(content)
+}
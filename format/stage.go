@@ -0,0 +1,141 @@
+package format
+
+import (
+	"bytes"
+	"errors"
+	"fmt"
+	"io"
+	"os"
+	"path/filepath"
+
+	"github.com/numtide/treefmt/v2/walk"
+)
+
+// stagedFile pairs a file in the tree with the copy a formatter is applied to in its place.
+type stagedFile struct {
+	original *walk.File
+	temp     *walk.File
+}
+
+// stageCopies copies each of files to a temporary file alongside it, so that any config the formatter discovers
+// relative to the file still applies, keeping the extension so the formatter treats it the same way.
+// The copies are returned in the same order as files and must be passed to commitStaged or removeStaged.
+func stageCopies(files []*walk.File) ([]stagedFile, error) {
+	staged := make([]stagedFile, 0, len(files))
+
+	for _, file := range files {
+		copyPath, err := copyAlongside(file.Path)
+		if err != nil {
+			removeStaged(staged)
+
+			return nil, fmt.Errorf("failed to stage a copy of %s: %w", file.RelPath, err)
+		}
+
+		staged = append(staged, stagedFile{
+			original: file,
+			temp: &walk.File{
+				Path:    copyPath,
+				RelPath: filepath.Join(filepath.Dir(file.RelPath), filepath.Base(copyPath)),
+			},
+		})
+	}
+
+	return staged, nil
+}
+
+// commitStaged replaces each original file with its formatted copy, but only where the content differs, so files
+// which were already formatted keep their modification time. All the copies are removed.
+func commitStaged(staged []stagedFile) error {
+	var errs []error
+
+	for _, s := range staged {
+		if err := commitCopy(s.original.Path, s.temp.Path); err != nil {
+			errs = append(errs, fmt.Errorf("failed to update %s: %w", s.original.RelPath, err))
+		}
+	}
+
+	removeStaged(staged)
+
+	return errors.Join(errs...)
+}
+
+// removeStaged removes any copies which remain, e.g. because the formatter failed.
+func removeStaged(staged []stagedFile) {
+	for _, s := range staged {
+		_ = os.Remove(s.temp.Path)
+	}
+}
+
+// restoreBackups replaces each original file with its backup, undoing whatever a failed formatter did to it.
+// Files whose content is unchanged are left alone, and any backups which are not restored must still be removed with
+// removeStaged.
+func restoreBackups(backups []stagedFile) error {
+	var errs []error
+
+	for _, b := range backups {
+		if err := commitCopy(b.original.Path, b.temp.Path); err != nil {
+			errs = append(errs, fmt.Errorf("failed to restore %s: %w", b.original.RelPath, err))
+		}
+	}
+
+	return errors.Join(errs...)
+}
+
+func copyAlongside(path string) (string, error) {
+	src, err := os.Open(path)
+	if err != nil {
+		return "", fmt.Errorf("failed to open file: %w", err)
+	}
+	defer src.Close()
+
+	info, err := src.Stat()
+	if err != nil {
+		return "", fmt.Errorf("failed to stat file: %w", err)
+	}
+
+	dst, err := os.CreateTemp(filepath.Dir(path), walk.TempFilePrefix+"*-"+filepath.Base(path))
+	if err != nil {
+		return "", fmt.Errorf("failed to create temporary file: %w", err)
+	}
+
+	_, err = io.Copy(dst, src)
+	if closeErr := dst.Close(); err == nil {
+		err = closeErr
+	}
+
+	if err == nil {
+		err = os.Chmod(dst.Name(), info.Mode().Perm())
+	}
+
+	if err != nil {
+		_ = os.Remove(dst.Name())
+
+		return "", fmt.Errorf("failed to copy file: %w", err)
+	}
+
+	return dst.Name(), nil
+}
+
+func commitCopy(originalPath, copyPath string) error {
+	// a missing original, e.g. one a failed formatter removed, is simply replaced
+	original, err := os.ReadFile(originalPath)
+	if err != nil && !errors.Is(err, os.ErrNotExist) {
+		return fmt.Errorf("failed to read file: %w", err)
+	}
+
+	formatted, err := os.ReadFile(copyPath)
+	if err != nil {
+		return fmt.Errorf("failed to read formatted copy: %w", err)
+	}
+
+	if bytes.Equal(original, formatted) {
+		return nil
+	}
+
+	// renaming within the same directory atomically replaces the original
+	if err = os.Rename(copyPath, originalPath); err != nil {
+		return fmt.Errorf("failed to replace file with formatted copy: %w", err)
+	}
+
+	return nil
+}
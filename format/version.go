@@ -0,0 +1,95 @@
+package format
+
+import (
+	"errors"
+	"fmt"
+	"os/exec"
+	"regexp"
+	"strconv"
+)
+
+// ErrVersionTooOld is returned when the version reported by the executable of a Formatter is older than its
+// configured MinVersion.
+var ErrVersionTooOld = errors.New("formatter version is older than min-version")
+
+// versionRegex matches the first version number of the form major.minor or major.minor.patch in a version string,
+// such as `rustfmt 1.7.0-stable (2024-05-01)`.
+var versionRegex = regexp.MustCompile(`(\d+)\.(\d+)(?:\.(\d+))?`)
+
+// version is a parsed major.minor.patch version number, with a missing patch being zero.
+type version [3]int
+
+func (v version) String() string {
+	return fmt.Sprintf("%d.%d.%d", v[0], v[1], v[2])
+}
+
+// less returns true if v is older than other.
+func (v version) less(other version) bool {
+	for i := range v {
+		if v[i] != other[i] {
+			return v[i] < other[i]
+		}
+	}
+
+	return false
+}
+
+// parseVersion returns the first version number found in s.
+func parseVersion(s string) (version, bool) {
+	var v version
+
+	match := versionRegex.FindStringSubmatch(s)
+	if match == nil {
+		return v, false
+	}
+
+	for i, part := range match[1:] {
+		if part == "" {
+			continue
+		}
+
+		n, err := strconv.Atoi(part)
+		if err != nil {
+			// the number has too many digits to be a real version
+			return v, false
+		}
+
+		v[i] = n
+	}
+
+	return v, true
+}
+
+// checkVersion runs the executable with the configured VersionArgs, defaulting to --version, returning
+// ErrVersionTooOld if the first version number in its output is older than MinVersion.
+func (f *Formatter) checkVersion() error {
+	minVersion, ok := parseVersion(f.config.MinVersion)
+	if !ok {
+		return fmt.Errorf("invalid min-version %q", f.config.MinVersion)
+	}
+
+	args := f.config.VersionArgs
+	if len(args) == 0 {
+		args = []string{"--version"}
+	}
+
+	cmd := exec.Command(f.executable, args...) //nolint:gosec
+	cmd.Dir = f.workingDir
+
+	out, err := cmd.CombinedOutput()
+	if err != nil {
+		return fmt.Errorf("failed to get the version of %s: %w\n%s", f.config.Command, err, out)
+	}
+
+	current, ok := parseVersion(string(out))
+	if !ok {
+		return fmt.Errorf("no version number found in the output of %s: %s", cmd.String(), out)
+	}
+
+	if current.less(minVersion) {
+		return fmt.Errorf("%w: %s reports version %s, but at least %s is required",
+			ErrVersionTooOld, f.config.Command, current, minVersion)
+	}
+
+	return nil
+}
@@ -0,0 +1,94 @@
+package format //nolint:testpackage
+
+import (
+	"os"
+	"path/filepath"
+	"testing"
+
+	"github.com/numtide/treefmt/v2/config"
+	"github.com/numtide/treefmt/v2/stats"
+	"github.com/numtide/treefmt/v2/walk"
+	"github.com/stretchr/testify/require"
+)
+
+func TestParseVersion(t *testing.T) {
+	as := require.New(t)
+
+	parses := func(input string, expected version) {
+		actual, ok := parseVersion(input)
+		as.True(ok, input)
+		as.Equal(expected, actual, input)
+	}
+
+	parses("1.2.3", version{1, 2, 3})
+	parses("1.2", version{1, 2, 0})
+	parses("v0.10", version{0, 10, 0})
+	parses("rustfmt 1.7.0-stable (2024-05-01)", version{1, 7, 0})
+	// only the first version is used
+	parses("black, 24.4.2 (compiled: yes)\nPython (CPython) 3.12.4", version{24, 4, 2})
+
+	for _, input := range []string{"", "1", "version unknown", "99999999999999999999.1"} {
+		_, ok := parseVersion(input)
+		as.False(ok, input)
+	}
+
+	as.True(version{1, 2, 3}.less(version{1, 10, 0}))
+	as.True(version{1, 2, 3}.less(version{2, 0, 0}))
+	as.False(version{1, 2, 3}.less(version{1, 2, 3}))
+	as.False(version{1, 10, 0}.less(version{1, 9, 9}))
+}
+
+func TestMinVersion(t *testing.T) {
+	as := require.New(t)
+
+	tempDir := t.TempDir()
+
+	// a formatter which reports its version on stderr, as some do, and only with the right args
+	script := "#!/bin/sh\nif [ \"$1\" = \"version\" ]; then echo 'fake-fmt v1.4.2' >&2; else exit 1; fi\n"
+	command := filepath.Join(tempDir, "fake-fmt")
+	as.NoError(os.WriteFile(command, []byte(script), 0o755)) //nolint:gosec
+
+	newComposite := func(minVersion string, allowMissing bool, validate bool) (*CompositeFormatter, error) {
+		statz := stats.New()
+
+		return NewCompositeFormatter(&config.Config{
+			OnUnmatched:           "info",
+			TreeRoot:              tempDir,
+			AllowMissingFormatter: allowMissing,
+			Validate:              validate,
+			FormatterConfigs: map[string]*config.Formatter{
+				"fake": {
+					Command:     command,
+					Includes:    []string{"*.fake"},
+					MinVersion:  minVersion,
+					VersionArgs: []string{"version"},
+				},
+			},
+		}, &statz, 1024)
+	}
+
+	_, err := newComposite("1.4", false, false)
+	as.NoError(err)
+
+	_, err = newComposite("1.4.2", false, false)
+	as.NoError(err)
+
+	_, err = newComposite("1.10.0", false, false)
+	as.ErrorIs(err, ErrVersionTooOld)
+	as.ErrorContains(err, "reports version 1.4.2, but at least 1.10.0 is required")
+
+	// an outdated formatter is skipped like a missing one
+	composite, err := newComposite("1.10.0", true, false)
+	as.NoError(err)
+	as.Empty(composite.FormattersFor(&walk.File{RelPath: "foo.fake"}))
+
+	_, err = newComposite("latest", false, false)
+	as.ErrorContains(err, `invalid min-version "latest"`)
+
+	// validating the config never runs the formatter, but still checks min-version can be parsed
+	_, err = newComposite("1.10.0", false, true)
+	as.NoError(err)
+
+	_, err = newComposite("latest", false, true)
+	as.ErrorContains(err, `invalid min-version "latest"`)
+}
@@ -44,6 +44,8 @@ in
       "-w"
       "-X github.com/numtide/treefmt/v2/build.Name=${pname}"
       "-X github.com/numtide/treefmt/v2/build.Version=v${version}"
+      "-X github.com/numtide/treefmt/v2/build.Commit=${flake.rev or flake.dirtyRev}"
+      "-X github.com/numtide/treefmt/v2/build.Profile=nix"
     ];
 
     nativeBuildInputs =
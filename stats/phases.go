@@ -0,0 +1,93 @@
+package stats
+
+import (
+	"encoding/json"
+	"fmt"
+	"os"
+	"sync"
+	"time"
+)
+
+// Phase records how long a distinct phase of execution took.
+type Phase struct {
+	Name     string        `json:"name"`
+	Duration time.Duration `json:"duration_ns"`
+}
+
+type phases struct {
+	lock sync.Mutex
+	list []Phase
+}
+
+// AddPhase records that the named phase took the given duration.
+func (s *Stats) AddPhase(name string, duration time.Duration) {
+	s.phases.lock.Lock()
+	defer s.phases.lock.Unlock()
+
+	s.phases.list = append(s.phases.list, Phase{Name: name, Duration: duration})
+}
+
+// Phase starts timing the named phase, returning a function which records it when called.
+func (s *Stats) Phase(name string) func() {
+	start := time.Now()
+
+	return func() {
+		s.AddPhase(name, time.Since(start))
+	}
+}
+
+// Phases returns the phases recorded so far, in the order they were recorded.
+func (s *Stats) Phases() []Phase {
+	s.phases.lock.Lock()
+	defer s.phases.lock.Unlock()
+
+	result := make([]Phase, len(s.phases.list))
+	copy(result, s.phases.list)
+
+	return result
+}
+
+// PrintPhasesToStderr prints the duration of each recorded phase along with its share of the total elapsed time.
+func (s *Stats) PrintPhasesToStderr() {
+	total := s.Elapsed()
+
+	for _, phase := range s.Phases() {
+		_, _ = fmt.Fprintf(
+			os.Stderr,
+			"%-24s %10v %5.1f%%\n",
+			phase.Name,
+			phase.Duration.Round(time.Microsecond),
+			100*float64(phase.Duration)/float64(total),
+		)
+	}
+
+	_, _ = fmt.Fprintf(os.Stderr, "%-24s %10v\n", "total", total.Round(time.Microsecond))
+}
+
+// WritePhases writes the recorded phases, the total elapsed time and the cache statistics to path as JSON.
+func (s *Stats) WritePhases(path string) error {
+	report := struct {
+		Total       time.Duration `json:"total_ns"`
+		Phases      []Phase       `json:"phases"`
+		CacheHits   int           `json:"cache_hits"`
+		CacheMisses int           `json:"cache_misses"`
+		HitRate     float64       `json:"hit_rate"`
+	}{
+		Total:       s.Elapsed(),
+		Phases:      s.Phases(),
+		CacheHits:   s.Value(CacheHits),
+		CacheMisses: s.CacheMisses(),
+		HitRate:     s.HitRate(),
+	}
+
+	bytes, err := json.MarshalIndent(report, "", "  ")
+	if err != nil {
+		return fmt.Errorf("failed to marshal phases: %w", err)
+	}
+
+	if err = os.WriteFile(path, bytes, 0o600); err != nil {
+		return fmt.Errorf("failed to write phases to %s: %w", path, err)
+	}
+
+	return nil
+}
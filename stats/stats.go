@@ -3,7 +3,6 @@ package stats
 import (
 	"fmt"
 	"os"
-	"strings"
 	"sync/atomic"
 	"time"
 )
@@ -16,11 +15,15 @@ const (
 	Matched
 	Formatted
 	Changed
+	// CacheHits counts matched files which were not formatted, as the cache shows the same formatters have already
+	// been applied to them in their current state
+	CacheHits
 )
 
 type Stats struct {
 	start    time.Time
 	counters map[Type]*atomic.Int64
+	phases   *phases
 }
 
 func (s *Stats) Add(t Type, delta int) int {
@@ -31,23 +34,40 @@ func (s *Stats) Value(t Type) int {
 	return int(s.counters[t].Load())
 }
 
+// CacheMisses returns the number of matched files which could not be served from the cache.
+func (s *Stats) CacheMisses() int {
+	return s.Value(Matched) - s.Value(CacheHits)
+}
+
+// HitRate returns the percentage of matched files which were served from the cache, or zero if none were matched.
+func (s *Stats) HitRate() float64 {
+	matched := s.Value(Matched)
+	if matched == 0 {
+		return 0
+	}
+
+	return 100 * float64(s.Value(CacheHits)) / float64(matched)
+}
+
 func (s *Stats) Elapsed() time.Duration {
 	return time.Since(s.start)
 }
 
-func (s *Stats) PrintToStderr() {
-	components := []string{
-		"traversed %d files",
-		"emitted %d files for processing",
-		"formatted %d files (%d changed) in %v",
-		"",
+// PrintToStderr prints a summary of the run to stderr.
+// The cache hits and misses are left out when the cache was not used, as there were none.
+func (s *Stats) PrintToStderr(cache bool) {
+	matched := fmt.Sprintf("matched %d files", s.Value(Matched))
+	if cache {
+		matched += fmt.Sprintf(
+			" (%d cache hits, %d cache misses, %.0f%% hit rate)", s.Value(CacheHits), s.CacheMisses(), s.HitRate(),
+		)
 	}
 
 	_, _ = fmt.Fprintf(
 		os.Stderr,
-		strings.Join(components, "\n"),
+		"traversed %d files\n%s\nformatted %d files (%d changed) in %v\n",
 		s.Value(Traversed),
-		s.Value(Matched),
+		matched,
 		s.Value(Formatted),
 		s.Value(Changed),
 		s.Elapsed().Round(time.Millisecond),
@@ -60,9 +80,11 @@ func New() Stats {
 	counters[Matched] = &atomic.Int64{}
 	counters[Formatted] = &atomic.Int64{}
 	counters[Changed] = &atomic.Int64{}
+	counters[CacheHits] = &atomic.Int64{}
 
 	return Stats{
 		start:    time.Now(),
 		counters: counters,
+		phases:   &phases{},
 	}
 }
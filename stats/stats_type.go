@@ -7,11 +7,11 @@ import (
 	"strings"
 )
 
-const _TypeName = "traversedmatchedformattedchanged"
+const _TypeName = "traversedmatchedformattedchangedcache_hits"
 
-var _TypeIndex = [...]uint8{0, 9, 16, 25, 32}
+var _TypeIndex = [...]uint8{0, 9, 16, 25, 32, 42}
 
-const _TypeLowerName = "traversedmatchedformattedchanged"
+const _TypeLowerName = "traversedmatchedformattedchangedcache_hits"
 
 func (i Type) String() string {
 	if i < 0 || i >= Type(len(_TypeIndex)-1) {
@@ -28,9 +28,10 @@ func _TypeNoOp() {
 	_ = x[Matched-(1)]
 	_ = x[Formatted-(2)]
 	_ = x[Changed-(3)]
+	_ = x[CacheHits-(4)]
 }
 
-var _TypeValues = []Type{Traversed, Matched, Formatted, Changed}
+var _TypeValues = []Type{Traversed, Matched, Formatted, Changed, CacheHits}
 
 var _TypeNameToValueMap = map[string]Type{
 	_TypeName[0:9]:        Traversed,
@@ -41,6 +42,8 @@ var _TypeNameToValueMap = map[string]Type{
 	_TypeLowerName[16:25]: Formatted,
 	_TypeName[25:32]:      Changed,
 	_TypeLowerName[25:32]: Changed,
+	_TypeName[32:42]:      CacheHits,
+	_TypeLowerName[32:42]: CacheHits,
 }
 
 var _TypeNames = []string{
@@ -48,6 +51,7 @@ var _TypeNames = []string{
 	_TypeName[9:16],
 	_TypeName[16:25],
 	_TypeName[25:32],
+	_TypeName[32:42],
 }
 
 // TypeString retrieves an enum value from the enum constants string name.
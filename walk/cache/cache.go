@@ -3,31 +3,64 @@ package cache
 import (
 	"crypto/sha256"
 	"encoding/hex"
+	"errors"
 	"fmt"
+	"io/fs"
+	"os"
+	"path/filepath"
 	"time"
 
 	"github.com/adrg/xdg"
+	"github.com/charmbracelet/log"
 	bolt "go.etcd.io/bbolt"
 )
 
 const (
 	bucketPaths = "paths"
+	bucketMeta  = "meta"
+
+	keyTreeRoot   = "tree_root"
+	keyConfigFile = "config_file"
 )
 
-func Open(root string) (*bolt.DB, error) {
-	var (
-		err  error
-		path string
-	)
+// Dir returns the default directory in which cache files are stored, within the user's XDG cache directory.
+func Dir() string {
+	return filepath.Join(xdg.CacheHome, "treefmt", "eval-cache")
+}
+
+// File returns the location of the cache file for the given tree root within dir, without creating anything.
+// An empty dir means the default location returned by Dir.
+func File(dir string, root string) string {
+	if dir == "" {
+		dir = Dir()
+	}
 
-	// Otherwise, the database will be located in `XDG_CACHE_DIR/treefmt/eval-cache/<name>.db`, where <name> is
-	// determined by hashing the treeRoot path.
+	// The database will be located in `<dir>/<name>.db`, where <name> is determined by hashing the treeRoot path.
 	// This associates a given treeRoot with a given instance of the cache.
 	digest := sha256.Sum256([]byte(root))
-
 	name := hex.EncodeToString(digest[:])
-	if path, err = xdg.CacheFile(fmt.Sprintf("treefmt/eval-cache/%v.db", name)); err != nil {
-		return nil, fmt.Errorf("could not resolve local path for the cache: %w", err)
+
+	return filepath.Join(dir, name+".db")
+}
+
+// Path returns the location of the cache file for the given tree root within dir, creating dir if needed.
+// An empty dir means the default location returned by Dir.
+func Path(dir string, root string) (string, error) {
+	path := File(dir, root)
+
+	if err := os.MkdirAll(filepath.Dir(path), 0o750); err != nil {
+		return "", fmt.Errorf("could not create cache directory %s: %w", filepath.Dir(path), err)
+	}
+
+	return path, nil
+}
+
+// Open opens the cache for the given tree root within dir, recording the tree root and config file it was created for.
+// If the cache was previously recorded against a different tree root, its entries are discarded.
+func Open(dir string, root string, configFile string) (*bolt.DB, error) {
+	path, err := Path(dir, root)
+	if err != nil {
+		return nil, err
 	}
 
 	// open db
@@ -36,13 +69,33 @@ func Open(root string) (*bolt.DB, error) {
 		return nil, fmt.Errorf("failed to open cache db: %w", err)
 	}
 
-	// ensure bucket exist
+	// ensure buckets exist and record which tree root and config file this cache belongs to
 	err = db.Update(func(tx *bolt.Tx) error {
-		_, err := tx.CreateBucketIfNotExists([]byte(bucketPaths))
+		paths, err := tx.CreateBucketIfNotExists([]byte(bucketPaths))
 		if err != nil {
 			return fmt.Errorf("failed to create bucket: %w", err)
 		}
 
+		meta, err := tx.CreateBucketIfNotExists([]byte(bucketMeta))
+		if err != nil {
+			return fmt.Errorf("failed to create bucket: %w", err)
+		}
+
+		// a mismatch means a hash collision or a tampered cache, either way we cannot trust the entries
+		if recorded := meta.Get([]byte(keyTreeRoot)); recorded != nil && string(recorded) != root {
+			if err = deleteAll(paths); err != nil {
+				return fmt.Errorf("failed to reset cache for tree root %s: %w", root, err)
+			}
+		}
+
+		if err = meta.Put([]byte(keyTreeRoot), []byte(root)); err != nil {
+			return fmt.Errorf("failed to record tree root: %w", err)
+		}
+
+		if err = meta.Put([]byte(keyConfigFile), []byte(configFile)); err != nil {
+			return fmt.Errorf("failed to record config file: %w", err)
+		}
+
 		return nil
 	})
 	if err != nil {
@@ -52,8 +105,56 @@ func Open(root string) (*bolt.DB, error) {
 	return db, nil
 }
 
+// OpenReadOnly opens the cache for the given tree root within dir without ever writing to it, e.g. when the cache
+// directory has been mounted read-only within a sandbox.
+// A nil DB is returned if there is no usable cache for the tree root.
+func OpenReadOnly(dir string, root string) (*bolt.DB, error) {
+	// we must not create the cache directory, it may be read-only
+	path := File(dir, root)
+
+	if _, err := os.Stat(path); errors.Is(err, fs.ErrNotExist) {
+		log.Debugf("no cache found for tree root %s", root)
+
+		return nil, nil //nolint:nilnil
+	} else if err != nil {
+		return nil, fmt.Errorf("failed to stat cache db %s: %w", path, err)
+	}
+
+	db, err := bolt.Open(path, 0o600, &bolt.Options{ReadOnly: true, Timeout: 1 * time.Second})
+	if err != nil {
+		return nil, fmt.Errorf("failed to open cache db: %w", err)
+	}
+
+	var usable bool
+
+	err = db.View(func(tx *bolt.Tx) error {
+		var recorded []byte
+		if meta := tx.Bucket([]byte(bucketMeta)); meta != nil {
+			recorded = meta.Get([]byte(keyTreeRoot))
+		}
+
+		// we cannot reset a cache recorded against a different tree root, so we ignore it instead
+		usable = PathsBucket(tx) != nil && (recorded == nil || string(recorded) == root)
+
+		return nil
+	})
+	if err != nil {
+		_ = db.Close()
+
+		return nil, fmt.Errorf("failed to read cache db: %w", err)
+	}
+
+	if !usable {
+		log.Warnf("ignoring cache %s as it is empty or was created for a different tree root", path)
+
+		return nil, db.Close() //nolint:wrapcheck
+	}
+
+	return db, nil
+}
+
 func PathsBucket(tx *bolt.Tx) *bolt.Bucket {
-	return tx.Bucket([]byte("paths"))
+	return tx.Bucket([]byte(bucketPaths))
 }
 
 func deleteAll(bucket *bolt.Bucket) error {
@@ -82,3 +183,83 @@ func Clear(db *bolt.DB) error {
 
 	return nil
 }
+
+// treeRoot reads the tree root recorded in the cache file at path.
+// An empty string is returned if the cache file pre-dates tree roots being recorded.
+func treeRoot(path string) (string, error) {
+	db, err := bolt.Open(path, 0o600, &bolt.Options{ReadOnly: true, Timeout: 1 * time.Second})
+	if err != nil {
+		return "", fmt.Errorf("failed to open cache db %s: %w", path, err)
+	}
+	defer db.Close()
+
+	var root string
+
+	err = db.View(func(tx *bolt.Tx) error {
+		if meta := tx.Bucket([]byte(bucketMeta)); meta != nil {
+			root = string(meta.Get([]byte(keyTreeRoot)))
+		}
+
+		return nil
+	})
+	if err != nil {
+		return "", fmt.Errorf("failed to read tree root from cache db %s: %w", path, err)
+	}
+
+	return root, nil
+}
+
+// GC removes cache files within dir whose recorded tree root no longer exists.
+// An empty dir means the default location returned by Dir.
+// It returns the number of cache files removed and the number of bytes reclaimed.
+func GC(dir string) (removed int, reclaimed int64, err error) {
+	if dir == "" {
+		dir = Dir()
+	}
+
+	entries, err := os.ReadDir(dir)
+	if errors.Is(err, fs.ErrNotExist) {
+		return 0, 0, nil
+	} else if err != nil {
+		return 0, 0, fmt.Errorf("failed to list cache directory %s: %w", dir, err)
+	}
+
+	for _, entry := range entries {
+		if entry.IsDir() || filepath.Ext(entry.Name()) != ".db" {
+			continue
+		}
+
+		path := filepath.Join(dir, entry.Name())
+
+		// a cache held open by a running treefmt cannot be read, but that also means it is not stale
+		root, err := treeRoot(path)
+		if err != nil {
+			log.Warnf("skipping cache file %s: %v", path, err)
+
+			continue
+		}
+
+		// we cannot tell which tree an older cache file belongs to, so we leave it alone
+		if root == "" {
+			continue
+		}
+
+		if _, err = os.Stat(root); !errors.Is(err, fs.ErrNotExist) {
+			continue
+		}
+
+		info, err := entry.Info()
+		if err != nil {
+			return removed, reclaimed, fmt.Errorf("failed to stat cache file %s: %w", path, err)
+		}
+
+		if err = os.Remove(path); err != nil {
+			return removed, reclaimed, fmt.Errorf("failed to remove cache file %s: %w", path, err)
+		}
+
+		removed++
+		reclaimed += info.Size()
+	}
+
+	return removed, reclaimed, nil
+}
@@ -0,0 +1,67 @@
+package cache_test
+
+import (
+	"path/filepath"
+	"testing"
+
+	"github.com/numtide/treefmt/v2/walk/cache"
+	"github.com/stretchr/testify/require"
+)
+
+func TestPath(t *testing.T) {
+	as := require.New(t)
+
+	foo, err := cache.Path("", "/tmp/foo")
+	as.NoError(err)
+
+	bar, err := cache.Path("", "/tmp/bar")
+	as.NoError(err)
+
+	// different tree roots must not share a cache file
+	as.NotEqual(foo, bar)
+
+	// the same tree root must always resolve to the same cache file
+	fooAgain, err := cache.Path("", "/tmp/foo")
+	as.NoError(err)
+	as.Equal(foo, fooAgain)
+
+	as.Contains(foo, cache.Dir())
+	as.Equal(foo, cache.File("", "/tmp/foo"))
+
+	// resolving the file alone does not create the directory
+	missing := filepath.Join(t.TempDir(), "missing")
+	as.Equal(missing, filepath.Dir(cache.File(missing, "/tmp/foo")))
+	as.NoDirExists(missing)
+
+	// a custom directory is created if needed
+	dir := filepath.Join(t.TempDir(), "cache")
+
+	custom, err := cache.Path(dir, "/tmp/foo")
+	as.NoError(err)
+	as.Equal(dir, filepath.Dir(custom))
+	as.DirExists(dir)
+}
+
+func TestOpenReadOnly(t *testing.T) {
+	as := require.New(t)
+
+	root := t.TempDir()
+	dir := filepath.Join(t.TempDir(), "cache")
+
+	// a missing cache is not an error, and nothing is created looking for it
+	db, err := cache.OpenReadOnly(dir, root)
+	as.NoError(err)
+	as.Nil(db)
+	as.NoDirExists(dir)
+
+	// once a cache exists it can be opened without writing to it
+	db, err = cache.Open(dir, root, "treefmt.toml")
+	as.NoError(err)
+	as.NoError(db.Close())
+
+	db, err = cache.OpenReadOnly(dir, root)
+	as.NoError(err)
+	as.NotNil(db)
+	as.True(db.IsReadOnly())
+	as.NoError(db.Close())
+}
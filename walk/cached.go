@@ -5,7 +5,9 @@ import (
 	"errors"
 	"fmt"
 	"io"
+	"path/filepath"
 	"runtime"
+	"strings"
 
 	"github.com/charmbracelet/log"
 	"github.com/numtide/treefmt/v2/walk/cache"
@@ -25,6 +27,32 @@ func GetNoCache(ctx context.Context) bool {
 	return ok && noCache
 }
 
+type ctxKeyForce struct{}
+
+// SetForce returns a context which instructs the CachedReader to ignore cache entries for files within the given
+// paths, which must be relative to the tree root.
+func SetForce(ctx context.Context, paths []string) context.Context {
+	return context.WithValue(ctx, ctxKeyForce{}, paths)
+}
+
+// GetForce returns the paths set with SetForce, if any.
+func GetForce(ctx context.Context) []string {
+	paths, _ := ctx.Value(ctxKeyForce{}).([]string)
+
+	return paths
+}
+
+// isForced checks if relPath is equal to, or contained within, one of the forced paths.
+func isForced(relPath string, forced []string) bool {
+	for _, path := range forced {
+		if path == "." || relPath == path || strings.HasPrefix(relPath, path+string(filepath.Separator)) {
+			return true
+		}
+	}
+
+	return false
+}
+
 // CachedReader reads files from a delegate Reader, appending a cache Entry on read (if on exists) and updating the
 // cache after the file has been processed.
 type CachedReader struct {
@@ -99,14 +127,20 @@ func (c *CachedReader) Read(ctx context.Context, files []*File) (n int, err erro
 		n, err = c.delegate.Read(ctx, files)
 		c.log.Debugf("read %d files from delegate", n)
 
+		forced := GetForce(ctx)
+
 		for i := range n {
 			file := files[i]
 
-			file.CachedFormatSignature = bucket.Get([]byte(file.RelPath))
+			// forced files are processed as if they had never been cached
+			if !isForced(file.RelPath, forced) {
+				file.CachedFormatSignature = bucket.Get([]byte(file.RelPath))
+			}
 
 			// set a release function which inserts this file into the update channel
+			// a read-only cache is never updated
 			file.AddReleaseFunc(func(ctx context.Context) error {
-				if !GetNoCache(ctx) {
+				if !GetNoCache(ctx) && !c.db.IsReadOnly() {
 					c.updateCh <- file
 				}
 
@@ -0,0 +1,96 @@
+package walk
+
+import (
+	"bufio"
+	"bytes"
+	"encoding/json"
+	"path/filepath"
+	"strings"
+
+	"github.com/pelletier/go-toml/v2"
+)
+
+// interpreterExtensions maps the interpreter named in a shebang to the file extension its scripts usually have.
+var interpreterExtensions = map[string]string{
+	"bash":    ".sh",
+	"dash":    ".sh",
+	"ksh":     ".sh",
+	"sh":      ".sh",
+	"zsh":     ".sh",
+	"fish":    ".fish",
+	"node":    ".js",
+	"deno":    ".ts",
+	"bun":     ".ts",
+	"lua":     ".lua",
+	"perl":    ".pl",
+	"php":     ".php",
+	"python":  ".py",
+	"python2": ".py",
+	"python3": ".py",
+	"ruby":    ".rb",
+}
+
+// DetectName guesses a file name for content whose path is unknown, based on a shebang or its structure, so that it
+// can be matched against formatter includes.
+// An empty string is returned if the content type cannot be determined with reasonable confidence.
+func DetectName(content []byte) string {
+	if ext := detectShebang(content); ext != "" {
+		return "stdin" + ext
+	}
+
+	trimmed := bytes.TrimSpace(bytes.TrimPrefix(content, []byte("\xef\xbb\xbf")))
+	if len(trimmed) == 0 {
+		return ""
+	}
+
+	lower := strings.ToLower(string(trimmed[:min(len(trimmed), 64)]))
+
+	switch {
+	case strings.HasPrefix(lower, "<?xml"):
+		return "stdin.xml"
+	case strings.HasPrefix(lower, "<!doctype html"), strings.HasPrefix(lower, "<html"):
+		return "stdin.html"
+	case (trimmed[0] == '{' || trimmed[0] == '[') && json.Valid(trimmed):
+		return "stdin.json"
+	case strings.HasPrefix(lower, "---\n"), strings.HasPrefix(lower, "---\r\n"):
+		return "stdin.yaml"
+	}
+
+	// a TOML document needs at least one key or table to be worth treating as such
+	var doc map[string]any
+	if toml.Unmarshal(trimmed, &doc) == nil && len(doc) > 0 {
+		return "stdin.toml"
+	}
+
+	return ""
+}
+
+// detectShebang returns the extension for the interpreter named in the shebang on the first line of content, if any.
+func detectShebang(content []byte) string {
+	line, _, _ := bufio.NewReader(bytes.NewReader(content)).ReadLine()
+	if !bytes.HasPrefix(line, []byte("#!")) {
+		return ""
+	}
+
+	fields := strings.Fields(string(line[2:]))
+	if len(fields) == 0 {
+		return ""
+	}
+
+	interpreter := filepath.Base(fields[0])
+
+	// with `#!/usr/bin/env [-S] python3`, the interpreter is the first argument which is not a flag
+	if interpreter == "env" {
+		interpreter = ""
+
+		for _, field := range fields[1:] {
+			if !strings.HasPrefix(field, "-") {
+				interpreter = filepath.Base(field)
+
+				break
+			}
+		}
+	}
+
+	return interpreterExtensions[interpreter]
+}
@@ -0,0 +1,40 @@
+package walk_test
+
+import (
+	"testing"
+
+	"github.com/numtide/treefmt/v2/walk"
+	"github.com/stretchr/testify/require"
+)
+
+func TestDetectName(t *testing.T) {
+	as := require.New(t)
+
+	type testCase struct {
+		content  string
+		expected string
+	}
+
+	for _, tc := range []testCase{
+		// shebangs
+		{"#!/bin/bash\necho hello\n", "stdin.sh"},
+		{"#!/usr/bin/env python3\nprint('hello')\n", "stdin.py"},
+		{"#!/usr/bin/env -S node --no-warnings\n", "stdin.js"},
+		{"#!/usr/bin/unknown\n", ""},
+		// structure
+		{"<?xml version=\"1.0\"?>\n<root/>\n", "stdin.xml"},
+		{"<!DOCTYPE html>\n<html></html>\n", "stdin.html"},
+		{"\xef\xbb\xbf{\"hello\": \"world\"}\n", "stdin.json"},
+		{"[1, 2, 3]\n", "stdin.json"},
+		{"---\nhello: world\n", "stdin.yaml"},
+		{"[section]\nkey = \"value\"\n", "stdin.toml"},
+		{"name = \"treefmt\"\n", "stdin.toml"},
+		// ambiguous or unknown
+		{"", ""},
+		{"hello world\n", ""},
+		{"{ not json\n", ""},
+		{"fn main() {}\n", ""},
+	} {
+		as.Equal(tc.expected, walk.DetectName([]byte(tc.content)), "content: %q", tc.content)
+	}
+}
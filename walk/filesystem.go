@@ -20,8 +20,12 @@ type FilesystemReader struct {
 	log       *log.Logger
 	root      string
 	path      string
+	maxDepth  int
 	batchSize int
 
+	// cacheDir is the configured cache directory relative to root, if it lies within it, see IsInternal.
+	cacheDir string
+
 	eg *errgroup.Group
 
 	stats   *stats.Stats
@@ -45,14 +49,35 @@ func (f *FilesystemReader) process() error {
 	}
 
 	// walk the path
+	walkPath := path
+
 	err := filepath.Walk(path, func(path string, info fs.FileInfo, err error) error {
+		// never traverse treefmt's own artifacts, regardless of any excludes, which includes temporary files which
+		// may already have been removed
+		if relPath, relErr := filepath.Rel(f.root, path); relErr == nil && IsInternal(relPath, f.cacheDir) {
+			if info != nil && info.IsDir() {
+				return filepath.SkipDir
+			}
+
+			return nil
+		}
+
 		// return errors immediately
 		if err != nil {
 			return err
 		}
 
-		// ignore directories and symlinks
-		if info.IsDir() || info.Mode()&os.ModeSymlink == os.ModeSymlink {
+		// skip directories whose contents would exceed the max depth
+		if info.IsDir() {
+			if f.maxDepth > 0 && depth(walkPath, path) >= f.maxDepth {
+				return filepath.SkipDir
+			}
+
+			return nil
+		}
+
+		// ignore symlinks
+		if info.Mode()&os.ModeSymlink == os.ModeSymlink {
 			return nil
 		}
 
@@ -136,6 +161,8 @@ func (f *FilesystemReader) Close() error {
 func NewFilesystemReader(
 	root string,
 	path string,
+	maxDepth int,
+	cacheDir string,
 	statz *stats.Stats,
 	batchSize int,
 ) *FilesystemReader {
@@ -146,7 +173,9 @@ func NewFilesystemReader(
 		log:       log.WithPrefix("walk | filesystem"),
 		root:      root,
 		path:      path,
+		maxDepth:  maxDepth,
 		batchSize: batchSize,
+		cacheDir:  RelCacheDir(root, cacheDir),
 
 		eg: &eg,
 
@@ -4,6 +4,8 @@ import (
 	"context"
 	"errors"
 	"io"
+	"os"
+	"path/filepath"
 	"testing"
 	"time"
 
@@ -56,7 +58,7 @@ func TestFilesystemReader(t *testing.T) {
 	tempDir := test.TempExamples(t)
 	statz := stats.New()
 
-	r := walk.NewFilesystemReader(tempDir, "", &statz, 1024)
+	r := walk.NewFilesystemReader(tempDir, "", 0, "", &statz, 1024)
 
 	count := 0
 
@@ -85,3 +87,75 @@ func TestFilesystemReader(t *testing.T) {
 	as.Equal(0, statz.Value(stats.Formatted))
 	as.Equal(0, statz.Value(stats.Changed))
 }
+
+func TestFilesystemReaderMaxDepth(t *testing.T) {
+	as := require.New(t)
+
+	tempDir := test.TempExamples(t)
+
+	for maxDepth, expected := range map[int]int{1: 3, 2: 28, 3: 33} {
+		statz := stats.New()
+		r := walk.NewFilesystemReader(tempDir, "", maxDepth, "", &statz, 1024)
+
+		count := 0
+
+		for {
+			ctx, cancel := context.WithTimeout(context.Background(), 100*time.Millisecond)
+
+			files := make([]*walk.File, 8)
+			n, err := r.Read(ctx, files)
+
+			count += n
+
+			cancel()
+
+			if errors.Is(err, io.EOF) {
+				break
+			}
+		}
+
+		as.Equal(expected, count, "max depth %d", maxDepth)
+	}
+}
+
+func TestFilesystemReaderSkipsInternal(t *testing.T) {
+	as := require.New(t)
+
+	tempDir := test.TempExamples(t)
+	statz := stats.New()
+
+	// treefmt's own artifacts, which are skipped regardless of any excludes
+	as.NoError(os.MkdirAll(filepath.Join(tempDir, walk.RepoCacheDir), 0o755))
+	as.NoError(os.WriteFile(filepath.Join(tempDir, walk.RepoCacheDir, "cache.db"), nil, 0o600))
+	as.NoError(os.WriteFile(filepath.Join(tempDir, "go", walk.TempFilePrefix+"123-main.go"), nil, 0o600))
+
+	// whereas a cache directory nested further down is just another directory
+	as.NoError(os.MkdirAll(filepath.Join(tempDir, "go", walk.RepoCacheDir), 0o755))
+	as.NoError(os.WriteFile(filepath.Join(tempDir, "go", walk.RepoCacheDir, "cache.db"), nil, 0o600))
+
+	r := walk.NewFilesystemReader(tempDir, "", 0, "", &statz, 1024)
+
+	var paths []string
+
+	for {
+		ctx, cancel := context.WithTimeout(context.Background(), 100*time.Millisecond)
+
+		files := make([]*walk.File, 8)
+		n, err := r.Read(ctx, files)
+
+		for _, file := range files[:n] {
+			paths = append(paths, file.RelPath)
+		}
+
+		cancel()
+
+		if errors.Is(err, io.EOF) {
+			break
+		}
+	}
+
+	as.Len(paths, 34)
+	as.Contains(paths, filepath.Join("go", walk.RepoCacheDir, "cache.db"))
+	as.NotContains(paths, filepath.Join(walk.RepoCacheDir, "cache.db"))
+	as.NotContains(paths, filepath.Join("go", walk.TempFilePrefix+"123-main.go"))
+}
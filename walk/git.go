@@ -16,9 +16,63 @@ import (
 	"golang.org/x/sync/errgroup"
 )
 
+type ctxKeySince struct{}
+
+// SetSince returns a context which instructs the GitReader to only read tracked files which differ from the given
+// ref, such as a branch or commit, including any uncommitted changes, instead of every tracked file.
+func SetSince(ctx context.Context, ref string) context.Context {
+	return context.WithValue(ctx, ctxKeySince{}, ref)
+}
+
+// GetSince returns the ref set with SetSince, if any.
+func GetSince(ctx context.Context) string {
+	ref, _ := ctx.Value(ctxKeySince{}).(string)
+
+	return ref
+}
+
+type ctxKeyOnlyChanged struct{}
+
+// SetOnlyChanged returns a context which instructs the GitReader to only read files with uncommitted changes, whether
+// staged or not, along with untracked files which are not ignored, instead of every tracked file.
+func SetOnlyChanged(ctx context.Context, onlyChanged bool) context.Context {
+	return context.WithValue(ctx, ctxKeyOnlyChanged{}, onlyChanged)
+}
+
+func GetOnlyChanged(ctx context.Context) bool {
+	onlyChanged, ok := ctx.Value(ctxKeyOnlyChanged{}).(bool)
+
+	return ok && onlyChanged
+}
+
+// emptyTree is the id of the tree with no entries, which every git repository knows about.
+// Comparing against it stands in for HEAD in a repository without any commits.
+const emptyTree = "4b825dc642cb6eb9a060e54bf8d69288fbee4904"
+
+// VerifyRef checks that ref names a commit in the git repository containing root.
+func VerifyRef(root string, ref string) error {
+	// refs cannot start with a dash, and allowing one would let it be interpreted as an option
+	if strings.HasPrefix(ref, "-") {
+		return fmt.Errorf("invalid ref %s", ref)
+	}
+
+	cmd := exec.Command("git", "rev-parse", "--verify", "--quiet", ref+"^{commit}") //nolint:gosec
+	cmd.Dir = root
+
+	if err := cmd.Run(); err != nil {
+		return fmt.Errorf("%s does not name a commit: %w", ref, err)
+	}
+
+	return nil
+}
+
 type GitReader struct {
-	root string
-	path string
+	root     string
+	path     string
+	maxDepth int
+
+	// cacheDir is the configured cache directory relative to root, if it lies within it, see IsInternal.
+	cacheDir string
 
 	log   *log.Logger
 	stats *stats.Stats
@@ -37,14 +91,23 @@ func (g *GitReader) Read(ctx context.Context, files []*File) (n int, err error)
 		// create a pipe to capture the command output
 		r, w := io.Pipe()
 
-		// create a command which will execute from the specified sub path within root
-		cmd := exec.Command("git", "ls-files")
-		cmd.Dir = filepath.Join(g.root, g.path)
-		cmd.Stdout = w
+		// create the commands which will execute from the specified sub path within root, one after the other
+		cmds := g.commands(ctx)
 
-		// execute the command in the background
+		for _, cmd := range cmds {
+			cmd.Dir = filepath.Join(g.root, g.path)
+			cmd.Stdout = w
+		}
+
+		// execute the commands in the background
 		g.eg.Go(func() error {
-			return w.CloseWithError(cmd.Run())
+			for _, cmd := range cmds {
+				if err := cmd.Run(); err != nil {
+					return w.CloseWithError(err)
+				}
+			}
+
+			return w.Close()
 		})
 
 		// create a new scanner for reading the output
@@ -87,7 +150,19 @@ LOOP:
 					return n, err
 				}
 
-				path := filepath.Join(g.root, g.path, entry)
+				// skip entries nested deeper than the max depth
+				if g.maxDepth > 0 && depth(".", entry) > g.maxDepth {
+					continue
+				}
+
+				relPath := filepath.Join(g.path, entry)
+
+				// never traverse treefmt's own artifacts, even if they have been committed by mistake
+				if IsInternal(relPath, g.cacheDir) {
+					continue
+				}
+
+				path := filepath.Join(g.root, relPath)
 
 				g.log.Debugf("processing file: %s", path)
 
@@ -95,7 +170,7 @@ LOOP:
 				if os.IsNotExist(err) {
 					// the underlying file might have been removed
 					g.log.Warnf(
-						"Path %s is in the worktree but appears to have been removed from the filesystem", path,
+						"Path %s is in the worktree but appears to have been removed from the filesystem", relPath,
 					)
 
 					continue
@@ -105,7 +180,7 @@ LOOP:
 
 				files[n] = &File{
 					Path:    path,
-					RelPath: filepath.Join(g.path, entry),
+					RelPath: relPath,
 					Info:    info,
 				}
 				n++
@@ -121,6 +196,32 @@ LOOP:
 	return n, err
 }
 
+// commands returns the git commands which list the files to be read, one per line.
+func (g *GitReader) commands(ctx context.Context) []*exec.Cmd {
+	// files which have since been deleted are left out when listing changes
+	diff := func(ref string) *exec.Cmd {
+		return exec.Command("git", "diff", "--name-only", "--relative", "--diff-filter=d", ref, "--") //nolint:gosec
+	}
+
+	if GetOnlyChanged(ctx) {
+		ref := "HEAD"
+		if VerifyRef(g.root, ref) != nil {
+			ref = emptyTree
+		}
+
+		return []*exec.Cmd{
+			diff(ref),
+			exec.Command("git", "ls-files", "--others", "--exclude-standard"),
+		}
+	}
+
+	if since := GetSince(ctx); since != "" {
+		return []*exec.Cmd{diff(since)}
+	}
+
+	return []*exec.Cmd{exec.Command("git", "ls-files")}
+}
+
 func (g *GitReader) Close() error {
 	err := g.eg.Wait()
 	if err != nil {
@@ -133,6 +234,8 @@ func (g *GitReader) Close() error {
 func NewGitReader(
 	root string,
 	path string,
+	maxDepth int,
+	cacheDir string,
 	statz *stats.Stats,
 ) (*GitReader, error) {
 	// check if the root is a git repository
@@ -146,10 +249,12 @@ func NewGitReader(
 	}
 
 	return &GitReader{
-		root:  root,
-		path:  path,
-		stats: statz,
-		eg:    &errgroup.Group{},
-		log:   log.WithPrefix("walk | git"),
+		root:     root,
+		path:     path,
+		maxDepth: maxDepth,
+		cacheDir: RelCacheDir(root, cacheDir),
+		stats:    statz,
+		eg:       &errgroup.Group{},
+		log:      log.WithPrefix("walk | git"),
 	}, nil
 }
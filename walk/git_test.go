@@ -26,7 +26,7 @@ func TestGitReader(t *testing.T) {
 
 	// read empty worktree
 	statz := stats.New()
-	reader, err := walk.NewGitReader(tempDir, "", &statz)
+	reader, err := walk.NewGitReader(tempDir, "", 0, "", &statz)
 	as.NoError(err)
 
 	files := make([]*walk.File, 8)
@@ -42,7 +42,7 @@ func TestGitReader(t *testing.T) {
 	cmd.Dir = tempDir
 	as.NoError(cmd.Run(), "failed to add everything to the index")
 
-	reader, err = walk.NewGitReader(tempDir, "", &statz)
+	reader, err = walk.NewGitReader(tempDir, "", 0, "", &statz)
 	as.NoError(err)
 
 	count := 0
@@ -0,0 +1,304 @@
+package walk
+
+import (
+	"bytes"
+	"context"
+	"errors"
+	"fmt"
+	"io"
+	"os"
+	"os/exec"
+	"path/filepath"
+	"strings"
+	"sync"
+
+	"github.com/charmbracelet/log"
+	"github.com/numtide/treefmt/v2/stats"
+)
+
+// stagedEntry is a regular file with changes staged in the git index.
+type stagedEntry struct {
+	mode    string
+	object  string
+	relPath string
+}
+
+// StagedReader traverses the files with changes staged in the git index, rather than the files in the working tree.
+// The staged content of each file is extracted into a temporary file alongside it, see TempFilePrefix, which is what
+// gets formatted, while includes and excludes are matched against the path of the file itself.
+// Once a file has been formatted, any changes are written back into the index, and into the working tree as well if
+// the file had no unstaged changes.
+type StagedReader struct {
+	root     string
+	path     string
+	maxDepth int
+
+	// prefix is the path of the tree root within the repository, as paths in the index are relative to the top of the
+	// repository rather than the tree root.
+	prefix string
+
+	log   *log.Logger
+	stats *stats.Stats
+
+	entries []stagedEntry
+	listed  bool
+
+	// updateLock serializes updates to the index, as files are released concurrently and git locks the index while
+	// updating it.
+	updateLock sync.Mutex
+}
+
+func (s *StagedReader) Read(ctx context.Context, files []*File) (n int, err error) {
+	// ensure we record how many files we traversed
+	defer func() {
+		s.stats.Add(stats.Traversed, n)
+	}()
+
+	if !s.listed {
+		if s.entries, err = s.listStaged(); err != nil {
+			return 0, err
+		}
+
+		s.listed = true
+	}
+
+	for n < len(files) && len(s.entries) > 0 {
+		// exit early if the context was cancelled
+		if ctx.Err() != nil {
+			return n, nil
+		}
+
+		file, extractErr := s.extract(s.entries[0])
+		if extractErr != nil {
+			return n, extractErr
+		}
+
+		s.entries = s.entries[1:]
+
+		files[n] = file
+		n++
+	}
+
+	if len(s.entries) == 0 {
+		return n, io.EOF
+	}
+
+	return n, nil
+}
+
+func (s *StagedReader) Close() error {
+	return nil
+}
+
+// listStaged returns the regular files beneath path which have been added or modified in the index.
+// Deleted files have nothing to format, and symlinks and submodules are not files to be formatted.
+func (s *StagedReader) listStaged() ([]stagedEntry, error) {
+	pathspec := s.path
+	if pathspec == "" {
+		pathspec = "."
+	}
+
+	out, err := s.git(nil,
+		"diff", "--cached", "--raw", "--no-abbrev", "--no-renames", "-z", "--diff-filter=ACM", "--relative",
+		"--", pathspec,
+	)
+	if err != nil {
+		return nil, err
+	}
+
+	// with -z, each entry is a NUL terminated ':<src mode> <dst mode> <src object> <dst object> <status>' followed by
+	// a NUL terminated path
+	fields := strings.Split(strings.TrimSuffix(string(out), "\x00"), "\x00")
+
+	var entries []stagedEntry
+
+	for i := 0; i+1 < len(fields); i += 2 {
+		meta := strings.Fields(strings.TrimPrefix(fields[i], ":"))
+		if len(meta) != 5 {
+			return nil, fmt.Errorf("unexpected output from git diff: %q", fields[i])
+		}
+
+		entry := stagedEntry{mode: meta[1], object: meta[3], relPath: filepath.FromSlash(fields[i+1])}
+
+		if entry.mode != "100644" && entry.mode != "100755" {
+			s.log.Debugf("skipping %s, which is not a regular file in the index", entry.relPath)
+
+			continue
+		}
+
+		// skip entries nested deeper than the max depth
+		if s.maxDepth > 0 && depth(filepath.Join(s.root, s.path), filepath.Join(s.root, entry.relPath)) > s.maxDepth {
+			continue
+		}
+
+		entries = append(entries, entry)
+	}
+
+	return entries, nil
+}
+
+// extract writes the staged content of entry into a temporary file alongside it, which is written back into the index
+// once the file is released.
+func (s *StagedReader) extract(entry stagedEntry) (*File, error) {
+	content, err := s.git(nil, "cat-file", "blob", entry.object)
+	if err != nil {
+		return nil, err
+	}
+
+	dir := filepath.Join(s.root, filepath.Dir(entry.relPath))
+	if info, err := os.Stat(dir); err != nil || !info.IsDir() {
+		// the directory has been removed from the working tree, so we fall back to the tree root
+		dir = s.root
+	}
+
+	file, err := os.CreateTemp(dir, TempFilePrefix+"*-"+filepath.Base(entry.relPath))
+	if err != nil {
+		return nil, fmt.Errorf("failed to create a temporary file for the staged content of %s: %w", entry.relPath, err)
+	}
+
+	_, err = file.Write(content)
+	if closeErr := file.Close(); err == nil {
+		err = closeErr
+	}
+
+	if err != nil {
+		_ = os.Remove(file.Name())
+
+		return nil, fmt.Errorf("failed to write the staged content of %s: %w", entry.relPath, err)
+	}
+
+	info, err := os.Stat(file.Name())
+	if err != nil {
+		return nil, fmt.Errorf("failed to get file info for temporary file: %w", err)
+	}
+
+	s.log.Debugf("extracted staged content of %s into %s", entry.relPath, file.Name())
+
+	// the file is matched by its path in the index, so patterns apply just as they would to the file in the working tree
+	result := &File{
+		Path:    file.Name(),
+		RelPath: entry.relPath,
+		Info:    info,
+	}
+
+	result.AddReleaseFunc(func(ctx context.Context) error {
+		defer func() {
+			_ = os.Remove(result.Path)
+		}()
+
+		// files which failed to format, or were not formatted at all, are left as they are
+		if GetNoCache(ctx) {
+			return nil
+		}
+
+		return s.update(entry, content, result.Path)
+	})
+
+	return result, nil
+}
+
+// update writes the formatted content at path into the index in place of entry, if it differs from the original
+// content.
+func (s *StagedReader) update(entry stagedEntry, original []byte, path string) error {
+	formatted, err := os.ReadFile(path)
+	if err != nil {
+		return fmt.Errorf("failed to read formatted content of %s: %w", entry.relPath, err)
+	}
+
+	if bytes.Equal(original, formatted) {
+		return nil
+	}
+
+	s.updateLock.Lock()
+	defer s.updateLock.Unlock()
+
+	// the content was extracted without applying any filters, so it is written back the same way
+	out, err := s.git(formatted, "hash-object", "-w", "--no-filters", "--stdin")
+	if err != nil {
+		return err
+	}
+
+	object := strings.TrimSpace(string(out))
+	cacheInfo := fmt.Sprintf("%s,%s,%s", entry.mode, object, s.prefix+filepath.ToSlash(entry.relPath))
+
+	if _, err = s.git(nil, "update-index", "--cacheinfo", cacheInfo); err != nil {
+		return err
+	}
+
+	s.log.Debugf("updated staged content of %s", entry.relPath)
+
+	// bring the working tree along, unless it has changes which are not staged, as those must not be lost
+	worktreePath := filepath.Join(s.root, entry.relPath)
+
+	current, err := os.ReadFile(worktreePath)
+	if errors.Is(err, os.ErrNotExist) {
+		return nil
+	} else if err != nil {
+		return fmt.Errorf("failed to read %s: %w", entry.relPath, err)
+	}
+
+	if !bytes.Equal(current, original) {
+		s.log.Warnf("formatted the staged content of %s, which has unstaged changes left unformatted", entry.relPath)
+
+		return nil
+	}
+
+	// the file is replaced rather than rewritten, so an interrupted run cannot leave it truncated
+	if err = writeAtomic(worktreePath, bytes.NewReader(formatted)); err != nil {
+		return fmt.Errorf("failed to update %s: %w", entry.relPath, err)
+	}
+
+	return nil
+}
+
+// git runs a git command in the tree root with stdin as its input, returning its output.
+func (s *StagedReader) git(stdin []byte, args ...string) ([]byte, error) {
+	cmd := exec.Command("git", args...)
+	cmd.Dir = s.root
+
+	if stdin != nil {
+		cmd.Stdin = bytes.NewReader(stdin)
+	}
+
+	out, err := cmd.Output()
+	if err != nil {
+		return nil, fmt.Errorf("failed to run git %s: %w", args[0], err)
+	}
+
+	return out, nil
+}
+
+func NewStagedReader(
+	root string,
+	path string,
+	maxDepth int,
+	statz *stats.Stats,
+) (*StagedReader, error) {
+	// check if the root is a git repository
+	cmd := exec.Command("git", "rev-parse", "--is-inside-work-tree")
+	cmd.Dir = root
+
+	if out, err := cmd.Output(); err != nil {
+		return nil, fmt.Errorf("failed to check if %s is a git repository: %w", root, err)
+	} else if strings.Trim(string(out), "\n") != "true" {
+		return nil, fmt.Errorf("%s is not a git repository", root)
+	}
+
+	// the tree root may be a subdirectory of the repository, e.g. `sub/dir/`, or empty at the top of the repository
+	cmd = exec.Command("git", "rev-parse", "--show-prefix")
+	cmd.Dir = root
+
+	prefix, err := cmd.Output()
+	if err != nil {
+		return nil, fmt.Errorf("failed to find the path of %s within its git repository: %w", root, err)
+	}
+
+	return &StagedReader{
+		root:     root,
+		path:     path,
+		maxDepth: maxDepth,
+		prefix:   strings.Trim(string(prefix), "\n"),
+		stats:    statz,
+		log:      log.WithPrefix("walk | staged"),
+	}, nil
+}
@@ -1,40 +1,141 @@
 package walk
 
 import (
+	"bytes"
 	"context"
 	"errors"
 	"fmt"
 	"io"
+	"io/fs"
 	"os"
 	"path/filepath"
+	"syscall"
 
+	"github.com/charmbracelet/log"
 	"github.com/numtide/treefmt/v2/stats"
 )
 
+type ctxKeyStdinReport struct{}
+
+// SetStdinReport returns a context which instructs the StdinReader to report on stderr whether formatting changed the
+// content, once it has been written out.
+func SetStdinReport(ctx context.Context, report bool) context.Context {
+	return context.WithValue(ctx, ctxKeyStdinReport{}, report)
+}
+
+func GetStdinReport(ctx context.Context) bool {
+	report, ok := ctx.Value(ctxKeyStdinReport{}).(bool)
+
+	return ok && report
+}
+
+type ctxKeyStdinDetect struct{}
+
+// SetStdinDetect returns a context which instructs the StdinReader to ignore its path and instead detect a name for the
+// content with DetectName.
+// If the content cannot be identified, it is written out unchanged.
+func SetStdinDetect(ctx context.Context, detect bool) context.Context {
+	return context.WithValue(ctx, ctxKeyStdinDetect{}, detect)
+}
+
+func GetStdinDetect(ctx context.Context) bool {
+	detect, ok := ctx.Value(ctxKeyStdinDetect{}).(bool)
+
+	return ok && detect
+}
+
+type ctxKeyStdinExchange struct{}
+
+// StdinExchange carries content which is formatted in place of reading stdin, and receives the formatted content in
+// place of writing it out, for callers which wrap both in a protocol of their own, e.g. --stdin-json.
+type StdinExchange struct {
+	Content   []byte
+	Formatted []byte
+}
+
+// SetStdinExchange returns a context which instructs the StdinReader to use exchange in place of stdin and stdout.
+func SetStdinExchange(ctx context.Context, exchange *StdinExchange) context.Context {
+	return context.WithValue(ctx, ctxKeyStdinExchange{}, exchange)
+}
+
+func GetStdinExchange(ctx context.Context) *StdinExchange {
+	exchange, _ := ctx.Value(ctxKeyStdinExchange{}).(*StdinExchange)
+
+	return exchange
+}
+
 type StdinReader struct {
-	root  string
-	path  string
-	stats stats.Stats
-	input *os.File
+	root   string
+	path   string
+	output string
+	stats  stats.Stats
+	input  *os.File
 
 	complete bool
 }
 
-func (s StdinReader) Read(_ context.Context, files []*File) (n int, err error) {
+func (s StdinReader) Read(ctx context.Context, files []*File) (n int, err error) {
 	if s.complete {
 		return 0, io.EOF
 	}
 
-	// read stdin into a temporary file with the same file extension
-	pattern := "*" + filepath.Ext(s.path)
+	input := io.Reader(s.input)
+
+	exchange := GetStdinExchange(ctx)
+	if exchange != nil {
+		input = bytes.NewReader(exchange.Content)
+	}
+
+	if GetStdinDetect(ctx) {
+		content, err := io.ReadAll(input)
+		if err != nil {
+			return 0, fmt.Errorf("failed to read stdin: %w", err)
+		}
+
+		s.path = DetectName(content)
+		if s.path == "" {
+			log.Warn("could not detect the type of content on stdin, writing it out unchanged")
+
+			return 0, s.passthrough(ctx, content)
+		}
+
+		log.Debugf("detected stdin content as %s", s.path)
+
+		input = bytes.NewReader(content)
+	}
+
+	// Read stdin into a temporary file in the same directory as the provided path, using its name as a suffix.
+	// Includes and excludes are matched against the provided path rather than the temporary file, see below, but
+	// formatters always run from the tree root, so those which discover their config by walking up from the file,
+	// such as prettier, find the same config they would for the provided path.
+	dir := nearestDir(s.root, filepath.Dir(s.path))
+
+	pattern := TempFilePrefix + "*-" + filepath.Base(s.path)
+
+	file, err := os.CreateTemp(dir, pattern)
+	if errors.Is(err, fs.ErrPermission) || errors.Is(err, syscall.EROFS) {
+		// The tree is read-only, e.g. in a Nix build, so we fall back to the system temp directory.
+		// The same formatters still match, but those which look for config alongside the file will not find it.
+		log.Debugf("cannot create a temporary file in %s, falling back to the system temp directory: %v", dir, err)
+
+		file, err = os.CreateTemp("", pattern)
+	}
 
-	file, err := os.CreateTemp(s.root, pattern)
 	if err != nil {
 		return 0, fmt.Errorf("failed to create a temporary file for processing stdin: %w", err)
 	}
+
+	log.Debugf("processing stdin via temporary file %s", file.Name())
 	defer file.Close()
 
-	if _, err = io.Copy(file, s.input); err != nil {
+	// if we are reporting on changes, we keep a copy of the original content to compare against
+	var original bytes.Buffer
+
+	if GetStdinReport(ctx) {
+		input = io.TeeReader(input, &original)
+	}
+
+	if _, err = io.Copy(file, input); err != nil {
 		return 0, errors.New("failed to copy stdin into a temporary file")
 	}
 
@@ -43,27 +144,43 @@ func (s StdinReader) Read(_ context.Context, files []*File) (n int, err error) {
 		return 0, fmt.Errorf("failed to get file info for temporary file: %w", err)
 	}
 
-	relPath, err := filepath.Rel(s.root, file.Name())
-	if err != nil {
-		return 0, fmt.Errorf("failed to get relative path for temporary file: %w", err)
-	}
-
+	// includes and excludes are matched against the provided path, the temporary file only stands in for it on disk
 	files[0] = &File{
 		Path:    file.Name(),
-		RelPath: relPath,
+		RelPath: filepath.Clean(s.path),
 		Info:    info,
 	}
 
-	// dump the temp file to stdout and remove it once the file is finished being processed
-	files[0].AddReleaseFunc(func(_ context.Context) error {
+	// dump the temp file to stdout, or the output file, and remove it once the file is finished being processed
+	files[0].AddReleaseFunc(func(ctx context.Context) error {
+		if exchange != nil {
+			formatted, err := os.ReadFile(file.Name())
+			if err != nil {
+				return fmt.Errorf("failed to read temp file %s: %w", file.Name(), err)
+			}
+
+			exchange.Formatted = formatted
+
+			if err = os.Remove(file.Name()); err != nil {
+				return fmt.Errorf("failed to remove temp file %s: %w", file.Name(), err)
+			}
+
+			return nil
+		}
+
 		// open the temp file
 		file, err := os.Open(file.Name())
 		if err != nil {
 			return fmt.Errorf("failed to open temp file %s: %w", file.Name(), err)
 		}
 
-		// dump file into stdout
-		if _, err = io.Copy(os.Stdout, file); err != nil {
+		if s.output != "" {
+			// write the output file
+			if err = writeAtomic(s.output, file); err != nil {
+				return err
+			}
+		} else if _, err = io.Copy(os.Stdout, file); err != nil {
+			// dump file into stdout
 			return fmt.Errorf("failed to copy %s to stdout: %w", file.Name(), err)
 		}
 
@@ -71,6 +188,20 @@ func (s StdinReader) Read(_ context.Context, files []*File) (n int, err error) {
 			return fmt.Errorf("failed to close temp file %s: %w", file.Name(), err)
 		}
 
+		if GetStdinReport(ctx) {
+			formatted, err := os.ReadFile(file.Name())
+			if err != nil {
+				return fmt.Errorf("failed to read temp file %s: %w", file.Name(), err)
+			}
+
+			report := "unchanged"
+			if !bytes.Equal(original.Bytes(), formatted) {
+				report = "changed"
+			}
+
+			_, _ = fmt.Fprintln(os.Stderr, report)
+		}
+
 		// clean up the temp file
 		if err = os.Remove(file.Name()); err != nil {
 			return fmt.Errorf("failed to remove temp file %s: %w", file.Name(), err)
@@ -85,15 +216,104 @@ func (s StdinReader) Read(_ context.Context, files []*File) (n int, err error) {
 	return 1, io.EOF
 }
 
+// nearestDir returns the directory at path relative to root or, if it does not exist, its nearest existing parent.
+// This keeps a temporary file for a new file, e.g. `src/new/foo.ts`, within `src` where any config alongside it can
+// still be found, rather than falling back to the tree root.
+func nearestDir(root string, path string) string {
+	for dir := filepath.Join(root, path); dir != root; dir = filepath.Dir(dir) {
+		if info, err := os.Stat(dir); err == nil && info.IsDir() {
+			return dir
+		}
+
+		// guard against a path which does not lie beneath root
+		if filepath.Dir(dir) == dir {
+			break
+		}
+	}
+
+	return root
+}
+
+// passthrough writes content, which could not be formatted, unchanged to stdout or the output file.
+func (s StdinReader) passthrough(ctx context.Context, content []byte) error {
+	s.stats.Add(stats.Traversed, 1)
+
+	if exchange := GetStdinExchange(ctx); exchange != nil {
+		exchange.Formatted = content
+
+		return io.EOF
+	}
+
+	if s.output != "" {
+		if err := writeAtomic(s.output, bytes.NewReader(content)); err != nil {
+			return err
+		}
+	} else if _, err := os.Stdout.Write(content); err != nil {
+		return fmt.Errorf("failed to write stdin to stdout: %w", err)
+	}
+
+	if GetStdinReport(ctx) {
+		_, _ = fmt.Fprintln(os.Stderr, "unchanged")
+	}
+
+	return io.EOF
+}
+
 func (s StdinReader) Close() error {
 	return nil
 }
 
-func NewStdinReader(root string, path string, statz *stats.Stats) StdinReader {
+// writeAtomic replaces the file at path with the contents of src, by writing to a temporary file in the same
+// directory and renaming it into place.
+// The permissions of an existing file at path are preserved.
+func writeAtomic(path string, src io.Reader) error {
+	mode := os.FileMode(0o644)
+
+	if info, err := os.Stat(path); err == nil {
+		mode = info.Mode().Perm()
+	}
+
+	out, err := os.CreateTemp(filepath.Dir(path), "."+filepath.Base(path)+".*")
+	if err != nil {
+		return fmt.Errorf("failed to create a temporary file for writing %s: %w", path, err)
+	}
+
+	// clean up the temporary file if we fail before renaming it
+	defer func() {
+		_ = os.Remove(out.Name())
+	}()
+
+	if _, err = io.Copy(out, src); err != nil {
+		_ = out.Close()
+
+		return fmt.Errorf("failed to write %s: %w", out.Name(), err)
+	}
+
+	if err = out.Chmod(mode); err != nil {
+		_ = out.Close()
+
+		return fmt.Errorf("failed to set permissions on %s: %w", out.Name(), err)
+	}
+
+	if err = out.Close(); err != nil {
+		return fmt.Errorf("failed to close %s: %w", out.Name(), err)
+	}
+
+	if err = os.Rename(out.Name(), path); err != nil {
+		return fmt.Errorf("failed to move %s to %s: %w", out.Name(), path, err)
+	}
+
+	return nil
+}
+
+// NewStdinReader creates a reader which formats stdin as if it were the file at path, relative to root.
+// The result is written to output if it is not empty, otherwise to stdout.
+func NewStdinReader(root string, path string, output string, statz *stats.Stats) StdinReader {
 	return StdinReader{
-		root:  root,
-		path:  path,
-		stats: *statz,
-		input: os.Stdin,
+		root:   root,
+		path:   path,
+		output: output,
+		stats:  *statz,
+		input:  os.Stdin,
 	}
 }
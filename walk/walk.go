@@ -9,6 +9,7 @@ import (
 	"io/fs"
 	"os"
 	"path/filepath"
+	"strings"
 
 	"github.com/numtide/treefmt/v2/stats"
 	bolt "go.etcd.io/bbolt"
@@ -22,10 +23,57 @@ const (
 	Stdin
 	Filesystem
 	Git
+	Staged
 
 	BatchSize = 1024
 )
 
+const (
+	// TempFilePrefix starts the name of the temporary files which formatters are applied to in place of the original
+	// files, such as when formatting stdin, or with --write-only-on-change or --staged.
+	// They come and go while formatting, so they are never traversed.
+	TempFilePrefix = ".treefmt-tmp-"
+
+	// RepoCacheDir is the directory, relative to the tree root, in which the cache is stored when it is kept with the
+	// repository. It is never traversed, and neither is any other cache-dir within the tree root, see RelCacheDir.
+	RepoCacheDir = ".treefmt-cache"
+)
+
+// IsTempFile returns true if path names a temporary file being formatted, see TempFilePrefix.
+func IsTempFile(path string) bool {
+	return strings.HasPrefix(filepath.Base(path), TempFilePrefix)
+}
+
+// IsInternal returns true if relPath, relative to the tree root, belongs to treefmt itself: a temporary file, the
+// repository cache directory or cacheDir, the configured cache directory as returned by RelCacheDir.
+// These are never traversed, regardless of any excludes, so treefmt does not end up formatting its own artifacts.
+func IsInternal(relPath string, cacheDir string) bool {
+	relPath = filepath.ToSlash(relPath)
+
+	return IsTempFile(relPath) || isWithin(relPath, RepoCacheDir) ||
+		(cacheDir != "" && isWithin(relPath, filepath.ToSlash(cacheDir)))
+}
+
+// RelCacheDir returns cacheDir, an absolute path, relative to root if the cache is stored within the tree root, or
+// an empty string otherwise.
+func RelCacheDir(root string, cacheDir string) string {
+	if cacheDir == "" {
+		return ""
+	}
+
+	relPath, err := filepath.Rel(root, cacheDir)
+	if err != nil || relPath == "." || strings.HasPrefix(relPath, "..") {
+		return ""
+	}
+
+	return relPath
+}
+
+// isWithin returns true if relPath is dir or lies beneath it, with both in slash form.
+func isWithin(relPath string, dir string) bool {
+	return relPath == dir || strings.HasPrefix(relPath, dir+"/")
+}
+
 type ReleaseFunc func(ctx context.Context) error
 
 // File represents a file object with its path, relative path, file info, and potential cache entry.
@@ -46,6 +94,21 @@ type File struct {
 	releaseFuncs []ReleaseFunc
 }
 
+// depth returns how many levels path is nested beneath base, with the direct children of base having a depth of 1.
+func depth(base string, path string) int {
+	rel, err := filepath.Rel(base, path)
+	if err != nil || rel == "." {
+		return 0
+	}
+
+	return strings.Count(rel, string(filepath.Separator)) + 1
+}
+
+// WithinMaxDepth returns true if path is reached when walking base with the given max depth, with 0 meaning no limit.
+func WithinMaxDepth(base string, path string, maxDepth int) bool {
+	return maxDepth <= 0 || depth(base, path) <= maxDepth
+}
+
 func formatSignature(formattersSig []byte, info fs.FileInfo) []byte {
 	h := md5.New() //nolint:gosec
 	h.Write(formattersSig)
@@ -192,6 +255,8 @@ func NewReader(
 	walkType Type,
 	root string,
 	path string,
+	maxDepth int,
+	cacheDir string,
 	db *bolt.DB,
 	statz *stats.Stats,
 ) (Reader, error) {
@@ -203,18 +268,20 @@ func NewReader(
 	switch walkType {
 	case Auto:
 		// for now, we keep it simple and try git first, filesystem second
-		reader, err = NewReader(Git, root, path, db, statz)
+		reader, err = NewReader(Git, root, path, maxDepth, cacheDir, db, statz)
 		if err != nil {
-			reader, err = NewReader(Filesystem, root, path, db, statz)
+			reader, err = NewReader(Filesystem, root, path, maxDepth, cacheDir, db, statz)
 		}
 
 		return reader, err
 	case Stdin:
 		return nil, errors.New("stdin walk type is not supported")
 	case Filesystem:
-		reader = NewFilesystemReader(root, path, statz, BatchSize)
+		reader = NewFilesystemReader(root, path, maxDepth, cacheDir, statz, BatchSize)
 	case Git:
-		reader, err = NewGitReader(root, path, statz)
+		reader, err = NewGitReader(root, path, maxDepth, cacheDir, statz)
+	case Staged:
+		reader, err = NewStagedReader(root, path, maxDepth, statz)
 
 	default:
 		return nil, fmt.Errorf("unknown walk type: %v", walkType)
@@ -238,12 +305,15 @@ func NewCompositeReader(
 	walkType Type,
 	root string,
 	paths []string,
+	maxDepth int,
+	cacheDir string,
+	stdinOutput string,
 	db *bolt.DB,
 	statz *stats.Stats,
 ) (Reader, error) {
 	// if not paths are provided we default to processing the tree root
 	if len(paths) == 0 {
-		return NewReader(walkType, root, "", db, statz)
+		return NewReader(walkType, root, "", maxDepth, cacheDir, db, statz)
 	}
 
 	readers := make([]Reader, len(paths))
@@ -254,7 +324,7 @@ func NewCompositeReader(
 			return nil, errors.New("stdin walk requires exactly one path")
 		}
 
-		return NewStdinReader(root, paths[0], statz), nil
+		return NewStdinReader(root, paths[0], stdinOutput, statz), nil
 	}
 
 	// create a reader for each provided path
@@ -273,12 +343,13 @@ func NewCompositeReader(
 			return nil, fmt.Errorf("failed to stat %s: %w", path, err)
 		}
 
-		if info.IsDir() {
-			// for directories, we honour the walk type as we traverse them
-			readers[idx], err = NewReader(walkType, root, relPath, db, statz)
+		if info.IsDir() || walkType == Staged {
+			// for directories, we honour the walk type as we traverse them, and staged content is always read from
+			// the index
+			readers[idx], err = NewReader(walkType, root, relPath, maxDepth, cacheDir, db, statz)
 		} else {
 			// for files, we enforce a simple filesystem read
-			readers[idx], err = NewReader(Filesystem, root, relPath, db, statz)
+			readers[idx], err = NewReader(Filesystem, root, relPath, maxDepth, cacheDir, db, statz)
 		}
 
 		if err != nil {